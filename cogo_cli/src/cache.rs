@@ -0,0 +1,96 @@
+//! Programs compiled by `run --cache`, kept on disk so that running an unchanged file
+//! skips the compilation.
+//!
+//! The cache directory holds a bytecode file per compiled source, named `<key>.cgc`,
+//! where the key is a hash of the source, the compiler which compiled it and the options.
+//! An entry starts with the length of the source and the source itself, as keys may collide.
+
+use std::convert::{TryFrom, TryInto};
+use std::path::PathBuf;
+use std::{env, fs, process};
+
+use cogo_compiler::bytecode::{self, FINGERPRINT, FORMAT_VERSION};
+use cogo_compiler::{CompilationUnit, CompileOptions};
+
+use crate::BYTECODE_EXT;
+
+/// Bytes the length of the source an entry starts with takes
+const SOURCE_LEN_SIZE: usize = 8;
+
+pub(crate) struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    /// Cache in `COGO_CACHE_DIR`, `$XDG_CACHE_HOME/cogo` or `$HOME/.cache/cogo`,
+    /// whichever variable is set first
+    pub(crate) fn locate() -> Option<Self> {
+        let var = |name| {
+            env::var_os(name)
+                .filter(|v| !v.is_empty())
+                .map(PathBuf::from)
+        };
+        let dir = var("COGO_CACHE_DIR")
+            .or_else(|| var("XDG_CACHE_HOME").map(|dir| dir.join("cogo")))
+            .or_else(|| var("HOME").map(|dir| dir.join(".cache").join("cogo")))?;
+
+        Some(Self { dir })
+    }
+
+    /// Program previously compiled from the source,
+    /// a missing or unreadable entry is not an error, the source is compiled again
+    pub(crate) fn get(&self, src: &str, options: &CompileOptions) -> Option<CompilationUnit> {
+        let contents = fs::read(self.path(src, options)).ok()?;
+        let (len, rest) = contents.split_at_checked(SOURCE_LEN_SIZE)?;
+        let len = u64::from_le_bytes(len.try_into().ok()?);
+        let (source, program) = rest.split_at_checked(usize::try_from(len).ok()?)?;
+        if source != src.as_bytes() {
+            return None;
+        }
+
+        bytecode::decode(program).ok()
+    }
+
+    /// Stores the program compiled from the source, failing to do so only loses the entry.
+    /// The file is renamed into place once written, so a concurrent run never reads it partially
+    pub(crate) fn put(&self, src: &str, options: &CompileOptions, cunit: &CompilationUnit) {
+        let path = self.path(src, options);
+        let tmp = path.with_extension(format!("{}.tmp", process::id()));
+        let mut contents = (src.len() as u64).to_le_bytes().to_vec();
+        contents.extend_from_slice(src.as_bytes());
+        contents.extend_from_slice(&bytecode::encode(cunit));
+        let stored = fs::create_dir_all(&self.dir)
+            .and_then(|_| fs::write(&tmp, contents))
+            .and_then(|_| fs::rename(&tmp, &path));
+        if stored.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+    }
+
+    fn path(&self, src: &str, options: &CompileOptions) -> PathBuf {
+        let mut hash = Fnv::default();
+        hash.write(FINGERPRINT.as_bytes());
+        hash.write(&FORMAT_VERSION.to_le_bytes());
+        hash.write(&[options.optimize as u8]);
+        hash.write(src.as_bytes());
+
+        self.dir.join(format!("{:016x}.{}", hash.0, BYTECODE_EXT))
+    }
+}
+
+/// 64-bit FNV-1a, unlike the hasher of `std` it is guaranteed to stay the same between builds
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
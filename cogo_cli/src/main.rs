@@ -1,68 +1,478 @@
-use std::{env, fs, process};
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
+use std::time::Duration;
+use std::{env, fs, process, thread};
 
-use cogo_compiler::{compile, ToStderrErrorHandler};
-use cogo_vm::{CUnitFrame, Vm};
+use cogo_compiler::{
+    bytecode, compile_with_options, disassemble, document, dump, CompilationUnit, CompileOptions,
+    Diagnostic, ErrorHandler, LintRule, LintRules, Pos, Severity, ToJsonErrorHandler,
+    ToStderrErrorHandler,
+};
+use cogo_vm::{CUnitFrame, Vm, VmOptions};
+
+use crate::cache::Cache;
+
+mod cache;
+
+/// Extension of the files `build` writes the bytecode to
+const BYTECODE_EXT: &str = "cgc";
+/// File name standing for the standard input
+const STDIN_FILE: &str = "-";
+/// Interval between the checks of the watched file for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(300);
+
+#[derive(Debug, PartialEq)]
+enum Command {
+    /// Compiles and runs a source file, or runs a bytecode file
+    Run,
+    /// Only compiles, reporting the errors found
+    Check,
+    /// Compiles into a bytecode file
+    Build,
+    /// Prints the opcodes of a source or a bytecode file
+    Disasm,
+    /// Prints the exported declarations of a source file along with their doc comments
+    Doc,
+}
+
+/// When the output is highlighted with ANSI escape codes
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum ColorChoice {
+    /// Only if stderr is a terminal and `NO_COLOR` is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    fn enabled(self) -> bool {
+        match self {
+            Self::Auto => {
+                env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()) && io::stderr().is_terminal()
+            }
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+struct Args {
+    command: Command,
+    file: String,
+    output: Option<String>,
+    debug: bool,
+    optimize: bool,
+    /// Errors are printed as JSON diagnostics
+    json: bool,
+    /// Documentation is printed as a JSON object
+    doc_json: bool,
+    /// Output to stderr is highlighted
+    color: bool,
+    /// The command is repeated each time the file changes
+    watch: bool,
+    /// Compiled programs are reused from the cache
+    cache: bool,
+    /// Lint rules checked along with the errors
+    lint: LintRules,
+    /// Execution metrics are printed after the run
+    #[cfg_attr(not(feature = "stats"), allow(dead_code))]
+    stats: bool,
+}
+
+impl Args {
+    /// Name of the file shown in errors
+    fn source_name(&self) -> &str {
+        if self.file == STDIN_FILE {
+            "stdin"
+        } else {
+            &self.file
+        }
+    }
+
+    /// Prints an error which is not tied to a position in the source and terminates the process
+    fn fail(&self, msg: &str) -> ! {
+        if self.json {
+            let diagnostic = Diagnostic::error("error", msg.to_string(), Pos(0, 0), None);
+            eprintln!("{}", diagnostic.to_json(self.source_name()));
+        } else {
+            print_error(msg, self.color);
+        }
+        process::exit(1);
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() == 1 {
-        print_error("Arguments not found.");
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        print_help();
+        process::exit(0);
+    }
+
+    let argv = args.clone();
+    let args = parse_args(args).unwrap_or_else(|err| {
+        print_error(&err, ColorChoice::Auto.enabled());
         process::exit(1);
+    });
+
+    if args.watch {
+        watch(&args, &argv);
     }
 
-    match args[1].as_str() {
-        "-h" | "--help" => {
-            print_help();
-            process::exit(0);
+    match args.command {
+        Command::Run => run(&args),
+        Command::Check => check(&args),
+        Command::Build => build(&args),
+        Command::Disasm => print!("{}", disassemble(&load(&args))),
+        Command::Doc => doc(&args),
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<Args, String> {
+    let mut args = args.into_iter().peekable();
+    let command = match args.peek().map(String::as_str) {
+        Some("run") => Some(Command::Run),
+        Some("check") => Some(Command::Check),
+        Some("build") => Some(Command::Build),
+        Some("disasm") => Some(Command::Disasm),
+        Some("doc") => Some(Command::Doc),
+        Some(_) => None,
+        None if !io::stdin().is_terminal() => None,
+        None => return Err("Arguments not found.".to_string()),
+    };
+    // a bare file is run
+    let command = match command {
+        Some(command) => {
+            args.next();
+            command
+        }
+        None => Command::Run,
+    };
+
+    let (mut file, mut output, mut debug, mut optimize, mut json) =
+        (None, None, false, false, false);
+    let mut color = ColorChoice::Auto;
+    let mut watch = false;
+    let mut stats = false;
+    let mut cache = false;
+    let mut doc_json = false;
+    let mut lint = LintRules::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-d" | "--debug" => debug = true,
+            "-O" | "--optimize" => optimize = true,
+            "--diagnostics=json" => json = true,
+            "--diagnostics=text" => json = false,
+            "--color=auto" => color = ColorChoice::Auto,
+            "--color=always" => color = ColorChoice::Always,
+            "--color=never" => color = ColorChoice::Never,
+            "--stats" if cfg!(feature = "stats") && command == Command::Run => stats = true,
+            "--stats" if command == Command::Run => {
+                return Err(
+                    "\"--stats\" requires cogo built with the \"stats\" feature.".to_string(),
+                )
+            }
+            "--format=json" if command == Command::Doc => doc_json = true,
+            "--format=text" if command == Command::Doc => doc_json = false,
+            "--lint" if command == Command::Check => lint = LintRules::all(),
+            flag if flag.starts_with("--lint=") && command == Command::Check => {
+                lint = LintRules::parse(&flag["--lint=".len()..])?
+            }
+            "--cache" if command == Command::Run => cache = true,
+            "-w" | "--watch" if command == Command::Run || command == Command::Check => {
+                watch = true
+            }
+            "-o" | "--output" if command == Command::Build => match args.next() {
+                Some(out) => output = Some(out),
+                None => return Err(format!("Missing file name after \"{}\".", arg)),
+            },
+            flag if flag.starts_with('-') && flag != STDIN_FILE => {
+                return Err(format!("Unknown flag \"{}\".", flag))
+            }
+            _ if file.is_some() => return Err(format!("Unexpected argument \"{}\".", arg)),
+            _ => file = Some(arg),
         }
-        _ => {}
     }
 
-    let debug = args.contains(&"--debug".to_string()) || args.contains(&"-d".to_string());
-    let args: Vec<String> = args
-        .into_iter()
-        .filter(|arg| !arg.starts_with('-'))
-        .collect();
+    // the source is piped in
+    if file.is_none() && !io::stdin().is_terminal() {
+        file = Some(STDIN_FILE.to_string());
+    }
+
+    let file = file.ok_or_else(|| "File not specified.".to_string())?;
+    if watch && file == STDIN_FILE {
+        return Err("Standard input cannot be watched.".to_string());
+    }
+
+    Ok(Args {
+        command,
+        file,
+        output,
+        debug,
+        optimize,
+        json,
+        doc_json,
+        color: color.enabled(),
+        watch,
+        cache,
+        lint,
+        stats,
+    })
+}
 
-    let contents = fs::read_to_string(&args[1]).unwrap_or_else(|_| {
-        panic!(
+/// Contents of the file or of the standard input
+fn read(args: &Args) -> Vec<u8> {
+    let contents = if args.file == STDIN_FILE {
+        let mut contents = vec![];
+        io::stdin().read_to_end(&mut contents).map(|_| contents)
+    } else {
+        fs::read(&args.file)
+    };
+    contents.unwrap_or_else(|_| {
+        args.fail(&format!(
             "Something went wrong while reading the file \"{}\"",
-            &args[1]
-        )
-    });
+            args.source_name()
+        ))
+    })
+}
 
-    let cunit = compile(&contents, &mut ToStderrErrorHandler);
-    let frame = CUnitFrame::new(cunit);
+/// Source code of the file, which must be valid UTF-8
+fn source(args: &Args, contents: Vec<u8>) -> String {
+    String::from_utf8(contents).unwrap_or_else(|_| {
+        args.fail(&format!(
+            "File \"{}\" is not valid UTF-8",
+            args.source_name()
+        ))
+    })
+}
+
+/// Compiles the source file, or reads the compiled program if it is a bytecode file
+fn load(args: &Args) -> CompilationUnit {
+    let contents = read(args);
+    if bytecode::is_bytecode(&contents) {
+        return bytecode::decode(&contents).unwrap_or_else(|err| args.fail(&err.to_string()));
+    }
 
-    //fixme
-    if debug {
-        eprintln!("\x1b[0;34m{:#?}\x1b[0m", frame);
+    let contents = source(args, contents);
+    let options = CompileOptions {
+        optimize: args.optimize,
+    };
+
+    let cache = if args.cache { Cache::locate() } else { None };
+    if let Some(cunit) = cache.as_ref().and_then(|c| c.get(&contents, &options)) {
+        return cunit;
+    }
+
+    let mut err_handler: Box<dyn ErrorHandler> = if args.json {
+        Box::new(ToJsonErrorHandler::new(args.source_name().to_string()))
+    } else {
+        Box::new(ToStderrErrorHandler::new(args.color))
+    };
+
+    // the handler terminates the process on errors, so only valid programs get cached
+    let cunit = compile_with_options(&contents, err_handler.as_mut(), &options);
+    if let Some(cache) = cache {
+        cache.put(&contents, &options, &cunit);
     }
 
-    let mut vm = Vm::new(None, frame);
+    cunit
+}
+
+fn run(args: &Args) {
+    let cunit = load(args);
+
+    if args.debug && args.color {
+        eprintln!("\x1b[0;34m{}\x1b[0m", dump(&cunit));
+    } else if args.debug {
+        eprintln!("{}", dump(&cunit));
+    }
+
+    let frame = CUnitFrame::new(cunit);
+
+    let vm_options = VmOptions {
+        source_name: Some(args.source_name().to_string()),
+        ..VmOptions::default()
+    };
+    let mut vm = Vm::with_options(None, frame, &vm_options);
     let res = vm.run();
+
+    #[cfg(feature = "stats")]
+    if args.stats {
+        eprintln!("{}", vm.stats());
+    }
+
     match res {
         Ok(()) => {
             process::exit(0);
         }
+        Err(e) if args.json => {
+            eprintln!("{}", e.diagnostic().to_json(args.source_name()));
+            process::exit(e.exit_code());
+        }
+        Err(e) if e.category() == "panic" => {
+            eprintln!("{}", e);
+            process::exit(e.exit_code());
+        }
         Err(e) => {
-            print_error(&e.to_string());
-            process::exit(1);
+            print_error(&e.to_string(), args.color);
+            process::exit(e.exit_code());
+        }
+    }
+}
+
+/// Reports every problem found in the file, a bytecode file is only checked to be decodable
+fn check(args: &Args) {
+    let contents = read(args);
+    if bytecode::is_bytecode(&contents) {
+        if let Err(err) = bytecode::decode(&contents) {
+            args.fail(&err.to_string());
+        }
+        return;
+    }
+
+    let diagnostics = cogo_compiler::check_with_lints(&source(args, contents), &args.lint);
+    for diagnostic in &diagnostics {
+        if args.json {
+            eprintln!("{}", diagnostic.to_json(args.source_name()));
+            continue;
+        }
+
+        let mut msg = format!(
+            "{}:{}:{}: {}: {}",
+            args.source_name(),
+            diagnostic.pos.0,
+            diagnostic.pos.1,
+            diagnostic.severity,
+            diagnostic.message
+        );
+        let warning = diagnostic.severity == Severity::Warning;
+        if warning {
+            msg.push_str(&format!(" [{}]", diagnostic.code));
+        }
+
+        if args.color && warning {
+            eprintln!("\x1b[0;33m{}\x1b[0m", msg);
+        } else if args.color {
+            eprintln!("\x1b[0;31m{}\x1b[0m", msg);
+        } else {
+            eprintln!("{}", msg);
+        }
+    }
+
+    if !diagnostics.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn build(args: &Args) {
+    let cunit = load(args);
+    let output = args.output.clone().unwrap_or_else(|| {
+        Path::new(args.source_name())
+            .with_extension(BYTECODE_EXT)
+            .to_string_lossy()
+            .into_owned()
+    });
+
+    if let Err(err) = fs::write(&output, bytecode::encode(&cunit)) {
+        args.fail(&format!("Cannot write \"{}\": {}", output, err));
+    }
+}
+
+fn doc(args: &Args) {
+    let contents = read(args);
+    if bytecode::is_bytecode(&contents) {
+        args.fail(&format!(
+            "File \"{}\" is a bytecode file, which keeps no documentation",
+            args.source_name()
+        ));
+    }
+
+    let doc = document(&source(args, contents));
+    if args.doc_json {
+        println!("{}", doc.to_json());
+    } else {
+        print!("{}", doc);
+    }
+}
+
+/// Repeats the command in a child process each time the file is modified,
+/// a run still in progress is stopped
+fn watch(args: &Args, argv: &[String]) -> ! {
+    let exe = env::current_exe()
+        .unwrap_or_else(|err| args.fail(&format!("Cannot watch \"{}\": {}", args.file, err)));
+    let argv: Vec<&String> = argv
+        .iter()
+        .filter(|arg| *arg != "-w" && *arg != "--watch")
+        .collect();
+
+    let mut modified = None;
+    let mut child: Option<process::Child> = None;
+    loop {
+        let last_modified = fs::metadata(&args.file).and_then(|m| m.modified()).ok();
+        if last_modified != modified {
+            if let Some(mut child) = child.take() {
+                let _ = child.kill();
+                let _ = child.wait();
+            }
+            if modified.is_some() {
+                eprintln!("\"{}\" changed, restarting", args.file);
+            }
+
+            modified = last_modified;
+            child = process::Command::new(&exe).args(&argv).spawn().ok();
+        }
+
+        if let Some(running) = &mut child {
+            if let Ok(Some(_)) = running.try_wait() {
+                child = None;
+            }
         }
+
+        thread::sleep(WATCH_INTERVAL);
     }
 }
 
-fn print_error(msg: &str) {
-    eprintln!("\x1b[0;31m{}\x1b[0m", msg);
+fn print_error(msg: &str, color: bool) {
+    if color {
+        eprintln!("\x1b[0;31m{}\x1b[0m", msg);
+    } else {
+        eprintln!("{}", msg);
+    }
     eprintln!("Run the command with \"--help\" to see help information.");
 }
 
 fn print_help() {
+    let rules: Vec<&str> = LintRule::ALL.iter().map(|rule| rule.name()).collect();
     println!(
         r#"
+    USAGE:
+        cogo <COMMAND> [FLAGS] <FILE>
+
+        FILE is read from the standard input if it is "-" or omitted with the input piped in.
+
+    COMMANDS:
+        run      Compile and run a source file, or run a bytecode file (default)
+        check    Compile a source file, only reporting errors
+        build    Compile a source file into a bytecode file
+        disasm   Print the opcodes of a source or a bytecode file
+        doc      Print the exported declarations of a source file with their doc comments
+
     FLAGS:
-        -h, --help     Print help
-        -d, --debug    Dump opcodes to stdout
-    "#
+        -h, --help         Print help
+        -d, --debug        Dump the signature, locals and opcodes of each function to stderr before running
+        -O, --optimize     Optimize bytecode
+        -o, --output FILE  Bytecode file to write, "<FILE>.cgc" by default (build only)
+        --diagnostics=json Print errors as JSON objects, one per line
+        --cache            Reuse the program compiled from unchanged FILE (run only),
+                           kept in COGO_CACHE_DIR, "$XDG_CACHE_HOME/cogo" or "~/.cache/cogo"
+        --lint[=RULES]     Also report likely mistakes as warnings (check only), RULES is a
+                           comma-separated list of rules to check, or to skip if prefixed with "-":
+                           {}
+        --format=FORMAT    Print documentation as "text" (default) or "json" (doc only)
+        --stats            Print execution metrics after the run (requires the "stats" feature)
+        -w, --watch        Repeat the command each time FILE changes (run and check only)
+        --color=WHEN       Highlight errors: "auto" (default), "always" or "never",
+                           "auto" disables colors if stderr is not a terminal or NO_COLOR is set
+    "#,
+        rules.join(", ")
     )
 }
@@ -0,0 +1,962 @@
+//! Binary form of compiled programs, so that they can be stored and run without the source.
+//!
+//! A program starts with the magic bytes, the format version and the fingerprint of the compiler,
+//! whose layout stays the same across versions.
+//! All integers are little-endian, `usize` values are stored as `u64`.
+//! Strings are length-prefixed UTF-8, variants are prefixed with a one-byte tag.
+
+use std::convert::TryFrom;
+use std::rc::Rc;
+
+use crate::error::DecodeError;
+use crate::jump_table::JumpTable;
+use crate::lex::lexeme::{Pos, Span};
+use crate::pos_table::PosTable;
+use crate::structure::{Function, Package};
+use crate::unit::{CompilationUnit, FuncUnit, LocalVar, PackageUnit};
+use crate::vtype::{CompositeType, FuncType, ParamType};
+use crate::{Chunk, OpCode, Unmanaged, ValType, Value};
+
+/// Every serialized program starts with it
+const MAGIC: &[u8; 4] = b"CGC\0";
+
+/// Version of the encoding, bumped whenever it changes, e.g. once an opcode is added.
+/// Programs of another version are refused instead of being misread
+pub const FORMAT_VERSION: u16 = 4;
+
+/// Compiler which encoded the program, reported when its version is not supported
+pub const FINGERPRINT: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+type DecodeResult<T> = Result<T, DecodeError>;
+
+/// Serializes a compiled program
+pub fn encode(cunit: &CompilationUnit) -> Vec<u8> {
+    let mut encoder = Encoder(MAGIC.to_vec());
+    encoder.0.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    encoder.str(FINGERPRINT);
+    encoder.cunit(cunit);
+
+    encoder.0
+}
+
+/// Restores a program serialized with `encode`
+pub fn decode(bytes: &[u8]) -> Result<CompilationUnit, DecodeError> {
+    let mut decoder = Decoder { bytes, at: 0 };
+    if decoder.take(MAGIC.len())? != MAGIC {
+        return Err(DecodeError::new("not a cogo bytecode file".to_string(), 0));
+    }
+
+    let at = decoder.at;
+    let version = decoder.take(2).map(|v| u16::from_le_bytes([v[0], v[1]]))?;
+    let producer = decoder.string();
+    if version != FORMAT_VERSION {
+        let producer = producer.unwrap_or_else(|_| "an unknown compiler".to_string());
+        let msg = format!(
+            "bytecode format version {} produced by {} is not supported, \
+            {} runs version {}. Recompile the program from its source",
+            version, producer, FINGERPRINT, FORMAT_VERSION
+        );
+        return Err(DecodeError::new(msg, at));
+    }
+    producer?;
+
+    let cunit = decoder.cunit()?;
+    if decoder.at != bytes.len() {
+        return decoder.err("trailing bytes after the program");
+    }
+
+    Ok(cunit)
+}
+
+/// Whether the bytes look like a serialized program rather than source code
+pub fn is_bytecode(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u8(&mut self, v: u8) {
+        self.0.push(v);
+    }
+
+    fn bool(&mut self, v: bool) {
+        self.u8(v as u8);
+    }
+
+    fn u64(&mut self, v: u64) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn i128(&mut self, v: i128) {
+        self.0.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn usize(&mut self, v: usize) {
+        self.u64(v as u64);
+    }
+
+    fn str(&mut self, v: &str) {
+        self.usize(v.len());
+        self.0.extend_from_slice(v.as_bytes());
+    }
+
+    fn cunit(&mut self, cunit: &CompilationUnit) {
+        match cunit {
+            CompilationUnit::Package(p) => {
+                self.u8(0);
+                self.str(&p.package().0);
+            }
+            CompilationUnit::Function(f) => {
+                self.u8(1);
+                self.str(&f.function().0);
+                self.func_type(f.func_type());
+                self.usize(f.slots());
+                self.usize(f.locals().len());
+                for local in f.locals() {
+                    self.local_var(local);
+                }
+            }
+        }
+
+        self.chunk(cunit.chunk());
+    }
+
+    fn local_var(&mut self, local: &LocalVar) {
+        self.str(&local.name);
+        self.usize(local.slot);
+        self.usize(local.start.0);
+        self.usize(local.start.1);
+        self.usize(local.end.0);
+        self.usize(local.end.1);
+    }
+
+    fn chunk(&mut self, chunk: &Chunk) {
+        self.usize(chunk.constants().len());
+        for constant in chunk.constants() {
+            self.value(constant);
+        }
+
+        self.usize(chunk.codes().len());
+        for code in chunk.codes() {
+            self.op_code(code);
+        }
+
+        let runs: Vec<_> = chunk.pos_table().runs().collect();
+        self.usize(runs.len());
+        for (count, pos, span) in runs {
+            self.usize(count);
+            self.usize(pos.0);
+            self.usize(pos.1);
+            self.usize(span.start);
+            self.usize(span.end);
+        }
+    }
+
+    fn op_code(&mut self, code: &OpCode) {
+        use OpCode::*;
+        match code {
+            Noop => self.u8(0),
+            Pop => self.u8(1),
+            Negate => self.u8(2),
+            PlusNoop => self.u8(3),
+            Not => self.u8(4),
+            Add => self.u8(5),
+            Subtract => self.u8(6),
+            Multiply => self.u8(7),
+            Divide => self.u8(8),
+            Remainder => self.u8(9),
+            BitwiseAnd => self.u8(10),
+            BitwiseOr => self.u8(11),
+            BitwiseXor => self.u8(12),
+            BitwiseNot => self.u8(13),
+            BitClear => self.u8(14),
+            LeftShift => self.u8(15),
+            RightShift => self.u8(16),
+            Call(argc, spread) => {
+                self.u8(17);
+                self.u8(*argc);
+                self.bool(*spread);
+            }
+            TailCall(argc, spread) => {
+                self.u8(18);
+                self.u8(*argc);
+                self.bool(*spread);
+            }
+            Equal => self.u8(19),
+            NotEqual => self.u8(20),
+            Greater => self.u8(21),
+            GreaterEqual => self.u8(22),
+            Less => self.u8(23),
+            LessEqual => self.u8(24),
+            Switch => self.u8(25),
+            JumpTable(table) => {
+                self.u8(61);
+                self.usize(table.miss());
+                let entries = table.entries();
+                self.usize(entries.len());
+                for (value, target) in &entries {
+                    self.value(value);
+                    self.usize(*target);
+                }
+            }
+            DefaultJump(t) => {
+                self.u8(26);
+                self.usize(*t);
+            }
+            CaseJump(t) => {
+                self.u8(27);
+                self.usize(*t);
+            }
+            DefaultCaseJump(t) => {
+                self.u8(28);
+                self.usize(*t);
+            }
+            IfFalseJump(t) => {
+                self.u8(29);
+                self.usize(*t);
+            }
+            Jump(t) => {
+                self.u8(30);
+                self.usize(*t);
+            }
+            CaseBreakJump(t) => {
+                self.u8(31);
+                self.usize(*t);
+            }
+            DoCaseBreakJump => self.u8(32),
+            Fallthrough => self.u8(33),
+            Return(n) => {
+                self.u8(34);
+                self.u8(*n);
+            }
+            Constant(c) => {
+                self.u8(35);
+                self.usize(*c);
+            }
+            Func(cunit) => {
+                self.u8(36);
+                self.cunit(cunit);
+            }
+            ArrayLiteral(size, vtype) => {
+                self.u8(37);
+                self.usize(*size);
+                self.vtype(vtype);
+            }
+            SliceLiteral(size, vtype) => {
+                self.u8(38);
+                self.usize(*size);
+                self.vtype(vtype);
+            }
+            VarGlobal(name, vtype) => {
+                self.u8(39);
+                self.str(name);
+                self.opt_vtype(vtype.as_ref());
+            }
+            ConstGlobal(name, vtype) => {
+                self.u8(40);
+                self.str(name);
+                self.opt_vtype(vtype.as_ref());
+            }
+            GetGlobal(name) => {
+                self.u8(41);
+                self.str(name);
+            }
+            SetGlobal(name) => {
+                self.u8(42);
+                self.str(name);
+            }
+            GetLocal(i) => {
+                self.u8(43);
+                self.usize(*i);
+            }
+            SetLocal(i) => {
+                self.u8(44);
+                self.usize(*i);
+            }
+            IncLocal(i, c) => {
+                self.u8(45);
+                self.usize(*i);
+                self.usize(*c);
+            }
+            DecLocal(i, c) => {
+                self.u8(46);
+                self.usize(*i);
+                self.usize(*c);
+            }
+            GetIndex => self.u8(47),
+            GetLocalIndex(i) => {
+                self.u8(48);
+                self.usize(*i);
+            }
+            GetGlobalIndex(name) => {
+                self.u8(49);
+                self.str(name);
+            }
+            SetIndex => self.u8(50),
+            SetLocalIndex(i, index_at, array_at_index) => {
+                self.u8(51);
+                self.usize(*i);
+                self.usize(*index_at);
+                self.bool(*array_at_index);
+            }
+            SetGlobalIndex(name, index_at, array_at_index) => {
+                self.u8(52);
+                self.str(name);
+                self.usize(*index_at);
+                self.bool(*array_at_index);
+            }
+            BlindLiteralCast(i) => {
+                self.u8(53);
+                self.usize(*i);
+            }
+            VariadicSliceCast(vtype, argc) => {
+                self.u8(54);
+                self.vtype(vtype);
+                self.u8(*argc);
+            }
+            LoseSoftReference(i) => {
+                self.u8(55);
+                self.usize(*i);
+            }
+            TypeValidation(vtype, i) => {
+                self.u8(56);
+                self.vtype(vtype);
+                self.usize(*i);
+            }
+            PutDefaultValue(vtype) => {
+                self.u8(57);
+                self.vtype(vtype);
+            }
+            SliceExpr(low, high, max) => {
+                self.u8(58);
+                self.bool(*low);
+                self.bool(*high);
+                self.bool(*max);
+            }
+            Convert(vtype) => {
+                self.u8(59);
+                self.vtype(vtype);
+            }
+            RangeInt => self.u8(60),
+            CallBuiltin(id, argc) => {
+                self.u8(62);
+                self.u8(*id);
+                self.u8(*argc);
+            }
+        }
+    }
+
+    fn opt_vtype(&mut self, vtype: Option<&ValType>) {
+        match vtype {
+            Some(vtype) => {
+                self.bool(true);
+                self.vtype(vtype);
+            }
+            None => self.bool(false),
+        }
+    }
+
+    fn vtype(&mut self, vtype: &ValType) {
+        use ValType::*;
+        match vtype {
+            Nil => self.u8(0),
+            Bool => self.u8(1),
+            Int8 => self.u8(2),
+            Int16 => self.u8(3),
+            Int32 => self.u8(4),
+            Int64 => self.u8(5),
+            Int => self.u8(6),
+            Uint8 => self.u8(7),
+            Uint16 => self.u8(8),
+            Uint32 => self.u8(9),
+            Uint64 => self.u8(10),
+            Uint => self.u8(11),
+            Uintptr => self.u8(12),
+            Float32 => self.u8(13),
+            Float64 => self.u8(14),
+            Complex64 => self.u8(15),
+            Complex128 => self.u8(16),
+            String => self.u8(17),
+            Array(vtype, size) => {
+                self.u8(18);
+                self.vtype(vtype);
+                self.usize(*size);
+            }
+            Slice(vtype) => {
+                self.u8(19);
+                self.vtype(vtype);
+            }
+            Func(ftype) => {
+                self.u8(20);
+                self.func_type(ftype);
+            }
+            Struct(name) => {
+                self.u8(21);
+                self.str(name);
+            }
+        }
+    }
+
+    fn func_type(&mut self, ftype: &FuncType) {
+        self.usize(ftype.args().len());
+        for ParamType(vtype, variadic) in ftype.args() {
+            self.vtype(vtype);
+            self.bool(*variadic);
+        }
+
+        let ret_types = ftype.ret_type().types();
+        self.usize(ret_types.len());
+        for vtype in ret_types {
+            self.vtype(vtype);
+        }
+    }
+
+    fn value(&mut self, value: &Value) {
+        use Value::*;
+        match value {
+            Bool(v) => {
+                self.u8(0);
+                self.bool(*v);
+            }
+            Int8(v) => {
+                self.u8(1);
+                self.u64(*v as u64);
+            }
+            Int16(v) => {
+                self.u8(2);
+                self.u64(*v as u64);
+            }
+            Int32(v) => {
+                self.u8(3);
+                self.u64(*v as u64);
+            }
+            Int64(v) => {
+                self.u8(4);
+                self.u64(*v as u64);
+            }
+            Int(v) => {
+                self.u8(5);
+                self.u64(*v as u64);
+            }
+            Uint8(v) => {
+                self.u8(6);
+                self.u64(*v as u64);
+            }
+            Uint16(v) => {
+                self.u8(7);
+                self.u64(*v as u64);
+            }
+            Uint32(v) => {
+                self.u8(8);
+                self.u64(*v as u64);
+            }
+            Uint64(v) => {
+                self.u8(9);
+                self.u64(*v);
+            }
+            Uint(v) => {
+                self.u8(10);
+                self.usize(*v);
+            }
+            Uintptr(v) => {
+                self.u8(11);
+                self.usize(*v);
+            }
+            Float32(v) => {
+                self.u8(12);
+                self.u64(v.to_bits() as u64);
+            }
+            Float64(v) => {
+                self.u8(13);
+                self.u64(v.to_bits());
+            }
+            Complex64(re, im) => {
+                self.u8(14);
+                self.u64(re.to_bits() as u64);
+                self.u64(im.to_bits() as u64);
+            }
+            Complex128(re, im) => {
+                self.u8(15);
+                self.u64(re.to_bits());
+                self.u64(im.to_bits());
+            }
+            String(v) => {
+                self.u8(16);
+                self.str(v);
+            }
+            Func(name, ftype) => {
+                self.u8(17);
+                self.str(name);
+                self.func_type(ftype);
+            }
+            FuncBuiltin(name) => {
+                self.u8(18);
+                self.str(name);
+            }
+            Array(vals, size, vtype) => {
+                self.u8(19);
+                self.values(&vals.borrow());
+                self.usize(*size);
+                self.vtype(vtype);
+            }
+            Slice(slice, vtype) => {
+                self.u8(20);
+                self.values(&slice.to_vec());
+                self.vtype(vtype);
+            }
+            Nil => self.u8(21),
+            IntLiteral(v) => {
+                self.u8(22);
+                self.i128(*v);
+            }
+            FloatLiteral(v) => {
+                self.u8(23);
+                self.u64(v.to_bits());
+            }
+        }
+    }
+
+    fn values(&mut self, vals: &[Value]) {
+        self.usize(vals.len());
+        for val in vals {
+            self.value(val);
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn err<T>(&self, msg: &str) -> DecodeResult<T> {
+        Err(DecodeError::new(msg.to_string(), self.at))
+    }
+
+    fn take(&mut self, n: usize) -> DecodeResult<&'a [u8]> {
+        if self.bytes.len() - self.at < n {
+            return self.err("unexpected end of bytecode");
+        }
+
+        let bytes = &self.bytes[self.at..self.at + n];
+        self.at += n;
+
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> DecodeResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> DecodeResult<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => self.err("invalid boolean"),
+        }
+    }
+
+    fn u64(&mut self) -> DecodeResult<u64> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn i128(&mut self) -> DecodeResult<i128> {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(self.take(16)?);
+
+        Ok(i128::from_le_bytes(bytes))
+    }
+
+    fn usize(&mut self) -> DecodeResult<usize> {
+        let v = self.u64()?;
+        match usize::try_from(v) {
+            Ok(v) => Ok(v),
+            Err(_) => self.err("value does not fit the platform size"),
+        }
+    }
+
+    /// Length of a sequence, which cannot exceed the bytes left
+    fn len(&mut self) -> DecodeResult<usize> {
+        let len = self.usize()?;
+        if len > self.bytes.len() - self.at {
+            return self.err("sequence length exceeds the bytecode size");
+        }
+
+        Ok(len)
+    }
+
+    fn string(&mut self) -> DecodeResult<String> {
+        let len = self.len()?;
+        let bytes = self.take(len)?;
+        match std::str::from_utf8(bytes) {
+            Ok(s) => Ok(s.to_string()),
+            Err(_) => self.err("invalid UTF-8 string"),
+        }
+    }
+
+    fn cunit(&mut self) -> DecodeResult<CompilationUnit> {
+        match self.u8()? {
+            0 => {
+                let package = Package(self.string()?);
+                let chunk = self.chunk()?;
+                Ok(CompilationUnit::Package(PackageUnit::from_codes(
+                    package, chunk,
+                )))
+            }
+            1 => {
+                let function = Function(self.string()?);
+                let ftype = self.func_type()?;
+                let slots = self.usize()?;
+                let len = self.len()?;
+                let mut locals = Vec::with_capacity(len);
+                for _ in 0..len {
+                    locals.push(self.local_var()?);
+                }
+
+                let chunk = self.chunk()?;
+                let mut funit = FuncUnit::from_codes(Some(function), ftype, chunk);
+                funit.set_locals(locals);
+                funit.set_slots(slots);
+                Ok(CompilationUnit::Function(funit))
+            }
+            _ => self.err("unknown compilation unit type"),
+        }
+    }
+
+    fn local_var(&mut self) -> DecodeResult<LocalVar> {
+        Ok(LocalVar {
+            name: self.string()?,
+            slot: self.usize()?,
+            start: Pos(self.usize()?, self.usize()?),
+            end: Pos(self.usize()?, self.usize()?),
+        })
+    }
+
+    fn chunk(&mut self) -> DecodeResult<Chunk> {
+        let len = self.len()?;
+        let mut constants = Vec::with_capacity(len);
+        for _ in 0..len {
+            constants.push(self.value()?);
+        }
+
+        let len = self.len()?;
+        let mut codes = Vec::with_capacity(len);
+        for _ in 0..len {
+            codes.push(self.op_code()?);
+        }
+
+        let run_count = self.len()?;
+        let mut runs = Vec::with_capacity(run_count);
+        for _ in 0..run_count {
+            let count = self.usize()?;
+            runs.push((
+                count,
+                Pos(self.usize()?, self.usize()?),
+                Span::new(self.usize()?, self.usize()?),
+            ));
+        }
+
+        let pos = match PosTable::from_runs(runs) {
+            Some(pos) if pos.len() == codes.len() => pos,
+            _ => return self.err("positions do not match the opcodes"),
+        };
+
+        Ok(Chunk::from_parts(codes, pos, constants))
+    }
+
+    fn op_code(&mut self) -> DecodeResult<OpCode> {
+        use OpCode::*;
+        let code = match self.u8()? {
+            0 => Noop,
+            1 => Pop,
+            2 => Negate,
+            3 => PlusNoop,
+            4 => Not,
+            5 => Add,
+            6 => Subtract,
+            7 => Multiply,
+            8 => Divide,
+            9 => Remainder,
+            10 => BitwiseAnd,
+            11 => BitwiseOr,
+            12 => BitwiseXor,
+            13 => BitwiseNot,
+            14 => BitClear,
+            15 => LeftShift,
+            16 => RightShift,
+            17 => Call(self.u8()?, self.bool()?),
+            18 => TailCall(self.u8()?, self.bool()?),
+            19 => Equal,
+            20 => NotEqual,
+            21 => Greater,
+            22 => GreaterEqual,
+            23 => Less,
+            24 => LessEqual,
+            25 => Switch,
+            26 => DefaultJump(self.usize()?),
+            27 => CaseJump(self.usize()?),
+            28 => DefaultCaseJump(self.usize()?),
+            29 => IfFalseJump(self.usize()?),
+            30 => Jump(self.usize()?),
+            31 => CaseBreakJump(self.usize()?),
+            32 => DoCaseBreakJump,
+            33 => Fallthrough,
+            34 => Return(self.u8()?),
+            35 => Constant(self.usize()?),
+            36 => Func(self.cunit()?),
+            37 => ArrayLiteral(self.usize()?, self.vtype()?),
+            38 => SliceLiteral(self.usize()?, self.vtype()?),
+            39 => VarGlobal(self.string()?, self.opt_vtype()?),
+            40 => ConstGlobal(self.string()?, self.opt_vtype()?),
+            41 => GetGlobal(self.string()?),
+            42 => SetGlobal(self.string()?),
+            43 => GetLocal(self.usize()?),
+            44 => SetLocal(self.usize()?),
+            45 => IncLocal(self.usize()?, self.usize()?),
+            46 => DecLocal(self.usize()?, self.usize()?),
+            47 => GetIndex,
+            48 => GetLocalIndex(self.usize()?),
+            49 => GetGlobalIndex(self.string()?),
+            50 => SetIndex,
+            51 => SetLocalIndex(self.usize()?, self.usize()?, self.bool()?),
+            52 => SetGlobalIndex(self.string()?, self.usize()?, self.bool()?),
+            53 => BlindLiteralCast(self.usize()?),
+            54 => VariadicSliceCast(self.vtype()?, self.u8()?),
+            55 => LoseSoftReference(self.usize()?),
+            56 => TypeValidation(self.vtype()?, self.usize()?),
+            57 => PutDefaultValue(self.vtype()?),
+            58 => SliceExpr(self.bool()?, self.bool()?, self.bool()?),
+            59 => Convert(self.vtype()?),
+            60 => RangeInt,
+            61 => JumpTable(Rc::new(self.jump_table()?)),
+            62 => CallBuiltin(self.u8()?, self.u8()?),
+            _ => return self.err("unknown opcode"),
+        };
+
+        Ok(code)
+    }
+
+    fn jump_table(&mut self) -> DecodeResult<JumpTable> {
+        let mut table = JumpTable::new(self.usize()?);
+        for _ in 0..self.len()? {
+            let value = self.value()?;
+            if !table.insert(&value, self.usize()?) {
+                return self.err("invalid jump table case");
+            }
+        }
+
+        Ok(table)
+    }
+
+    fn opt_vtype(&mut self) -> DecodeResult<Option<ValType>> {
+        if self.bool()? {
+            Ok(Some(self.vtype()?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn vtype(&mut self) -> DecodeResult<ValType> {
+        use ValType::*;
+        let vtype = match self.u8()? {
+            0 => Nil,
+            1 => Bool,
+            2 => Int8,
+            3 => Int16,
+            4 => Int32,
+            5 => Int64,
+            6 => Int,
+            7 => Uint8,
+            8 => Uint16,
+            9 => Uint32,
+            10 => Uint64,
+            11 => Uint,
+            12 => Uintptr,
+            13 => Float32,
+            14 => Float64,
+            15 => Complex64,
+            16 => Complex128,
+            17 => String,
+            18 => Array(Box::new(self.vtype()?), self.usize()?),
+            19 => Slice(Box::new(self.vtype()?)),
+            20 => Func(Box::new(self.func_type()?)),
+            21 => Struct(self.string()?),
+            _ => return self.err("unknown type"),
+        };
+
+        Ok(vtype)
+    }
+
+    fn func_type(&mut self) -> DecodeResult<FuncType> {
+        let len = self.len()?;
+        let mut args = Vec::with_capacity(len);
+        for _ in 0..len {
+            args.push(ParamType(self.vtype()?, self.bool()?));
+        }
+
+        let len = self.len()?;
+        let mut ret_types = Vec::with_capacity(len);
+        for _ in 0..len {
+            ret_types.push(self.vtype()?);
+        }
+
+        Ok(FuncType::new(args, CompositeType::new(ret_types)))
+    }
+
+    fn value(&mut self) -> DecodeResult<Value> {
+        use Value::*;
+        let value = match self.u8()? {
+            0 => Bool(self.bool()?),
+            1 => Int8(self.u64()? as i8),
+            2 => Int16(self.u64()? as i16),
+            3 => Int32(self.u64()? as i32),
+            4 => Int64(self.u64()? as i64),
+            5 => Int(self.u64()? as isize),
+            6 => Uint8(self.u64()? as u8),
+            7 => Uint16(self.u64()? as u16),
+            8 => Uint32(self.u64()? as u32),
+            9 => Uint64(self.u64()?),
+            10 => Uint(self.usize()?),
+            11 => Uintptr(self.usize()?),
+            12 => Float32(f32::from_bits(self.u64()? as u32)),
+            13 => Float64(f64::from_bits(self.u64()?)),
+            14 => Complex64(
+                f32::from_bits(self.u64()? as u32),
+                f32::from_bits(self.u64()? as u32),
+            ),
+            15 => Complex128(f64::from_bits(self.u64()?), f64::from_bits(self.u64()?)),
+            16 => String(self.string()?.into()),
+            17 => Func(self.string()?, Box::new(self.func_type()?)),
+            18 => FuncBuiltin(self.string()?),
+            19 => {
+                let vals = self.values()?;
+                Value::new_array(vals, self.usize()?, self.vtype()?, &mut Unmanaged)
+            }
+            20 => {
+                let vals = self.values()?;
+                Value::new_slice(vals, self.vtype()?, &mut Unmanaged)
+            }
+            21 => Nil,
+            22 => IntLiteral(self.i128()?),
+            23 => FloatLiteral(f64::from_bits(self.u64()?)),
+            _ => return self.err("unknown value type"),
+        };
+
+        Ok(value)
+    }
+
+    fn values(&mut self) -> DecodeResult<Vec<Value>> {
+        let len = self.len()?;
+        let mut vals = Vec::with_capacity(len);
+        for _ in 0..len {
+            vals.push(self.value()?);
+        }
+
+        Ok(vals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::compile_valid;
+
+    #[test]
+    fn test_encode_decode() {
+        let src = r#"package main
+
+var total int = 0
+
+func sum(nums ...int) (int, bool) {
+    s := 0
+    for i := 0; i < len(nums); i++ {
+        s += nums[i]
+    }
+    return s, true
+}
+
+func grade(s string) int {
+    switch s {
+    case "a":
+        return 4
+    case "b":
+        return 3
+    case "c":
+        return 2
+    case "d":
+        return 1
+    }
+    return 0
+}
+
+func main() {
+    var arr [2]float64 = [2]float64{1.5, -2.0}
+    x, _ := sum(1, 2)
+    switch x {
+    case 3:
+        total = x
+    default:
+        println("no", arr[0])
+    }
+}
+"#;
+        let cunit = compile_valid(src);
+        let bytes = encode(&cunit);
+        assert!(is_bytecode(&bytes));
+
+        let decoded = decode(&bytes).unwrap();
+        assert_eq!(format!("{:?}", decoded), format!("{:?}", cunit));
+        assert_eq!(encode(&decoded), bytes);
+
+        // every truncation is reported, not panicked on
+        for len in 0..bytes.len() {
+            assert!(decode(&bytes[..len]).is_err());
+        }
+        assert!(decode(b"package main").is_err());
+
+        let mut other_version = bytes.clone();
+        other_version[MAGIC.len()] += 1;
+        let err = decode(&other_version).unwrap_err().to_string();
+        assert!(
+            err.contains("format version 5 produced by cogo_compiler"),
+            "{}",
+            err
+        );
+        assert!(err.contains("Recompile the program"), "{}", err);
+    }
+
+    #[test]
+    fn test_decode_invalid_positions() {
+        let decode_chunk = |runs: &[usize]| {
+            let mut encoder = Encoder(Vec::new());
+            encoder.usize(0);
+            encoder.usize(2);
+            encoder.op_code(&OpCode::Noop);
+            encoder.op_code(&OpCode::Noop);
+            encoder.usize(runs.len());
+            for &count in runs {
+                encoder.usize(count);
+                for _ in 0..4 {
+                    encoder.usize(1);
+                }
+            }
+
+            let mut decoder = Decoder {
+                bytes: &encoder.0,
+                at: 0,
+            };
+            decoder.chunk().map(|_| ())
+        };
+
+        assert!(decode_chunk(&[2]).is_ok());
+        assert!(decode_chunk(&[2, 0]).is_err());
+        assert!(decode_chunk(&[1]).is_err());
+        // the opcode counts would wrap around to the number of opcodes
+        assert!(decode_chunk(&[3, usize::MAX]).is_err());
+    }
+}
@@ -1,46 +1,282 @@
+use std::collections::{HashMap, HashSet};
 use std::mem;
+use std::ops::Range;
+use std::rc::Rc;
 
-use crate::error::CompileError;
+use crate::completion::{is_nameable, Completion, CompletionKind, CompletionQuery};
+use crate::error::{CompileError, CompilerError};
 use crate::flow::ControlFlow;
-use crate::lex::lexeme::{Lexeme, Token};
-use crate::lex::Lexer;
+use crate::init_order::{init_order, VarInit};
+use crate::jump_table::JumpTable;
+use crate::lex::lexeme::{Lexeme, Pos, Span, Token};
+use crate::lex::{LexemeStream, Lexer, KEYWORDS};
+use crate::lint::{Lint, LintRule, LintRules};
+use crate::refs::{Symbol, SymbolTable};
 use crate::scope::Scope;
+use crate::semantic::{token_class, SemanticToken, TokenClass};
 use crate::structure::{EntryPoint, Function, Package};
-use crate::unit::{CompilationUnit as CUnit, FuncUnit, PackageUnit};
+use crate::unit::{CompilationUnit as CUnit, FuncUnit, LocalVar, PackageUnit};
 use crate::vtype::{CompositeType, FuncType, ParamType};
-use crate::{ErrorHandler, OpCode, ValType, Value};
+use crate::{optimizer, Diagnostic, ErrorHandler, OpCode, ValType, Value};
+
+/// Options affecting the produced bytecode
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+    /// Run the optimizer over the emitted chunks
+    pub optimize: bool,
+}
 
 pub fn compile(src: &str, err_handler: &mut dyn ErrorHandler) -> CUnit {
-    let mut lexer = Lexer::new(src);
-    let (lexemes, errors) = lexer.lex();
+    compile_with_options(src, err_handler, &CompileOptions::default())
+}
 
-    if !errors.is_empty() {
-        err_handler.on_error(errors);
+pub fn compile_with_options(
+    src: &str,
+    err_handler: &mut dyn ErrorHandler,
+    options: &CompileOptions,
+) -> CUnit {
+    let parser = Compiler::new(src, Lexer::new(src));
+    let (mut cunit, errors) = parser.compile();
+
+    // the lex errors are reported on their own, before the ones they may have caused
+    let (lex_errors, errors): (Vec<_>, Vec<_>) = errors
+        .into_iter()
+        .partition(|err| matches!(err, CompilerError::Lex(_)));
+    if !lex_errors.is_empty() {
+        err_handler.on_error(&lex_errors);
     }
 
-    let mut parser = Compiler::new(lexemes);
-    let (cunit, errors) = parser.compile();
-
     if !errors.is_empty() {
-        err_handler.on_error(errors);
+        err_handler.on_error(&errors);
+    }
+
+    if options.optimize {
+        optimizer::optimize(&mut cunit);
     }
 
     cunit
 }
 
+/// Reports every problem found in the source without producing bytecode
+pub fn check(src: &str) -> Vec<Diagnostic> {
+    check_with_lints(src, &LintRules::default())
+}
+
+/// Same as `check()`, also reporting the warnings of the enabled lint rules after the errors
+pub fn check_with_lints(src: &str, rules: &LintRules) -> Vec<Diagnostic> {
+    let mut parser = Compiler::new(src, Lexer::new(src));
+    // a package other than the entry one is a library, it is checked without being run
+    parser.executable = false;
+    parser.lint_rules = rules.clone();
+    let (_, errors, lints) = parser.compile_linted();
+
+    errors
+        .iter()
+        .map(Diagnostic::from)
+        .chain(lints.iter().map(Diagnostic::from))
+        .collect()
+}
+
+/// Names visible at the byte offset of the source, for editor completion:
+/// the locals in scope there, innermost first, the package-level names and the imported packages,
+/// sorted by name, then the builtins and the keywords.
+/// The source is compiled to find them, errors in it are ignored
+pub fn completions(src: &str, offset: usize) -> Vec<Completion> {
+    let mut parser = Compiler::new(src, Lexer::new(src));
+    parser.executable = false;
+    parser.completion = Some(CompletionQuery::new(offset));
+    parser.complete_locals();
+    parser.compile_package();
+    let query = parser.completion.take().unwrap();
+
+    let mut package: Vec<Completion> = parser
+        .package_names
+        .iter()
+        .filter(|(name, _)| is_nameable(name))
+        .map(|(name, is_const)| {
+            let vtype = parser.global_types.get(name).cloned();
+            let kind = if *is_const {
+                CompletionKind::Constant
+            } else if parser.func_results.contains_key(name) {
+                CompletionKind::Function
+            } else {
+                CompletionKind::Global
+            };
+            Completion::new(name, kind, vtype)
+        })
+        .chain(
+            parser
+                .imports
+                .iter()
+                .map(|name| Completion::new(name, CompletionKind::Package, None)),
+        )
+        .collect();
+    package.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut completions = query.locals.unwrap_or_default();
+    completions.extend(package);
+    completions.extend(
+        BUILTINS
+            .iter()
+            .chain(&["true", "false", "nil"])
+            .filter(|name| is_nameable(name))
+            .map(|name| Completion::new(name, CompletionKind::Builtin, None)),
+    );
+    completions.extend(
+        KEYWORDS
+            .iter()
+            .map(|name| Completion::new(name, CompletionKind::Keyword, None)),
+    );
+
+    completions
+}
+
+/// Classes of the lexemes and comments of the source for semantic highlighting, in source order,
+/// punctuation and operators are left out.
+/// The source is compiled to tell what each identifier refers to, errors in it are ignored
+pub fn semantic_tokens(src: &str) -> Vec<SemanticToken> {
+    let mut parser = Compiler::new(src, Lexer::new(src).with_comments());
+    parser.executable = false;
+    parser.classes = Some(HashMap::new());
+    parser.compile_package();
+    let classes = parser.classes.take().unwrap();
+    parser.lexemes.finish();
+
+    let mut tokens: Vec<SemanticToken> = parser
+        .lexemes
+        .lexemes()
+        .iter()
+        .enumerate()
+        .filter_map(|(i, lexeme)| {
+            let class = match lexeme.token {
+                Token::Identifier => match classes.get(&i) {
+                    Some(class) => *class,
+                    None => parser.global_class(&lexeme.literal),
+                },
+                token => token_class(token)?,
+            };
+            Some(SemanticToken {
+                class,
+                pos: lexeme.pos,
+                span: lexeme.span,
+            })
+        })
+        .collect();
+
+    tokens.extend(
+        parser
+            .lexemes
+            .lexer()
+            .comments()
+            .iter()
+            .map(|comment| SemanticToken {
+                class: TokenClass::Comment,
+                pos: comment.pos,
+                span: comment.span,
+            }),
+    );
+    tokens.sort_by_key(|token| token.span.start);
+
+    tokens
+}
+
+/// Spans of the identifiers referring to variables, constants and functions,
+/// along with what they refer to, in source order
+pub(crate) fn symbols(src: &str) -> Vec<(Span, Symbol)> {
+    let mut parser = Compiler::new(src, Lexer::new(src));
+    parser.executable = false;
+    parser.symbols = Some(SymbolTable::default());
+    parser.compile_package();
+    let table = parser.symbols.take().unwrap();
+
+    let mut symbols: Vec<(Span, Symbol)> = table
+        .uses
+        .into_iter()
+        .filter(|(_, symbol)| match symbol {
+            Symbol::Local(_) => true,
+            // builtins and undefined names
+            Symbol::Global(name) => parser.package_names.contains_key(name),
+        })
+        .map(|(lexeme, symbol)| (parser.lexemes[lexeme].span, symbol))
+        .collect();
+    symbols.sort_by_key(|(span, _)| span.start);
+
+    symbols
+}
+
 struct Compiler<'a> {
-    lexemes: &'a [Lexeme],
+    src: &'a str,
+    lexemes: LexemeStream<'a>,
     current: usize,
     cunit: CUnit,
-    errs: Vec<Box<dyn std::error::Error>>,
+    errs: Vec<CompilerError>,
     panic: bool,
     scope: Scope,
     control_flow: ControlFlow,
     cur_package: Option<Package>,
+    /// Names of the imported packages
+    imports: Vec<String>,
+    /// Package-level names declared so far, mapped to whether they are constants
+    package_names: HashMap<String, bool>,
+    /// Whether a const initializer is being compiled, it may only hold constant expressions
+    const_expr: bool,
+    /// Index of the const spec being compiled within its declaration, the value of `iota`
+    iota: usize,
+    /// Type and initializer lexemes of the previous spec of a const group,
+    /// repeated by the specs that omit them
+    const_spec: Option<(Option<ValType>, Range<usize>)>,
+    /// Whether the expression being parsed is an operand of an enclosing operator
+    operand: bool,
+    /// First opcode of the left operand of the binary operator being parsed
+    operand_start: usize,
+    /// Type the untyped integer constants of the initializer being parsed are converted to
+    constant_type: Option<ValType>,
     assign_start: usize,
+    /// Token ending the expression statement being compiled,
+    /// which is the only place `++` and `--` may appear
+    inc_dec_end: Option<Token>,
     multi_count: usize,
     composite_return: bool,
+    /// Index of the last emitted call of the function being compiled to itself
+    self_call: Option<usize>,
+    /// Index of the last emitted call to a builtin that cannot be used as a statement, e.g. `len`
+    value_call: Option<usize>,
     entry_point: EntryPoint,
+    entry_point_declared: bool,
+    /// Whether the program is compiled to be run, which takes the package of the entry point
+    executable: bool,
+    /// Internal names of the `init` functions in the declaration order
+    inits: Vec<String>,
+    /// Package-level names referred to by the package-level declaration being compiled
+    package_refs: Vec<String>,
+    /// Package-level variables and constants, initialized in dependency order
+    var_inits: Vec<VarInit>,
+    /// Package-level names each function refers to
+    func_refs: HashMap<String, Vec<String>>,
+    /// Number of values each package-level function returns
+    func_results: HashMap<String, usize>,
+    /// Calls assigned to several names, as the callee, the name count and the callee lexeme,
+    /// checked once every function is declared
+    multi_calls: Vec<(String, usize, usize)>,
+    /// Builtins a package-level declaration may shadow, calls to them are resolved at runtime
+    shadowed_builtins: HashSet<String>,
+    /// Types of the package-level names declared so far, declared or known from the initializer
+    global_types: HashMap<String, ValType>,
+    /// Values of the package-level constants declared so far, if the compiler could evaluate them
+    const_values: HashMap<String, Value>,
+    /// Index of the last emitted `GetIndex` along with the type of the element it loads, if known
+    index_type: Option<(usize, ValType)>,
+    lint_rules: LintRules,
+    /// Warnings of the enabled lint rules
+    lints: Vec<Lint>,
+    /// Names visible at a position, gathered for `completions()`
+    completion: Option<CompletionQuery>,
+    /// Classes of the identifiers by lexeme index, gathered for `semantic_tokens()`
+    classes: Option<HashMap<usize, TokenClass>>,
+    /// Declarations the identifiers refer to, gathered for `references()`
+    symbols: Option<SymbolTable>,
+    /// Variables of the function being compiled, kept in its unit for debuggers
+    locals: Vec<LocalVar>,
 }
 
 type ParseCallback<T> = fn(&mut T, bool);
@@ -51,9 +287,10 @@ type ParseRule<T> = (
 );
 
 impl<'a> Compiler<'a> {
-    fn new(lexemes: &'a [Lexeme]) -> Self {
+    fn new(src: &'a str, lexer: Lexer<'a>) -> Self {
         Self {
-            lexemes,
+            src,
+            lexemes: LexemeStream::new(lexer),
             current: 0,
             cunit: CUnit::Package(PackageUnit::new()),
             errs: Vec::new(),
@@ -61,16 +298,67 @@ impl<'a> Compiler<'a> {
             scope: Scope::new(),
             control_flow: ControlFlow::new(),
             cur_package: None,
+            imports: Vec::new(),
+            package_names: HashMap::new(),
+            const_expr: false,
+            iota: 0,
+            const_spec: None,
+            operand: false,
+            operand_start: 0,
+            constant_type: None,
             assign_start: 0,
+            inc_dec_end: None,
+            value_call: None,
             multi_count: 0,
             composite_return: false,
+            self_call: None,
             entry_point: EntryPoint::new(Package("main".to_string()), Function("main".to_string())),
+            entry_point_declared: false,
+            executable: true,
+            inits: Vec::new(),
+            package_refs: Vec::new(),
+            var_inits: Vec::new(),
+            func_refs: HashMap::new(),
+            func_results: HashMap::new(),
+            multi_calls: Vec::new(),
+            shadowed_builtins: shadowed_builtins(Lexer::new(src)),
+            global_types: HashMap::new(),
+            const_values: HashMap::new(),
+            index_type: None,
+            lint_rules: LintRules::default(),
+            lints: Vec::new(),
+            completion: None,
+            classes: None,
+            symbols: None,
+            locals: Vec::new(),
         }
     }
 
     /// Compilation entry point
-    /// Returns Compilation Unit ("main" package) and a slice of errors
-    fn compile(&mut self) -> (CUnit, &[Box<dyn std::error::Error>]) {
+    /// Returns Compilation Unit ("main" package) and the errors found
+    fn compile(self) -> (CUnit, Vec<CompilerError>) {
+        let (cunit, errs, _) = self.compile_linted();
+        (cunit, errs)
+    }
+
+    /// Same as `compile()`, also returning the warnings of the enabled lint rules
+    /// The lex errors come first
+    fn compile_linted(mut self) -> (CUnit, Vec<CompilerError>, Vec<Lint>) {
+        self.compile_package();
+        self.lexemes.finish();
+
+        let errs = self
+            .lexemes
+            .errors()
+            .iter()
+            .cloned()
+            .map(CompilerError::Lex)
+            .chain(self.errs)
+            .collect();
+        (self.cunit, errs, self.lints)
+    }
+
+    fn compile_package(&mut self) {
         self.add_code(OpCode::Noop);
         self.decl_package();
 
@@ -78,8 +366,10 @@ impl<'a> Compiler<'a> {
             self.decl();
         }
 
+        self.validate_multi_calls();
+        self.order_package_vars();
+        self.validate_entry_point_declared();
         self.add_entry_point();
-        (self.cunit.clone(), &self.errs)
     }
 
     /// Parses package declaration statement
@@ -91,6 +381,7 @@ impl<'a> Compiler<'a> {
 
         if self.consume_if(Token::Package) {
             let name = self.parse_name().to_string();
+            self.classify(self.current - 1, TokenClass::Package);
             if let CUnit::Package(p) = &mut self.cunit {
                 let package = Package(name);
                 p.set_package(package.clone());
@@ -107,7 +398,9 @@ impl<'a> Compiler<'a> {
 
     /// Various types of declarations (including group declarations)
     fn decl(&mut self) {
-        if self.consume_if(Token::Var) {
+        if self.consume_if(Token::Import) {
+            self.decl_group_import();
+        } else if self.consume_if(Token::Var) {
             self.decl_group_var();
         } else if self.consume_if(Token::Const) {
             self.decl_group_const();
@@ -122,15 +415,131 @@ impl<'a> Compiler<'a> {
         }
     }
 
+    fn decl_group_import(&mut self) {
+        if !self.is_package_scope() {
+            self.err("Imports can be only in package level".to_string());
+            return;
+        }
+
+        if self.consume_if(Token::LeftParen) {
+            while !self.check(Token::RightParen) && !self.check(Token::Eof) {
+                self.decl_import();
+                if self.panic {
+                    return;
+                }
+            }
+            self.consume(Token::RightParen);
+            self.consume(Token::Semicolon);
+        } else {
+            self.decl_import();
+        }
+    }
+
+    /// Import of one of the packages the VM provides, e.g. `import "fmt"`
+    fn decl_import(&mut self) {
+        self.consume(Token::StringLiteral);
+        if self.panic {
+            return;
+        }
+
+        let path = self.prev().literal.clone();
+        if !SUPPORTED_PACKAGES.contains(&path.as_str()) {
+            self.err_prev(format!("Package \"{}\" is not found.", path));
+            // the declaration itself is well-formed, there is nothing to recover from
+            self.panic = false;
+        } else if self.imports.contains(&path) {
+            self.err_prev(format!("Package \"{}\" is imported twice.", path));
+            self.panic = false;
+        } else {
+            self.imports.push(path);
+        }
+
+        self.consume(Token::Semicolon);
+    }
+
     fn decl_group_var(&mut self) {
         if self.consume_if(Token::LeftParen) {
-            while !self.consume_if(Token::RightParen) {
-                self.decl_var();
+            while !self.check(Token::RightParen) && !self.check(Token::Eof) {
+                self.decl_package_var(Self::decl_var);
+                if self.panic {
+                    return;
+                }
             }
+            self.consume(Token::RightParen);
             self.consume(Token::Semicolon);
         } else {
-            self.decl_var();
+            self.decl_package_var(Self::decl_var);
+        }
+    }
+
+    /// Package-level variables and constants are initialized in dependency order rather than
+    /// in the textual one, so the opcodes of each declaration are recorded along with
+    /// the package-level names it refers to
+    fn decl_package_var(&mut self, decl: fn(&mut Self)) {
+        if !self.is_global_scope() {
+            decl(self);
+            return;
+        }
+
+        let (start, first) = (self.code_len(), self.current);
+        self.package_refs.clear();
+        decl(self);
+
+        let codes = start..self.code_len();
+        let names = self.cunit.chunk().codes()[codes.clone()]
+            .iter()
+            .filter_map(|code| match code {
+                OpCode::VarGlobal(name, _) | OpCode::ConstGlobal(name, _) => Some(name.clone()),
+                _ => None,
+            })
+            .collect();
+        self.var_inits.push(VarInit {
+            names,
+            refs: mem::take(&mut self.package_refs),
+            codes,
+            decl: first,
+        });
+    }
+
+    fn validate_multi_calls(&mut self) {
+        for (name, expect, lexeme) in mem::take(&mut self.multi_calls) {
+            match self.func_results.get(&name) {
+                Some(results) if *results != expect => {
+                    let msg = format!("assignment count mismatch {} = {}", expect, results);
+                    self.err_lexeme(lexeme, msg);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Functions are defined before any package-level variable is initialized,
+    /// the variables follow in the order their dependencies dictate
+    fn order_package_vars(&mut self) {
+        if !self.errs.is_empty() {
+            return;
+        }
+
+        let order = match init_order(&self.var_inits, &self.func_refs) {
+            Ok(order) => order,
+            Err(i) => {
+                let init = &self.var_inits[i];
+                let msg = format!("Initialization cycle for \"{}\".", init.names.join(", "));
+                self.err_lexeme(init.decl, msg);
+                return;
+            }
+        };
+
+        let mut ranges = vec![];
+        let mut start = 0;
+        for init in &self.var_inits {
+            ranges.push(start..init.codes.start);
+            start = init.codes.end;
         }
+        ranges.push(start..self.code_len());
+        ranges.extend(order.into_iter().map(|i| self.var_inits[i].codes.clone()));
+
+        self.cunit.chunk_mut().reorder(&ranges);
     }
 
     fn decl_var(&mut self) {
@@ -153,19 +562,28 @@ impl<'a> Compiler<'a> {
 
         names.reverse();
         if self.consume_if(Token::Equal) {
+            self.constant_type = Some(vtype.clone().unwrap_or(ValType::Int));
             self.expr_multi_assign_validation(names.len());
+            let inferred = self.initializer_type(names.len());
 
             for (i, name) in names.iter().enumerate() {
                 self.def_var(name.clone(), vtype.clone(), true, true, i);
             }
-        } else {
             if vtype.is_none() {
-                self.err("Type declaration expected.".to_string());
+                self.infer_type(&names[0], inferred);
             }
+        } else {
+            let vtype = match vtype {
+                Some(vtype) => vtype,
+                None => {
+                    self.err("Type declaration expected.".to_string());
+                    return;
+                }
+            };
 
             for (i, name) in names.iter().enumerate() {
-                self.add_code(OpCode::PutDefaultValue(vtype.clone().unwrap()));
-                self.def_var(name.clone(), vtype.clone(), false, true, i);
+                self.add_code(OpCode::PutDefaultValue(vtype.clone()));
+                self.def_var(name.clone(), Some(vtype.clone()), false, true, i);
             }
         }
 
@@ -201,11 +619,42 @@ impl<'a> Compiler<'a> {
         }
 
         self.consume(Token::ColonEqual);
+        self.constant_type = Some(ValType::Int);
         self.expr_multi_assign_validation(names.len());
+        let inferred = self.initializer_type(names.len());
 
         for (i, name) in names.iter().enumerate() {
             self.def_var(name.clone(), None, false, true, i);
         }
+        if let Some(name) = names.first() {
+            self.infer_type(name, inferred);
+        }
+    }
+
+    /// Type of the value of a declaration of a single name, if the compiler knows it
+    /// from the opcode loading the initializer, e.g. an array literal
+    fn initializer_type(&self, name_count: usize) -> Option<ValType> {
+        if name_count != 1 {
+            return None;
+        }
+
+        self.static_type(self.code_len().checked_sub(1)?)
+    }
+
+    /// Records the type of the variable just declared without one,
+    /// the type is known to editor queries and to constant index checks
+    fn infer_type(&mut self, name: &str, vtype: Option<ValType>) {
+        let Some(vtype) = vtype else {
+            return;
+        };
+
+        if self.is_global_scope() {
+            self.global_types.entry(name.to_string()).or_insert(vtype);
+        } else if let Some(var) = self.scope.vars.last_mut() {
+            if var.name == name && var.vtype.is_none() {
+                var.vtype = Some(vtype);
+            }
+        }
     }
 
     //FIXME change flags
@@ -218,39 +667,79 @@ impl<'a> Compiler<'a> {
         pos: usize,
     ) {
         if self.is_global_scope() {
+            if let Some(vtype) = &vtype {
+                self.global_types.insert(name.clone(), vtype.clone());
+            }
             self.add_code(OpCode::VarGlobal(name, vtype));
         } else {
             //FIXME change logic
+            if validate || litcast {
+                // an array assigned to the variable is copied
+                self.add_code(OpCode::LoseSoftReference(pos));
+            }
+
             if validate {
-                if let Some(vtype) = vtype {
+                if let Some(vtype) = vtype.clone() {
                     self.add_code(OpCode::TypeValidation(vtype, pos));
                 } else if litcast {
                     self.add_code(OpCode::BlindLiteralCast(pos));
                 }
             } else if litcast {
-                self.add_code(OpCode::LoseSoftReference(pos));
                 self.add_code(OpCode::BlindLiteralCast(pos));
             }
 
-            self.scope.init_last();
+            self.init_local(vtype);
+        }
+    }
+
+    /// Initialises the local declared last, remembering where it is declared for `references()`
+    /// and the slot it takes from here on for debuggers
+    fn init_local(&mut self, vtype: Option<ValType>) {
+        let Some(i) = self.scope.init_last_as(vtype) else {
+            return;
+        };
+        if let Some(table) = &self.symbols {
+            self.scope.vars[i].decl = table.declared_at.get(&self.scope.vars[i].name).copied();
         }
+
+        let pos = self.current().pos;
+        self.scope.vars[i].debug = Some(self.locals.len());
+        self.locals.push(LocalVar {
+            name: self.scope.vars[i].name.clone(),
+            slot: i,
+            start: pos,
+            end: pos,
+        });
     }
 
     fn decl_group_const(&mut self) {
+        self.iota = 0;
+        self.const_spec = None;
+
         if self.consume_if(Token::LeftParen) {
-            while !self.consume_if(Token::RightParen) {
-                self.decl_const();
+            while !self.check(Token::RightParen) && !self.check(Token::Eof) {
+                self.decl_package_var(Self::decl_const);
+                if self.panic {
+                    return;
+                }
+                self.iota += 1;
             }
+            self.consume(Token::RightParen);
             self.consume(Token::Semicolon);
         } else {
-            self.decl_const();
+            self.decl_package_var(Self::decl_const);
         }
+
+        self.const_spec = None;
     }
 
     fn decl_const(&mut self) {
         let mut names: Vec<String> = vec![];
+        let (values_start, first);
+        let spec = self.current;
         loop {
             let name = self.parse_name().to_string();
+            self.classify(self.current - 1, TokenClass::Constant);
             self.decl_scoped_const(name.clone());
             names.push(name);
 
@@ -259,16 +748,61 @@ impl<'a> Compiler<'a> {
             }
         }
 
-        let vtype = if !self.check(Token::Equal) {
-            Some(self.parse_type())
+        let vtype = if self.check_in(&[Token::Semicolon, Token::RightParen]) {
+            // the type and the initializer of the previous spec are repeated
+            let (vtype, expr) = match self.const_spec.clone() {
+                Some(spec) => spec,
+                None => {
+                    // the spec is well-formed otherwise, so the group goes on
+                    self.err("Missing init expression for const declaration.".to_string());
+                    self.panic = false;
+                    self.consume(Token::Semicolon);
+                    return;
+                }
+            };
+
+            let resume = self.current;
+            self.current = expr.start;
+            values_start = self.code_len();
+            first = spec;
+            self.const_expr = true;
+            self.expr_const();
+            self.const_expr = false;
+            self.current = resume;
+
+            vtype
         } else {
-            None
+            let vtype = if !self.check(Token::Equal) {
+                Some(self.parse_type())
+            } else {
+                None
+            };
+            self.consume(Token::Equal);
+
+            let start = self.current;
+            values_start = self.code_len();
+            first = start;
+            self.const_expr = true;
+            self.expr_const();
+            self.const_expr = false;
+            self.const_spec = Some((vtype.clone(), start..self.current));
+
+            vtype
         };
-        self.consume(Token::Equal);
-        self.expr_const();
+
+        let values = self
+            .constant_values(values_start)
+            .filter(|values| values.len() == names.len());
+        if let (Some(vtype), Some(values)) = (&vtype, &values) {
+            self.validate_typed_constants(vtype, values, first);
+        }
+        self.record_const_values(&names, values);
 
         for (i, name) in names.iter().rev().enumerate() {
             if self.is_global_scope() {
+                if let Some(vtype) = &vtype {
+                    self.global_types.insert(name.clone(), vtype.clone());
+                }
                 self.add_code(OpCode::ConstGlobal(name.clone(), vtype.clone()));
             } else {
                 if let Some(vtype) = vtype.clone() {
@@ -277,20 +811,77 @@ impl<'a> Compiler<'a> {
                     self.add_code(OpCode::BlindLiteralCast(i));
                 }
 
-                self.scope.init_last();
+                self.init_local(vtype.clone());
             }
         }
 
         self.consume(Token::Semicolon);
     }
 
+    /// Remembers the values of the constants just declared, for constant index checks
+    fn record_const_values(&mut self, names: &[String], values: Option<Vec<Value>>) {
+        let Some(values) = values else {
+            return;
+        };
+
+        if self.is_global_scope() {
+            for (name, value) in names.iter().zip(values) {
+                self.const_values.insert(name.clone(), value);
+            }
+        } else {
+            // the names are the last ones declared
+            let first = self.scope.vars.len() - names.len();
+            for (var, value) in self.scope.vars[first..].iter_mut().zip(values) {
+                var.value = Some(value);
+            }
+        }
+    }
+
     fn decl_func(&mut self) {
         let name = self.parse_name().to_string();
+        self.classify(self.current - 1, TokenClass::Function);
+        if name == INIT_FUNC {
+            self.decl_init();
+            return;
+        }
+
+        self.decl_package_name(name.clone(), false);
+        self.package_refs.clear();
+        let ftype = self.func(Some(Function(name.clone())));
+        let refs = mem::take(&mut self.package_refs);
+        self.func_refs.insert(name.clone(), refs);
+        self.func_results
+            .insert(name.clone(), ftype.ret_type().len());
+        self.def_var(name, Some(ValType::Func(Box::new(ftype))), false, false, 0);
+    }
+
+    /// A package may have any number of `init` functions, none of them can be referred to,
+    /// so each one gets a name that is not a valid identifier
+    fn decl_init(&mut self) {
+        let decl = self.current - 1;
+        let name = format!("{}.{}", INIT_FUNC, self.inits.len());
+        self.inits.push(name.clone());
+
         let ftype = self.func(Some(Function(name.clone())));
+        if !ftype.args().is_empty() || !ftype.ret_type().is_void() {
+            self.err_lexeme(
+                decl,
+                format!(
+                    "Function \"{}\" must not have parameters and a return value",
+                    INIT_FUNC
+                ),
+            );
+        }
         self.def_var(name, Some(ValType::Func(Box::new(ftype))), false, false, 0);
     }
 
     fn func(&mut self, name: Option<Function>) -> FuncType {
+        // the name has just been parsed
+        let decl = self.current - 1;
+        let outer_locals = mem::take(&mut self.locals);
+        // loops and switches of the enclosing unit, only malformed source declares a function in them
+        let outer_flow = mem::replace(&mut self.control_flow, ControlFlow::new());
+        let outer_slots = mem::replace(&mut self.scope.slots, self.scope.vars.len());
         self.begin_scope();
         self.consume(Token::LeftParen);
 
@@ -385,12 +976,16 @@ impl<'a> Compiler<'a> {
 
         let mut cunit = mem::replace(&mut self.cunit, cunit);
         if let CUnit::Function(funit) = &mut cunit {
-            match self
-                .entry_point
-                .check(self.cur_package.as_ref().unwrap(), funit)
-            {
-                Ok(()) => {}
-                Err(e) => self.err(e.0),
+            funit.set_locals(mem::replace(&mut self.locals, outer_locals));
+            funit.set_slots(mem::replace(&mut self.scope.slots, outer_slots));
+            self.control_flow = outer_flow;
+            if let Some(package) = &self.cur_package {
+                if self.entry_point.is_entry_point(package, funit) {
+                    self.entry_point_declared = true;
+                }
+                if let Err(e) = self.entry_point.check(package, funit) {
+                    self.err_lexeme(decl, e.0);
+                }
             }
         } else {
             panic!("Compilation unit must be of function type");
@@ -401,16 +996,18 @@ impl<'a> Compiler<'a> {
         ftype
     }
 
-    fn rule(&self, mut t: &Token) -> ParseRule<Self> {
+    fn rule(&mut self, mut t: &Token) -> ParseRule<Self> {
         if matches!(
             t,
             Token::Int
                 | Token::Int8
                 | Token::Int16
                 | Token::Int32
+                | Token::Rune
                 | Token::Int64
                 | Token::Uint
                 | Token::Uint8
+                | Token::Byte
                 | Token::Uint16
                 | Token::Uint32
                 | Token::Uint64
@@ -456,6 +1053,7 @@ impl<'a> Compiler<'a> {
             Token::StringLiteral => (Some(Self::string), None, Precedence::None),
             Token::IntLiteral => (Some(Self::int), None, Precedence::None),
             Token::FloatLiteral => (Some(Self::float), None, Precedence::None),
+            Token::ImagLiteral => (Some(Self::imag), None, Precedence::None),
             Token::LogicAnd => (None, Some(Self::and), Precedence::And),
             Token::LogicOr => (None, Some(Self::or), Precedence::Or),
             Token::Struct => (None, None, Precedence::None),
@@ -471,21 +1069,28 @@ impl<'a> Compiler<'a> {
             Token::Const => (None, None, Precedence::None),
             Token::Ellipsis => (None, None, Precedence::None),
             Token::Eof => (None, None, Precedence::None),
-            tok => panic!("Unknown token {}", tok),
+            tok => {
+                self.err(format!("Unexpected token \"{}\"", tok));
+                (None, None, Precedence::None)
+            }
         }
     }
 
     fn current(&self) -> &Lexeme {
-        &self.lexemes[self.current]
+        &self.lexemes[self.current.min(self.lexemes.len() - 1)]
     }
 
     fn prev(&self) -> &Lexeme {
-        &self.lexemes[self.current - 1]
+        &self.lexemes[self.current.saturating_sub(1)]
     }
 
     fn consume(&mut self, tok: Token) {
         if self.current().token == tok {
             self.advance();
+        } else if tok == Token::Semicolon
+            && self.check_in(&[Token::RightParen, Token::RightCurlyBrace])
+        {
+            // a semicolon may be omitted before a closing ")" or "}"
         } else {
             self.err(format!(
                 "Expected token \"{}\", got \"{}\"",
@@ -512,11 +1117,11 @@ impl<'a> Compiler<'a> {
         toks.contains(&self.current().token)
     }
 
-    fn check_rhs(&self, search: Token) -> bool {
+    fn check_rhs(&mut self, search: Token) -> bool {
         let mut start = self.current;
 
         loop {
-            let tok = self.lexemes[start].token;
+            let tok = self.lexemes.lookahead(start).token;
 
             if tok == search {
                 break true;
@@ -530,8 +1135,50 @@ impl<'a> Compiler<'a> {
         }
     }
 
+    /// Moves to the next lexeme, at most one past the trailing `Eof`,
+    /// so that `prev()` is `Eof` once it has been consumed
     fn advance(&mut self) {
-        self.current += 1;
+        if self.current < self.lexemes.len() {
+            self.current += 1;
+            self.lexemes.fill(self.current);
+        }
+
+        if matches!(&self.completion, Some(query) if query.locals.is_none()) {
+            self.complete_locals();
+        }
+    }
+
+    /// Takes the locals in scope once the lexeme to be compiled next reaches the queried offset
+    fn complete_locals(&mut self) {
+        let offset = self.completion.as_ref().map_or(0, |query| query.offset);
+        let reached = match self.lexemes.get(self.current) {
+            Some(lexeme) => lexeme.span.end >= offset,
+            None => true,
+        };
+        if !reached {
+            return;
+        }
+
+        let mut locals: Vec<Completion> = vec![];
+        for var in self.scope.vars.iter().rev() {
+            if !var.is_initialised()
+                || !is_nameable(&var.name)
+                || locals.iter().any(|local| local.name == var.name)
+            {
+                continue;
+            }
+
+            let kind = if var.mutable {
+                CompletionKind::Local
+            } else {
+                CompletionKind::Constant
+            };
+            locals.push(Completion::new(&var.name, kind, var.vtype.clone()));
+        }
+
+        if let Some(query) = &mut self.completion {
+            query.locals = Some(locals);
+        }
     }
 
     fn rollback(&mut self) {
@@ -539,9 +1186,61 @@ impl<'a> Compiler<'a> {
     }
 
     fn err(&mut self, msg: String) {
+        let lexeme = self.current();
+        let (pos, span) = (lexeme.pos, lexeme.span);
+        self.err_at(msg, pos, span);
+    }
+
+    /// Error pointing to the lexeme just consumed, e.g. a malformed literal
+    fn err_prev(&mut self, msg: String) {
+        let lexeme = self.prev();
+        let (pos, span) = (lexeme.pos, lexeme.span);
+        self.err_at(msg, pos, span);
+    }
+
+    /// Part of a const initializer that cannot be evaluated at compile time
+    fn err_not_constant(&mut self, lexeme: usize, what: &str) {
+        self.err_lexeme(lexeme, format!("{} is not constant.", what));
+    }
+
+    /// Error pointing to an earlier lexeme of a well-formed statement,
+    /// so the compilation goes on
+    fn err_lexeme(&mut self, lexeme: usize, msg: String) {
+        let lexeme = &self.lexemes[lexeme.min(self.lexemes.len() - 1)];
+        let (pos, span) = (lexeme.pos, lexeme.span);
+        let panic = self.panic;
+        self.err_at(msg, pos, span);
+        self.panic = panic;
+    }
+
+    /// Errors following the first one are mostly caused by it,
+    /// so nothing else is reported until the compiler recovers
+    fn err_at(&mut self, msg: String, pos: Pos, span: Span) {
+        if self.panic {
+            return;
+        }
+
         self.panic = true;
-        self.errs
-            .push(Box::new(CompileError(msg, self.current().pos)));
+        let err = CompileError::new(msg, pos, span).with_source(self.src);
+        self.errs.push(CompilerError::Compile(err));
+    }
+
+    /// Warning of the lint rule about the lexemes in the range, which must not be empty
+    fn lint(&mut self, rule: LintRule, lexemes: Range<usize>, message: String) {
+        let first = &self.lexemes[lexemes.start];
+        let span = Span::new(first.span.start, self.lexemes[lexemes.end - 1].span.end);
+        self.lints.push(Lint {
+            rule,
+            message,
+            pos: first.pos,
+            span,
+        });
+    }
+
+    /// Source code of the lexemes in the range, which must not be empty
+    fn source_of(&self, lexemes: Range<usize>) -> &'a str {
+        let end = self.lexemes[lexemes.end - 1].span.end;
+        &self.src[self.lexemes[lexemes.start].span.start..end]
     }
 
     fn recover(&mut self) {
@@ -553,6 +1252,8 @@ impl<'a> Compiler<'a> {
             }
             match self.current().token {
                 Struct | Func | Var | If | For | Return | Switch | Const => return,
+                // the enclosing block is left to be closed
+                RightCurlyBrace if !self.is_package_scope() => return,
                 _ => {}
             }
 
@@ -589,15 +1290,16 @@ impl<'a> Compiler<'a> {
 
     /// Simple expression or an empty expression with a semicolon
     fn stmt_simple(&mut self) {
-        self.expr_simple();
+        self.expr_simple(Token::Semicolon);
         self.consume(Token::Semicolon);
     }
 
-    fn expr_simple(&mut self) {
+    /// Simple statement followed by `end`
+    fn expr_simple(&mut self, end: Token) {
         if self.check_rhs(Token::ColonEqual) {
             self.expr_decl_short_var();
         } else {
-            self.expr_expr();
+            self.expr_expr(end);
         };
     }
 
@@ -607,9 +1309,7 @@ impl<'a> Compiler<'a> {
             return;
         }
 
-        self.add_code(OpCode::BackJump(
-            self.code_len() - self.control_flow.continue_jump(),
-        ));
+        self.add_code(OpCode::Jump(self.control_flow.continue_jump()));
     }
 
     fn stmt_break(&mut self) {
@@ -632,18 +1332,86 @@ impl<'a> Compiler<'a> {
     }
 
     fn stmt_return(&mut self) {
-        //FIXME add package checking type
+        let keyword = self.current - 1;
+        let ret_type = match &self.cunit {
+            CUnit::Function(funit) => funit.func_type().ret_type().clone(),
+            CUnit::Package(_) => {
+                self.err_prev("Return statement outside of function body.".to_string());
+                return;
+            }
+        };
+
         if self.consume_if(Token::Semicolon) {
+            self.validate_return_count(keyword, &ret_type, 0);
             self.add_code(OpCode::Return(0));
         } else {
             self.multi_count = 1;
+            self.self_call = None;
+            let start = self.code_len();
             self.expr();
             self.consume(Token::Semicolon);
-            self.add_code(OpCode::Return(self.multi_count as u8));
+
+            // `return f(...)` inside of `f` reuses the frame instead of returning
+            let last = self.last_op_code_index();
+            match self.cunit.chunk().codes().get(last) {
+                Some(OpCode::Call(args, spread)) if self.self_call == Some(last) => {
+                    let tail_call = OpCode::TailCall(*args, *spread);
+                    self.cunit.chunk_mut().write_at(last, tail_call);
+                }
+                // a single call may return several values, it is validated at runtime
+                Some(OpCode::Call(..) | OpCode::CallBuiltin(..)) if self.multi_count == 1 => {
+                    self.add_code(OpCode::Return(1));
+                }
+                _ => {
+                    self.validate_return_count(keyword, &ret_type, self.multi_count);
+                    self.validate_return_constant(keyword, &ret_type, start);
+                    self.add_code(OpCode::Return(self.multi_count as u8));
+                }
+            }
             self.multi_count = 0;
         }
     }
 
+    fn validate_return_count(&mut self, keyword: usize, ret_type: &CompositeType, count: usize) {
+        let expected = ret_type.len();
+        if count == expected {
+            return;
+        }
+
+        let msg = if count > expected {
+            "Too many return values"
+        } else {
+            "Not enough return values"
+        };
+        self.err_lexeme(
+            keyword,
+            format!("{}, expected {}, got {}.", msg, expected, count),
+        );
+    }
+
+    /// Only a single constant is known to be of a wrong type before the program runs
+    fn validate_return_constant(&mut self, keyword: usize, ret_type: &CompositeType, start: usize) {
+        let constant = match self.cunit.chunk().codes() {
+            [.., OpCode::Constant(i)] if self.code_len() == start + 1 => {
+                self.cunit.chunk().constant(*i).clone()
+            }
+            _ => return,
+        };
+
+        if let [vtype] = ret_type.types() {
+            if !constant.is_of_type(vtype) {
+                self.err_lexeme(
+                    keyword,
+                    format!(
+                        "Cannot use value of type \"{}\" as \"{}\" in return statement.",
+                        constant.get_type(),
+                        vtype
+                    ),
+                );
+            }
+        }
+    }
+
     fn begin_scope(&mut self) {
         self.scope.depth += 1;
     }
@@ -678,14 +1446,20 @@ impl<'a> Compiler<'a> {
             remove_indices.push(i);
         }
 
+        let end = self.current().pos;
         for rem in remove_indices {
             self.add_code(OpCode::Pop);
-            self.scope.vars.remove(rem);
+            let debug = self.scope.vars.remove(rem).debug;
+            // a malformed function may end the scope of another one's locals
+            if let Some(local) = debug.and_then(|i| self.locals.get_mut(i)) {
+                local.end = end;
+            }
         }
     }
 
     fn err_if_package(&mut self) {
-        if self.is_package_scope() {
+        // a misplaced return statement is reported by the statement itself
+        if self.is_package_scope() && !self.check(Token::Return) {
             self.err(format!("Unexpected token {}", self.current().token));
         }
     }
@@ -703,13 +1477,53 @@ impl<'a> Compiler<'a> {
         self.consume_if(Token::Semicolon);
     }
 
-    fn expr_expr(&mut self) {
+    fn expr_expr(&mut self, end: Token) {
         if !self.check(Token::Semicolon) {
+            let (start, errs) = (self.current, self.errs.len());
+            self.inc_dec_end = Some(end);
             self.expr();
+            self.inc_dec_end = None;
+            // an invalid expression is reported already,
+            // and one not followed by `end` is a condition, e.g. in `if x > 0 {`
+            if errs == self.errs.len() && self.check(end) {
+                self.validate_expr_stmt(start);
+            }
             self.add_code(OpCode::Pop);
         }
     }
 
+    /// Only calls, assignments and `++`, `--` may be used as statements
+    fn validate_expr_stmt(&mut self, start: usize) {
+        if self.panic || self.current <= start {
+            return;
+        }
+
+        let last = self.last_op_code_index();
+        let used = match self.cunit.chunk().codes().get(last) {
+            Some(OpCode::Call(..) | OpCode::CallBuiltin(..)) => self.value_call != Some(last),
+            Some(
+                OpCode::SetLocal(_)
+                | OpCode::SetGlobal(_)
+                | OpCode::SetIndex
+                | OpCode::SetLocalIndex(..)
+                | OpCode::SetGlobalIndex(..),
+            ) => true,
+            _ => false,
+        };
+
+        if !used {
+            let (first, prev) = (&self.lexemes[start], self.prev());
+            let span = Span::new(first.span.start, prev.span.end);
+            let msg = format!(
+                "\"{}\" evaluated but not used.",
+                &self.src[span.start..span.end]
+            );
+            self.err_at(msg, first.pos, span);
+            // the statement itself is well-formed
+            self.panic = false;
+        }
+    }
+
     fn last_op_code_index(&self) -> usize {
         let len = self.code_len();
         if len == 0 {
@@ -726,6 +1540,8 @@ impl<'a> Compiler<'a> {
 
     fn expr_multi_assign_validation(&mut self, expect: usize) {
         self.multi_count = 1;
+        self.composite_return = false;
+        let (start, first) = (self.code_len(), self.current);
 
         self.expr();
 
@@ -736,6 +1552,15 @@ impl<'a> Compiler<'a> {
             ));
         }
 
+        // the values of a single call to a package-level function can be counted
+        // once the function is declared
+        if self.composite_return && self.multi_count == 1 {
+            let codes = &self.cunit.chunk().codes()[start..];
+            if let [OpCode::GetGlobal(name), .., OpCode::Call(..)] = codes {
+                self.multi_calls.push((name.clone(), expect, first));
+            }
+        }
+
         self.composite_return = false;
     }
 
@@ -754,13 +1579,62 @@ impl<'a> Compiler<'a> {
         self.parse_precedence(Precedence::Assignment)
     }
 
+    /// Name being declared, a variable unless the caller classifies it otherwise
     fn parse_name(&mut self) -> &str {
+        self.classify(self.current, TokenClass::Variable);
+        if self.symbols.is_some() && self.check(Token::Identifier) {
+            self.declare(self.current);
+        }
         self.consume(Token::Identifier);
         &self.prev().literal
     }
 
+    /// Records the declaration of the name at the lexeme for `references()`,
+    /// locals are told apart by the lexeme declaring them
+    fn declare(&mut self, lexeme: usize) {
+        let name = &self.lexemes[lexeme].literal;
+        // neither the package nor the `init` functions can be referred to
+        let global = self.is_global_scope();
+        if self.prev().token == Token::Package || global && name == INIT_FUNC {
+            return;
+        }
+
+        let symbol = if global {
+            Symbol::Global(name.clone())
+        } else {
+            Symbol::Local(lexeme)
+        };
+        if let Some(table) = &mut self.symbols {
+            table.uses.insert(lexeme, symbol);
+            table.declared_at.insert(name.clone(), lexeme);
+        }
+    }
+
+    /// Records what the identifier at the lexeme refers to, for `semantic_tokens()`
+    fn classify(&mut self, lexeme: usize, class: TokenClass) {
+        if let Some(classes) = &mut self.classes {
+            if self.lexemes.get(lexeme).map(|l| l.token) == Some(Token::Identifier) {
+                classes.insert(lexeme, class);
+            }
+        }
+    }
+
+    /// Class of an identifier not referring to a local, known once the whole package is compiled
+    fn global_class(&self, name: &str) -> TokenClass {
+        match self.package_names.get(name) {
+            Some(true) => TokenClass::Constant,
+            Some(false) if self.func_results.contains_key(name) => TokenClass::Function,
+            Some(false) => TokenClass::Variable,
+            None if self.imports.iter().any(|import| import == name) => TokenClass::Package,
+            None if builtin_id(name).is_some() => TokenClass::Function,
+            None if name == IOTA => TokenClass::Constant,
+            None => TokenClass::Variable,
+        }
+    }
+
     fn decl_scoped_name(&mut self, name: String) {
         if self.is_global_scope() {
+            self.decl_package_name(name, false);
             return;
         }
 
@@ -773,6 +1647,7 @@ impl<'a> Compiler<'a> {
 
     fn decl_scoped_const(&mut self, name: String) {
         if self.is_global_scope() {
+            self.decl_package_name(name, true);
             return;
         }
 
@@ -783,6 +1658,27 @@ impl<'a> Compiler<'a> {
         self.scope.add_const(name);
     }
 
+    /// Package-level names are visible in the whole package, so they must be unique
+    /// regardless of the declaration order, the name must be the lexeme just consumed
+    fn decl_package_name(&mut self, name: String, is_const: bool) {
+        if name == INIT_FUNC {
+            self.err_prev(format!(
+                "Cannot declare \"{}\", it must be a function.",
+                name
+            ));
+            self.panic = false;
+            return;
+        }
+
+        if self.package_names.contains_key(&name) {
+            self.err_prev(format!("\"{}\" redeclared in this block.", name));
+            self.panic = false;
+            return;
+        }
+
+        self.package_names.insert(name, is_const);
+    }
+
     /// Either returns a fully constructed value type or panics
     fn parse_type(&mut self) -> ValType {
         let current = self.current();
@@ -831,8 +1727,20 @@ impl<'a> Compiler<'a> {
                 return ValType::Array(Box::new(array_type), size);
             }
 
-            Token::Identifier => ValType::Struct(current.literal.clone()),
-            tok => panic!("Type expected, got {}.", tok),
+            Token::Func => {
+                self.advance();
+                return ValType::Func(Box::new(self.parse_func_type()));
+            }
+
+            // there are no type declarations, so a type name is never defined
+            Token::Identifier => {
+                self.err(format!("Undefined type \"{}\".", current.literal));
+                ValType::Nil
+            }
+            tok => {
+                self.err(format!("Type expected, got \"{}\".", tok));
+                return ValType::Nil;
+            }
         };
 
         self.advance();
@@ -868,7 +1776,10 @@ impl<'a> Compiler<'a> {
                     (ValType::Array(Box::new(array_type), size), finished)
                 }
             }
-            tok => panic!("Literal Type expected, got {}.", tok),
+            tok => {
+                self.err(format!("Literal type expected, got \"{}\".", tok));
+                (ValType::Nil, true)
+            }
         }
     }
 
@@ -892,24 +1803,166 @@ impl<'a> Compiler<'a> {
         }
     }
 
-    fn parse_variadic(&mut self) -> bool {
-        let variadic = matches!(self.current().token, Token::Ellipsis);
-        if variadic {
-            self.advance();
-        }
+    /// Signature of a function type, e.g. `(int, ...string) bool` or `(a, b int) bool`,
+    /// parameter names are parsed and discarded
+    fn parse_func_type(&mut self) -> FuncType {
+        self.consume(Token::LeftParen);
 
-        variadic
+        let named = self.func_type_params_named();
+        let mut param_types = Vec::<ParamType>::new();
+        let mut group = 0;
+        while !self.check(Token::RightParen) && !self.check(Token::Eof) {
+            if named {
+                self.consume(Token::Identifier);
+                group += 1;
+                if self.consume_if(Token::Comma) {
+                    continue;
+                }
+            }
+
+            let variadic = self.parse_variadic();
+            let vtype = self.parse_type();
+            for _ in 0..group.max(1) {
+                param_types.push(ParamType(vtype.clone(), variadic));
+            }
+            group = 0;
+
+            if !self.consume_if(Token::Comma) {
+                break;
+            }
+        }
+        self.consume(Token::RightParen);
+
+        let ret_type = if self.starts_type() {
+            self.parse_composite_type()
+        } else {
+            CompositeType::new_void()
+        };
+
+        FuncType::new(param_types, ret_type)
+    }
+
+    /// Whether the parameters of a function type being parsed have names,
+    /// which is the case once any of them is followed by its type
+    fn func_type_params_named(&mut self) -> bool {
+        let mut depth = 0;
+        for i in self.current.. {
+            match self.lexemes.lookahead(i).token {
+                Token::LeftParen | Token::LeftBracket => depth += 1,
+                Token::RightParen | Token::RightBracket if depth == 0 => return false,
+                Token::RightParen | Token::RightBracket => depth -= 1,
+                Token::Identifier if depth == 0 => {
+                    let next = self.lexemes.lookahead(i + 1).token;
+                    if !matches!(next, Token::Comma | Token::RightParen | Token::Dot) {
+                        return true;
+                    }
+                }
+                Token::Semicolon | Token::LeftCurlyBrace | Token::Eof => return false,
+                _ => {}
+            }
+        }
+
+        false
+    }
+
+    /// Whether the current token can begin a type or a list of return types
+    fn starts_type(&self) -> bool {
+        matches!(
+            self.current().token,
+            Token::Bool
+                | Token::Int8
+                | Token::Int16
+                | Token::Int32
+                | Token::Rune
+                | Token::Int64
+                | Token::Int
+                | Token::Uint8
+                | Token::Byte
+                | Token::Uint16
+                | Token::Uint32
+                | Token::Uint64
+                | Token::Uint
+                | Token::Uintptr
+                | Token::Float32
+                | Token::Float64
+                | Token::Complex64
+                | Token::Complex128
+                | Token::String
+                | Token::LeftBracket
+                | Token::LeftParen
+                | Token::Func
+                | Token::Identifier
+        )
+    }
+
+    fn parse_variadic(&mut self) -> bool {
+        let variadic = matches!(self.current().token, Token::Ellipsis);
+        if variadic {
+            self.advance();
+        }
+
+        variadic
     }
 
     fn parse_constant_int(&mut self) -> usize {
         self.advance();
-        let size = self
-            .prev()
-            .literal
-            .parse::<usize>()
-            .expect("Constant integer expected");
+        if self.prev().token == Token::Identifier
+            && self.prev().literal == "len"
+            && self.check(Token::LeftParen)
+        {
+            return self.parse_constant_len();
+        }
+
+        if self.prev().token != Token::IntLiteral {
+            self.err_prev("Constant integer expected.".to_string());
+            return 0;
+        }
+
+        match literal::parse_int(&self.prev().literal) {
+            Ok(size) if size <= usize::MAX as u128 => size as usize,
+            Ok(_) => {
+                self.err_prev(format!("Constant {} overflows uint.", self.prev().literal));
+                0
+            }
+            Err(e) => {
+                self.err_prev(e);
+                0
+            }
+        }
+    }
+
+    /// `len` of a string literal or of an array literal, which Go evaluates at compile time
+    fn parse_constant_len(&mut self) -> usize {
+        self.consume(Token::LeftParen);
+        let len = if self.consume_if(Token::StringLiteral) {
+            self.prev().literal.len()
+        } else if self.consume_if(Token::LeftBracket) {
+            let size = self.parse_constant_int();
+            self.consume(Token::RightBracket);
+            self.parse_type();
+            self.skip_braced();
+            size
+        } else {
+            self.err("Constant string or array expected.".to_string());
+            0
+        };
+        self.consume(Token::RightParen);
+
+        len
+    }
 
-        size
+    /// Skips a curly braced block, nested blocks included
+    fn skip_braced(&mut self) {
+        self.consume(Token::LeftCurlyBrace);
+        let mut depth = 1;
+        while depth > 0 && !self.check(Token::Eof) {
+            match self.current().token {
+                Token::LeftCurlyBrace => depth += 1,
+                Token::RightCurlyBrace => depth -= 1,
+                _ => {}
+            }
+            self.advance();
+        }
     }
 
     /// For array or slice literals (the curly braced part of expressions like `[2]int{1, 2}`)
@@ -942,19 +1995,24 @@ impl<'a> Compiler<'a> {
 
         if self.check(Token::LeftCurlyBrace) {
             // switch {}
-            self.add_code(OpCode::Bool(Value::Bool(true)));
+            self.add_constant(Value::Bool(true));
         } else {
             // switch expr {}
             self.expr();
         }
         self.consume(Token::LeftCurlyBrace);
         self.add_code(OpCode::Switch);
+        // replaced with a jump table if all the cases turn out to be constants
+        let table_at = self.add_code(OpCode::Noop);
+        let mut table_cases = Some(vec![]);
 
         let mut case_jump: Option<usize> = None;
         let mut break_jumps = vec![];
 
         let mut default_case = false;
         let mut default_jump: Option<usize> = None;
+        // constants matched by the cases so far
+        let mut matched: Vec<Value> = vec![];
 
         while self.check(Token::Case) || self.check(Token::Default) {
             if let Some(sw_jump) = case_jump {
@@ -971,8 +2029,28 @@ impl<'a> Compiler<'a> {
                 default_jump = Some(self.last_op_code_index());
             } else {
                 self.consume(Token::Case);
+                let start = self.code_len();
+                let expr_start = self.current;
                 self.expr();
+                let constant = self.case_constant(start);
+
+                if let Some(constant) = &constant {
+                    if !matched.contains(constant) {
+                        matched.push(constant.clone());
+                    } else if self.lint_rules.is_enabled(LintRule::UnreachableCase) {
+                        let msg = format!(
+                            "Case \"{}\" is unreachable, an earlier case matches the same value.",
+                            self.source_of(expr_start..self.current)
+                        );
+                        self.lint(LintRule::UnreachableCase, expr_start..self.current, msg);
+                    }
+                }
                 case_jump = Some(self.add_code(OpCode::CaseJump(0)));
+
+                match (&mut table_cases, constant) {
+                    (Some(cases), Some(constant)) => cases.push((constant, self.code_len())),
+                    _ => table_cases = None,
+                }
             }
 
             self.case_block();
@@ -985,11 +2063,20 @@ impl<'a> Compiler<'a> {
             self.finish_jump(sw_jump);
         }
 
+        if let Some(cases) = table_cases.filter(|cases| cases.len() >= JUMP_TABLE_MIN_CASES) {
+            let mut table = JumpTable::new(self.code_len());
+            if cases.iter().all(|(value, body)| table.insert(value, *body)) {
+                let table = OpCode::JumpTable(Rc::new(table));
+                self.cunit.chunk_mut().write_at(table_at, table);
+            }
+        }
+
         self.consume(Token::RightCurlyBrace);
         self.consume_if(Token::Semicolon);
 
         if let Some(default_jump) = default_jump {
-            self.add_code(OpCode::DefaultJump(self.code_len() - default_jump));
+            // leads to the body of the default case, right after its jump
+            self.add_code(OpCode::DefaultJump(default_jump + 1));
         }
 
         break_jumps.append(self.control_flow.switch_breaks());
@@ -1013,6 +2100,7 @@ impl<'a> Compiler<'a> {
             && !self.check(Token::Default)
             && !self.check(Token::RightCurlyBrace)
             && !self.check(Token::Fallthrough)
+            && !self.check(Token::Eof)
         {
             self.decl();
         }
@@ -1029,42 +2117,51 @@ impl<'a> Compiler<'a> {
 
     /// All forms of `for` statements
     fn stmt_for(&mut self) {
+        if self.is_range_clause() {
+            self.stmt_for_range();
+            return;
+        }
+
         self.begin_loop();
 
-        let (for_like, mut exit_jump) = if self.check(Token::Semicolon) {
+        // `loop_start` is the position each iteration starts from (the condition)
+        let (for_like, mut loop_start) = if self.check(Token::Semicolon) {
             // no init clause
             // for ; expr; expr {}
             self.consume(Token::Semicolon);
-            let jump = self.last_op_code_index();
 
-            (true, jump)
+            (true, self.code_len())
         } else if self.check(Token::LeftCurlyBrace) {
             // for {}
-            let jump = self.last_op_code_index();
-            self.add_code(OpCode::Bool(Value::Bool(true)));
+            let start = self.code_len();
+            self.add_constant(Value::Bool(true));
 
-            (false, jump)
+            (false, start)
         } else {
-            let jump = self.last_op_code_index();
-            self.expr_simple();
+            let start = self.code_len();
+            let expr_start = self.current;
+            self.expr_simple(Token::Semicolon);
 
             if self.check(Token::Semicolon) {
                 // for expr; expr; expr {}
                 self.consume(Token::Semicolon);
-                let jump = self.last_op_code_index();
-                (true, jump)
+                (true, self.code_len())
             } else {
                 // for expr {}
                 self.pop_code(OpCode::Pop);
-                (false, jump)
+                self.lint_constant_condition(start, expr_start);
+                (false, start)
             }
         };
 
         if for_like {
             if self.consume_if(Token::Semicolon) {
-                self.add_code(OpCode::Bool(Value::Bool(true)));
+                self.add_constant(Value::Bool(true));
             } else {
+                let start = self.code_len();
+                let expr_start = self.current;
                 self.expr();
+                self.lint_constant_condition(start, expr_start);
                 self.consume(Token::Semicolon);
             }
         }
@@ -1073,27 +2170,24 @@ impl<'a> Compiler<'a> {
         self.add_code(OpCode::Pop);
 
         if for_like && !self.check(Token::LeftCurlyBrace) {
+            // the post statement is placed before the body and is jumped over on the first pass,
+            // every next iteration starts with it
             let inc_jump = self.add_code(OpCode::Jump(0));
-            let inc_begin = self.last_op_code_index();
-
-            self.control_flow.add_continue(exit_jump);
+            let inc_begin = self.code_len();
 
-            self.expr_simple();
-            self.add_code(OpCode::BackJump(self.code_len() - exit_jump));
-            exit_jump = inc_begin;
+            self.expr_simple(Token::LeftCurlyBrace);
+            self.add_code(OpCode::Jump(loop_start));
+            loop_start = inc_begin;
 
-            self.control_flow.add_continue(exit_jump);
             self.finish_jump(inc_jump);
-        } else {
-            self.control_flow.add_continue(exit_jump);
         }
 
+        self.control_flow.add_continue(loop_start);
+
         self.consume(Token::LeftCurlyBrace);
         self.stmt_block();
 
-        let exit_jump = self.code_len() - exit_jump;
-        self.control_flow.add_continue(exit_jump);
-        self.add_code(OpCode::BackJump(exit_jump));
+        self.add_code(OpCode::Jump(loop_start));
 
         self.finish_jump(if_jump);
 
@@ -1109,10 +2203,117 @@ impl<'a> Compiler<'a> {
         self.end_loop();
     }
 
+    /// Loop condition emitted since `start` which is a boolean constant, e.g. `for true {}`
+    fn lint_constant_condition(&mut self, start: usize, expr_start: usize) {
+        if !self.lint_rules.is_enabled(LintRule::ConstantCondition) || self.current <= expr_start {
+            return;
+        }
+
+        let msg = match self.cunit.chunk().codes()[start..] {
+            [OpCode::Constant(i)] => match self.cunit.chunk().constant(i) {
+                Value::Bool(true) => {
+                    "Loop condition is always true, \"for {}\" loops forever as well."
+                }
+                Value::Bool(false) => "Loop condition is always false, the body never runs.",
+                _ => return,
+            },
+            _ => return,
+        };
+        self.lint(
+            LintRule::ConstantCondition,
+            expr_start..self.current,
+            msg.to_string(),
+        );
+    }
+
     /// `if` statement, covers `if else` and `else` clauses as well
+    /// `for range n {}` or `for i := range n {}`
+    fn is_range_clause(&mut self) -> bool {
+        self.lexemes.fill(self.current + 2);
+        let token = |i: usize| self.lexemes.get(self.current + i).map(|l| l.token);
+        token(0) == Some(Token::Range)
+            || token(0) == Some(Token::Identifier)
+                && token(1) == Some(Token::ColonEqual)
+                && token(2) == Some(Token::Range)
+    }
+
+    /// Range over an integer, the variable gets a copy of the hidden counter each iteration,
+    /// so changing it in the body does not affect the iteration
+    fn stmt_for_range(&mut self) {
+        self.begin_loop();
+
+        let name = if self.check(Token::Identifier) {
+            let name = self.parse_name().to_string();
+            self.consume(Token::ColonEqual);
+            Some(name)
+        } else {
+            None
+        };
+        self.consume(Token::Range);
+        self.expr();
+
+        self.add_code(OpCode::RangeInt);
+        self.scope.add_var(RANGE_LIMIT.to_string());
+        self.scope.init_last();
+        self.scope.add_var(RANGE_COUNTER.to_string());
+        self.scope.init_last();
+        let counter = self.scope.vars.len() - 1;
+
+        let var = name.map(|name| {
+            self.add_code(OpCode::GetLocal(counter));
+            self.scope.add_var(name);
+            self.init_local(None);
+            self.scope.vars.len() - 1
+        });
+
+        let loop_start = self.code_len();
+        self.add_code(OpCode::GetLocal(counter));
+        self.add_code(OpCode::GetLocal(counter - 1));
+        self.add_code(OpCode::Less);
+        let if_jump = self.add_code(OpCode::IfFalseJump(0));
+        self.add_code(OpCode::Pop);
+
+        // the increment is jumped over on the first pass, every next iteration starts with it
+        let inc_jump = self.add_code(OpCode::Jump(0));
+        let inc_begin = self.code_len();
+        self.add_code(OpCode::GetLocal(counter));
+        self.add_constant(Value::IntLiteral(1));
+        self.add_code(OpCode::Add);
+        self.add_code(OpCode::SetLocal(counter));
+        self.add_code(OpCode::Pop);
+        self.add_code(OpCode::Jump(loop_start));
+        self.finish_jump(inc_jump);
+
+        if let Some(var) = var {
+            self.add_code(OpCode::GetLocal(counter));
+            self.add_code(OpCode::SetLocal(var));
+            self.add_code(OpCode::Pop);
+        }
+
+        self.control_flow.add_continue(inc_begin);
+
+        self.consume(Token::LeftCurlyBrace);
+        self.stmt_block();
+
+        self.add_code(OpCode::Jump(inc_begin));
+        self.finish_jump(if_jump);
+        self.add_code(OpCode::Pop);
+
+        let mut break_jumps = Vec::new();
+        break_jumps.append(self.control_flow.loop_breaks());
+        for inserted_break in break_jumps {
+            self.finish_jump(inserted_break);
+        }
+
+        self.end_loop();
+    }
+
     fn stmt_if(&mut self) {
         self.begin_scope();
-        self.expr_simple();
+        if self.lint_rules.is_enabled(LintRule::Shadow) && self.check_rhs(Token::ColonEqual) {
+            self.lint_shadowing_init();
+        }
+        self.expr_simple(Token::Semicolon);
         if self.check(Token::Semicolon) {
             // if with an initialization statement
             // if init_stmt; expr {}
@@ -1146,6 +2347,29 @@ impl<'a> Compiler<'a> {
         self.end_scope();
     }
 
+    /// Names declared by the initialization statement of an `if`
+    /// that hide variables and package-level names declared outside of it
+    fn lint_shadowing_init(&mut self) {
+        let mut i = self.current;
+        while self.lexemes.lookahead(i).token == Token::Identifier {
+            let name = &self.lexemes[i].literal;
+            if name != "_"
+                && (self.scope.resolve(name).is_some() || self.package_names.contains_key(name))
+            {
+                let msg = format!(
+                    "Declaration of \"{}\" shadows a declaration outside of the if statement.",
+                    name
+                );
+                self.lint(LintRule::Shadow, i..i + 1, msg);
+            }
+
+            if self.lexemes.lookahead(i + 1).token != Token::Comma {
+                break;
+            }
+            i += 2;
+        }
+    }
+
     fn and(&mut self, _: bool) {
         let if_jump = self.add_code(OpCode::IfFalseJump(0));
         self.add_code(OpCode::Pop);
@@ -1165,18 +2389,37 @@ impl<'a> Compiler<'a> {
     }
 
     fn string(&mut self, _: bool) {
-        let string = Value::String(self.prev().literal.clone());
-        self.add_code(OpCode::String(string));
+        let string = Value::String(self.prev().literal.as_str().into());
+        self.add_constant(string);
     }
 
     fn int(&mut self, _: bool) {
-        let int = Value::IntLiteral(self.prev().literal.parse::<isize>().unwrap());
-        self.add_code(OpCode::IntLiteral(int));
+        match literal::parse_int(&self.prev().literal) {
+            Ok(int) if int <= i128::MAX as u128 => {
+                self.add_constant(Value::IntLiteral(int as i128));
+            }
+            Ok(_) => self.err_prev(format!("Constant {} overflows int.", self.prev().literal)),
+            Err(e) => self.err_prev(e),
+        }
     }
 
     fn float(&mut self, _: bool) {
-        let float = Value::FloatLiteral(self.prev().literal.parse::<f64>().unwrap());
-        self.add_code(OpCode::FloatLiteral(float));
+        match literal::parse_float(&self.prev().literal) {
+            Ok(float) => {
+                self.add_constant(Value::FloatLiteral(float));
+            }
+            Err(e) => self.err_prev(e),
+        }
+    }
+
+    /// Imaginary literals are constants of `complex128`, the default type of complex constants
+    fn imag(&mut self, _: bool) {
+        match literal::parse_imag(&self.prev().literal) {
+            Ok(imag) => {
+                self.add_constant(Value::Complex128(0_f64, imag));
+            }
+            Err(e) => self.err_prev(e),
+        }
     }
 
     fn var(&mut self, assign: bool) {
@@ -1235,6 +2478,7 @@ impl<'a> Compiler<'a> {
             }
         };
 
+        self.inc_dec_end = None;
         self.expr();
         self.add_code(code);
         self.add_code(set_code);
@@ -1277,7 +2521,16 @@ impl<'a> Compiler<'a> {
             }
         };
 
-        self.add_code(OpCode::IntLiteral(Value::IntLiteral(1)));
+        if !self.inc_dec_end.is_some_and(|end| self.check(end)) {
+            self.err_lexeme(
+                self.current - 1,
+                format!("\"{}\" can only be used as a statement.", self.prev().token),
+            );
+            return;
+        }
+        self.inc_dec_end = None;
+
+        self.add_constant(Value::IntLiteral(1));
         self.add_code(code);
         self.add_code(set_code);
     }
@@ -1293,10 +2546,20 @@ impl<'a> Compiler<'a> {
 
         // lhs
         let mut names = vec![];
+        // lexemes of each target, e.g. `a[i]`
+        let mut targets = vec![];
         loop {
             let mut context = context;
+            let target_start = self.current - 1;
+            if self.symbols.is_some() {
+                self.classify_use(target_start);
+            }
             let name = self.prev().literal.clone();
             let name_resolution = self.scope.resolve(&name);
+            let mut indexed = match name_resolution {
+                Some((i, _)) => self.scope.vars[i].vtype.clone(),
+                None => self.global_types.get(&name).cloned(),
+            };
 
             let mut index_depth = 0;
             let mut last_code: Option<OpCode> = None;
@@ -1305,8 +2568,15 @@ impl<'a> Compiler<'a> {
                 index_depth += 1;
                 context |= val_context::INDEX;
 
+                let (bracket, start) = (self.current - 1, self.code_len());
+                let global = match name_resolution {
+                    None if index_depth == 1 => Some(name.as_str()),
+                    _ => None,
+                };
+                self.validate_indexed(bracket, global, indexed.as_ref(), "index");
                 self.expr_no_assign();
                 self.consume(Token::RightBracket);
+                indexed = self.validate_index(bracket, start, indexed);
 
                 let code = if let Some((i, _)) = name_resolution {
                     OpCode::GetLocalIndex(i)
@@ -1323,6 +2593,7 @@ impl<'a> Compiler<'a> {
                 self.pop_code(code);
             }
 
+            targets.push(target_start..self.current);
             names.push(AssignmentName {
                 name,
                 context,
@@ -1340,7 +2611,12 @@ impl<'a> Compiler<'a> {
 
         // rhs
         self.consume(Token::Equal);
+        self.inc_dec_end = None;
+        let values_start = self.current;
         self.expr_multi_assign_validation(names.len());
+        if self.lint_rules.is_enabled(LintRule::SelfAssign) && !self.panic {
+            self.lint_self_assign(&targets, values_start..self.current);
+        }
 
         // set opcodes after the rhs values in a reverse order
         names.reverse();
@@ -1375,10 +2651,63 @@ impl<'a> Compiler<'a> {
         }
     }
 
+    /// Targets assigned the same expression they are written as, e.g. `x = x` or `a, b = a, b`.
+    /// Expressions with calls are skipped, evaluating them may have effects
+    fn lint_self_assign(&mut self, targets: &[Range<usize>], values: Range<usize>) {
+        let mut exprs = vec![];
+        let (mut start, mut depth) = (values.start, 0usize);
+        for i in values.clone() {
+            match self.lexemes[i].token {
+                Token::LeftParen | Token::LeftBracket | Token::LeftCurlyBrace => depth += 1,
+                Token::RightParen | Token::RightBracket | Token::RightCurlyBrace => {
+                    depth = depth.saturating_sub(1)
+                }
+                Token::Comma if depth == 0 => {
+                    exprs.push(start..i);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        exprs.push(start..values.end);
+
+        if exprs.len() != targets.len() {
+            return;
+        }
+
+        for (target, value) in targets.iter().zip(exprs) {
+            let target_lexemes = &self.lexemes[target.clone()];
+            let same = target.len() == value.len()
+                && target_lexemes
+                    .iter()
+                    .zip(&self.lexemes[value])
+                    .all(|(a, b)| a.token == b.token && a.literal == b.literal);
+
+            if same && target_lexemes.iter().all(|l| l.token != Token::LeftParen) {
+                let msg = format!(
+                    "\"{}\" is assigned to itself.",
+                    self.source_of(target.clone())
+                );
+                self.lint(LintRule::SelfAssign, target.clone(), msg);
+            }
+        }
+    }
+
     /// Parses named variable value.
     /// Expects `context` of a variable to be able to decide which opcodes to emit
     fn named_var(&mut self, context: val_context::Context) {
-        if val_context::is_assignment(context) {
+        if self.classes.is_some() || self.symbols.is_some() {
+            self.classify_use(self.current - 1);
+        }
+
+        if self.const_expr && self.prev().literal == IOTA && self.scope.resolve(IOTA).is_none() {
+            self.add_constant(Value::IntLiteral(self.iota as i128));
+            return;
+        }
+
+        if self.is_package_name() {
+            self.expr_qualified_name();
+        } else if val_context::is_assignment(context) {
             if self.check_rhs(Token::Equal) {
                 self.expr_assign(context);
             } else if self.check_in(&ASSIGN_OPERATORS) {
@@ -1393,10 +2722,88 @@ impl<'a> Compiler<'a> {
         }
     }
 
+    /// Classifies the identifier used at the lexeme and records what it refers to
+    /// if it is a local or a package, others are package-level names which may be declared further on
+    fn classify_use(&mut self, lexeme: usize) {
+        self.lexemes.fill(lexeme + 2);
+        let name = &self.lexemes[lexeme].literal;
+        // a local being declared is not in scope yet, e.g. the second `x` of `x := x`
+        let local = self
+            .scope
+            .vars
+            .iter()
+            .rev()
+            .find(|var| var.name == *name && var.is_initialised());
+
+        let symbol = match local {
+            Some(var) => {
+                let class = if var.mutable {
+                    TokenClass::Variable
+                } else {
+                    TokenClass::Constant
+                };
+                let symbol = var.decl.map(Symbol::Local);
+                self.classify(lexeme, class);
+                symbol
+            }
+            None if self.imports.contains(name) => {
+                self.classify(lexeme, TokenClass::Package);
+                // members of the packages are all functions
+                if self.lexemes.get(lexeme + 1).map(|l| l.token) == Some(Token::Dot) {
+                    self.classify(lexeme + 2, TokenClass::Function);
+                }
+                None
+            }
+            None => Some(Symbol::Global(name.clone())),
+        };
+
+        if let (Some(table), Some(symbol)) = (&mut self.symbols, symbol) {
+            table.uses.insert(lexeme, symbol);
+        }
+    }
+
+    /// Whether the previous identifier names an imported package, not shadowed by a variable
+    fn is_package_name(&self) -> bool {
+        let name = &self.prev().literal;
+        self.imports.contains(name) && self.scope.resolve(name).is_none()
+    }
+
+    /// Member of an imported package, e.g. `fmt.Println`
+    fn expr_qualified_name(&mut self) {
+        let package = self.prev().literal.clone();
+        self.consume(Token::Dot);
+        self.consume(Token::Identifier);
+        let name = format!("{}.{}", package, self.prev().literal);
+        if self.const_expr {
+            self.err_not_constant(self.current - 1, &format!("\"{}\"", name));
+        }
+
+        self.add_code(OpCode::GetGlobal(name));
+    }
+
     fn expr_get_var(&mut self, context: val_context::Context) {
         let name = self.prev().literal.clone();
         let resolved = self.scope.resolve(&name);
 
+        if resolved.is_none() {
+            self.package_refs.push(name.clone());
+        }
+
+        if name == INIT_FUNC && resolved.is_none() {
+            self.err_lexeme(self.current - 1, format!("Undefined \"{}\".", name));
+        }
+
+        // a called function is reported by the call itself
+        if self.const_expr && !self.check(Token::LeftParen) {
+            let is_var = match resolved {
+                Some((_, mutable)) => mutable,
+                None => self.package_names.get(&name) == Some(&false),
+            };
+            if is_var {
+                self.err_not_constant(self.current - 1, &format!("\"{}\"", name));
+            }
+        }
+
         let code = if let Some((i, _)) = resolved {
             if self.scope.vars[i].depth == -1 {
                 if val_context::is_index(context) {
@@ -1425,12 +2832,14 @@ impl<'a> Compiler<'a> {
     }
 
     fn group(&mut self, _: bool) {
+        self.operand = true;
         self.expr();
         self.consume(Token::RightParen);
     }
 
     fn unary(&mut self, _: bool) {
         let operator = self.prev().token;
+        self.operand = true;
         self.parse_precedence(Precedence::Unary);
 
         let code = match operator {
@@ -1447,24 +2856,53 @@ impl<'a> Compiler<'a> {
     }
 
     fn parse_precedence(&mut self, prec: Precedence) {
+        let operand = std::mem::take(&mut self.operand);
+        let constant_type = if operand {
+            None
+        } else {
+            self.constant_type.take()
+        };
+        let (start, first) = (self.code_len(), self.current);
         self.advance();
-        let prefix = self.rule(&self.prev().token).0;
+        let prefix = self.rule(&self.prev().token.clone()).0;
         if prefix.is_none() {
+            self.err_prev(format!(
+                "Expression expected, got \"{}\".",
+                self.prev().token
+            ));
+            // leave the closing brace to the enclosing block
+            if self.prev().token == Token::RightCurlyBrace && !self.is_package_scope() {
+                self.rollback();
+            }
             return;
         }
 
         let can_assign = prec <= Precedence::Assignment;
         prefix.unwrap()(self, can_assign);
 
-        while prec <= self.rule(&self.current().token).2 {
+        // the untyped constants are converted once the whole expression is known
+        let mut validated = operand || self.const_expr;
+        while prec <= self.rule(&self.current().token.clone()).2 {
             self.advance();
-            let inflix = self.rule(&self.prev().token).1;
+            let inflix = self.rule(&self.prev().token.clone()).1;
             if inflix.is_none() {
                 return;
             }
 
+            // each of the comma-separated values is converted on its own
+            if self.prev().token == Token::Comma && !validated {
+                self.validate_int_constant(start, first, constant_type.as_ref());
+                self.constant_type = constant_type.clone();
+                validated = true;
+            }
+
+            self.operand_start = start;
             inflix.unwrap()(self, can_assign);
         }
+
+        if !validated {
+            self.validate_int_constant(start, first, constant_type.as_ref());
+        }
     }
 
     /// Parses actual arguments, returns their count
@@ -1509,7 +2947,10 @@ impl<'a> Compiler<'a> {
     fn binary(&mut self, _: bool) {
         let operator = self.prev().token;
         let precedence = self.rule(&operator).2;
+        let (lexeme, lhs_start, rhs_start) =
+            (self.current - 1, self.operand_start, self.code_len());
 
+        self.operand = true;
         self.parse_precedence(precedence.next());
 
         let code = match operator {
@@ -1535,14 +2976,25 @@ impl<'a> Compiler<'a> {
             }
         };
 
+        self.validate_constant_operation(&code, lexeme, lhs_start..rhs_start);
         self.add_code(code);
     }
 
     fn literal(&mut self, _: bool) {
         let code = match self.prev().token {
-            Token::True => OpCode::Bool(Value::Bool(true)),
-            Token::False => OpCode::Bool(Value::Bool(false)),
+            Token::True => {
+                self.add_constant(Value::Bool(true));
+                return;
+            }
+            Token::False => {
+                self.add_constant(Value::Bool(false));
+                return;
+            }
             Token::LeftBracket => {
+                if self.const_expr {
+                    self.err_not_constant(self.current - 1, "Composite literal");
+                }
+
                 let code = if self.check(Token::RightBracket) {
                     // slice
                     OpCode::SliceLiteral
@@ -1553,6 +3005,14 @@ impl<'a> Compiler<'a> {
 
                 self.rollback();
                 let (mut vtype, finished) = self.parse_literal_type();
+                if finished && self.consume_if(Token::LeftParen) {
+                    // conversion, e.g. `[]byte(s)`
+                    self.expr();
+                    self.consume(Token::RightParen);
+                    self.add_code(OpCode::Convert(vtype));
+                    return;
+                }
+
                 let len = self.parse_array_body();
                 //fixme add array length validation
                 if !finished {
@@ -1572,59 +3032,447 @@ impl<'a> Compiler<'a> {
     }
 
     fn call(&mut self, _: bool) {
+        let callee = self.cunit.chunk().codes().last();
+        let is_self_call = match (callee, &self.cunit) {
+            (Some(OpCode::GetGlobal(name)), CUnit::Function(funit)) => *name == funit.function().0,
+            _ => false,
+        };
+        let is_value_call = matches!(
+            callee,
+            Some(OpCode::GetGlobal(name)) if CONST_FUNCS.contains(&name.as_str()) || name == "append"
+        );
+        let builtin = match callee {
+            Some(OpCode::GetGlobal(name))
+                if self.scope.resolve(name).is_none() && !self.shadowed_builtins.contains(name) =>
+            {
+                builtin_id(name)
+            }
+            _ => None,
+        };
+
+        if self.const_expr {
+            match callee {
+                Some(OpCode::GetGlobal(name)) if CONST_FUNCS.contains(&name.as_str()) => {}
+                Some(OpCode::GetGlobal(name)) => {
+                    let what = format!("\"{}()\"", name);
+                    self.err_not_constant(self.current.saturating_sub(2), &what);
+                }
+                _ => self.err_not_constant(self.current.saturating_sub(2), "Function call"),
+            }
+        }
+
+        // a builtin is called by its id, without the callee on the stack
+        if builtin.is_some() {
+            self.pop_code(OpCode::GetGlobal(String::new()));
+        }
+
         let (args, spread) = self.parse_args();
         // FIXME add validation if the return value is composite
         self.composite_return = true;
-        self.add_code(OpCode::Call(args, spread));
+        let call = match builtin {
+            Some(id) => self.add_code(OpCode::CallBuiltin(id, args)),
+            None => self.add_code(OpCode::Call(args, spread)),
+        };
+
+        if is_self_call {
+            self.self_call = Some(call);
+        }
+        // folded constant calls may leave the index to a later call
+        self.value_call = Some(call).filter(|_| is_value_call);
     }
 
     fn index(&mut self, assign: bool) {
+        if self.const_expr {
+            self.err_not_constant(self.current - 1, "Index expression");
+        }
+
         let context = if assign {
             val_context::ASSIGNMENT | val_context::INDEX
         } else {
             val_context::INDEX
         };
 
-        self.expr();
+        let (bracket, start) = (self.current - 1, self.code_len());
+        let indexed = start.checked_sub(1).and_then(|at| self.static_type(at));
+        let global = match start
+            .checked_sub(1)
+            .map(|at| &self.cunit.chunk().codes()[at])
+        {
+            Some(OpCode::GetGlobal(name)) => Some(name.clone()),
+            _ => None,
+        };
+        let low = !self.check(Token::Colon);
+        if low {
+            self.expr_no_assign();
+        }
+
+        if self.consume_if(Token::Colon) {
+            self.validate_indexed(bracket, global.as_deref(), indexed.as_ref(), "slice");
+            self.slice_expr(bracket, if low { Some(start) } else { None });
+            return;
+        }
         self.consume(Token::RightBracket);
 
+        self.validate_indexed(bracket, global.as_deref(), indexed.as_ref(), "index");
+        let element = self.validate_index(bracket, start, indexed);
+
         self.named_var(context);
+        if let (Some(element), Some(OpCode::GetIndex)) =
+            (element, self.cunit.chunk().codes().last())
+        {
+            self.index_type = Some((self.code_len() - 1, element));
+        }
     }
 
-    fn add_code(&mut self, code: OpCode) -> usize {
-        let pos = if self.current > 0 {
-            self.prev().pos
-        } else {
-            self.current().pos
+    /// Reports indexing or slicing, as `operation` tells, of a builtin or of a value
+    /// of a type known to have no elements. `global` is the name of the package level value indexed
+    fn validate_indexed(
+        &mut self,
+        bracket: usize,
+        global: Option<&str>,
+        indexed: Option<&ValType>,
+        operation: &str,
+    ) {
+        let msg = match (global, indexed) {
+            (Some(name), _)
+                if builtin_id(name).is_some() && !self.shadowed_builtins.contains(name) =>
+            {
+                format!(
+                    "Invalid operation: cannot {} builtin \"{}\".",
+                    operation, name
+                )
+            }
+            (_, Some(ValType::Array(..) | ValType::Slice(_) | ValType::String)) | (_, None) => {
+                return;
+            }
+            (_, Some(vtype)) => format!(
+                "Invalid operation: cannot {} value of type \"{}\".",
+                operation, vtype
+            ),
         };
 
-        self.cunit.chunk_mut().write(code, pos)
+        self.err_lexeme(bracket, msg);
     }
 
-    /// Duplicates last N opcodes.
-    fn duplicate_codes(&mut self, last: usize) {
-        let len = self.cunit.chunk().codes().len();
-        let mut codes = vec![];
-        for i in 0..last {
-            let code = &self.cunit.chunk().codes()[len - i - 1];
-            codes.push(code.clone())
-        }
-        codes.reverse();
-        for code in codes {
-            self.add_code(code);
+    /// Reports the constant index loaded by the opcodes emitted since `start` if it is negative,
+    /// or out of the bounds of the array it indexes, if the length of the array is known.
+    /// Returns the type of the elements, if the indexed type is known
+    fn validate_index(
+        &mut self,
+        bracket: usize,
+        start: usize,
+        indexed: Option<ValType>,
+    ) -> Option<ValType> {
+        let kind = match &indexed {
+            Some(ValType::Array(..)) => "array index",
+            Some(ValType::Slice(_)) => "slice index",
+            _ => "index",
+        };
+
+        if let Some(index) = self.int_constant(start) {
+            if index < 0 {
+                let msg = format!("Invalid {} {} (index must be non-negative).", kind, index);
+                self.err_lexeme(bracket, msg);
+            } else if let Some(ValType::Array(_, len)) = &indexed {
+                if index >= *len as i128 {
+                    let msg = format!(
+                        "Invalid array index {} (out of bounds for {}-element array).",
+                        index, len
+                    );
+                    self.err_lexeme(bracket, msg);
+                }
+            }
+        }
+
+        match indexed? {
+            ValType::Array(element, _) | ValType::Slice(element) => Some(*element),
+            _ => None,
+        }
+    }
+
+    /// Type of the value the opcode at `at` loads, if the compiler knows it without evaluating
+    /// the expression, e.g. a variable of a declared type or an array literal
+    fn static_type(&self, at: usize) -> Option<ValType> {
+        match self.cunit.chunk().codes().get(at)? {
+            OpCode::GetLocal(i) => self.scope.vars.get(*i)?.vtype.clone(),
+            OpCode::GetGlobal(name) => self.global_types.get(name).cloned(),
+            OpCode::ArrayLiteral(_, vtype) | OpCode::SliceLiteral(_, vtype) => Some(vtype.clone()),
+            OpCode::GetIndex => match &self.index_type {
+                Some((i, vtype)) if *i == at => Some(vtype.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Rest of a slice expression `a[low:high]` or `a[low:high:max]` following the first colon.
+    /// Either bound of the first form may be omitted, only the low one of the second form may be.
+    /// `low` is where the opcodes of the low bound start
+    fn slice_expr(&mut self, bracket: usize, low: Option<usize>) {
+        let mut constants = vec![low.and_then(|start| self.int_constant(start))];
+        let high = self.slice_bound(&mut constants);
+        let max = self.consume_if(Token::Colon);
+        if max {
+            if !high {
+                let msg = "Middle index required in 3-index slice.".to_string();
+                self.err_lexeme(self.current - 1, msg);
+            }
+            if !self.slice_bound(&mut constants) {
+                let msg = "Final index required in 3-index slice.".to_string();
+                self.err_lexeme(self.current, msg);
+            }
+        }
+        self.consume(Token::RightBracket);
+
+        self.validate_slice_constants(bracket, &constants);
+        self.add_code(OpCode::SliceExpr(low.is_some(), high, max));
+    }
+
+    /// Parses a slice bound unless it is omitted, records its value if it is an integer constant
+    fn slice_bound(&mut self, constants: &mut Vec<Option<i128>>) -> bool {
+        if self.check(Token::Colon) || self.check(Token::RightBracket) {
+            return false;
+        }
+
+        let start = self.code_len();
+        self.expr_no_assign();
+        constants.push(self.int_constant(start));
+
+        true
+    }
+
+    /// Value of the integer constant the opcodes emitted since `start` evaluate to,
+    /// if that is all they do
+    fn int_constant(&self, start: usize) -> Option<i128> {
+        match self.constant_values(start)?.as_slice() {
+            [Value::IntLiteral(v)] => Some(*v),
+            _ => None,
+        }
+    }
+
+    /// Values the opcodes emitted since `start` push, folded the way the optimizer folds them.
+    /// Named constants are resolved to their values. `None` if anything is left to the runtime
+    fn constant_values(&self, start: usize) -> Option<Vec<Value>> {
+        self.constant_values_in(start..self.code_len())
+    }
+
+    /// Values the opcodes in the range push, see `constant_values`
+    fn constant_values_in(&self, codes: Range<usize>) -> Option<Vec<Value>> {
+        let chunk = self.cunit.chunk();
+        let mut values = vec![];
+
+        for code in &chunk.codes()[codes] {
+            let value = match code {
+                OpCode::Constant(i) => chunk.constant(*i).clone(),
+                OpCode::GetGlobal(name) => self.const_values.get(name)?.clone(),
+                OpCode::GetLocal(i) => self.scope.vars.get(*i)?.value.clone()?,
+                op => {
+                    let rhs = values.pop()?;
+                    match optimizer::fold_unary(op, &rhs) {
+                        Some(value) => value,
+                        None => optimizer::fold_binary(op, &values.pop()?, &rhs)?,
+                    }
+                }
+            };
+            values.push(value);
+        }
+
+        Some(values)
+    }
+
+    /// Untyped integer constant the expression emitted since `start` evaluates to
+    /// must be representable by the type it is converted to, `int` unless the type is given.
+    /// Since the type is often unknown here, any 64-bit integer is let through then
+    fn validate_int_constant(&mut self, start: usize, first: usize, vtype: Option<&ValType>) {
+        let value = match self.int_constant(start) {
+            Some(value) => value,
+            None => return,
+        };
+        let (name, (min, max)) = match vtype {
+            Some(vtype) => match vtype.int_range() {
+                Some(range) => (vtype.name(), range),
+                // floats and complex numbers hold any integer constant
+                None => return,
+            },
+            None => (ValType::Int.name(), (i64::MIN as i128, u64::MAX as i128)),
+        };
+
+        if value < min || value > max {
+            self.err_lexeme(first, format!("Constant {} overflows {}.", value, name));
+        }
+    }
+
+    /// Integer constants of a typed const spec must be representable by its type
+    fn validate_typed_constants(&mut self, vtype: &ValType, values: &[Value], first: usize) {
+        let (min, max) = match vtype.int_range() {
+            Some(range) => range,
+            None => return,
+        };
+
+        for value in values {
+            match value {
+                Value::IntLiteral(v) if *v < min || *v > max => {
+                    let msg = format!("Constant {} overflows {}.", v, vtype.name());
+                    self.err_lexeme(first, msg);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Integer constant operands of a binary operator must give a representable result
+    fn validate_constant_operation(&mut self, code: &OpCode, lexeme: usize, lhs: Range<usize>) {
+        let rhs_start = lhs.end;
+        let (lhs, rhs) = match (
+            self.constant_values_in(lhs),
+            self.constant_values(rhs_start),
+        ) {
+            (Some(lhs), Some(rhs)) => (lhs, rhs),
+            _ => return,
+        };
+        let (lhs, rhs, b) = match (lhs.as_slice(), rhs.as_slice()) {
+            ([lhs @ Value::IntLiteral(_)], [rhs @ Value::IntLiteral(b)]) => (lhs, rhs, *b),
+            _ => return,
+        };
+        if optimizer::fold_binary(code, lhs, rhs).is_some() {
+            return;
+        }
+
+        let msg = match code {
+            OpCode::Divide | OpCode::Remainder if b == 0 => "Division by zero.".to_string(),
+            OpCode::LeftShift | OpCode::RightShift if b < 0 => {
+                format!("Invalid negative shift count {}.", b)
+            }
+            OpCode::Add => "Constant addition overflow.".to_string(),
+            OpCode::Subtract => "Constant subtraction overflow.".to_string(),
+            OpCode::Multiply => "Constant multiplication overflow.".to_string(),
+            OpCode::LeftShift => "Constant shift overflow.".to_string(),
+            _ => return,
+        };
+        self.err_lexeme(lexeme, msg);
+    }
+
+    /// Untyped integer or string constant loaded by the case expression emitted since `start`
+    fn case_constant(&self, start: usize) -> Option<Value> {
+        match self.cunit.chunk().codes()[start..] {
+            [OpCode::Constant(i)] => match self.cunit.chunk().constant(i) {
+                Value::String(s) => Some(Value::String(s.clone())),
+                Value::IntLiteral(v) => Some(Value::IntLiteral(*v)),
+                _ => None,
+            },
+            [OpCode::Constant(_), OpCode::Negate] => {
+                self.int_constant(start).map(Value::IntLiteral)
+            }
+            _ => None,
+        }
+    }
+
+    /// Constant slice bounds must not be negative and must not decrease
+    fn validate_slice_constants(&mut self, bracket: usize, constants: &[Option<i128>]) {
+        let constants: Vec<i128> = constants.iter().flatten().copied().collect();
+        if let Some(index) = constants.iter().find(|index| **index < 0) {
+            let msg = format!(
+                "Invalid slice index {} (index must be non-negative).",
+                index
+            );
+            self.err_lexeme(bracket, msg);
+            return;
+        }
+
+        for (i, high) in constants.iter().enumerate() {
+            if let Some(low) = constants[..i].iter().find(|low| *low > high) {
+                let msg = format!("Invalid slice indices: {} < {}.", high, low);
+                self.err_lexeme(bracket, msg);
+                return;
+            }
+        }
+    }
+
+    fn add_code(&mut self, code: OpCode) -> usize {
+        let lexeme = if self.current > 0 {
+            self.prev()
+        } else {
+            self.current()
+        };
+        let (pos, span) = (lexeme.pos, lexeme.span);
+
+        self.cunit.chunk_mut().write_spanned(code, pos, span)
+    }
+
+    /// Puts the value into the constant pool of the current chunk
+    /// and adds an opcode loading it
+    fn add_constant(&mut self, value: Value) -> usize {
+        let i = self.cunit.chunk_mut().add_constant(value);
+        self.add_code(OpCode::Constant(i))
+    }
+
+    /// Duplicates last N opcodes.
+    /// Jumps within the duplicated range are relocated to lead to the copied opcodes.
+    fn duplicate_codes(&mut self, last: usize) {
+        let len = self.code_len();
+        let start = len - last;
+        let codes = self.cunit.chunk().codes()[start..].to_vec();
+
+        for mut code in codes {
+            code.map_jump_targets(|target| {
+                if target >= start && target <= len {
+                    target + last
+                } else {
+                    target
+                }
+            });
+            self.add_code(code);
         }
     }
 
     /// Removes last OpCode if it matches.
-    /// Panic otherwise to prevent possible errors
+    /// A mismatch means the preceding expression was malformed, which is reported unless already
     fn pop_code(&mut self, code: OpCode) {
-        let popped = self.cunit.chunk_mut().pop().unwrap();
-        if mem::discriminant(&popped) != mem::discriminant(&code) {
-            panic!("Wrong op code popped from a chunk");
+        let matches = self
+            .cunit
+            .chunk()
+            .codes()
+            .last()
+            .is_some_and(|last| mem::discriminant(last) == mem::discriminant(&code));
+
+        if matches {
+            self.cunit.chunk_mut().pop();
+        } else if self.errs.is_empty() {
+            self.err("Unexpected expression.".to_string());
+        }
+    }
+
+    /// The missing entry point is reported at the package clause
+    fn validate_entry_point_declared(&mut self) {
+        if let Some(package) = &self.cur_package {
+            let declared = self
+                .entry_point
+                .check_declared(package, self.entry_point_declared);
+            let runnable = if self.executable {
+                self.entry_point.check_package(package)
+            } else {
+                Ok(())
+            };
+
+            if let Err(e) = declared.and(runnable) {
+                let clause = self
+                    .lexemes
+                    .lexemes()
+                    .iter()
+                    .position(|lexeme| lexeme.token == Token::Package)
+                    .unwrap_or(0);
+                self.err_lexeme(clause + 1, e.0);
+            }
         }
     }
 
     fn add_entry_point(&mut self) {
+        for init in self.inits.clone() {
+            self.add_code(OpCode::GetGlobal(init));
+            self.add_code(OpCode::Call(0, false));
+            self.add_code(OpCode::Pop);
+        }
+
         self.add_code(OpCode::GetGlobal(
             self.entry_point.func_name().0.to_string(),
         ));
@@ -1632,23 +3480,12 @@ impl<'a> Compiler<'a> {
     }
 
     /// Adjust the value of previously put Jump opcode
-    /// `i` is the new position where the Jump must lead
+    /// so that it leads to the position of the next opcode to be added
     fn finish_jump(&mut self, i: usize) {
-        let jump = self.last_op_code_index() - i;
-
-        use OpCode::*;
-        let jump = match &self.cunit.chunk().codes()[i] {
-            Jump(_) => Jump(jump),
-            CaseJump(_) => CaseJump(jump),
-            DefaultCaseJump(_) => DefaultCaseJump(jump),
-            DefaultJump(_) => DefaultJump(jump),
-            CaseBreakJump(_) => CaseBreakJump(jump),
-            BackJump(_) => BackJump(jump),
-            IfFalseJump(_) => IfFalseJump(jump),
-            code => panic!("Cannot change non-jump opcode \"{:?}\".", code),
-        };
-
-        self.cunit.chunk_mut().write_at(i, jump);
+        let target = self.code_len();
+        if let Err(err) = self.cunit.chunk_mut().patch_jump(i, target) {
+            self.err_at(err.msg().to_string(), err.pos(), err.span());
+        }
     }
 
     fn code_len(&self) -> usize {
@@ -1716,6 +3553,108 @@ const ASSIGN_OPERATORS: [Token; 12] = [
 
 const INC_OPERATORS: [Token; 2] = [Token::Inc, Token::Dec];
 
+/// Packages the VM provides the members of
+/// Index of the spec within a const declaration
+const IOTA: &str = "iota";
+
+/// Functions run before the entry point
+const INIT_FUNC: &str = "init";
+
+/// Builtins whose result is constant when their arguments are
+const CONST_FUNCS: [&str; 21] = [
+    "len", "cap", "complex", "real", "imag", "string", "int", "int8", "int16", "int32", "rune",
+    "int64", "uint", "uint8", "byte", "uint16", "uint32", "uint64", "uintptr", "float32",
+    "float64",
+];
+
+/// Functions the VM provides, a call to a builtin carries its index in the list
+pub const BUILTINS: [&str; 31] = [
+    "print",
+    "println",
+    "int",
+    "int8",
+    "int16",
+    "int32",
+    "rune",
+    "int64",
+    "uint",
+    "uintptr",
+    "uint8",
+    "byte",
+    "uint16",
+    "uint32",
+    "uint64",
+    "float32",
+    "float64",
+    "string",
+    "len",
+    "cap",
+    "copy",
+    "append",
+    "complex",
+    "real",
+    "imag",
+    "panic",
+    "flush",
+    "fmt.Print",
+    "fmt.Println",
+    "runtime.NumGoroutine",
+    "runtime.Gosched",
+];
+
+/// Id of the builtin calls to it are compiled with
+pub fn builtin_id(name: &str) -> Option<u8> {
+    BUILTINS
+        .iter()
+        .position(|builtin| *builtin == name)
+        .map(|id| id as u8)
+}
+
+/// Builtins possibly declared at the package level, which applies to every name
+/// following a declaration keyword or starting an item of a list outside of function bodies
+/// The source is scanned ahead of the compiler for them, none of the lexemes are kept
+fn shadowed_builtins(lexer: Lexer) -> HashSet<String> {
+    let mut depth = 0usize;
+    let mut names = HashSet::new();
+    let mut prev = None;
+    for lexeme in lexer.flatten() {
+        match lexeme.token {
+            Token::LeftCurlyBrace => depth += 1,
+            Token::RightCurlyBrace => depth = depth.saturating_sub(1),
+            Token::Identifier if depth == 0 => {
+                let declares = matches!(
+                    prev,
+                    Some(
+                        Token::Func
+                            | Token::Var
+                            | Token::Const
+                            | Token::Type
+                            | Token::LeftParen
+                            | Token::Semicolon
+                            | Token::Comma
+                    )
+                );
+                if declares && builtin_id(&lexeme.literal).is_some() {
+                    names.insert(lexeme.literal);
+                }
+            }
+            _ => {}
+        }
+        prev = Some(lexeme.token);
+    }
+
+    names
+}
+
+/// Switches with fewer constant cases are matched case by case
+const JUMP_TABLE_MIN_CASES: usize = 4;
+
+/// Hidden locals of a range loop, the names cannot clash with identifiers
+const RANGE_LIMIT: &str = "range limit";
+const RANGE_COUNTER: &str = "range counter";
+
+const SUPPORTED_PACKAGES: [&str; 2] = ["fmt", "runtime"];
+
 /// Context of a value in an expression.
 mod val_context {
     pub type Context = u8;
@@ -1732,3 +3671,1023 @@ mod val_context {
         context & INDEX == INDEX
     }
 }
+
+/// Numeric literals as written in the source, see `Lexer::number` for the accepted forms
+mod literal {
+    use std::num::IntErrorKind;
+
+    /// Integer literal value, an error if it is malformed or does not fit even `u128`
+    pub fn parse_int(literal: &str) -> Result<u128, String> {
+        let digits = literal.replace('_', "").to_lowercase();
+        let (digits, radix) = if let Some(bin) = digits.strip_prefix("0b") {
+            (bin, 2)
+        } else if let Some(oct) = digits.strip_prefix("0o") {
+            (oct, 8)
+        } else if let Some(hex) = digits.strip_prefix("0x") {
+            (hex, 16)
+        } else if digits.len() > 1 && digits.starts_with('0') {
+            (&digits[1..], 8)
+        } else {
+            (digits.as_str(), 10)
+        };
+
+        u128::from_str_radix(digits, radix).map_err(|e| match e.kind() {
+            IntErrorKind::PosOverflow => format!("Constant {} overflows int.", literal),
+            _ => format!("Invalid integer literal {}.", literal),
+        })
+    }
+
+    /// Floating-point literal value, an error if it does not fit `f64`
+    pub fn parse_float(literal: &str) -> Result<f64, String> {
+        let digits = literal.replace('_', "").to_lowercase();
+        let float = match digits.strip_prefix("0x") {
+            Some(hex) => parse_hex_float(hex),
+            None => digits.parse::<f64>().ok(),
+        };
+
+        match float {
+            Some(float) if float.is_finite() => Ok(float),
+            Some(_) => Err(format!("Constant {} overflows float64.", literal)),
+            None => Err(format!("Invalid float literal {}.", literal)),
+        }
+    }
+
+    /// Hexadecimal mantissa scaled by a power of two, e.g. `1.8p3`
+    fn parse_hex_float(hex: &str) -> Option<f64> {
+        let (mantissa, exp) = hex.split_once('p')?;
+        let (int, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let mut value = 0_f64;
+        for c in int.chars().chain(fraction.chars()) {
+            value = value * 16.0 + f64::from(c.to_digit(16)?);
+        }
+        if value == 0.0 {
+            return Some(0.0);
+        }
+
+        // exponents beyond the range of `f64` overflow or vanish either way
+        let exp = exp.parse::<i64>().ok()?.clamp(-2000, 2000) as i32;
+        Some(value * 2_f64.powi(exp - 4 * fraction.len() as i32))
+    }
+
+    /// Imaginary part of an imaginary literal. Integers with a base prefix keep their base,
+    /// other digits are decimal even with a leading zero, as in `0123i`
+    pub fn parse_imag(literal: &str) -> Result<f64, String> {
+        let digits = literal.strip_suffix('i').unwrap_or(literal);
+        let lower = digits.to_lowercase();
+        let prefixed_int = lower.starts_with("0b")
+            || lower.starts_with("0o")
+            || lower.starts_with("0x") && !lower.contains('p');
+
+        if prefixed_int {
+            parse_int(digits).map(|int| int as f64)
+        } else {
+            parse_float(digits)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::panic;
+
+    use super::*;
+    use crate::testing::CollectingErrorHandler;
+
+    const SRC: &str = r#"package main
+
+import "fmt"
+
+const (
+    a = 1
+    b int = 2
+)
+
+var x, y int = 1, 2
+
+func sum(nums ...int) (int, bool) {
+    s := 0
+    for i := 0; i < len(nums); i++ {
+        s += nums[i]
+    }
+
+    return s, true
+}
+
+func main() {
+    var arr [2]int = [...]int{1, 2}
+    sl := []string{"a", `b`}
+    switch arr[0] {
+    case 1, 2:
+        println(sl[1], 1.5, 'c', !true)
+        fallthrough
+    default:
+        x = -x
+    }
+    if x > 0 && y != 0 || false {
+        sum(arr[0], 2)
+    } else if true {
+    } else {
+        x--
+    }
+}
+"#;
+
+    /// Compiles the source, failing the test if the compiler panics
+    fn compile_checked(src: &str) {
+        let res = panic::catch_unwind(|| {
+            for optimize in [false, true] {
+                let mut errors = CollectingErrorHandler::new();
+                compile_with_options(src, &mut errors, &CompileOptions { optimize });
+            }
+        });
+
+        assert!(res.is_ok(), "Compiler panicked on:\n{}", src);
+    }
+
+    #[test]
+    fn test_compile_truncated() {
+        for (i, _) in SRC.char_indices() {
+            compile_checked(&SRC[..i]);
+        }
+    }
+
+    #[test]
+    fn test_compile_mutilated() {
+        for (i, c) in SRC.char_indices() {
+            let mut src = SRC.to_string();
+            src.replace_range(i..i + c.len_utf8(), "");
+            compile_checked(&src);
+        }
+    }
+
+    #[test]
+    fn test_compile_garbled() {
+        let fragments = [
+            "}",
+            "(",
+            "[",
+            "&",
+            "...",
+            ":=",
+            "func",
+            "var",
+            "99999999999999999999",
+            "1e",
+            "0x",
+            "\"",
+            "'",
+            "`",
+            "/*",
+            "é",
+            "\n",
+        ];
+        for (i, _) in SRC.char_indices() {
+            for fragment in &fragments {
+                let mut src = SRC.to_string();
+                src.insert_str(i, fragment);
+                compile_checked(&src);
+            }
+        }
+    }
+
+    #[test]
+    fn test_func_in_loop() {
+        // jumps pending in the loop must not be patched in the function
+        let sources = [
+            "package main\n\nfunc main() {\n    for i := 0; i < 10; i++ {\n func           break\n    }\n}\n",
+            "package main\n\nfunc main() {\n    switch 1 {\n    case 1:\n        func f() { break }\n    }\n}\n",
+        ];
+        for src in &sources {
+            assert!(!check(src).is_empty(), "{}", src);
+        }
+
+        let src = "package main\n\nfunc main() {\n    for {\n        func g() { for { break } }\n        break\n    }\n}\n";
+        assert!(check(src).is_empty());
+    }
+
+    #[test]
+    fn test_numeric_literals() {
+        assert_eq!(literal::parse_int("0b1010"), Ok(10));
+        assert_eq!(literal::parse_int("0o17"), Ok(15));
+        assert_eq!(literal::parse_int("017"), Ok(15));
+        assert_eq!(literal::parse_int("0X_1f"), Ok(31));
+        assert_eq!(literal::parse_int("1_000_000"), Ok(1_000_000));
+        assert_eq!(literal::parse_int("0"), Ok(0));
+        assert!(literal::parse_int("089").is_err());
+        assert_eq!(literal::parse_float("6_0.0_1"), Ok(60.01));
+        assert_eq!(literal::parse_float("2.5E-3"), Ok(0.0025));
+
+        let errs = |src: &str| -> Vec<String> {
+            let compiler = Compiler::new(src, Lexer::new(src));
+            let (_, errs) = compiler.compile();
+            errs.iter().map(|e| e.to_string()).collect()
+        };
+
+        assert_eq!(
+            errs("package main\nvar a int = 9223372036854775808\nfunc main() {}\n"),
+            vec![
+                "Compile error: Constant 9223372036854775808 overflows int. at [2:13]\n    \
+                var a int = 9223372036854775808\n                \
+                ^^^^^^^^^^^^^^^^^^^"
+            ]
+        );
+        assert_eq!(
+            errs("package main\nvar a = 1e400\nfunc main() {}\n"),
+            vec![
+                "Compile error: Constant 1e400 overflows float64. at [2:9]\n    \
+                var a = 1e400\n            ^^^^^"
+            ]
+        );
+        assert!(errs("package main\nvar a int = 9223372036854775807\nfunc main() {}\n").is_empty());
+        assert!(
+            errs("package main\nvar a uint64 = 18446744073709551615\nfunc main() {}\n").is_empty()
+        );
+        assert!(
+            errs("package main\nconst c = 1 << 100\nconst d = c >> 98\nfunc main() {}\n")
+                .is_empty()
+        );
+
+        let errs = |body: &str| errs(&format!("package main\nfunc main() {{\n{}\n}}\n", body));
+        let first_err = |body: &str| errs(body)[0].lines().next().unwrap().to_string();
+        assert_eq!(
+            first_err("x := 1 << 63"),
+            "Compile error: Constant 9223372036854775808 overflows int. at [3:6]"
+        );
+        assert_eq!(
+            first_err("println(-1 - 1<<64)"),
+            "Compile error: Constant -18446744073709551617 overflows int. at [3:9]"
+        );
+        assert_eq!(
+            first_err("var b uint8 = 1 << 8"),
+            "Compile error: Constant 256 overflows uint8. at [3:15]"
+        );
+        assert_eq!(
+            first_err("const c int8 = -129"),
+            "Compile error: Constant -129 overflows int8. at [3:16]"
+        );
+        assert_eq!(
+            first_err("const c = 1 << 127"),
+            "Compile error: Constant shift overflow. at [3:13]"
+        );
+        assert_eq!(
+            first_err("x := 1 / (2 - 2)"),
+            "Compile error: Division by zero. at [3:8]"
+        );
+        assert_eq!(
+            first_err("x := 1 >> -1"),
+            "Compile error: Invalid negative shift count -1. at [3:8]"
+        );
+        assert!(errs("var f float64 = 1 << 100\nx := 1 << 100 >> 98\nprintln(f, x)").is_empty());
+    }
+
+    #[test]
+    fn test_check() {
+        assert!(check("package main\n\nfunc main() {}\n").is_empty());
+
+        let diagnostics = check("package main\n\nfunc main() {\n    x := \"a\n}\n");
+        assert_eq!(diagnostics[0].code, "lex");
+        assert_eq!(diagnostics[0].pos, Pos(4, 10));
+
+        let diagnostics = check("package main\n\nfunc main() {\n    x := 1 +\n}\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, "compile");
+        assert_eq!(diagnostics[0].message, "Expression expected, got \"}\".");
+        assert_eq!(diagnostics[0].pos, Pos(5, 1));
+
+        assert!(
+            check("package main\n\nimport \"fmt\"\n\nfunc main() {\n    fmt.Println(1)\n}\n")
+                .is_empty()
+        );
+        let diagnostics =
+            check("package main\n\nimport (\n    \"fmt\"\n    \"os\"\n)\n\nfunc main() {}\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Package \"os\" is not found.");
+
+        // unknown type names are reported, rather than left for the vm to fail on
+        let diagnostics = check(
+            "package main\n\nvar g [2]point\n\nfunc main() {\n    var x uint11_0000\n    y := []int{}\n    var z func(qux)\n}\n",
+        );
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Undefined type \"point\".",
+                "Undefined type \"uint11_0000\".",
+                "Undefined type \"qux\".",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint() {
+        let src = r#"package main
+
+var total = 0
+
+func main() {
+    x := 1
+    if x := 2; x > 1 {
+        total = x
+    }
+    if total, y := 3, 4; y > total {
+    }
+    if z := 5; z > 0 {
+    }
+
+    arr := [2]int{1, 2}
+    x = x
+    arr[0], x = arr[0], 2
+    arr[x-1] = arr[x - 1]
+
+    switch x {
+    case 1:
+    case 2, 3:
+    case 1:
+    }
+
+    for true {
+        break
+    }
+    for i := 0; false; i++ {
+    }
+    for x > 0 {
+        x--
+    }
+}
+"#;
+        let warnings = |rules: &str| -> Vec<(String, Pos)> {
+            check_with_lints(src, &LintRules::parse(rules).unwrap())
+                .into_iter()
+                .map(|d| {
+                    assert_eq!(d.severity, crate::Severity::Warning);
+                    (d.code, d.pos)
+                })
+                .collect()
+        };
+
+        let all = warnings("-self-assign,self-assign");
+        let codes: Vec<_> = all.iter().map(|(code, _)| code.as_str()).collect();
+        assert_eq!(
+            codes,
+            vec![
+                "shadow",
+                "shadow",
+                "self-assign",
+                "self-assign",
+                "self-assign",
+                "unreachable-case",
+                "constant-condition",
+                "constant-condition",
+            ]
+        );
+        assert_eq!(all[0].1, Pos(7, 8));
+        assert_eq!(all[1].1, Pos(10, 8));
+        assert_eq!(all[5].1, Pos(23, 10));
+        assert_eq!(all[7].1, Pos(29, 17));
+
+        assert_eq!(warnings("unreachable-case").len(), 1);
+        assert_eq!(warnings("-shadow,-constant-condition").len(), 4);
+        assert!(check(src).is_empty());
+
+        let diagnostics = check_with_lints(src, &LintRules::all());
+        assert_eq!(diagnostics[2].message, "\"x\" is assigned to itself.");
+        assert_eq!(
+            diagnostics[5].message,
+            "Case \"1\" is unreachable, an earlier case matches the same value."
+        );
+    }
+
+    #[test]
+    fn test_completions() {
+        let src = r#"package main
+
+import "fmt"
+
+const limit = 10
+var count int
+
+func add(a int, b float64) int {
+    var c int8
+    d := 1
+    if d > 0 {
+        e := 2
+        a = e
+    }
+    const f = 1
+    return a
+}
+
+func main() {
+    fmt.Println(add(1, 2))
+}
+"#;
+        let names = |offset: usize| -> Vec<(String, CompletionKind, Option<String>)> {
+            completions(src, offset)
+                .into_iter()
+                .take_while(|c| c.kind != CompletionKind::Builtin)
+                .map(|c| (c.name, c.kind, c.vtype.map(|t| t.to_string())))
+                .collect()
+        };
+        let local = |name: &str, kind, vtype: Option<&str>| {
+            (name.to_string(), kind, vtype.map(str::to_string))
+        };
+        let package = vec![
+            local(
+                "add",
+                CompletionKind::Function,
+                Some("func (int, float64)int"),
+            ),
+            local("count", CompletionKind::Global, Some("int")),
+            local("fmt", CompletionKind::Package, None),
+            local("limit", CompletionKind::Constant, None),
+            local("main", CompletionKind::Function, Some("func ()")),
+        ];
+
+        let mut expected = vec![
+            local("e", CompletionKind::Local, None),
+            local("d", CompletionKind::Local, None),
+            local("c", CompletionKind::Local, Some("int8")),
+            local("b", CompletionKind::Local, Some("float64")),
+            local("a", CompletionKind::Local, Some("int")),
+        ];
+        expected.extend(package.clone());
+        assert_eq!(names(src.find("a = e").unwrap()), expected);
+
+        let mut expected = vec![
+            local("f", CompletionKind::Constant, None),
+            local("d", CompletionKind::Local, None),
+            local("c", CompletionKind::Local, Some("int8")),
+            local("b", CompletionKind::Local, Some("float64")),
+            local("a", CompletionKind::Local, Some("int")),
+        ];
+        expected.extend(package.clone());
+        assert_eq!(names(src.find("return").unwrap() + 3), expected);
+
+        assert_eq!(names(0), package);
+
+        let all = completions(src, src.len());
+        assert!(all
+            .iter()
+            .any(|c| c.name == "len" && c.kind == CompletionKind::Builtin));
+        assert!(!all.iter().any(|c| c.name.contains('.')));
+        assert_eq!(all.last().unwrap().kind, CompletionKind::Keyword);
+    }
+
+    #[test]
+    fn test_semantic_tokens() {
+        let src = r#"package main
+
+import "fmt"
+
+// counter of calls
+var calls int
+
+func main() {
+    const limit = 2
+    n := limit + 1.5
+    fmt.Println(twice(n), len("ab"), calls, max)
+}
+
+func twice(x float64) float64 { return x * 2 }
+
+const max = true
+"#;
+        let classes: Vec<_> = semantic_tokens(src)
+            .into_iter()
+            .map(|t| (&src[t.span.start..t.span.end], t.class))
+            .collect();
+
+        use TokenClass::*;
+        assert_eq!(
+            classes,
+            vec![
+                ("package", Keyword),
+                ("main", Package),
+                ("import", Keyword),
+                ("\"fmt\"", String),
+                ("// counter of calls", Comment),
+                ("var", Keyword),
+                ("calls", Variable),
+                ("int", Type),
+                ("func", Keyword),
+                ("main", Function),
+                ("const", Keyword),
+                ("limit", Constant),
+                ("2", Number),
+                ("n", Variable),
+                ("limit", Constant),
+                ("1.5", Number),
+                ("fmt", Package),
+                ("Println", Function),
+                ("twice", Function),
+                ("n", Variable),
+                ("len", Function),
+                ("\"ab\"", String),
+                ("calls", Variable),
+                ("max", Constant),
+                ("func", Keyword),
+                ("twice", Function),
+                ("x", Variable),
+                ("float64", Type),
+                ("float64", Type),
+                ("return", Keyword),
+                ("x", Variable),
+                ("2", Number),
+                ("const", Keyword),
+                ("max", Constant),
+                ("true", Constant),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_return_validation() {
+        let src = "package main
+
+func pair() (int, string) {
+    return 1, \"a\"
+}
+
+func wrap() (int, string) {
+    return pair()
+}
+
+func void() {
+    return
+}
+
+func main() {}
+";
+        assert!(check(src).is_empty());
+
+        let cases = [
+            (
+                "return",
+                "Return statement outside of function body.",
+                Pos(3, 1),
+            ),
+            (
+                "func f() int {\n    return\n}",
+                "Not enough return values, expected 1, got 0.",
+                Pos(4, 5),
+            ),
+            (
+                "func f() {\n    return 1, 2\n}",
+                "Too many return values, expected 0, got 2.",
+                Pos(4, 5),
+            ),
+            (
+                "func f() bool {\n    return \"s\"\n}",
+                "Cannot use value of type \"string\" as \"bool\" in return statement.",
+                Pos(4, 5),
+            ),
+        ];
+        for (decl, msg, pos) in cases {
+            let diagnostics = check(&format!("package main\n\n{}\n\nfunc main() {{}}\n", decl));
+            assert_eq!(diagnostics.len(), 1, "{}", decl);
+            assert_eq!(diagnostics[0].message, msg);
+            assert_eq!(diagnostics[0].pos, pos, "{}", decl);
+        }
+    }
+
+    #[test]
+    fn test_entry_point() {
+        let diagnostics = check("package main\n\nfunc f() {}\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Function \"main\" is undeclared in package \"main\""
+        );
+        assert_eq!(diagnostics[0].pos, Pos(1, 9));
+
+        let diagnostics = check("package main\n\nfunc main() int {\n    return 1\n}\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].pos, Pos(3, 6));
+
+        assert!(check("package other\n\nfunc f() {}\n").is_empty());
+
+        let run = |src: &str| -> Vec<Diagnostic> {
+            let (_, errs) = Compiler::new(src, Lexer::new(src)).compile();
+            errs.iter().map(Diagnostic::from).collect()
+        };
+
+        assert!(run("package main\n\nfunc main() {}\n").is_empty());
+        for src in [
+            "package other\n\nfunc f() {}\n",
+            "package other\n\nfunc main() {}\n",
+        ] {
+            let diagnostics = run(src);
+            assert_eq!(diagnostics.len(), 1);
+            assert!(diagnostics[0]
+                .message
+                .starts_with("Package \"other\" cannot be run"));
+            assert!(diagnostics[0]
+                .message
+                .ends_with("Rename the package to \"main\" to run it"));
+            assert_eq!(diagnostics[0].pos, Pos(1, 9));
+        }
+    }
+
+    #[test]
+    fn test_init() {
+        assert!(
+            check("package main\n\nfunc init() {}\n\nfunc init() {}\n\nfunc main() {}\n")
+                .is_empty()
+        );
+
+        let diagnostics = check(
+            "package main\n\nvar init = 1\n\nfunc init(x int) {}\n\nfunc main() {\n    init()\n}\n",
+        );
+        let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "Cannot declare \"init\", it must be a function.",
+                "Function \"init\" must not have parameters and a return value",
+                "Undefined \"init\".",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undefined_type() {
+        let diagnostics = check("package main\n\nfunc main() {\n    var x uint11_0000\n}\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Undefined type \"uint11_0000\".");
+        assert_eq!(diagnostics[0].pos, Pos(4, 11));
+    }
+
+    #[test]
+    fn test_const_omitted_init() {
+        let diagnostics = check("package main\n\nconst (\n    a\n    b = 1\n)\n\nfunc main() {}\n");
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].message,
+            "Missing init expression for const declaration."
+        );
+        assert_eq!(diagnostics[0].pos, Pos(4, 6));
+    }
+
+    #[test]
+    fn test_multi_assign_count() {
+        let src = "package main
+
+func main() {
+    a, b := pair()
+    var c, d = pair()
+    e, f := 1, 2
+    println(a, b, c, d, e, f)
+}
+
+func pair() (int, string) {
+    return 1, \"a\"
+}
+";
+        assert!(check(src).is_empty());
+
+        let cases = [
+            ("a, b := single()", Pos(4, 13)),
+            ("var a, b, c = pair()", Pos(4, 19)),
+            ("a, b := 1", Pos(4, 14)),
+        ];
+        for (stmt, pos) in cases {
+            let src = format!(
+                "package main\n\nfunc main() {{\n    {}\n}}\n\nfunc single() int {{\n    return 1\n}}\n\nfunc pair() (int, int) {{\n    return 1, 2\n}}\n",
+                stmt
+            );
+            let diagnostics = check(&src);
+            assert_eq!(diagnostics.len(), 1, "{}", stmt);
+            assert!(diagnostics[0]
+                .message
+                .starts_with("assignment count mismatch"));
+            assert_eq!(diagnostics[0].pos, pos, "{}", stmt);
+        }
+    }
+
+    #[test]
+    fn test_init_cycle() {
+        let diagnostics = check(
+            "package main\n\nvar a = f()\n\nfunc f() int {\n    return b\n}\n\nvar b = a\n\nfunc main() {}\n",
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "Initialization cycle for \"a\".");
+        assert_eq!(diagnostics[0].pos, Pos(3, 5));
+    }
+
+    #[test]
+    fn test_package_redeclaration() {
+        let src = "package main
+
+var x = 1
+const x = 2
+
+func y() {}
+
+var (
+    y int
+    z = 3
+)
+
+func main() {
+    x := 4
+    println(x, z)
+}
+";
+        let diagnostics = check(src);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].message, "\"x\" redeclared in this block.");
+        assert_eq!(diagnostics[0].pos, Pos(4, 7));
+        assert_eq!(diagnostics[1].message, "\"y\" redeclared in this block.");
+        assert_eq!(diagnostics[1].pos, Pos(9, 5));
+    }
+
+    #[test]
+    fn test_const_expr() {
+        let src = "package main
+
+var v = 1
+
+func f() int {
+    return 1
+}
+
+const a, b = 1 + 2, len(\"abc\") * int8(2)
+const c = a - b
+
+func main() {
+    const d = c + 1
+}
+";
+        assert!(check(src).is_empty());
+
+        let cases = [
+            ("const x = f()", "\"f()\" is not constant.", Pos(11, 11)),
+            ("const x = v + 1", "\"v\" is not constant.", Pos(11, 11)),
+            ("const x = a + f", "\"f\" is not constant.", Pos(11, 15)),
+            (
+                "const x = \"abc\"[1]",
+                "Index expression is not constant.",
+                Pos(11, 16),
+            ),
+            (
+                "func g() {\n    y := 1\n    const x = y\n}",
+                "\"y\" is not constant.",
+                Pos(13, 15),
+            ),
+        ];
+        for (decl, msg, pos) in cases {
+            let src = format!(
+                "package main\n\nvar v = 1\n\nfunc f() int {{\n    return 1\n}}\n\nconst a = 1\n\n{}\n\nfunc main() {{}}\n",
+                decl
+            );
+            let diagnostics = check(&src);
+            assert_eq!(diagnostics.len(), 1, "{}", decl);
+            assert_eq!(diagnostics[0].message, msg);
+            assert_eq!(diagnostics[0].pos, pos, "{}", decl);
+        }
+    }
+
+    #[test]
+    fn test_inc_dec_stmt() {
+        let cases = [
+            (
+                "y := x++",
+                "\"++\" can only be used as a statement.",
+                Pos(5, 11),
+            ),
+            (
+                "x = x--",
+                "\"--\" can only be used as a statement.",
+                Pos(5, 10),
+            ),
+            (
+                "if x++ {\n    }",
+                "\"++\" can only be used as a statement.",
+                Pos(5, 9),
+            ),
+            (
+                "for x-- {\n    }",
+                "\"--\" can only be used as a statement.",
+                Pos(5, 10),
+            ),
+        ];
+
+        for (stmt, msg, pos) in cases.iter() {
+            let src = format!(
+                "package main\n\nfunc main() {{\n    x := 1\n    {}\n    println(x)\n}}\n",
+                stmt
+            );
+            let diagnostics = check(&src);
+            assert_eq!(diagnostics.len(), 1, "{}", stmt);
+            assert_eq!(&diagnostics[0].message, msg);
+            assert_eq!(&diagnostics[0].pos, pos, "{}", stmt);
+        }
+
+        let src = "package main
+
+func main() {
+    x := [2]int{}
+    i := 0
+    x[i]++
+    for ; i < 2; i++ {
+        x[i]--
+    }
+    if i++; i > 0 {
+        println(x)
+    }
+}
+";
+        assert!(check(src).is_empty());
+    }
+
+    #[test]
+    fn test_unused_expr_stmt() {
+        let cases = [
+            ("x + 1", "\"x + 1\" evaluated but not used.", Pos(5, 5)),
+            ("len(s)", "\"len(s)\" evaluated but not used.", Pos(5, 5)),
+            (
+                "append(s, x)",
+                "\"append(s, x)\" evaluated but not used.",
+                Pos(5, 5),
+            ),
+            ("-f()", "\"-f()\" evaluated but not used.", Pos(5, 5)),
+            ("f", "\"f\" evaluated but not used.", Pos(5, 5)),
+            (
+                "for ; x < 2; x + 1 {\n    }",
+                "\"x + 1\" evaluated but not used.",
+                Pos(5, 18),
+            ),
+        ];
+
+        for (stmt, msg, pos) in cases.iter() {
+            let src = format!(
+                "package main\n\nfunc main() {{\n    x, s := 1, []int{{}}\n    {}\n    println(x, s)\n}}\n\nfunc f() int {{\n    return 1\n}}\n",
+                stmt
+            );
+            let diagnostics = check(&src);
+            assert_eq!(diagnostics.len(), 1, "{}", stmt);
+            assert_eq!(&diagnostics[0].message, msg);
+            assert_eq!(&diagnostics[0].pos, pos, "{}", stmt);
+        }
+
+        let src = "package main
+
+func main() {
+    s := []int{1}
+    s[0] = f()
+    s[0] += 1
+    f()
+    (f())
+    copy(s, s)
+    println(s)
+}
+
+func f() int {
+    return 1
+}
+";
+        assert!(check(src).is_empty());
+    }
+
+    #[test]
+    fn test_slice_expr_constants() {
+        let cases = [
+            ("s[2:1]", "Invalid slice indices: 1 < 2.", Pos(5, 14)),
+            ("s[1:3:2]", "Invalid slice indices: 2 < 3.", Pos(5, 14)),
+            ("s[3:i:2]", "Invalid slice indices: 2 < 3.", Pos(5, 14)),
+            (
+                "s[:-1]",
+                "Invalid slice index -1 (index must be non-negative).",
+                Pos(5, 14),
+            ),
+            (
+                "s[1::3]",
+                "Middle index required in 3-index slice.",
+                Pos(5, 17),
+            ),
+            (
+                "s[1:2:]",
+                "Final index required in 3-index slice.",
+                Pos(5, 19),
+            ),
+        ];
+
+        for (expr, msg, pos) in cases.iter() {
+            let src = format!(
+                "package main\n\nfunc main() {{\n    s, i := []int{{1, 2, 3}}, 1\n    println({}, i)\n}}\n",
+                expr
+            );
+            let diagnostics = check(&src);
+            assert_eq!(diagnostics.len(), 1, "{}", expr);
+            assert_eq!(&diagnostics[0].message, msg);
+            assert_eq!(&diagnostics[0].pos, pos, "{}", expr);
+        }
+
+        let src = "package main\n\nfunc main() {\n    s, i := []int{1, 2, 3}, 1\n    println(s[i:2:3], s[:i:i], s[1:1:1])\n}\n";
+        assert!(check(src).is_empty());
+    }
+
+    #[test]
+    fn test_constant_index() {
+        let out_of_bounds = |index, len| {
+            format!(
+                "Invalid array index {} (out of bounds for {}-element array).",
+                index, len
+            )
+        };
+        let cases = [
+            ("println(a[3])", out_of_bounds(3, 3)),
+            ("println(b[5])", out_of_bounds(5, 3)),
+            ("println(m[1][4])", out_of_bounds(4, 4)),
+            ("println(m[2][0])", out_of_bounds(2, 2)),
+            ("println(g[2])", out_of_bounds(2, 2)),
+            ("println([3]int{}[3])", out_of_bounds(3, 3)),
+            ("a[3] = 1", out_of_bounds(3, 3)),
+            ("m[0][4] = 1", out_of_bounds(4, 4)),
+            ("a[3] += 1", out_of_bounds(3, 3)),
+            ("a[3]++", out_of_bounds(3, 3)),
+            ("println(a[K])", out_of_bounds(5, 3)),
+            ("const j = 4; println(a[j])", out_of_bounds(4, 3)),
+            ("println(a[1+2])", out_of_bounds(3, 3)),
+            ("a[K-2] = 1", out_of_bounds(3, 3)),
+            (
+                "println(a[-1])",
+                "Invalid array index -1 (index must be non-negative).".to_string(),
+            ),
+            (
+                "s[-1] = 2",
+                "Invalid slice index -1 (index must be non-negative).".to_string(),
+            ),
+        ];
+
+        for (stmt, msg) in cases.iter() {
+            let src = format!(
+                "package main\n\nvar g [2]int\n\nconst K = 5\n\nfunc main() {{\n    var a [3]int\n    s := []int{{1}}\n    var m [2][4]int\n    b := a\n    {}\n    println(a, s, m, b)\n}}\n",
+                stmt
+            );
+            let diagnostics = check(&src);
+            assert_eq!(diagnostics.len(), 1, "{}", stmt);
+            assert_eq!(&diagnostics[0].message, msg);
+            assert_eq!(diagnostics[0].pos.0, 12, "{}", stmt);
+        }
+
+        let src = "package main
+
+var g [2]int
+
+const K = 5
+
+func main() {
+    a, i := [3]int{1, 2, 3}, 1
+    var m [2][4]int
+    b := a
+    s := []int{1}
+    const j = K - 3
+    m[1][3] = a[2] + b[i] + g[1] + s[5] + a[j] + a[K/2]
+    println(m)
+}
+";
+        assert!(check(src).is_empty());
+    }
+
+    #[test]
+    fn test_index_non_indexable() {
+        let cases = [
+            (
+                "println[:0](len(a))",
+                "Invalid operation: cannot slice builtin \"println\".",
+            ),
+            (
+                "println(len[0])",
+                "Invalid operation: cannot index builtin \"len\".",
+            ),
+            (
+                "println(i[1:])",
+                "Invalid operation: cannot slice value of type \"int\".",
+            ),
+            (
+                "i[0] = 1",
+                "Invalid operation: cannot index value of type \"int\".",
+            ),
+            (
+                "len[0] = 1",
+                "Invalid operation: cannot index builtin \"len\".",
+            ),
+        ];
+
+        for (stmt, msg) in cases.iter() {
+            let src = format!(
+                "package main\n\nfunc main() {{\n    a := []int{{1}}\n    var i int\n    {}\n    println(a, i)\n}}\n",
+                stmt
+            );
+            let diagnostics = check(&src);
+            assert_eq!(diagnostics.len(), 1, "{}", stmt);
+            assert_eq!(diagnostics[0].message, *msg);
+            assert_eq!(diagnostics[0].pos.0, 6, "{}", stmt);
+        }
+
+        let src =
+            "package main\n\nfunc main() {\n    len := \"abc\"\n    println(len[1:], len[0])\n}\n";
+        assert!(check(src).is_empty());
+    }
+}
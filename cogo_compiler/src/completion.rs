@@ -0,0 +1,61 @@
+use crate::ValType;
+
+/// Name an editor may offer to complete an identifier with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Completion {
+    pub name: String,
+    pub kind: CompletionKind,
+    /// Declared type, or the one of an initializer the compiler knows without evaluating it,
+    /// e.g. an array literal. Unknown for other inferred types
+    pub vtype: Option<ValType>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CompletionKind {
+    /// Variable or parameter of the function around the position
+    Local,
+    /// Package-level variable
+    Global,
+    /// Local or package-level constant
+    Constant,
+    /// Package-level function
+    Function,
+    /// Imported package
+    Package,
+    /// Builtin function or conversion, or a predeclared constant
+    Builtin,
+    Keyword,
+}
+
+impl Completion {
+    pub(crate) fn new(name: &str, kind: CompletionKind, vtype: Option<ValType>) -> Self {
+        Self {
+            name: name.to_string(),
+            kind,
+            vtype,
+        }
+    }
+}
+
+/// Names gathered by the compiler for `completions()`
+pub(crate) struct CompletionQuery {
+    /// Byte offset of the position the names are visible at
+    pub(crate) offset: usize,
+    /// Locals in scope at the offset, innermost first, taken once the compiler reaches it
+    pub(crate) locals: Option<Vec<Completion>>,
+}
+
+impl CompletionQuery {
+    pub(crate) fn new(offset: usize) -> Self {
+        Self {
+            offset,
+            locals: None,
+        }
+    }
+}
+
+/// Whether a name the compiler declares can be written in the source,
+/// internal names such as those of `init` functions cannot
+pub(crate) fn is_nameable(name: &str) -> bool {
+    !name.contains(|c: char| c == '.' || c.is_whitespace())
+}
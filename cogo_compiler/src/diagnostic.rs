@@ -0,0 +1,127 @@
+use std::fmt;
+
+use crate::error::CompilerError;
+use crate::lex::lexeme::{Pos, Span};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// Problem found in a program, independent of the stage that found it
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Kind of the problem, e.g. `lex`, `compile` or `runtime`, or the name of a lint rule
+    pub code: String,
+    pub message: String,
+    pub pos: Pos,
+    /// Source bytes the problem relates to, when known
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    pub fn error(code: &str, message: String, pos: Pos, span: Option<Span>) -> Self {
+        Self {
+            severity: Severity::Error,
+            code: code.to_string(),
+            message,
+            pos,
+            span,
+        }
+    }
+
+    /// Single-line JSON object describing the diagnostic found in `file`
+    pub fn to_json(&self, file: &str) -> String {
+        let span = match self.span {
+            Some(span) => format!("{{\"start\":{},\"end\":{}}}", span.start, span.end),
+            None => "null".to_string(),
+        };
+
+        format!(
+            "{{\"file\":{},\"line\":{},\"column\":{},\"span\":{},\"severity\":\"{}\",\"code\":{},\"message\":{}}}",
+            json_string(file),
+            self.pos.0,
+            self.pos.1,
+            span,
+            self.severity,
+            json_string(&self.code),
+            json_string(&self.message),
+        )
+    }
+}
+
+impl From<&CompilerError> for Diagnostic {
+    fn from(err: &CompilerError) -> Self {
+        let code = match err {
+            CompilerError::Lex(_) => "lex",
+            CompilerError::Compile(_) => "compile",
+        };
+
+        Self::error(code, err.msg(), err.pos(), err.span())
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} at {}", self.severity, self.message, self.pos)
+    }
+}
+
+/// Quoted JSON string with the necessary characters escaped
+pub(crate) fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_json() {
+        let err = CompilerError::Compile(crate::error::CompileError::new(
+            "Expected \"}\"".to_string(),
+            Pos(2, 5),
+            Span::new(14, 15),
+        ));
+        let diagnostic = Diagnostic::from(&err);
+        assert_eq!(
+            diagnostic.to_json("dir\\main.go"),
+            r#"{"file":"dir\\main.go","line":2,"column":5,"span":{"start":14,"end":15},"severity":"error","code":"compile","message":"Expected \"}\""}"#
+        );
+
+        let err = CompilerError::Lex(crate::lex::LexError::UnterminatedString(Pos(1, 1)));
+        let diagnostic = Diagnostic::from(&err);
+        assert_eq!(
+            diagnostic.to_json("main.go"),
+            r#"{"file":"main.go","line":1,"column":1,"span":null,"severity":"error","code":"lex","message":"Unterminated string"}"#
+        );
+
+        assert_eq!(json_string("a\tb\u{1}"), r#""a\tb\u0001""#);
+    }
+}
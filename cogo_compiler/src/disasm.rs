@@ -0,0 +1,221 @@
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use crate::{Chunk, CompilationUnit, OpCode, BUILTINS};
+
+/// Listing of the opcodes of the unit and of every function defined in it
+pub fn disassemble(cunit: &CompilationUnit) -> String {
+    let mut listing = String::new();
+    disassemble_unit(cunit, &mut listing);
+
+    listing
+}
+
+fn disassemble_unit(cunit: &CompilationUnit, listing: &mut String) {
+    writeln!(listing, "{}:", header(cunit)).unwrap();
+
+    let chunk = cunit.chunk();
+    for (i, code) in chunk.codes().iter().enumerate() {
+        writeln!(
+            listing,
+            "{:>6}  {:<40} {}",
+            format!("#{}", i),
+            code_text(chunk, code),
+            chunk.pos(i)
+        )
+        .unwrap();
+    }
+
+    for func in functions(chunk) {
+        listing.push('\n');
+        disassemble_unit(func, listing);
+    }
+}
+
+/// Listing of the unit and of every function defined in it, each headed by its signature
+/// and the slots of its locals. Jumps show where they lead, the opcodes they lead to are marked
+pub fn dump(cunit: &CompilationUnit) -> String {
+    let mut listing = String::new();
+    dump_unit(cunit, &mut listing);
+
+    listing
+}
+
+fn dump_unit(cunit: &CompilationUnit, listing: &mut String) {
+    let chunk = cunit.chunk();
+    writeln!(listing, "{}", header(cunit)).unwrap();
+    match cunit {
+        CompilationUnit::Package(_) => {
+            let globals: Vec<&str> = chunk
+                .codes()
+                .iter()
+                .filter_map(|code| match code {
+                    OpCode::VarGlobal(name, _) | OpCode::ConstGlobal(name, _) => {
+                        Some(name.as_str())
+                    }
+                    _ => None,
+                })
+                .collect();
+            if !globals.is_empty() {
+                writeln!(listing, "  globals: {}", globals.join(", ")).unwrap();
+            }
+        }
+        CompilationUnit::Function(f) => {
+            let params: Vec<String> = f
+                .func_type()
+                .args()
+                .iter()
+                .enumerate()
+                .map(|(i, param)| format!("#{} {}", i, param))
+                .collect();
+            if !params.is_empty() {
+                writeln!(listing, "  params: {}", params.join(", ")).unwrap();
+            }
+
+            let locals: Vec<String> = local_slots(chunk)
+                .into_iter()
+                .filter(|slot| *slot >= params.len())
+                .map(|slot| format!("#{}", slot))
+                .collect();
+            if !locals.is_empty() {
+                writeln!(listing, "  locals: {}", locals.join(", ")).unwrap();
+            }
+        }
+    }
+
+    let targets = chunk.jump_targets();
+    for (i, code) in chunk.codes().iter().enumerate() {
+        let mut text = code_text(chunk, code);
+        for target in code.jump_targets() {
+            let direction = if target <= i { "back " } else { "" };
+            write!(text, " -> {}#{}", direction, target).unwrap();
+        }
+
+        writeln!(
+            listing,
+            "{} {:>6}  {:<40} {}",
+            if targets.contains(&i) { '>' } else { ' ' },
+            format!("#{}", i),
+            text,
+            chunk.pos(i)
+        )
+        .unwrap();
+    }
+
+    for func in functions(chunk) {
+        listing.push('\n');
+        dump_unit(func, listing);
+    }
+}
+
+fn header(cunit: &CompilationUnit) -> String {
+    match cunit {
+        CompilationUnit::Package(p) => format!("package {}", p.package().0),
+        CompilationUnit::Function(f) => {
+            format!("func {}{}", f.function().0, signature(f.func_type()))
+        }
+    }
+}
+
+/// Opcode along with the constant or the builtin it refers to, types are shown as in Go
+fn code_text(chunk: &Chunk, code: &OpCode) -> String {
+    match code {
+        OpCode::VarGlobal(name, Some(vtype)) => format!("VarGlobal({:?}, {})", name, vtype),
+        OpCode::ConstGlobal(name, Some(vtype)) => format!("ConstGlobal({:?}, {})", name, vtype),
+        OpCode::Func(CompilationUnit::Function(f)) => format!("Func({})", f.function().0),
+        OpCode::Func(CompilationUnit::Package(_)) => "Func".to_string(),
+        OpCode::CallBuiltin(id, _) => format!("{:?} ({})", code, BUILTINS[*id as usize]),
+        code => match code.constant_index() {
+            Some(c) => format!("{:?} ({})", code, chunk.constant(c)),
+            None => format!("{:?}", code),
+        },
+    }
+}
+
+fn functions(chunk: &Chunk) -> impl Iterator<Item = &CompilationUnit> {
+    chunk.codes().iter().filter_map(|code| match code {
+        OpCode::Func(func) => Some(func),
+        _ => None,
+    })
+}
+
+/// Slots of the locals the opcodes access, in ascending order
+fn local_slots(chunk: &Chunk) -> BTreeSet<usize> {
+    chunk
+        .codes()
+        .iter()
+        .filter_map(|code| match code {
+            OpCode::GetLocal(i)
+            | OpCode::SetLocal(i)
+            | OpCode::IncLocal(i, _)
+            | OpCode::DecLocal(i, _)
+            | OpCode::GetLocalIndex(i)
+            | OpCode::SetLocalIndex(i, _, false) => Some(*i),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Function type without the `func` keyword, e.g. `(int, ...string) bool`
+fn signature(ftype: &crate::vtype::FuncType) -> String {
+    let args: Vec<String> = ftype.args().iter().map(|a| a.to_string()).collect();
+    let ret_type = ftype.ret_type().to_string();
+    if ret_type.is_empty() {
+        format!("({})", args.join(", "))
+    } else {
+        format!("({}) {}", args.join(", "), ret_type)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::compile_valid;
+
+    #[test]
+    fn test_disassemble() {
+        let src = "package main\n\nfunc main() {\n    println(\"hi\")\n}\n";
+        let listing = disassemble(&compile_valid(src));
+
+        assert_eq!(
+            listing,
+            r#"package main:
+    #0  Noop                                     [1:1]
+    #1  Func(main)                               [5:2]
+    #2  VarGlobal("main", func ())               [5:2]
+    #3  GetGlobal("main")                        [6:1]
+    #4  Call(0, false)                           [6:1]
+
+func main():
+    #0  Constant(0) (hi)                         [4:13]
+    #1  CallBuiltin(1, 1) (println)              [4:17]
+    #2  Pop                                      [4:17]
+"#
+        );
+    }
+
+    #[test]
+    fn test_dump() {
+        let src = "package main\n\nfunc count(n int) {\n    for i := 0; i < n; i++ {\n    }\n}\n\nfunc main() {\n    count(2)\n}\n";
+        let listing = dump(&compile_valid(src));
+
+        assert!(listing.starts_with("package main\n  globals: count, main\n"));
+        assert!(listing.contains("\nfunc count(int)\n  params: #0 int\n  locals: #1\n"));
+
+        let lines: Vec<&str> = listing.lines().collect();
+        let jump_back = lines
+            .iter()
+            .find(|line| line.contains("-> back #"))
+            .unwrap();
+        let target = jump_back
+            .split("-> back ")
+            .nth(1)
+            .unwrap()
+            .split(' ')
+            .next()
+            .unwrap();
+        assert!(lines
+            .iter()
+            .any(|line| line.starts_with('>') && line.split_whitespace().nth(1) == Some(target)));
+    }
+}
@@ -0,0 +1,447 @@
+use std::fmt;
+
+use crate::diagnostic::json_string;
+use crate::lex::lexeme::{Comment, Lexeme, Pos, Token};
+use crate::lex::Lexer;
+
+/// Documentation of a package: its doc comment and the exported declarations, in source order
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackageDoc {
+    pub name: String,
+    pub doc: String,
+    pub items: Vec<DocItem>,
+}
+
+/// Exported package-level declaration along with its doc comment
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocItem {
+    pub kind: DocKind,
+    pub name: String,
+    /// Declaration as written in the source, without the body of a function
+    pub signature: String,
+    /// Text of the comments right above the declaration, without the delimiters
+    pub doc: String,
+    /// Position of the name
+    pub pos: Pos,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DocKind {
+    Const,
+    Var,
+    Type,
+    Func,
+}
+
+impl fmt::Display for DocKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Const => write!(f, "const"),
+            Self::Var => write!(f, "var"),
+            Self::Type => write!(f, "type"),
+            Self::Func => write!(f, "func"),
+        }
+    }
+}
+
+/// Documents the package in the source.
+/// Only the lexemes are looked at, so a source which does not compile is documented as well.
+/// Names starting with an upper case letter are exported, as are methods with such names.
+pub fn document(src: &str) -> PackageDoc {
+    let mut lexer = Lexer::new(src).with_comments();
+    let lexemes = lexer.lex().0.to_vec();
+    let extractor = Extractor {
+        src,
+        lexemes: &lexemes,
+        comments: lexer.comments(),
+    };
+
+    extractor.package()
+}
+
+impl PackageDoc {
+    /// Single-line JSON object describing the package
+    pub fn to_json(&self) -> String {
+        let items: Vec<String> = self
+            .items
+            .iter()
+            .map(|item| {
+                format!(
+                    "{{\"kind\":\"{}\",\"name\":{},\"signature\":{},\"doc\":{},\"line\":{},\"column\":{}}}",
+                    item.kind,
+                    json_string(&item.name),
+                    json_string(&item.signature),
+                    json_string(&item.doc),
+                    item.pos.0,
+                    item.pos.1,
+                )
+            })
+            .collect();
+
+        format!(
+            "{{\"package\":{},\"doc\":{},\"items\":[{}]}}",
+            json_string(&self.name),
+            json_string(&self.doc),
+            items.join(","),
+        )
+    }
+}
+
+impl fmt::Display for PackageDoc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "package {}", self.name)?;
+        if !self.doc.is_empty() {
+            writeln!(f, "\n{}", self.doc)?;
+        }
+
+        for item in &self.items {
+            writeln!(f, "\n{}", item.signature)?;
+            for line in item.doc.lines() {
+                if line.is_empty() {
+                    writeln!(f)?;
+                } else {
+                    writeln!(f, "    {}", line)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct Extractor<'a> {
+    src: &'a str,
+    lexemes: &'a [Lexeme],
+    comments: &'a [Comment],
+}
+
+impl Extractor<'_> {
+    fn package(&self) -> PackageDoc {
+        let mut doc = PackageDoc {
+            name: String::new(),
+            doc: String::new(),
+            items: vec![],
+        };
+
+        let mut depth = 0usize;
+        for (i, lexeme) in self.lexemes.iter().enumerate() {
+            match lexeme.token {
+                Token::LeftCurlyBrace | Token::LeftParen | Token::LeftBracket => depth += 1,
+                Token::RightCurlyBrace | Token::RightParen | Token::RightBracket => {
+                    depth = depth.saturating_sub(1)
+                }
+                // keywords starting declarations nested in others, e.g. `var f func()`
+                _ if depth > 0 || (i > 0 && self.token(i - 1) != Token::Semicolon) => {}
+                Token::Package => {
+                    doc.name = self.identifier(i + 1).unwrap_or_default().to_string();
+                    doc.doc = self.doc_comment(i);
+                }
+                Token::Func => doc.items.extend(self.func(i)),
+                Token::Const => self.decl(i, DocKind::Const, &mut doc.items),
+                Token::Var => self.decl(i, DocKind::Var, &mut doc.items),
+                Token::Type => self.decl(i, DocKind::Type, &mut doc.items),
+                _ => {}
+            }
+        }
+
+        doc
+    }
+
+    /// Function or method declared at `start`, its signature ends where the body starts
+    fn func(&self, start: usize) -> Option<DocItem> {
+        let mut name = start + 1;
+        if self.token(name) == Token::LeftParen {
+            name = self.closing(name) + 1;
+        }
+        let ident = self.identifier(name)?;
+        if !is_exported(ident) {
+            return None;
+        }
+
+        let mut end = name;
+        let mut depth = 0usize;
+        loop {
+            match self.token(end) {
+                Token::LeftParen | Token::LeftBracket => depth += 1,
+                Token::RightParen | Token::RightBracket => depth = depth.saturating_sub(1),
+                Token::LeftCurlyBrace | Token::Semicolon if depth == 0 => break,
+                Token::Eof => break,
+                _ => {}
+            }
+            end += 1;
+        }
+
+        Some(DocItem {
+            kind: DocKind::Func,
+            name: ident.to_string(),
+            signature: self.text(start, end).to_string(),
+            doc: self.doc_comment(start),
+            pos: self.lexemes[name].pos,
+        })
+    }
+
+    /// Specs of the `const`, `var` or `type` declaration at `start`, either a single one or a group
+    fn decl(&self, start: usize, kind: DocKind, items: &mut Vec<DocItem>) {
+        if self.token(start + 1) != Token::LeftParen {
+            let end = self.spec_end(start + 1);
+            let doc = self.doc_comment(start);
+            let signature = self.text(start, end).to_string();
+            self.spec(start + 1, kind, signature, doc, items);
+            return;
+        }
+
+        let close = self.closing(start + 1);
+        let mut spec = start + 2;
+        while spec < close {
+            if self.token(spec) == Token::Semicolon {
+                spec += 1;
+                continue;
+            }
+
+            let end = self.spec_end(spec).min(close);
+            let mut doc = self.doc_comment(spec);
+            if doc.is_empty() {
+                doc = self.doc_comment(start);
+            }
+            let signature = format!("{} {}", kind, self.text(spec, end));
+            self.spec(spec, kind, signature, doc, items);
+            spec = end.max(spec + 1);
+        }
+    }
+
+    /// Adds an item for every exported name declared by the spec at `start`
+    fn spec(
+        &self,
+        start: usize,
+        kind: DocKind,
+        signature: String,
+        doc: String,
+        items: &mut Vec<DocItem>,
+    ) {
+        let mut i = start;
+        while let Some(name) = self.identifier(i) {
+            if is_exported(name) {
+                items.push(DocItem {
+                    kind,
+                    name: name.to_string(),
+                    signature: signature.clone(),
+                    doc: doc.clone(),
+                    pos: self.lexemes[i].pos,
+                });
+            }
+
+            if kind == DocKind::Type || self.token(i + 1) != Token::Comma {
+                break;
+            }
+            i += 2;
+        }
+    }
+
+    /// Index of the semicolon or the closing parenthesis of the group the spec at `start` ends at
+    fn spec_end(&self, start: usize) -> usize {
+        let mut depth = 0usize;
+        let mut end = start;
+        loop {
+            match self.token(end) {
+                Token::LeftCurlyBrace | Token::LeftParen | Token::LeftBracket => depth += 1,
+                Token::RightCurlyBrace | Token::RightParen | Token::RightBracket if depth == 0 => {
+                    return end
+                }
+                Token::RightCurlyBrace | Token::RightParen | Token::RightBracket => depth -= 1,
+                Token::Semicolon if depth == 0 => return end,
+                Token::Eof => return end,
+                _ => {}
+            }
+            end += 1;
+        }
+    }
+
+    /// Index of the parenthesis closing the one at `open`
+    fn closing(&self, open: usize) -> usize {
+        let mut depth = 0usize;
+        let mut i = open;
+        loop {
+            match self.token(i) {
+                Token::LeftParen => depth += 1,
+                Token::RightParen if depth <= 1 => return i,
+                Token::RightParen => depth -= 1,
+                Token::Eof => return i,
+                _ => {}
+            }
+            i += 1;
+        }
+    }
+
+    fn token(&self, i: usize) -> Token {
+        self.lexemes
+            .get(i)
+            .map_or(Token::Eof, |lexeme| lexeme.token)
+    }
+
+    fn identifier(&self, i: usize) -> Option<&str> {
+        match self.lexemes.get(i) {
+            Some(lexeme) if lexeme.token == Token::Identifier => Some(&lexeme.literal),
+            _ => None,
+        }
+    }
+
+    /// Source of the lexemes from `start` up to `end` (exclusive)
+    fn text(&self, start: usize, end: usize) -> &str {
+        let last = &self.lexemes[end.max(start + 1) - 1];
+        &self.src[self.lexemes[start].span.start..last.span.end]
+    }
+
+    /// Comments on the lines right above the lexeme, with nothing but blanks before them
+    fn doc_comment(&self, lexeme: usize) -> String {
+        let mut line = self.lexemes[lexeme].pos.0;
+        let mut block = vec![];
+        for comment in self.comments.iter().rev().filter(|c| c.lexeme == lexeme) {
+            let end_line = comment.pos.0 + comment.text.matches('\n').count();
+            let before = &self.src[..comment.span.start];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            if end_line + 1 != line || !before[line_start..].trim().is_empty() {
+                break;
+            }
+
+            block.push(comment_text(&comment.text));
+            line = comment.pos.0;
+        }
+        block.reverse();
+
+        block.join("\n")
+    }
+}
+
+fn is_exported(name: &str) -> bool {
+    name.chars().next().is_some_and(char::is_uppercase)
+}
+
+/// Comment text without the delimiters and the indentation
+fn comment_text(text: &str) -> String {
+    if let Some(line) = text.strip_prefix("//") {
+        return line
+            .strip_prefix(' ')
+            .unwrap_or(line)
+            .trim_end()
+            .to_string();
+    }
+
+    let body = text.trim_start_matches("/*").trim_end_matches("*/");
+    let lines: Vec<&str> = body.lines().map(str::trim).collect();
+    lines.join("\n").trim_matches('\n').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document() {
+        let src = r#"// Package geo measures shapes.
+package geo
+
+// Pi is approximately
+// the ratio of a circumference to its diameter.
+const Pi = 3.14
+
+const (
+    // Origin is where the axes cross.
+    Origin = 0
+    hidden = 1
+)
+
+// Ratio of things.
+var (
+    Scale, Unit float64 = 1.0, 2.0
+)
+
+var internal = 1 // not a doc comment
+func Area(r float64) (a float64) {
+    return Pi * r * r
+}
+
+/*
+   Point is a location.
+*/
+type Point struct {
+    X, Y float64
+}
+
+// not attached to anything
+
+func (p Point) Norm() float64 { return 0 }
+
+func helper() {}
+"#;
+
+        let doc = document(src);
+        assert_eq!(doc.name, "geo");
+        assert_eq!(doc.doc, "Package geo measures shapes.");
+
+        let items: Vec<_> = doc
+            .items
+            .iter()
+            .map(|item| {
+                (
+                    item.kind,
+                    item.name.as_str(),
+                    item.signature.as_str(),
+                    item.doc.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            items,
+            vec![
+                (
+                    DocKind::Const,
+                    "Pi",
+                    "const Pi = 3.14",
+                    "Pi is approximately\nthe ratio of a circumference to its diameter."
+                ),
+                (
+                    DocKind::Const,
+                    "Origin",
+                    "const Origin = 0",
+                    "Origin is where the axes cross."
+                ),
+                (
+                    DocKind::Var,
+                    "Scale",
+                    "var Scale, Unit float64 = 1.0, 2.0",
+                    "Ratio of things."
+                ),
+                (
+                    DocKind::Var,
+                    "Unit",
+                    "var Scale, Unit float64 = 1.0, 2.0",
+                    "Ratio of things."
+                ),
+                (
+                    DocKind::Func,
+                    "Area",
+                    "func Area(r float64) (a float64)",
+                    ""
+                ),
+                (
+                    DocKind::Type,
+                    "Point",
+                    "type Point struct {\n    X, Y float64\n}",
+                    "Point is a location."
+                ),
+                (DocKind::Func, "Norm", "func (p Point) Norm() float64", ""),
+            ]
+        );
+        assert_eq!(doc.items[0].pos, Pos(6, 7));
+        assert_eq!(doc.items[4].pos, Pos(20, 6));
+
+        let text = doc.to_string();
+        assert!(text.starts_with("package geo\n\nPackage geo measures shapes.\n\nconst Pi = 3.14\n    Pi is approximately\n"));
+
+        let json = document("package main\n\n// Run \"it\".\nfunc Run() {}\n").to_json();
+        assert_eq!(
+            json,
+            r#"{"package":"main","doc":"","items":[{"kind":"func","name":"Run","signature":"func Run()","doc":"Run \"it\".","line":4,"column":6}]}"#
+        );
+    }
+}
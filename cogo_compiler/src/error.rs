@@ -1,32 +1,238 @@
 use std::{error, fmt};
 
-use crate::lex::lexeme::Pos;
-use crate::ValType;
+use crate::lex::lexeme::{Pos, Span};
+use crate::lex::LexError;
+use crate::{Diagnostic, ValType};
+
+/// Any error found in the source, either while lexing or while compiling it
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompilerError {
+    Lex(LexError),
+    Compile(CompileError),
+}
+
+impl CompilerError {
+    pub fn msg(&self) -> String {
+        match self {
+            Self::Lex(err) => err.msg(),
+            Self::Compile(err) => err.msg().to_string(),
+        }
+    }
+
+    pub fn pos(&self) -> Pos {
+        match self {
+            Self::Lex(err) => err.pos(),
+            Self::Compile(err) => err.pos(),
+        }
+    }
+
+    /// Source bytes the error relates to, lexing errors only know their position
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Lex(_) => None,
+            Self::Compile(err) => Some(err.span()),
+        }
+    }
+}
+
+impl error::Error for CompilerError {}
+
+impl fmt::Display for CompilerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Lex(err) => write!(f, "{}", err),
+            Self::Compile(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl From<LexError> for CompilerError {
+    fn from(err: LexError) -> Self {
+        Self::Lex(err)
+    }
+}
+
+impl From<CompileError> for CompilerError {
+    fn from(err: CompileError) -> Self {
+        Self::Compile(err)
+    }
+}
 
 /// Errors occurred during the compilation process
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct CompileError(pub String, pub Pos);
+pub struct CompileError {
+    msg: String,
+    pos: Pos,
+    span: Span,
+    /// Source line containing the span and the byte offset it starts at
+    line: Option<(String, usize)>,
+}
+
+impl CompileError {
+    pub(crate) fn new(msg: String, pos: Pos, span: Span) -> Self {
+        Self {
+            msg,
+            pos,
+            span,
+            line: None,
+        }
+    }
+
+    pub fn msg(&self) -> &str {
+        &self.msg
+    }
+
+    pub fn pos(&self) -> Pos {
+        self.pos
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Attaches the line of the source the error span starts in
+    pub(crate) fn with_source(mut self, src: &str) -> Self {
+        let at = self.span.start.min(src.len());
+        let start = src[..at].rfind('\n').map_or(0, |i| i + 1);
+        let end = src[start..].find('\n').map_or(src.len(), |i| start + i);
+
+        self.line = Some((src[start..end].to_string(), start));
+        self
+    }
+}
 
 impl error::Error for CompileError {}
 
 impl fmt::Display for CompileError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "Compile error: {} at {}", self.0, self.1)
+        write!(f, "Compile error: {} at {}", self.msg, self.pos)?;
+
+        if let Some((line, line_start)) = self.line.as_ref().filter(|(l, _)| !l.trim().is_empty()) {
+            let offset = (self.span.start - line_start).min(line.len());
+            let end = (self.span.end - line_start).min(line.len());
+            let padding = line[..offset].chars().count();
+            let carets = line[offset..end].chars().count().max(1);
+
+            write!(
+                f,
+                "\n    {}\n    {}{}",
+                line,
+                " ".repeat(padding),
+                "^".repeat(carets)
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Errors found while verifying the bytecode before it gets executed
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerificationError {
+    msg: String,
+    at: usize,
+}
+
+impl VerificationError {
+    pub(crate) fn new(msg: String, at: usize) -> Self {
+        Self { msg, at }
+    }
+
+    /// Index of the offending opcode
+    pub fn at(&self) -> usize {
+        self.at
+    }
+}
+
+impl error::Error for VerificationError {}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Bytecode verification error: {} at #{}",
+            self.msg, self.at
+        )
+    }
+}
+
+/// Errors found while reading serialized bytecode
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodeError {
+    msg: String,
+    at: usize,
+}
+
+impl DecodeError {
+    pub(crate) fn new(msg: String, at: usize) -> Self {
+        Self { msg, at }
+    }
+}
+
+impl error::Error for DecodeError {}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Bytecode decoding error: {} at byte {}",
+            self.msg, self.at
+        )
     }
 }
 
 /// Trait for defining custom error handling
 pub trait ErrorHandler {
-    fn on_error(&mut self, errs: &[Box<dyn error::Error>]);
+    fn on_error(&mut self, errs: &[CompilerError]);
 }
 
 /// Standard handler that outputs errors to stderr and terminates the process
-pub struct ToStderrErrorHandler;
+pub struct ToStderrErrorHandler {
+    /// Errors are highlighted with ANSI escape codes
+    colored: bool,
+}
+
+impl ToStderrErrorHandler {
+    pub fn new(colored: bool) -> Self {
+        Self { colored }
+    }
+}
+
+impl Default for ToStderrErrorHandler {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
 
 impl ErrorHandler for ToStderrErrorHandler {
-    fn on_error(&mut self, errs: &[Box<dyn error::Error>]) {
+    fn on_error(&mut self, errs: &[CompilerError]) {
         for err in errs {
-            eprintln!("\x1b[0;31m{}\x1b[0m", err);
+            if self.colored {
+                eprintln!("\x1b[0;31m{}\x1b[0m", err);
+            } else {
+                eprintln!("{}", err);
+            }
+        }
+
+        std::process::exit(1);
+    }
+}
+
+/// Handler that outputs errors to stderr as JSON diagnostics, one per line, and terminates the process
+pub struct ToJsonErrorHandler {
+    source_name: String,
+}
+
+impl ToJsonErrorHandler {
+    pub fn new(source_name: String) -> Self {
+        Self { source_name }
+    }
+}
+
+impl ErrorHandler for ToJsonErrorHandler {
+    fn on_error(&mut self, errs: &[CompilerError]) {
+        for err in errs {
+            eprintln!("{}", Diagnostic::from(err).to_json(&self.source_name));
         }
 
         std::process::exit(1);
@@ -60,3 +266,24 @@ impl TypeError {
 
 /// Errors occurred during the definition of any named values
 pub(crate) struct DefinitionError;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_error_underline() {
+        let src = "package main\nvar ä = 10 + \"str\"\n";
+        let start = src.find("10").unwrap();
+        let span = Span::new(start, start + 2).to(Span::new(src.len() - 6, src.len() - 1));
+
+        let err = CompileError::new("Mismatched types".to_string(), Pos(2, 10), span);
+        assert_eq!(err.to_string(), "Compile error: Mismatched types at [2:10]");
+
+        let err = err.with_source(src);
+        assert_eq!(
+            err.to_string(),
+            "Compile error: Mismatched types at [2:10]\n    var ä = 10 + \"str\"\n            ^^^^^^^^^^"
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use std::collections::HashMap;
 
+/// Tracks the nesting of loops and switches along with their pending jumps.
+/// Continue jumps hold absolute positions of the loop iteration start.
 #[derive(Debug)]
 pub(super) struct ControlFlow {
     continue_jumps: HashMap<usize, usize>,
@@ -0,0 +1,101 @@
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Package-level variable or constant declaration along with the opcodes initializing it
+#[derive(Debug)]
+pub(super) struct VarInit {
+    pub(super) names: Vec<String>,
+    /// Package-level names the initializer refers to
+    pub(super) refs: Vec<String>,
+    pub(super) codes: Range<usize>,
+    /// Lexeme of the first declared name
+    pub(super) decl: usize,
+}
+
+/// Order in which the declarations are to be initialized, as indices into `inits`.
+/// A declaration is initialized once everything it depends on is, the earliest one first,
+/// references through functions count as well.
+/// Returns the index of a declaration that is part of an initialization cycle.
+pub(super) fn init_order(
+    inits: &[VarInit],
+    func_refs: &HashMap<String, Vec<String>>,
+) -> Result<Vec<usize>, usize> {
+    let owners: HashMap<&str, usize> = inits
+        .iter()
+        .enumerate()
+        .flat_map(|(i, init)| init.names.iter().map(move |name| (name.as_str(), i)))
+        .collect();
+
+    let deps: Vec<HashSet<usize>> = inits
+        .iter()
+        .map(|init| {
+            let mut deps = HashSet::new();
+            let mut visited = HashSet::new();
+            let mut pending: Vec<&str> = init.refs.iter().map(String::as_str).collect();
+            while let Some(name) = pending.pop() {
+                if !visited.insert(name) {
+                    continue;
+                }
+                if let Some(i) = owners.get(name) {
+                    deps.insert(*i);
+                } else if let Some(refs) = func_refs.get(name) {
+                    pending.extend(refs.iter().map(String::as_str));
+                }
+            }
+
+            deps
+        })
+        .collect();
+
+    let mut order = Vec::with_capacity(inits.len());
+    let mut done = vec![false; inits.len()];
+    while order.len() < inits.len() {
+        let ready = (0..inits.len()).find(|i| !done[*i] && deps[*i].iter().all(|d| done[*d]));
+        match ready {
+            Some(i) => {
+                done[i] = true;
+                order.push(i);
+            }
+            None => return Err(done.iter().position(|d| !d).unwrap()),
+        }
+    }
+
+    Ok(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init(name: &str, refs: &[&str]) -> VarInit {
+        VarInit {
+            names: vec![name.to_string()],
+            refs: refs.iter().map(|r| r.to_string()).collect(),
+            codes: 0..0,
+            decl: 0,
+        }
+    }
+
+    #[test]
+    fn test_init_order() {
+        let no_funcs = HashMap::new();
+
+        let inits = [init("a", &["b"]), init("b", &[]), init("c", &["a", "len"])];
+        assert_eq!(init_order(&inits, &no_funcs), Ok(vec![1, 0, 2]));
+
+        let inits = [init("a", &[]), init("b", &[])];
+        assert_eq!(init_order(&inits, &no_funcs), Ok(vec![0, 1]));
+
+        let mut funcs = HashMap::new();
+        funcs.insert("f".to_string(), vec!["g".to_string()]);
+        funcs.insert("g".to_string(), vec!["b".to_string(), "f".to_string()]);
+        let inits = [init("a", &["f"]), init("b", &[])];
+        assert_eq!(init_order(&inits, &funcs), Ok(vec![1, 0]));
+
+        let inits = [init("x", &[]), init("a", &["b"]), init("b", &["a"])];
+        assert_eq!(init_order(&inits, &no_funcs), Err(1));
+
+        let inits = [init("a", &["a"])];
+        assert_eq!(init_order(&inits, &no_funcs), Err(0));
+    }
+}
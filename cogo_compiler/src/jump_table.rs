@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::Value;
+
+/// Targets of a switch whose cases are integer or string constants,
+/// the matching case is found without comparing the value to each case in turn
+#[derive(Clone)]
+pub struct JumpTable {
+    cases: HashMap<CaseKey, usize>,
+    /// Position right after the cases, where the execution goes when none of them matches
+    miss: usize,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum CaseKey {
+    Int(i128),
+    String(Rc<str>),
+}
+
+impl CaseKey {
+    fn of(value: &Value) -> Option<Self> {
+        use Value::*;
+        let int = match value {
+            String(s) => return Some(Self::String(s.clone())),
+            Int8(v) => *v as i128,
+            Int16(v) => *v as i128,
+            Int32(v) => *v as i128,
+            Int64(v) => *v as i128,
+            Int(v) => *v as i128,
+            IntLiteral(v) => *v,
+            Uint8(v) => *v as i128,
+            Uint16(v) => *v as i128,
+            Uint32(v) => *v as i128,
+            Uint64(v) => *v as i128,
+            Uint(v) | Uintptr(v) => *v as i128,
+            _ => return None,
+        };
+
+        Some(Self::Int(int))
+    }
+
+    fn same_kind(&self, other: &Self) -> bool {
+        matches!(
+            (self, other),
+            (Self::Int(_), Self::Int(_)) | (Self::String(_), Self::String(_))
+        )
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Self::Int(v) => Value::IntLiteral(*v),
+            Self::String(s) => Value::String(s.clone()),
+        }
+    }
+}
+
+impl JumpTable {
+    pub(crate) fn new(miss: usize) -> Self {
+        Self {
+            cases: HashMap::new(),
+            miss,
+        }
+    }
+
+    /// Adds a case, the first one wins for a repeated value.
+    /// Returns `false` if the value cannot be a key or is of another kind than the others
+    pub(crate) fn insert(&mut self, value: &Value, target: usize) -> bool {
+        let key = match CaseKey::of(value) {
+            Some(key) => key,
+            None => return false,
+        };
+
+        if let Some(other) = self.cases.keys().next() {
+            if !key.same_kind(other) {
+                return false;
+            }
+        }
+
+        self.cases.entry(key).or_insert(target);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.cases.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cases.is_empty()
+    }
+
+    pub fn miss(&self) -> usize {
+        self.miss
+    }
+
+    /// Whether the switch over the value can be decided by the table,
+    /// other values are matched against the cases one by one
+    pub fn covers(&self, value: &Value) -> bool {
+        match (CaseKey::of(value), self.cases.keys().next()) {
+            (Some(key), Some(other)) => key.same_kind(other),
+            _ => false,
+        }
+    }
+
+    /// Body of the case matching the value
+    pub fn lookup(&self, value: &Value) -> Option<usize> {
+        self.cases.get(&CaseKey::of(value)?).copied()
+    }
+
+    /// Case values along with their targets, in the order of the targets
+    pub(crate) fn entries(&self) -> Vec<(Value, usize)> {
+        let mut entries: Vec<_> = self
+            .cases
+            .iter()
+            .map(|(key, target)| (key.to_value(), *target))
+            .collect();
+        entries.sort_by_key(|(_, target)| *target);
+
+        entries
+    }
+
+    /// Every position the table may lead to
+    pub(crate) fn targets(&self) -> impl Iterator<Item = usize> + '_ {
+        self.cases
+            .values()
+            .copied()
+            .chain(std::iter::once(self.miss))
+    }
+
+    pub(crate) fn map_targets(&mut self, f: impl Fn(usize) -> usize) {
+        self.cases
+            .values_mut()
+            .for_each(|target| *target = f(*target));
+        self.miss = f(self.miss);
+    }
+}
+
+impl fmt::Debug for JumpTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let entries: Vec<String> = self
+            .entries()
+            .iter()
+            .map(|(value, target)| match value {
+                Value::String(s) => format!("{:?}: {}", s, target),
+                value => format!("{}: {}", value, target),
+            })
+            .collect();
+
+        write!(f, "{{{}}} miss: {}", entries.join(", "), self.miss)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jump_table() {
+        let mut table = JumpTable::new(9);
+        assert!(table.insert(&Value::IntLiteral(1), 3));
+        assert!(table.insert(&Value::IntLiteral(-2), 5));
+        assert!(table.insert(&Value::IntLiteral(1), 7));
+        assert!(!table.insert(&Value::String("a".into()), 8));
+        assert!(!table.insert(&Value::FloatLiteral(1.0), 8));
+        assert_eq!(table.len(), 2);
+
+        assert!(table.covers(&Value::Uint8(1)));
+        assert!(!table.covers(&Value::String("1".into())));
+        assert_eq!(table.lookup(&Value::Uint8(1)), Some(3));
+        assert_eq!(table.lookup(&Value::Int64(-2)), Some(5));
+        assert_eq!(table.lookup(&Value::Int(4)), None);
+
+        table.map_targets(|t| t * 2);
+        assert_eq!(table.miss(), 18);
+        assert_eq!(format!("{:?}", table), "{1: 6, -2: 10} miss: 18");
+    }
+}
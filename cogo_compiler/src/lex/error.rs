@@ -3,33 +3,41 @@ use std::{error, fmt};
 use super::lexeme::Pos;
 
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) enum LexError {
+pub enum LexError {
     /// Error when a comment has no closing delimiter.
     UnclosedComment(Pos),
     /// Unknown character when scanning the source.
     UnknownCharacter(Pos, char),
     /// String has no closing quote delimiter.
     UnterminatedString(Pos),
+    /// Numeric literal with invalid digits or misplaced underscores.
+    MalformedNumber(Pos, String),
 }
 
 impl error::Error for LexError {}
 
 impl LexError {
-    fn msg(&self) -> String {
+    pub fn msg(&self) -> String {
         match self {
             Self::UnclosedComment(_) => String::from("Unclosed comment"),
             Self::UnknownCharacter(_, ch) => format!("Unknown character \"{}\"", ch),
             Self::UnterminatedString(_) => String::from("Unterminated string"),
+            Self::MalformedNumber(_, literal) => format!("Malformed number \"{}\"", literal),
         }
     }
-}
 
-impl fmt::Display for LexError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    pub fn pos(&self) -> Pos {
         match self {
             Self::UnclosedComment(pos)
             | Self::UnknownCharacter(pos, _)
-            | Self::UnterminatedString(pos) => write!(f, "Error: {} at {}", self.msg(), pos),
+            | Self::UnterminatedString(pos)
+            | Self::MalformedNumber(pos, _) => *pos,
         }
     }
 }
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error: {} at {}", self.msg(), self.pos())
+    }
+}
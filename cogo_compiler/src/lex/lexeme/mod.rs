@@ -1,16 +1,17 @@
 use std::fmt;
 
-pub(crate) use self::token::Token;
+pub use self::token::Token;
 
 mod token;
 
 type Literal = String;
 
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct Lexeme {
-    pub(crate) token: Token,
-    pub(crate) pos: Pos,
-    pub(crate) literal: Literal,
+pub struct Lexeme {
+    pub token: Token,
+    pub pos: Pos,
+    pub span: Span,
+    pub literal: Literal,
 }
 
 impl Lexeme {
@@ -22,13 +23,49 @@ impl Lexeme {
         Self {
             token,
             pos,
+            span: Span::default(),
             literal,
         }
     }
+
+    pub(super) fn with_span(mut self, span: Span) -> Self {
+        self.span = span;
+        self
+    }
+}
+
+/// Comment recorded by a lexer which preserves comments
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    /// Comment text including the delimiters
+    pub text: String,
+    pub pos: Pos,
+    pub span: Span,
+    /// Index of the lexeme the comment is attached to, that is the first one following it,
+    /// automatically inserted semicolons are skipped
+    pub lexeme: usize,
+}
+
+/// Byte offsets of the start and the end (exclusive) of a piece of source
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Span covering both spans and everything in between
+    pub fn to(self, other: Span) -> Self {
+        Self::new(self.start.min(other.start), self.end.max(other.end))
+    }
 }
 
 #[derive(Copy, Clone, PartialEq)]
-pub(crate) struct Pos(pub usize, pub usize);
+pub struct Pos(pub usize, pub usize);
 
 impl fmt::Display for Pos {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
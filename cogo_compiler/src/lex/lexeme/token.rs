@@ -2,7 +2,7 @@ use std::fmt;
 
 #[derive(Copy, Clone, PartialEq, Debug, Eq, Hash)]
 #[allow(dead_code)]
-pub(crate) enum Token {
+pub enum Token {
     // Operators
     Colon,
     Semicolon,
@@ -71,6 +71,7 @@ pub(crate) enum Token {
     RawStringLiteral,
     IntLiteral,
     FloatLiteral,
+    ImagLiteral,
 
     // Keywords
     Break,
@@ -201,6 +202,7 @@ impl Token {
             Self::RawStringLiteral => "",
             Self::IntLiteral => "",
             Self::FloatLiteral => "",
+            Self::ImagLiteral => "",
 
             //Keywords
             Self::Break => "break",
@@ -268,6 +270,10 @@ impl fmt::Display for Token {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Self::Identifier => write!(f, "identifier"),
+            Self::StringLiteral | Self::RawStringLiteral => write!(f, "string literal"),
+            Self::IntLiteral => write!(f, "integer literal"),
+            Self::FloatLiteral => write!(f, "float literal"),
+            Self::ImagLiteral => write!(f, "imaginary literal"),
             _ => write!(f, "{}", self.str_value()),
         }
     }
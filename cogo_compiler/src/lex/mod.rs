@@ -1,39 +1,111 @@
-use self::error::LexError;
-use self::lexeme::{Lexeme, Pos, Token};
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+
+pub use self::error::LexError;
+use self::lexeme::{Comment, Lexeme, Pos, Span, Token};
+pub(crate) use self::stream::LexemeStream;
+use self::unicode::{DECIMAL_DIGIT_ZEROS, NON_LETTER_ALPHABETICS};
 
 mod error;
-pub(crate) mod lexeme;
+pub mod lexeme;
+mod stream;
+mod unicode;
+
+/// Keywords of the language, none of them can be used as an identifier
+pub const KEYWORDS: [&str; 25] = [
+    "break",
+    "case",
+    "chan",
+    "const",
+    "continue",
+    "default",
+    "defer",
+    "else",
+    "fallthrough",
+    "for",
+    "func",
+    "go",
+    "goto",
+    "if",
+    "import",
+    "interface",
+    "map",
+    "package",
+    "range",
+    "return",
+    "select",
+    "struct",
+    "switch",
+    "type",
+    "var",
+];
 
-pub(crate) struct Lexer<'a> {
+/// Lexer can either be iterated over, yielding lexemes lazily, or run to completion with `lex()`
+pub struct Lexer<'a> {
     src: &'a str,
     lexemes: Vec<Lexeme>,
+    /// Lexemes and errors of the last scanned token not yet yielded
+    pending: VecDeque<Result<Lexeme, LexError>>,
+    /// Last yielded token, needed to insert semicolons automatically
+    last_token: Option<Token>,
+    eof: bool,
+    /// Number of lexemes produced so far
+    count: usize,
+    /// Comments are only recorded when it is set
+    comments: Option<Vec<Comment>>,
+    /// Comments not yet attached to a lexeme
+    unattached: Vec<Comment>,
+    /// Byte offset of the lexeme being scanned
     start: usize,
+    /// Byte offset of the next character, always at a char boundary
     current: usize,
     line: usize,
-    pos: usize,
-    errors: Vec<Box<dyn std::error::Error>>,
+    /// Column of the next character, counted in characters
+    column: usize,
+    /// Position of the lexeme being scanned
+    start_pos: Pos,
+    errors: Vec<LexError>,
 }
 
 impl<'a> Lexer<'a> {
-    pub(crate) fn new(src: &'a str) -> Self {
+    pub fn new(src: &'a str) -> Self {
         Self {
             src,
             lexemes: vec![],
+            pending: VecDeque::new(),
+            last_token: None,
+            eof: false,
+            count: 0,
+            comments: None,
+            unattached: vec![],
             start: 0,
             current: 0,
             line: 1,
-            pos: 1,
+            column: 1,
+            start_pos: Pos(1, 1),
             errors: vec![],
         }
     }
 
-    pub(crate) fn lex(&mut self) -> (&[Lexeme], &[Box<dyn std::error::Error>]) {
-        while !self.is_at_end() {
-            self.start = self.current;
-            self.token();
-        }
+    /// Makes the lexer record comments instead of discarding them
+    pub fn with_comments(mut self) -> Self {
+        self.comments = Some(vec![]);
+        self
+    }
 
-        self.lexemes.push(Lexeme::new(Token::Eof, self.pos()));
+    /// Comments scanned so far, empty unless the lexer was created `with_comments()`
+    pub fn comments(&self) -> &[Comment] {
+        self.comments.as_deref().unwrap_or_default()
+    }
+
+    /// Scans the whole source, the last lexeme is always `Token::Eof`
+    pub fn lex(&mut self) -> (&[Lexeme], &[LexError]) {
+        while let Some(lexeme) = self.next() {
+            match lexeme {
+                Ok(lexeme) => self.lexemes.push(lexeme),
+                Err(err) => self.errors.push(err),
+            }
+        }
 
         (&self.lexemes, &self.errors)
     }
@@ -47,6 +119,7 @@ impl<'a> Lexer<'a> {
             '[' => self.add_lexeme(Token::LeftBracket),
             ']' => self.add_lexeme(Token::RightBracket),
             ',' => self.add_lexeme(Token::Comma),
+            '.' if self.is_digit(self.peek()) => self.number(),
             '.' => {
                 let t = if self.peek() == '.' && self.peek_next() == '.' {
                     self.advance();
@@ -185,6 +258,7 @@ impl<'a> Lexer<'a> {
                     while self.peek() != '\n' && !self.is_at_end() {
                         self.advance();
                     }
+                    self.add_comment();
                 } else if self.match_char('*') {
                     loop {
                         if self.peek() == '*' && self.peek_next() == '/' {
@@ -192,7 +266,7 @@ impl<'a> Lexer<'a> {
                         }
 
                         if self.is_at_end() {
-                            self.add_err(LexError::UnclosedComment(self.pos()));
+                            self.add_err(LexError::UnclosedComment(self.start_pos));
                             return;
                         }
 
@@ -200,31 +274,33 @@ impl<'a> Lexer<'a> {
                     }
                     self.advance();
                     self.advance();
+                    self.add_comment();
+
+                    // a general comment spanning several lines acts like a newline
+                    let text = self.src_substr(self.start, self.current);
+                    if text.contains('\n') && self.is_auto_semicolon() {
+                        self.add_lexeme(Token::Semicolon);
+                    }
                 } else if self.match_char('=') {
                     self.add_lexeme(Token::SlashEqual);
                 } else {
                     self.add_lexeme(Token::Slash);
                 }
             }
-            ' ' | '\r' | '\t' => {
-                self.pos += 1;
-            }
+            ' ' | '\r' | '\t' => {}
             '\n' => {
                 if self.is_auto_semicolon() {
                     self.add_lexeme(Token::Semicolon);
                 }
-                self.line += 1;
-                self.pos = 0;
             }
             '"' => self.string(),
             c => {
                 if self.is_digit(c) {
                     self.number();
-                } else if self.is_alphabetic(c) {
+                } else if self.is_letter(c) {
                     self.identifier();
                 } else {
-                    self.pos += 1;
-                    self.add_err(LexError::UnknownCharacter(self.pos(), c));
+                    self.add_err(LexError::UnknownCharacter(self.start_pos, c));
                 }
             }
         };
@@ -235,60 +311,57 @@ impl<'a> Lexer<'a> {
     }
 
     fn advance(&mut self) -> char {
-        self.current += 1;
+        let c = self.rest().chars().next().unwrap();
+        self.current += c.len_utf8();
 
-        self.src.chars().nth(self.current - 1).unwrap()
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        c
+    }
+
+    /// Source text not yet consumed
+    fn rest(&self) -> &'a str {
+        &self.src[self.current..]
     }
 
     fn add_lexeme(&mut self, token: Token) {
-        self.lexemes.push(Lexeme::new(token, self.pos()));
-        let text = self.src_substr(self.start, self.current);
-        self.pos += text.len();
+        self.push_lexeme(Lexeme::new(token, self.start_pos));
     }
 
     fn add_lexeme_with_literal(&mut self, token: Token, literal: &str) {
-        self.lexemes.push(Lexeme::new_with_literal(
+        self.push_lexeme(Lexeme::new_with_literal(
             token,
-            self.pos(),
+            self.start_pos,
             literal.to_string(),
         ));
-
-        self.pos += literal.len();
     }
 
     fn match_char(&mut self, expected: char) -> bool {
-        if self.is_at_end() {
-            return false;
-        }
-
-        if self.src.chars().nth(self.current).unwrap() != expected {
+        if self.is_at_end() || self.peek() != expected {
             return false;
         }
 
-        self.current += 1;
+        self.advance();
 
         true
     }
 
     fn peek(&self) -> char {
-        if self.is_at_end() {
-            return '\0';
-        }
-
-        self.src.chars().nth(self.current).unwrap()
+        self.rest().chars().next().unwrap_or('\0')
     }
 
     fn string(&mut self) {
         while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
-                self.pos = 0;
-            }
             self.advance();
         }
 
         if self.is_at_end() {
-            self.add_err(LexError::UnterminatedString(self.pos()));
+            self.add_err(LexError::UnterminatedString(self.start_pos));
             return;
         }
 
@@ -299,50 +372,159 @@ impl<'a> Lexer<'a> {
     }
 
     fn src_substr(&self, start: usize, end: usize) -> String {
-        self.src.chars().skip(start).take(end - start).collect()
+        self.src[start..end].to_string()
     }
 
     fn is_digit(&self, c: char) -> bool {
-        c.is_digit(10)
+        c.is_ascii_digit()
     }
 
-    fn is_alphabetic(&self, c: char) -> bool {
-        c.is_alphabetic() || c == '_'
+    /// Letter as defined by the Go specification: a Unicode letter (L) or an underscore.
+    /// Letter numbers such as Roman numerals and combining vowel signs are alphabetic,
+    /// but not letters.
+    fn is_letter(&self, c: char) -> bool {
+        let code = c as u32;
+        c == '_'
+            || c.is_alphabetic()
+                && NON_LETTER_ALPHABETICS
+                    .binary_search_by(|&(start, end)| {
+                        if end < code {
+                            Ordering::Less
+                        } else if start > code {
+                            Ordering::Greater
+                        } else {
+                            Ordering::Equal
+                        }
+                    })
+                    .is_err()
     }
 
-    fn is_alphanumeric(&self, c: char) -> bool {
-        c.is_alphanumeric() || c == '_'
+    /// Unicode decimal digit, i.e. of the Nd category
+    fn is_unicode_digit(&self, c: char) -> bool {
+        let code = c as u32;
+        DECIMAL_DIGIT_ZEROS
+            .iter()
+            .any(|zero| (*zero..*zero + 10).contains(&code))
     }
 
+    /// Integer, floating-point and imaginary literals. Integers may be binary, octal or hexadecimal
+    /// (`0b`, `0o` or a leading `0`, `0x`), floats may have an exponent, leave out the digits
+    /// on either side of the dot (`.5`, `1.`) or be hexadecimal with a binary exponent (`0x1p-2`).
+    /// Any of them followed by `i` is imaginary, digits may be separated by underscores: `1_000_000`
     fn number(&mut self) {
         let mut float = false;
+        let radix = match (&self.src[self.start..self.current], self.peek()) {
+            ("0", 'b' | 'B') => 2,
+            ("0", 'o' | 'O') => 8,
+            ("0", 'x' | 'X') => 16,
+            _ => 10,
+        };
+
+        let mut malformed = false;
+        if radix == 10 {
+            // a literal starting with the dot has consumed it already
+            float = self.src[self.start..].starts_with('.');
+            self.digits(10);
 
-        while self.is_digit(self.peek()) {
+            if !float && self.peek() == '.' {
+                float = true;
+                // consume '.'
+                self.advance();
+                self.digits(10);
+            }
+        } else {
+            // consume the prefix
             self.advance();
+            self.digits(radix);
+
+            if radix == 16 && self.peek() == '.' {
+                float = true;
+                self.advance();
+                self.digits(16);
+            }
+
+            let mantissa = &self.src[self.start + 2..self.current];
+            malformed = !mantissa.chars().any(|c| c.is_digit(radix));
         }
 
-        if self.peek() == '.' && self.is_digit(self.peek_next()) {
+        if self.is_exponent(radix) {
             float = true;
-            // consume '.'
+            // consume the exponent mark and the sign
             self.advance();
-
-            while self.is_digit(self.peek()) {
+            if matches!(self.peek(), '+' | '-') {
                 self.advance();
             }
+            self.digits(10);
+        } else if radix == 16 && float {
+            // hexadecimal mantissa requires a 'p' exponent
+            malformed = true;
         }
 
-        self.add_lexeme_with_literal(
-            if float {
-                Token::FloatLiteral
-            } else {
-                Token::IntLiteral
-            },
-            &self.src_substr(self.start, self.current),
-        );
+        let imaginary = self.peek() == 'i';
+        if imaginary {
+            self.advance();
+        }
+
+        while self.is_letter(self.peek()) || self.is_digit(self.peek()) {
+            malformed = true;
+            self.advance();
+        }
+
+        let literal = self.src_substr(self.start, self.current);
+        let digits = literal.trim_end_matches('i').to_lowercase();
+        let misplaced = if radix == 16 {
+            ["_.", "._", "_p", "p_", "p+_", "p-_"]
+        } else {
+            ["_.", "._", "_e", "e_", "e+_", "e-_"]
+        };
+        if malformed
+            || misplaced.iter().any(|s| digits.contains(s))
+            || digits.ends_with('_')
+            || digits.contains("__")
+        {
+            self.add_err(LexError::MalformedNumber(self.start_pos, literal));
+            return;
+        }
+
+        let token = if imaginary {
+            Token::ImagLiteral
+        } else if float {
+            Token::FloatLiteral
+        } else {
+            Token::IntLiteral
+        };
+        self.add_lexeme_with_literal(token, &literal);
+    }
+
+    /// Digits of the given base, possibly separated by underscores
+    fn digits(&mut self, radix: u32) {
+        while self.peek().is_digit(radix) || self.peek() == '_' {
+            self.advance();
+        }
+    }
+
+    /// Exponent of a decimal (`e`) or a hexadecimal (`p`) literal, its digits are decimal
+    fn is_exponent(&self, radix: u32) -> bool {
+        let mut rest = self.rest().chars();
+        let mark = rest.next();
+        let is_mark = match radix {
+            10 => matches!(mark, Some('e' | 'E')),
+            16 => matches!(mark, Some('p' | 'P')),
+            _ => false,
+        };
+        if !is_mark {
+            return false;
+        }
+
+        match rest.next() {
+            Some('+' | '-') => rest.next().is_some_and(|c| self.is_digit(c)),
+            Some(c) => self.is_digit(c),
+            None => false,
+        }
     }
 
     fn identifier(&mut self) {
-        while self.is_alphanumeric(self.peek()) {
+        while self.is_letter(self.peek()) || self.is_unicode_digit(self.peek()) {
             self.advance();
         }
 
@@ -359,15 +541,12 @@ impl<'a> Lexer<'a> {
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.src.len() {
-            return '\0';
-        }
-
-        self.src.chars().nth(self.current + 1).unwrap()
+        self.rest().chars().nth(1).unwrap_or('\0')
     }
 
+    /// Position of the next character, columns are counted in characters
     fn pos(&self) -> Pos {
-        Pos(self.line, self.pos)
+        Pos(self.line, self.column)
     }
 
     fn keyword(&self, keyword: &str) -> Option<(Token, bool)> {
@@ -434,12 +613,47 @@ impl<'a> Lexer<'a> {
         Some(tok)
     }
 
+    fn push_lexeme(&mut self, lexeme: Lexeme) {
+        let lexeme = lexeme.with_span(Span::new(self.start, self.current));
+        let inserted =
+            lexeme.token == Token::Semicolon && &self.src[self.start..self.current] != ";";
+        if !inserted {
+            self.attach_comments();
+        }
+
+        self.last_token = Some(lexeme.token);
+        self.count += 1;
+        self.pending.push_back(Ok(lexeme));
+    }
+
+    fn add_comment(&mut self) {
+        if self.comments.is_some() {
+            self.unattached.push(Comment {
+                text: self.src_substr(self.start, self.current),
+                pos: self.start_pos,
+                span: Span::new(self.start, self.current),
+                lexeme: 0,
+            });
+        }
+    }
+
+    /// Attaches the pending comments to the lexeme about to be produced
+    fn attach_comments(&mut self) {
+        if let Some(comments) = &mut self.comments {
+            for mut comment in self.unattached.drain(..) {
+                comment.lexeme = self.count;
+                comments.push(comment);
+            }
+        }
+    }
+
+    /// Whether a semicolon goes after the last lexeme at the end of a line or of the source.
+    /// Types, `true`, `false` and `nil` are predeclared identifiers in Go, hence included.
     fn is_auto_semicolon(&self) -> bool {
-        let last = self.lexemes.last();
-        if let Some(l) = last {
+        if let Some(token) = self.last_token {
             use Token::*;
             matches!(
-                l.token,
+                token,
                 RightParen
                     | RightCurlyBrace
                     | RightBracket
@@ -471,9 +685,12 @@ impl<'a> Lexer<'a> {
                     | Complex128
                     | String
                     | Identifier
+                    | Nil
                     | StringLiteral
+                    | RawStringLiteral
                     | IntLiteral
                     | FloatLiteral
+                    | ImagLiteral
             )
         } else {
             false
@@ -481,7 +698,42 @@ impl<'a> Lexer<'a> {
     }
 
     fn add_err(&mut self, err: LexError) {
-        self.errors.push(Box::new(err));
+        self.pending.push_back(Err(err));
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<Lexeme, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(lexeme) = self.pending.pop_front() {
+                return Some(lexeme);
+            }
+
+            if self.is_at_end() {
+                if self.eof {
+                    return None;
+                }
+
+                if self.is_auto_semicolon() {
+                    self.start = self.current;
+                    self.start_pos = self.pos();
+                    self.add_lexeme(Token::Semicolon);
+                    continue;
+                }
+
+                self.eof = true;
+                self.attach_comments();
+                self.count += 1;
+                let eof = Lexeme::new(Token::Eof, self.pos());
+                return Some(Ok(eof.with_span(Span::new(self.current, self.current))));
+            }
+
+            self.start = self.current;
+            self.start_pos = self.pos();
+            self.token();
+        }
     }
 }
 
@@ -489,13 +741,20 @@ impl<'a> Lexer<'a> {
 mod tests {
     use super::*;
 
+    fn without_spans(lexemes: &[Lexeme]) -> Vec<Lexeme> {
+        lexemes
+            .iter()
+            .map(|l| l.clone().with_span(Span::default()))
+            .collect()
+    }
+
     #[test]
     fn test_lex() {
         let mut lexer = Lexer::new("var x uint64 = 100; y := \"str\"");
         let (lexemes, errs) = lexer.lex();
         assert!(errs.is_empty());
         assert_eq!(
-            lexemes,
+            without_spans(lexemes),
             &[
                 Lexeme::new(Token::Var, Pos(1, 1)),
                 Lexeme::new_with_literal(Token::Identifier, Pos(1, 5), String::from("x")),
@@ -506,7 +765,8 @@ mod tests {
                 Lexeme::new_with_literal(Token::Identifier, Pos(1, 21), String::from("y")),
                 Lexeme::new(Token::ColonEqual, Pos(1, 23)),
                 Lexeme::new_with_literal(Token::StringLiteral, Pos(1, 26), String::from("str")),
-                Lexeme::new(Token::Eof, Pos(1, 29)),
+                Lexeme::new(Token::Semicolon, Pos(1, 31)),
+                Lexeme::new(Token::Eof, Pos(1, 31)),
             ]
         );
     }
@@ -521,11 +781,11 @@ mod tests {
             LexError::UnterminatedString(Pos(1, 6)).to_string(),
         );
         assert_eq!(
-            lexemes,
+            without_spans(lexemes),
             &[
                 Lexeme::new_with_literal(Token::Identifier, Pos(1, 1), String::from("y")),
                 Lexeme::new(Token::ColonEqual, Pos(1, 3)),
-                Lexeme::new(Token::Eof, Pos(1, 6)),
+                Lexeme::new(Token::Eof, Pos(1, 10)),
             ]
         );
 
@@ -536,6 +796,327 @@ mod tests {
             errs[0].to_string(),
             LexError::UnclosedComment(Pos(1, 1)).to_string(),
         );
-        assert_eq!(lexemes, &[Lexeme::new(Token::Eof, Pos(1, 1)),]);
+        assert_eq!(
+            without_spans(lexemes),
+            &[Lexeme::new(Token::Eof, Pos(1, 11)),]
+        );
+    }
+
+    #[test]
+    fn test_lex_iter() {
+        let mut lexer = Lexer::new("x := 1 # 2\ny");
+        assert_eq!(
+            lexer.next(),
+            Some(Ok(Lexeme::new_with_literal(
+                Token::Identifier,
+                Pos(1, 1),
+                String::from("x")
+            )
+            .with_span(Span::new(0, 1))))
+        );
+
+        let rest: Vec<_> = lexer
+            .map(|l| l.map(|l| l.with_span(Span::default())))
+            .collect();
+        assert_eq!(
+            rest,
+            vec![
+                Ok(Lexeme::new(Token::ColonEqual, Pos(1, 3))),
+                Ok(Lexeme::new_with_literal(
+                    Token::IntLiteral,
+                    Pos(1, 6),
+                    String::from("1")
+                )),
+                Err(LexError::UnknownCharacter(Pos(1, 8), '#')),
+                Ok(Lexeme::new_with_literal(
+                    Token::IntLiteral,
+                    Pos(1, 10),
+                    String::from("2")
+                )),
+                Ok(Lexeme::new(Token::Semicolon, Pos(1, 11))),
+                Ok(Lexeme::new_with_literal(
+                    Token::Identifier,
+                    Pos(2, 1),
+                    String::from("y")
+                )),
+                Ok(Lexeme::new(Token::Semicolon, Pos(2, 2))),
+                Ok(Lexeme::new(Token::Eof, Pos(2, 2))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_utf8() {
+        let mut lexer = Lexer::new("héllo := \"прывітанне\"; x");
+        let (lexemes, errs) = lexer.lex();
+        assert!(errs.is_empty());
+        assert_eq!(
+            without_spans(lexemes),
+            &[
+                Lexeme::new_with_literal(Token::Identifier, Pos(1, 1), String::from("héllo")),
+                Lexeme::new(Token::ColonEqual, Pos(1, 7)),
+                Lexeme::new_with_literal(
+                    Token::StringLiteral,
+                    Pos(1, 10),
+                    String::from("прывітанне")
+                ),
+                Lexeme::new(Token::Semicolon, Pos(1, 22)),
+                Lexeme::new_with_literal(Token::Identifier, Pos(1, 24), String::from("x")),
+                Lexeme::new(Token::Semicolon, Pos(1, 25)),
+                Lexeme::new(Token::Eof, Pos(1, 25)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unicode_digits() {
+        // Kawi, Nag Mundari and segmented digits
+        let mut lexer = Lexer::new("x\u{11F51} y\u{1E4F9} z\u{1FBF0}");
+        let (lexemes, errs) = lexer.lex();
+        assert!(errs.is_empty());
+        assert_eq!(
+            without_spans(lexemes)[..3],
+            [
+                Lexeme::new_with_literal(Token::Identifier, Pos(1, 1), "x\u{11F51}".to_string()),
+                Lexeme::new_with_literal(Token::Identifier, Pos(1, 4), "y\u{1E4F9}".to_string()),
+                Lexeme::new_with_literal(Token::Identifier, Pos(1, 7), "z\u{1FBF0}".to_string()),
+            ]
+        );
+
+        for zero in DECIMAL_DIGIT_ZEROS {
+            for digit in zero..zero + 10 {
+                assert!(char::from_u32(digit).is_some_and(char::is_numeric));
+            }
+        }
+    }
+
+    #[test]
+    fn test_lex_spans() {
+        let src = "é := \"str\" >>= 10";
+        let spans: Vec<_> = Lexer::new(src).map(|l| l.unwrap().span).collect();
+        assert_eq!(
+            spans,
+            vec![
+                Span::new(0, 2),
+                Span::new(3, 5),
+                Span::new(6, 11),
+                Span::new(12, 15),
+                Span::new(16, 18),
+                Span::new(18, 18),
+                Span::new(18, 18),
+            ]
+        );
+        assert_eq!(&src[spans[2].start..spans[2].end], "\"str\"");
+        assert_eq!(spans[0].to(spans[2]), Span::new(0, 11));
+    }
+
+    #[test]
+    fn test_keywords() {
+        for keyword in KEYWORDS {
+            let mut lexer = Lexer::new(keyword);
+            let (lexemes, _) = lexer.lex();
+            assert_ne!(lexemes[0].token, Token::Identifier, "{}", keyword);
+        }
+    }
+
+    #[test]
+    fn test_lex_comments() {
+        let src = "// package\npackage main /* trailing */\n\n/*\n doc\n */\nx\n// end";
+
+        let mut lexer = Lexer::new(src);
+        lexer.lex();
+        assert!(lexer.comments().is_empty());
+
+        let mut lexer = Lexer::new(src).with_comments();
+        let (lexemes, _) = lexer.lex();
+        let tokens: Vec<_> = lexemes.iter().map(|l| l.token).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Package,
+                Token::Identifier,
+                Token::Semicolon,
+                Token::Identifier,
+                Token::Semicolon,
+                Token::Eof,
+            ]
+        );
+
+        let comments: Vec<_> = lexer
+            .comments()
+            .iter()
+            .map(|c| (c.text.as_str(), c.lexeme))
+            .collect();
+        assert_eq!(
+            comments,
+            vec![
+                ("// package", 0),
+                ("/* trailing */", 3),
+                ("/*\n doc\n */", 3),
+                ("// end", 5),
+            ]
+        );
+
+        let doc = &lexer.comments()[2];
+        assert_eq!(&src[doc.span.start..doc.span.end], doc.text);
+    }
+
+    #[test]
+    fn test_lex_unicode_identifiers() {
+        let mut lexer = Lexer::new("_ö١ := 1\n\tπ2 := Ⅻ");
+        let (lexemes, errs) = lexer.lex();
+        assert_eq!(
+            without_spans(lexemes),
+            &[
+                Lexeme::new_with_literal(Token::Identifier, Pos(1, 1), String::from("_ö١")),
+                Lexeme::new(Token::ColonEqual, Pos(1, 5)),
+                Lexeme::new_with_literal(Token::IntLiteral, Pos(1, 8), String::from("1")),
+                Lexeme::new(Token::Semicolon, Pos(1, 9)),
+                Lexeme::new_with_literal(Token::Identifier, Pos(2, 2), String::from("π2")),
+                Lexeme::new(Token::ColonEqual, Pos(2, 5)),
+                Lexeme::new(Token::Eof, Pos(2, 9)),
+            ]
+        );
+
+        // letter numbers cannot start an identifier
+        assert_eq!(errs.len(), 1);
+        assert_eq!(
+            errs[0].to_string(),
+            LexError::UnknownCharacter(Pos(2, 8), 'Ⅻ').to_string()
+        );
+
+        // combining vowel signs are alphabetic, but not letters
+        let mut lexer = Lexer::new("kा := 1");
+        let (lexemes, errs) = lexer.lex();
+        assert_eq!(lexemes[0].literal, "k");
+        assert_eq!(errs.len(), 1);
+        assert_eq!(
+            errs[0].to_string(),
+            LexError::UnknownCharacter(Pos(1, 2), '\u{093E}').to_string()
+        );
+
+        // string literals spanning several lines
+        let mut lexer = Lexer::new("\"a\nb\" x");
+        let (lexemes, _) = lexer.lex();
+        assert_eq!(lexemes[0].pos, Pos(1, 1));
+        assert_eq!(lexemes[1].pos, Pos(2, 4));
+    }
+
+    #[test]
+    fn test_auto_semicolon() {
+        let tokens = |src: &str| -> Vec<Token> {
+            let mut lexer = Lexer::new(src);
+            let (lexemes, _) = lexer.lex();
+            lexemes.iter().map(|l| l.token).collect()
+        };
+
+        use Token::*;
+        // binary operator and opening parenthesis at the line end
+        assert_eq!(
+            tokens("x := 1 +\n2\nf(\nx,\n)\n"),
+            vec![
+                Identifier, ColonEqual, IntLiteral, Plus, IntLiteral, Semicolon, Identifier,
+                LeftParen, Identifier, Comma, RightParen, Semicolon, Eof,
+            ]
+        );
+        // predeclared identifiers and the end of source
+        assert_eq!(
+            tokens("return nil\nreturn"),
+            vec![Return, Nil, Semicolon, Return, Semicolon, Eof]
+        );
+        // comments
+        assert_eq!(
+            tokens("x // c\ny /* a\nb */ z /* c */\n"),
+            vec![Identifier, Semicolon, Identifier, Semicolon, Identifier, Semicolon, Eof]
+        );
+        // no semicolon after a dot splitting a chain or after an opening brace
+        assert_eq!(
+            tokens("a.\nb {\n}"),
+            vec![
+                Identifier,
+                Dot,
+                Identifier,
+                LeftCurlyBrace,
+                RightCurlyBrace,
+                Semicolon,
+                Eof
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lex_numbers() {
+        let literals = |src: &str| -> Vec<(Token, String)> {
+            let mut lexer = Lexer::new(src);
+            let (lexemes, errs) = lexer.lex();
+            assert!(errs.is_empty(), "{:?}", errs);
+            lexemes
+                .iter()
+                .filter(|l| {
+                    matches!(
+                        l.token,
+                        Token::IntLiteral | Token::FloatLiteral | Token::ImagLiteral
+                    )
+                })
+                .map(|l| (l.token, l.literal.clone()))
+                .collect()
+        };
+
+        let int = |l: &str| (Token::IntLiteral, l.to_string());
+        let float = |l: &str| (Token::FloatLiteral, l.to_string());
+        let imag = |l: &str| (Token::ImagLiteral, l.to_string());
+
+        assert_eq!(
+            literals("0b1010 0o17 0755 0xFF 0X_1f 1_000_000 0"),
+            vec![
+                int("0b1010"),
+                int("0o17"),
+                int("0755"),
+                int("0xFF"),
+                int("0X_1f"),
+                int("1_000_000"),
+                int("0"),
+            ]
+        );
+        assert_eq!(
+            literals("1.5 1e9 2.5E-3 6_0.0_1 .5 1. 1.e2 0x1p-2 0X1.8P+1 0x_A.p0 0x.8p0"),
+            vec![
+                float("1.5"),
+                float("1e9"),
+                float("2.5E-3"),
+                float("6_0.0_1"),
+                float(".5"),
+                float("1."),
+                float("1.e2"),
+                float("0x1p-2"),
+                float("0X1.8P+1"),
+                float("0x_A.p0"),
+                float("0x.8p0"),
+            ]
+        );
+        assert_eq!(
+            literals("2i 0123i 1.5i .5e1i 0x1p-2i 0b1i x[1:]"),
+            vec![
+                imag("2i"),
+                imag("0123i"),
+                imag("1.5i"),
+                imag(".5e1i"),
+                imag("0x1p-2i"),
+                imag("0b1i"),
+                int("1"),
+            ]
+        );
+
+        for malformed in [
+            "0x", "0b12", "1__0", "1_", "12abc", "1_e3", "0x_", "1.e", "0x1.8", "0x.p1", "0x1p_2",
+            "1_i", "1ii",
+        ] {
+            let mut lexer = Lexer::new(malformed);
+            let (_, errs) = lexer.lex();
+            assert_eq!(
+                errs[0].to_string(),
+                LexError::MalformedNumber(Pos(1, 1), malformed.to_string()).to_string()
+            );
+        }
     }
 }
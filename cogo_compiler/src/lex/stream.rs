@@ -0,0 +1,116 @@
+use std::ops::{Index, Range};
+
+use super::lexeme::Lexeme;
+use super::{LexError, Lexer};
+
+/// Lexemes pulled from the lexer only once the parser looks at them,
+/// so the source is scanned at most a few lexemes ahead of the one being compiled.
+/// The lexemes pulled are kept, diagnostics and lints refer back to them by index
+pub(crate) struct LexemeStream<'a> {
+    lexer: Lexer<'a>,
+    lexemes: Vec<Lexeme>,
+    errors: Vec<LexError>,
+}
+
+impl<'a> LexemeStream<'a> {
+    pub(crate) fn new(lexer: Lexer<'a>) -> Self {
+        let mut stream = Self {
+            lexer,
+            lexemes: vec![],
+            errors: vec![],
+        };
+        stream.fill(0);
+
+        stream
+    }
+
+    /// Pulls lexemes up to the one at the index, unless the source ends before it
+    pub(crate) fn fill(&mut self, index: usize) {
+        while self.lexemes.len() <= index {
+            match self.lexer.next() {
+                Some(Ok(lexeme)) => self.lexemes.push(lexeme),
+                Some(Err(err)) => self.errors.push(err),
+                None => break,
+            }
+        }
+    }
+
+    /// Lexeme at the index, pulled if it has not been yet, the trailing `Eof` past the end
+    pub(crate) fn lookahead(&mut self, index: usize) -> &Lexeme {
+        self.fill(index);
+        &self.lexemes[index.min(self.lexemes.len() - 1)]
+    }
+
+    /// Lexeme at the index if it has been pulled
+    pub(crate) fn get(&self, index: usize) -> Option<&Lexeme> {
+        self.lexemes.get(index)
+    }
+
+    /// Number of the lexemes pulled so far, the first one is pulled right away
+    pub(crate) fn len(&self) -> usize {
+        self.lexemes.len()
+    }
+
+    /// Pulls the rest of the source
+    pub(crate) fn finish(&mut self) {
+        self.fill(usize::MAX);
+    }
+
+    /// Lexemes pulled so far, all of them once the stream is finished
+    pub(crate) fn lexemes(&self) -> &[Lexeme] {
+        &self.lexemes
+    }
+
+    /// Errors of the lexemes pulled so far
+    pub(crate) fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+
+    pub(crate) fn lexer(&self) -> &Lexer<'a> {
+        &self.lexer
+    }
+}
+
+impl Index<usize> for LexemeStream<'_> {
+    type Output = Lexeme;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.lexemes[index]
+    }
+}
+
+impl Index<Range<usize>> for LexemeStream<'_> {
+    type Output = [Lexeme];
+
+    fn index(&self, index: Range<usize>) -> &Self::Output {
+        &self.lexemes[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::lexeme::Token;
+
+    #[test]
+    fn test_pulls_lazily() {
+        let mut stream = LexemeStream::new(Lexer::new("a := 1\nb := 2\n"));
+        assert_eq!(stream.len(), 1);
+
+        assert_eq!(stream.lookahead(2).token, Token::IntLiteral);
+        assert_eq!(stream.len(), 3);
+
+        assert_eq!(stream.lookahead(100).token, Token::Eof);
+        stream.finish();
+        assert_eq!(stream.lexemes().len(), 9);
+    }
+
+    #[test]
+    fn test_collects_errors() {
+        let mut stream = LexemeStream::new(Lexer::new("a := 1 $ 2"));
+        assert!(stream.errors().is_empty());
+
+        stream.finish();
+        assert_eq!(stream.errors().len(), 1);
+    }
+}
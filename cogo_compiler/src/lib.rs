@@ -1,18 +1,47 @@
 pub(crate) mod error;
 pub(crate) mod unit;
 
-pub use self::compiler::compile;
-pub use self::error::{ErrorHandler, ToStderrErrorHandler, TypeError};
-pub use self::opcode::OpCode;
-pub use self::unit::{CompilationUnit, FuncUnit};
-pub use self::value::Value;
+pub use self::compiler::{
+    builtin_id, check, check_with_lints, compile, compile_with_options, completions,
+    semantic_tokens, CompileOptions, BUILTINS,
+};
+pub use self::completion::{Completion, CompletionKind};
+pub use self::diagnostic::{Diagnostic, Severity};
+pub use self::disasm::{disassemble, dump};
+pub use self::doc::{document, DocItem, DocKind, PackageDoc};
+pub use self::error::{
+    CompileError, CompilerError, DecodeError, ErrorHandler, ToJsonErrorHandler,
+    ToStderrErrorHandler, TypeError, VerificationError,
+};
+pub use self::jump_table::JumpTable;
+pub use self::lex::lexeme::{Pos, Span};
+pub use self::lint::{Lint, LintRule, LintRules};
+pub use self::opcode::{Chunk, OpCode};
+pub use self::refs::{apply, references, rename, Edit};
+pub use self::semantic::{SemanticToken, TokenClass};
+pub use self::unit::{CompilationUnit, FuncUnit, LocalVar};
+pub use self::value::{Allocator, RefIterator, SliceRef, Unmanaged, Value};
 pub use self::vtype::ValType;
 
+pub mod bytecode;
 pub mod compiler;
+mod completion;
+mod diagnostic;
+mod disasm;
+mod doc;
 mod flow;
-mod lex;
+mod init_order;
+mod jump_table;
+pub mod lex;
+mod lint;
 mod opcode;
+mod optimizer;
+mod pos_table;
+mod refs;
 mod scope;
+mod semantic;
 mod structure;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 mod value;
 mod vtype;
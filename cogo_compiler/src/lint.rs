@@ -0,0 +1,141 @@
+use crate::lex::lexeme::{Pos, Span};
+use crate::{Diagnostic, Severity};
+
+/// Check for code which compiles but is likely a mistake, reported as a warning.
+/// A rule is added as a variant here and checked by the compiler where it sees the construct.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LintRule {
+    /// `if x := f(); ...` hiding a variable `x` declared outside the `if`
+    Shadow,
+    /// `x = x`, assigning a variable to itself
+    SelfAssign,
+    /// `case` matching a constant already matched by a previous case of the switch
+    UnreachableCase,
+    /// `for true {}` or `for false {}`
+    ConstantCondition,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 4] = [
+        Self::Shadow,
+        Self::SelfAssign,
+        Self::UnreachableCase,
+        Self::ConstantCondition,
+    ];
+
+    /// Name the rule is enabled and disabled by, also the code of its diagnostics
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Shadow => "shadow",
+            Self::SelfAssign => "self-assign",
+            Self::UnreachableCase => "unreachable-case",
+            Self::ConstantCondition => "constant-condition",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|rule| rule.name() == name)
+    }
+}
+
+/// Rules the compiler checks, none by default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LintRules(Vec<LintRule>);
+
+impl LintRules {
+    pub fn all() -> Self {
+        Self(LintRule::ALL.to_vec())
+    }
+
+    /// Rules of a comma-separated list of names, a name prefixed with `-` disables the rule.
+    /// A list starting with a disabled rule starts from all the rules, e.g. `-shadow`
+    pub fn parse(list: &str) -> Result<Self, String> {
+        let mut rules = if list.starts_with('-') {
+            Self::all()
+        } else {
+            Self::default()
+        };
+
+        for name in list.split(',') {
+            let (name, enable) = match name.strip_prefix('-') {
+                Some(name) => (name, false),
+                None => (name, true),
+            };
+            let rule = LintRule::from_name(name)
+                .ok_or_else(|| format!("Unknown lint rule \"{}\".", name))?;
+
+            if enable {
+                rules.enable(rule);
+            } else {
+                rules.disable(rule);
+            }
+        }
+
+        Ok(rules)
+    }
+
+    pub fn enable(&mut self, rule: LintRule) {
+        if !self.is_enabled(rule) {
+            self.0.push(rule);
+        }
+    }
+
+    pub fn disable(&mut self, rule: LintRule) {
+        self.0.retain(|r| *r != rule);
+    }
+
+    pub fn is_enabled(&self, rule: LintRule) -> bool {
+        self.0.contains(&rule)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+/// Warning found by a lint rule
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lint {
+    pub rule: LintRule,
+    pub message: String,
+    pub pos: Pos,
+    pub span: Span,
+}
+
+impl From<&Lint> for Diagnostic {
+    fn from(lint: &Lint) -> Self {
+        Self {
+            severity: Severity::Warning,
+            code: lint.rule.name().to_string(),
+            message: lint.message.clone(),
+            pos: lint.pos,
+            span: Some(lint.span),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_rules() {
+        let rules = LintRules::parse("shadow,self-assign").unwrap();
+        assert!(rules.is_enabled(LintRule::Shadow));
+        assert!(!rules.is_enabled(LintRule::ConstantCondition));
+
+        let rules = LintRules::parse("-shadow").unwrap();
+        assert!(!rules.is_enabled(LintRule::Shadow));
+        assert!(rules.is_enabled(LintRule::UnreachableCase));
+
+        let rules = LintRules::parse("-shadow,shadow,-self-assign").unwrap();
+        assert!(rules.is_enabled(LintRule::Shadow));
+        assert!(!rules.is_enabled(LintRule::SelfAssign));
+
+        assert_eq!(
+            LintRules::parse("shadow,unused"),
+            Err("Unknown lint rule \"unused\".".to_string())
+        );
+        assert!(LintRules::default().is_empty());
+    }
+}
@@ -1,7 +1,13 @@
-use std::fmt;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::rc::Rc;
+use std::{fmt, mem};
 
-use crate::lex::lexeme::Pos;
-use crate::{CompilationUnit, ValType, Value};
+use crate::error::{CompileError, VerificationError};
+use crate::jump_table::JumpTable;
+use crate::lex::lexeme::{Pos, Span};
+use crate::pos_table::PosTable;
+use crate::{CompilationUnit, ValType, Value, BUILTINS};
 
 #[derive(Debug, Clone)]
 pub enum OpCode {
@@ -32,6 +38,11 @@ pub enum OpCode {
 
     //Misc
     Call(u8, bool),
+    /// Call which replaces the current frame, emitted for `return f(...)` inside of `f`
+    TailCall(u8, bool),
+    /// Call of the builtin with the id, followed by the argument count.
+    /// The callee is known at compile time and is not put on the stack
+    CallBuiltin(u8, u8),
 
     Equal,
     NotEqual,
@@ -41,23 +52,22 @@ pub enum OpCode {
     LessEqual,
 
     Switch,
+    /// Jumps straight to the body of the matching case of a switch over constants
+    JumpTable(Rc<JumpTable>),
     DefaultJump(usize),
     CaseJump(usize),
     DefaultCaseJump(usize),
     IfFalseJump(usize),
     Jump(usize),
-    BackJump(usize),
     CaseBreakJump(usize),
     DoCaseBreakJump,
     Fallthrough,
     Return(u8),
 
-    Bool(Value),
-    String(Value),
+    /// Loads a value from the constant pool of the chunk
+    Constant(usize),
     Func(CompilationUnit),
 
-    IntLiteral(Value),
-    FloatLiteral(Value),
     ArrayLiteral(usize, ValType),
     SliceLiteral(usize, ValType),
 
@@ -68,6 +78,10 @@ pub enum OpCode {
     SetGlobal(String),
     GetLocal(usize),
     SetLocal(usize),
+    /// Adds a constant to a local in place, `x++` and `x += 1` are optimized into it
+    IncLocal(usize, usize),
+    /// Subtracts a constant from a local in place
+    DecLocal(usize, usize),
 
     GetIndex,
     GetLocalIndex(usize),
@@ -76,6 +90,8 @@ pub enum OpCode {
     SetIndex,
     SetLocalIndex(usize, usize, bool),
     SetGlobalIndex(String, usize, bool),
+    /// Slice expression `a[low:high:max]`, flags tell whether each of the bounds is on the stack
+    SliceExpr(bool, bool, bool),
 
     // Value casting manipulation
     BlindLiteralCast(usize),
@@ -83,12 +99,124 @@ pub enum OpCode {
     LoseSoftReference(usize),
     TypeValidation(ValType, usize),
     PutDefaultValue(ValType),
+    /// Conversion of a value to a composite type, e.g. `[]byte(s)`
+    Convert(ValType),
+    /// Checks the value ranged over is an integer and pushes the zero of its type,
+    /// which the iteration starts from
+    RangeInt,
+}
+
+impl OpCode {
+    /// Absolute index of the opcode the jump leads to, `None` for non-jump opcodes
+    pub fn jump_target(&self) -> Option<usize> {
+        use OpCode::*;
+        match self {
+            Jump(t) | IfFalseJump(t) | CaseJump(t) | DefaultCaseJump(t) | DefaultJump(t)
+            | CaseBreakJump(t) => Some(*t),
+            _ => None,
+        }
+    }
+
+    /// Every position the opcode may jump to, a jump table leads to each of its cases
+    pub fn jump_targets(&self) -> Vec<usize> {
+        match self {
+            OpCode::JumpTable(table) => table.targets().collect(),
+            code => code.jump_target().into_iter().collect(),
+        }
+    }
+
+    /// Changes each target of a jump opcode with `f`
+    pub(crate) fn map_jump_targets(&mut self, f: impl Fn(usize) -> usize) {
+        if let OpCode::JumpTable(table) = self {
+            Rc::make_mut(table).map_targets(f);
+        } else if let Some(target) = self.jump_target() {
+            self.set_jump_target(f(target));
+        }
+    }
+
+    /// Changes the target of a jump opcode, returns `false` for non-jump opcodes
+    pub(crate) fn set_jump_target(&mut self, target: usize) -> bool {
+        use OpCode::*;
+        match self {
+            Jump(t) | IfFalseJump(t) | CaseJump(t) | DefaultCaseJump(t) | DefaultJump(t)
+            | CaseBreakJump(t) => {
+                *t = target;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Index of the constant the opcode refers to, `None` for opcodes without one
+    pub fn constant_index(&self) -> Option<usize> {
+        use OpCode::*;
+        match self {
+            Constant(c) | IncLocal(_, c) | DecLocal(_, c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    /// Frame slot of the local the opcode reads or writes, `None` for other opcodes
+    pub fn local_slot(&self) -> Option<usize> {
+        use OpCode::*;
+        match self {
+            GetLocal(i)
+            | SetLocal(i)
+            | IncLocal(i, _)
+            | DecLocal(i, _)
+            | GetLocalIndex(i)
+            | SetLocalIndex(i, ..) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// How deep below the top of the stack the value the opcode works on lies,
+    /// those are values about to be bound to locals
+    pub fn stack_offset(&self) -> Option<usize> {
+        use OpCode::*;
+        match self {
+            BlindLiteralCast(by) | LoseSoftReference(by) | TypeValidation(_, by) => Some(*by),
+            VariadicSliceCast(_, until) => Some(*until as usize),
+            _ => None,
+        }
+    }
+
+    /// Whether the opcode works on the innermost switch being executed
+    pub fn is_switch_case(&self) -> bool {
+        use OpCode::*;
+        matches!(
+            self,
+            JumpTable(_)
+                | DefaultJump(_)
+                | CaseJump(_)
+                | DefaultCaseJump(_)
+                | CaseBreakJump(_)
+                | DoCaseBreakJump
+                | Fallthrough
+        )
+    }
+
+    /// Changes the constant the opcode refers to, returns `false` for opcodes without one
+    pub(crate) fn set_constant_index(&mut self, index: usize) -> bool {
+        use OpCode::*;
+        match self {
+            Constant(c) | IncLocal(_, c) | DecLocal(_, c) => {
+                *c = index;
+                true
+            }
+            _ => false,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct Chunk {
     codes: Vec<OpCode>,
-    pos: Vec<Pos>,
+    /// Positions and source spans of the lexemes each opcode was emitted for
+    pos: PosTable,
+    constants: Vec<Value>,
+    /// Index of each constant in the pool, for the constants added to be stored once
+    pool: HashMap<ConstantKey, usize>,
 }
 
 impl Default for Chunk {
@@ -101,13 +229,30 @@ impl Chunk {
     pub(crate) fn new() -> Self {
         Self {
             codes: vec![],
-            pos: vec![],
+            pos: PosTable::default(),
+            constants: vec![],
+            pool: HashMap::new(),
+        }
+    }
+
+    /// Chunk made of already compiled parts, the table holds the location of each opcode
+    pub(crate) fn from_parts(codes: Vec<OpCode>, pos: PosTable, constants: Vec<Value>) -> Self {
+        Self {
+            codes,
+            pos,
+            pool: pool_of(&constants),
+            constants,
         }
     }
 
+    #[cfg(test)]
     pub(crate) fn write(&mut self, op_code: OpCode, pos: Pos) -> usize {
+        self.write_spanned(op_code, pos, Span::default())
+    }
+
+    pub(crate) fn write_spanned(&mut self, op_code: OpCode, pos: Pos, span: Span) -> usize {
         self.codes.push(op_code);
-        self.pos.push(pos);
+        self.pos.push(pos, span);
 
         self.codes.len() - 1
     }
@@ -120,14 +265,308 @@ impl Chunk {
         }
     }
 
+    /// Removes opcodes not marked to be kept, jumps are adjusted to the new positions.
+    /// A jump to a removed opcode leads to the next kept one.
+    pub(crate) fn retain_codes(&mut self, keep: &[bool]) {
+        let mut new_index = Vec::with_capacity(keep.len() + 1);
+        let mut kept = 0;
+        for k in keep {
+            new_index.push(kept);
+            if *k {
+                kept += 1;
+            }
+        }
+        new_index.push(kept);
+
+        let codes = mem::take(&mut self.codes);
+        self.pos = self.pos.select((0..keep.len()).filter(|i| keep[*i]));
+
+        for (mut code, _) in codes.into_iter().zip(keep).filter(|(_, k)| **k) {
+            code.map_jump_targets(|target| new_index[target]);
+            self.codes.push(code);
+        }
+    }
+
+    /// Positions which any jump of the chunk leads to
+    pub(crate) fn jump_targets(&self) -> HashSet<usize> {
+        self.codes.iter().flat_map(OpCode::jump_targets).collect()
+    }
+
+    /// Rearranges the opcodes, the ranges must cover all of them exactly once.
+    /// Jumps may only lead inside the range they belong to or right past its end.
+    pub(crate) fn reorder(&mut self, ranges: &[Range<usize>]) {
+        let mut codes = Vec::with_capacity(self.codes.len());
+
+        for range in ranges {
+            let start = codes.len();
+            for i in range.clone() {
+                let mut code = self.codes[i].clone();
+                code.map_jump_targets(|target| target - range.start + start);
+                codes.push(code);
+            }
+        }
+
+        self.codes = codes;
+        self.pos = self.pos.select(ranges.iter().cloned().flatten());
+    }
+
+    /// Drops constants no opcode refers to anymore
+    pub(crate) fn prune_constants(&mut self) {
+        let mut used = vec![false; self.constants.len()];
+        for code in &self.codes {
+            if let Some(c) = code.constant_index() {
+                used[c] = true;
+            }
+        }
+
+        let mut new_index = Vec::with_capacity(used.len());
+        let mut kept = 0;
+        for u in &used {
+            new_index.push(kept);
+            if *u {
+                kept += 1;
+            }
+        }
+
+        let constants = mem::take(&mut self.constants);
+        self.constants = constants
+            .into_iter()
+            .zip(&used)
+            .filter(|(_, u)| **u)
+            .map(|(c, _)| c)
+            .collect();
+        self.pool = pool_of(&self.constants);
+
+        for code in &mut self.codes {
+            if let Some(c) = code.constant_index() {
+                code.set_constant_index(new_index[c]);
+            }
+        }
+    }
+
+    /// Adds a literal value to the constant pool, identical constants are stored once.
+    /// Returns the index of the constant in the pool.
+    pub(crate) fn add_constant(&mut self, value: Value) -> usize {
+        let Some(key) = ConstantKey::of(&value) else {
+            self.constants.push(value);
+            return self.constants.len() - 1;
+        };
+
+        let len = self.constants.len();
+        let i = *self.pool.entry(key).or_insert(len);
+        if i == len {
+            self.constants.push(value);
+        }
+
+        i
+    }
+
+    /// Points a previously written jump opcode to `target`,
+    /// fails if the opcode is not a jump, which only malformed source leads to
+    pub(crate) fn patch_jump(&mut self, at: usize, target: usize) -> Result<(), CompileError> {
+        let code = self.codes.get_mut(at);
+        if code.is_some_and(|code| code.set_jump_target(target)) {
+            return Ok(());
+        }
+
+        let (pos, span) = if at < self.codes.len() {
+            self.pos.get(at)
+        } else {
+            (Pos(0, 0), Span::default())
+        };
+
+        Err(CompileError::new(
+            "Jump out of a malformed statement.".to_string(),
+            pos,
+            span,
+        ))
+    }
+
     pub(crate) fn pop(&mut self) -> Option<OpCode> {
         self.pos.pop();
         self.codes.pop()
     }
 
+    /// Line and column of the source the opcode at `i` was compiled from
+    pub fn pos(&self, i: usize) -> Pos {
+        self.pos.get(i).0
+    }
+
+    /// Byte offsets of the source the opcode at `i` was compiled from
+    pub fn span(&self, i: usize) -> Span {
+        self.pos.get(i).1
+    }
+
+    /// Line, column and byte offsets of the source the opcode at `i` was compiled from,
+    /// `None` past the end of the chunk
+    pub fn location(&self, i: usize) -> Option<(Pos, Span)> {
+        if i < self.codes.len() {
+            Some(self.pos.get(i))
+        } else {
+            None
+        }
+    }
+
+    /// Index of the first opcode compiled from the source line, where a debugger stops
+    /// for a breakpoint on the line. `None` if no code of the chunk comes from the line
+    pub fn first_code_at_line(&self, line: usize) -> Option<usize> {
+        self.pos.first_at_line(line)
+    }
+
+    pub(crate) fn pos_table(&self) -> &PosTable {
+        &self.pos
+    }
+
     pub fn codes(&self) -> &[OpCode] {
         &self.codes
     }
+
+    pub(crate) fn codes_mut(&mut self) -> &mut [OpCode] {
+        &mut self.codes
+    }
+
+    pub fn constant(&self, i: usize) -> &Value {
+        &self.constants[i]
+    }
+
+    pub fn constants(&self) -> &[Value] {
+        &self.constants
+    }
+
+    /// Checks that every jump leads inside the chunk (or right past its end)
+    /// and that jumps with a structural meaning land where they are expected to.
+    /// Locals and the values bound to them must fit in the `slots` of the frame,
+    /// case opcodes must follow a switch.
+    /// Nested function chunks are verified as well.
+    pub fn verify(&self, slots: usize) -> Result<(), VerificationError> {
+        let len = self.codes.len();
+        let mut in_switch = false;
+
+        for (i, code) in self.codes.iter().enumerate() {
+            for target in code.jump_targets() {
+                if target > len {
+                    return Err(VerificationError::new(
+                        format!(
+                            "jump target {} is out of bounds (chunk length {})",
+                            target, len
+                        ),
+                        i,
+                    ));
+                }
+
+                if let OpCode::DefaultJump(_) = code {
+                    if target == 0 || !matches!(self.codes[target - 1], OpCode::DefaultCaseJump(_))
+                    {
+                        return Err(VerificationError::new(
+                            "default jump must lead to a default case body".to_string(),
+                            i,
+                        ));
+                    }
+                }
+            }
+
+            if let Some(slot) = code.local_slot() {
+                if slot >= slots {
+                    return Err(VerificationError::new(
+                        format!("local {} is out of the frame ({} slots)", slot, slots),
+                        i,
+                    ));
+                }
+            }
+
+            if let Some(by) = code.stack_offset() {
+                if by >= slots {
+                    return Err(VerificationError::new(
+                        format!("stack offset {} is out of the frame ({} slots)", by, slots),
+                        i,
+                    ));
+                }
+            }
+
+            match code {
+                OpCode::Switch => in_switch = true,
+                code if code.is_switch_case() && !in_switch => {
+                    return Err(VerificationError::new(
+                        "case outside of a switch".to_string(),
+                        i,
+                    ));
+                }
+                _ => {}
+            }
+
+            if let Some(c) = code.constant_index() {
+                if c >= self.constants.len() {
+                    return Err(VerificationError::new(
+                        format!("constant {} is not in the pool", c),
+                        i,
+                    ));
+                }
+            }
+
+            if let OpCode::CallBuiltin(id, _) = code {
+                if *id as usize >= BUILTINS.len() {
+                    return Err(VerificationError::new(format!("unknown builtin {}", id), i));
+                }
+            }
+
+            if let OpCode::Func(cunit) = code {
+                cunit.verify()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Identity of a constant in the pool. The variant of the value is a part of it,
+/// floats are compared bitwise so that e.g. `0.0` and `-0.0` are kept apart
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct ConstantKey(mem::Discriminant<Value>, ConstantBits);
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum ConstantBits {
+    Int(i128),
+    Float(u64, u64),
+    Str(Rc<str>),
+}
+
+impl ConstantKey {
+    /// `None` for values other than literals, those are not shared
+    fn of(value: &Value) -> Option<Self> {
+        use ConstantBits::*;
+        let bits = match value {
+            Value::Nil => Int(0),
+            Value::Bool(v) => Int(*v as i128),
+            Value::Int8(v) => Int(*v as i128),
+            Value::Int16(v) => Int(*v as i128),
+            Value::Int32(v) => Int(*v as i128),
+            Value::Int64(v) => Int(*v as i128),
+            Value::Int(v) => Int(*v as i128),
+            Value::IntLiteral(v) => Int(*v),
+            Value::Uint8(v) => Int(*v as i128),
+            Value::Uint16(v) => Int(*v as i128),
+            Value::Uint32(v) => Int(*v as i128),
+            Value::Uint64(v) => Int(*v as i128),
+            Value::Uint(v) | Value::Uintptr(v) => Int(*v as i128),
+            Value::Float32(v) => Float(v.to_bits() as u64, 0),
+            Value::Float64(v) | Value::FloatLiteral(v) => Float(v.to_bits(), 0),
+            Value::Complex64(re, im) => Float(re.to_bits() as u64, im.to_bits() as u64),
+            Value::Complex128(re, im) => Float(re.to_bits(), im.to_bits()),
+            Value::String(s) => Str(Rc::clone(s)),
+            _ => return None,
+        };
+
+        Some(Self(mem::discriminant(value), bits))
+    }
+}
+
+/// Index of the constants of a pool whose values are all distinct
+fn pool_of(constants: &[Value]) -> HashMap<ConstantKey, usize> {
+    constants
+        .iter()
+        .enumerate()
+        .filter_map(|(i, c)| Some((ConstantKey::of(c)?, i)))
+        .collect()
 }
 
 impl fmt::Debug for Chunk {
@@ -135,9 +574,138 @@ impl fmt::Debug for Chunk {
         let mut buffer = String::new();
 
         for (i, code) in self.codes.iter().enumerate() {
-            buffer += &format!("#{}: {:?} {}\n", i, code, self.pos[i]);
+            if let Some(c) = code.constant_index() {
+                buffer += &format!(
+                    "#{}: {:?} {:?} {}\n",
+                    i,
+                    code,
+                    self.constants[c],
+                    self.pos(i)
+                );
+            } else {
+                buffer += &format!("#{}: {:?} {}\n", i, code, self.pos(i));
+            }
         }
 
         write!(f, "{}", buffer)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk_reorder() {
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::Noop, Pos(1, 1));
+        chunk.write(OpCode::IfFalseJump(3), Pos(2, 1));
+        chunk.write(OpCode::Pop, Pos(2, 2));
+        chunk.write(OpCode::Not, Pos(3, 1));
+
+        chunk.reorder(&[3..4, 0..1, 1..3]);
+        assert!(matches!(
+            chunk.codes(),
+            [
+                OpCode::Not,
+                OpCode::Noop,
+                OpCode::IfFalseJump(4),
+                OpCode::Pop
+            ]
+        ));
+        assert_eq!(chunk.pos(2), Pos(2, 1));
+        assert_eq!(chunk.first_code_at_line(2), Some(2));
+        assert_eq!(chunk.first_code_at_line(4), None);
+        assert_eq!(chunk.location(0).map(|(pos, _)| pos), Some(Pos(3, 1)));
+        assert_eq!(chunk.location(4), None);
+        assert!(chunk.verify(0).is_ok());
+    }
+
+    #[test]
+    fn test_chunk_verify() {
+        let mut chunk = Chunk::new();
+        let c = chunk.add_constant(Value::Bool(true));
+        chunk.write(OpCode::Constant(c), Pos(1, 1));
+        let jump = chunk.write(OpCode::IfFalseJump(0), Pos(1, 1));
+        chunk.write(OpCode::Pop, Pos(1, 1));
+        chunk.patch_jump(jump, 3).unwrap();
+        assert!(chunk.verify(0).is_ok());
+        assert!(chunk.patch_jump(jump + 1, 3).is_err());
+
+        chunk.patch_jump(jump, 4).unwrap();
+        assert_eq!(chunk.verify(0).unwrap_err().at(), jump);
+
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::Pop, Pos(1, 1));
+        chunk.write(OpCode::DefaultJump(1), Pos(1, 1));
+        assert!(chunk.verify(0).is_err());
+
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::Constant(0), Pos(1, 1));
+        assert!(chunk.verify(0).is_err());
+
+        // locals and the values bound to them fit in the frame
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::GetLocal(1), Pos(1, 1));
+        chunk.write(OpCode::SetLocal(1), Pos(1, 1));
+        chunk.write(OpCode::TypeValidation(ValType::Int, 1), Pos(1, 1));
+        assert!(chunk.verify(2).is_ok());
+        assert_eq!(chunk.verify(1).unwrap_err().at(), 0);
+        chunk.write(OpCode::TypeValidation(ValType::Int, usize::MAX), Pos(1, 1));
+        assert_eq!(chunk.verify(2).unwrap_err().at(), 3);
+
+        // a case break left over from no switch
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::CaseBreakJump(1), Pos(1, 1));
+        assert!(chunk.verify(0).is_err());
+
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::Switch, Pos(1, 1));
+        chunk.write(OpCode::DoCaseBreakJump, Pos(1, 1));
+        chunk.write(OpCode::CaseBreakJump(3), Pos(1, 1));
+        assert!(chunk.verify(0).is_ok());
+    }
+
+    #[test]
+    fn test_retain_codes() {
+        let mut chunk = Chunk::new();
+        chunk.write(OpCode::Jump(3), Pos(1, 1));
+        chunk.write(OpCode::Pop, Pos(1, 1));
+        chunk.write(OpCode::Pop, Pos(1, 1));
+        chunk.write(OpCode::Pop, Pos(1, 1));
+        chunk.write(OpCode::Jump(2), Pos(1, 1));
+        chunk.retain_codes(&[true, true, false, true, true]);
+
+        assert_eq!(chunk.codes().len(), 4);
+        assert_eq!(chunk.codes()[0].jump_target(), Some(2));
+        assert_eq!(chunk.codes()[3].jump_target(), Some(2));
+    }
+
+    #[test]
+    fn test_prune_constants() {
+        let mut chunk = Chunk::new();
+        chunk.add_constant(Value::IntLiteral(1));
+        let c = chunk.add_constant(Value::IntLiteral(2));
+        chunk.write(OpCode::Constant(c), Pos(1, 1));
+        chunk.prune_constants();
+
+        assert_eq!(chunk.constants(), &[Value::IntLiteral(2)]);
+        assert!(matches!(chunk.codes()[0], OpCode::Constant(0)));
+        assert_eq!(chunk.add_constant(Value::IntLiteral(2)), 0);
+        assert_eq!(chunk.add_constant(Value::IntLiteral(1)), 1);
+    }
+
+    #[test]
+    fn test_constant_pool() {
+        let mut chunk = Chunk::new();
+        assert_eq!(chunk.add_constant(Value::IntLiteral(1)), 0);
+        assert_eq!(chunk.add_constant(Value::String("a".into())), 1);
+        assert_eq!(chunk.add_constant(Value::IntLiteral(1)), 0);
+        assert_eq!(chunk.add_constant(Value::FloatLiteral(0.0)), 2);
+        assert_eq!(chunk.add_constant(Value::FloatLiteral(-0.0)), 3);
+        // the same bits of another type are another constant
+        assert_eq!(chunk.add_constant(Value::Int(1)), 4);
+        assert_eq!(chunk.add_constant(Value::String("a".into())), 1);
+        assert_eq!(chunk.constants().len(), 5);
+    }
+}
@@ -0,0 +1,640 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
+use crate::opcode::Chunk;
+use crate::{CompilationUnit, OpCode, Value};
+
+/// Optimizes the chunk of the unit and all the function units nested in it
+pub(crate) fn optimize(cunit: &mut CompilationUnit) {
+    let chunk = cunit.chunk_mut();
+    for code in chunk.codes_mut() {
+        if let OpCode::Func(funit) = code {
+            optimize(funit);
+        }
+    }
+
+    while fold_constants(chunk) | eliminate_unreachable(chunk) | rewrite_peephole(chunk) {}
+
+    if let CompilationUnit::Package(_) = cunit {
+        eliminate_unused_functions(cunit.chunk_mut());
+    }
+
+    cunit.chunk_mut().prune_constants();
+}
+
+/// Folds arithmetic and comparisons over constants, collapses constant conditions
+/// and removes jumps that became meaningless.
+/// Opcodes some jump leads to are never folded into the preceding ones.
+/// Returns whether the chunk has changed.
+fn fold_constants(chunk: &mut Chunk) -> bool {
+    let targets = chunk.jump_targets();
+    let len = chunk.codes().len();
+    let mut keep = vec![true; len];
+    let mut changed = false;
+
+    let foldable = |i: usize| i < len && !targets.contains(&i);
+
+    let mut i = 0;
+    while i < len {
+        let code = chunk.codes()[i].clone();
+        let next = chunk.codes().get(i + 1).cloned();
+        let after_next = chunk.codes().get(i + 2).cloned();
+
+        match (code, next, after_next) {
+            // binary operation over two constants
+            (OpCode::Constant(a), Some(OpCode::Constant(b)), Some(op))
+                if foldable(i + 1) && foldable(i + 2) =>
+            {
+                if let Some(v) = fold_binary(&op, chunk.constant(a), chunk.constant(b)) {
+                    let c = chunk.add_constant(v);
+                    chunk.write_at(i, OpCode::Constant(c));
+                    keep[i + 1] = false;
+                    keep[i + 2] = false;
+                    changed = true;
+                    i += 3;
+                    continue;
+                }
+            }
+            // condition which is always true: the check and the following pop are no-ops
+            (OpCode::Constant(c), Some(OpCode::IfFalseJump(_)), Some(OpCode::Pop))
+                if foldable(i + 1)
+                    && foldable(i + 2)
+                    && matches!(chunk.constant(c), Value::Bool(true)) =>
+            {
+                keep[i] = false;
+                keep[i + 1] = false;
+                keep[i + 2] = false;
+                changed = true;
+                i += 3;
+                continue;
+            }
+            _ => {}
+        }
+
+        let code = chunk.codes()[i].clone();
+        let next = chunk.codes().get(i + 1).cloned();
+
+        match (code, next) {
+            // unary operation over a constant
+            (OpCode::Constant(a), Some(op)) if foldable(i + 1) => {
+                if let Some(v) = fold_unary(&op, chunk.constant(a)) {
+                    let c = chunk.add_constant(v);
+                    chunk.write_at(i, OpCode::Constant(c));
+                    keep[i + 1] = false;
+                    changed = true;
+                    i += 2;
+                    continue;
+                }
+
+                if let OpCode::IfFalseJump(target) = op {
+                    match chunk.constant(a) {
+                        // the jump is never taken
+                        Value::Bool(true) => {
+                            keep[i + 1] = false;
+                            changed = true;
+                        }
+                        // the jump is always taken
+                        Value::Bool(false) => {
+                            chunk.write_at(i + 1, OpCode::Jump(target));
+                            changed = true;
+                        }
+                        _ => {}
+                    }
+
+                    i += 2;
+                    continue;
+                }
+            }
+            // jump to the very next opcode
+            (OpCode::Jump(target), _) if target == i + 1 => {
+                keep[i] = false;
+                changed = true;
+            }
+            _ => {}
+        }
+
+        i += 1;
+    }
+
+    if changed {
+        chunk.retain_codes(&keep);
+    }
+
+    changed
+}
+
+/// Applies small local rewrites:
+/// values pushed only to be popped right away are dropped,
+/// `x += <int>` style assignments are fused into `IncLocal` and `DecLocal`,
+/// jumps leading to other jumps are pointed to the final target.
+/// Returns whether the chunk has changed.
+fn rewrite_peephole(chunk: &mut Chunk) -> bool {
+    let targets = chunk.jump_targets();
+    let len = chunk.codes().len();
+    let mut keep = vec![true; len];
+    let mut changed = false;
+
+    let untargeted = |from: usize, to: usize| (from..=to).all(|i| i < len && !targets.contains(&i));
+
+    let mut i = 0;
+    while i < len {
+        let codes = chunk.codes();
+
+        // a pop following a store is skipped by the vm,
+        // so only the pairs right after a previous pop are safe to drop
+        let after_pop = i == 0 || matches!(codes[i - 1], OpCode::Pop);
+        if let (OpCode::Constant(_) | OpCode::GetLocal(_), Some(OpCode::Pop)) =
+            (&codes[i], codes.get(i + 1))
+        {
+            if after_pop && untargeted(i, i + 1) {
+                keep[i] = false;
+                keep[i + 1] = false;
+                changed = true;
+                i += 2;
+                continue;
+            }
+        }
+
+        if let [OpCode::GetLocal(a), OpCode::Constant(c), op, OpCode::SetLocal(b), OpCode::Pop, ..] =
+            &codes[i..]
+        {
+            let fused = match op {
+                OpCode::Add => Some(OpCode::IncLocal(*a, *c)),
+                OpCode::Subtract => Some(OpCode::DecLocal(*a, *c)),
+                _ => None,
+            };
+
+            if let Some(fused) = fused {
+                if a == b
+                    && matches!(chunk.constant(*c), Value::IntLiteral(_))
+                    && untargeted(i + 1, i + 4)
+                {
+                    chunk.write_at(i, fused);
+                    for k in &mut keep[i + 1..=i + 4] {
+                        *k = false;
+                    }
+                    changed = true;
+                    i += 5;
+                    continue;
+                }
+            }
+        }
+
+        i += 1;
+    }
+
+    for i in 0..len {
+        let target = match chunk.codes()[i] {
+            OpCode::Jump(t) | OpCode::IfFalseJump(t) => t,
+            _ => continue,
+        };
+
+        let mut seen = HashSet::new();
+        let mut last = target;
+        while let Some(OpCode::Jump(next)) = chunk.codes().get(last) {
+            if !seen.insert(last) {
+                break;
+            }
+            last = *next;
+        }
+
+        if last != target {
+            chunk.codes_mut()[i].set_jump_target(last);
+            changed = true;
+        }
+    }
+
+    if changed {
+        chunk.retain_codes(&keep);
+    }
+
+    changed
+}
+
+/// Removes opcodes no execution path can reach.
+/// Returns whether the chunk has changed.
+fn eliminate_unreachable(chunk: &mut Chunk) -> bool {
+    let codes = chunk.codes();
+    let mut reachable = vec![false; codes.len()];
+    let mut pending = vec![0];
+
+    while let Some(i) = pending.pop() {
+        if i >= codes.len() || reachable[i] {
+            continue;
+        }
+        reachable[i] = true;
+
+        match &codes[i] {
+            OpCode::Jump(target) => pending.push(*target),
+            OpCode::Return(_) | OpCode::TailCall(..) => {}
+            // default case body is entered right after its `DefaultCaseJump`,
+            // which must stay in place
+            OpCode::DefaultJump(target) => {
+                pending.push(i + 1);
+                pending.push(*target);
+                pending.push(*target - 1);
+            }
+            code => {
+                pending.push(i + 1);
+                pending.extend(code.jump_targets());
+            }
+        }
+    }
+
+    if reachable.iter().all(|r| *r) {
+        return false;
+    }
+
+    chunk.retain_codes(&reachable);
+
+    true
+}
+
+/// Removes package level functions which are never referenced starting from the globals
+/// initialization and the entry point call.
+fn eliminate_unused_functions(chunk: &mut Chunk) {
+    let codes = chunk.codes();
+
+    // function declarations are pairs of the function itself and its global variable
+    let is_declaration = |i: usize| {
+        matches!(
+            (codes.get(i), codes.get(i + 1)),
+            (Some(OpCode::Func(_)), Some(OpCode::VarGlobal(..)))
+        )
+    };
+
+    let mut referenced = HashSet::new();
+    let mut i = 0;
+    while i < codes.len() {
+        if is_declaration(i) {
+            i += 2;
+        } else {
+            collect_globals(&codes[i], &mut referenced);
+            i += 1;
+        }
+    }
+
+    let mut used = HashSet::new();
+    loop {
+        let mut changed = false;
+        for i in (0..codes.len()).filter(|i| is_declaration(*i)) {
+            if let OpCode::VarGlobal(name, _) = &codes[i + 1] {
+                if referenced.contains(name) && used.insert(i) {
+                    collect_globals(&codes[i], &mut referenced);
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let mut keep = vec![true; codes.len()];
+    for i in (0..codes.len()).filter(|i| is_declaration(*i) && !used.contains(i)) {
+        keep[i] = false;
+        keep[i + 1] = false;
+    }
+
+    chunk.retain_codes(&keep);
+}
+
+/// Names of globals an opcode accesses, including those inside of function bodies
+fn collect_globals(code: &OpCode, names: &mut HashSet<String>) {
+    match code {
+        OpCode::GetGlobal(name)
+        | OpCode::SetGlobal(name)
+        | OpCode::GetGlobalIndex(name)
+        | OpCode::SetGlobalIndex(name, ..) => {
+            names.insert(name.clone());
+        }
+        OpCode::Func(funit) => {
+            for code in funit.chunk().codes() {
+                collect_globals(code, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Evaluates a binary operation at compile time.
+/// Returns `None` when the operation cannot be folded and must be left to the runtime,
+/// e.g. on type errors, integer overflows or division by zero.
+pub(crate) fn fold_binary(op: &OpCode, lhs: &Value, rhs: &Value) -> Option<Value> {
+    if let (Value::IntLiteral(a), Value::IntLiteral(b)) = (lhs, rhs) {
+        let checked = match op {
+            OpCode::Add => Some(a.checked_add(*b)),
+            OpCode::Subtract => Some(a.checked_sub(*b)),
+            OpCode::Multiply => Some(a.checked_mul(*b)),
+            OpCode::Divide => Some(a.checked_div(*b)),
+            OpCode::Remainder => Some(a.checked_rem(*b)),
+            OpCode::LeftShift => Some(shift_left(*a, *b)),
+            OpCode::RightShift => Some(shift_right(*a, *b)),
+            _ => None,
+        };
+
+        if let Some(res) = checked {
+            return res.map(Value::IntLiteral);
+        }
+    }
+
+    let mut res = lhs.clone();
+    let folded = match op {
+        OpCode::Add => res.add(rhs),
+        OpCode::Subtract => res.sub(rhs),
+        OpCode::Multiply => res.mult(rhs),
+        OpCode::Divide => res.div(rhs),
+        OpCode::Remainder => res.modulo(rhs),
+        OpCode::BitwiseAnd => res.bitwise_and(rhs),
+        OpCode::BitwiseOr => res.bitwise_or(rhs),
+        OpCode::BitwiseXor => res.bitwise_xor(rhs),
+        OpCode::BitClear => res.bit_clear(rhs),
+        OpCode::Equal => return lhs.equal(rhs).ok(),
+        OpCode::NotEqual => {
+            let mut eq = lhs.equal(rhs).ok()?;
+            return eq.not().ok().map(|_| eq);
+        }
+        OpCode::Greater => return lhs.greater(rhs).ok(),
+        OpCode::GreaterEqual => return lhs.greater_equal(rhs).ok(),
+        OpCode::Less => return lhs.less(rhs).ok(),
+        OpCode::LessEqual => return lhs.less_equal(rhs).ok(),
+        _ => return None,
+    };
+
+    folded.ok().map(|_| res)
+}
+
+/// Constant left shift, `None` if the count is negative or bits would be shifted out
+fn shift_left(a: i128, count: i128) -> Option<i128> {
+    let count = u32::try_from(count)
+        .ok()
+        .filter(|count| *count < i128::BITS)?;
+    let shifted = a << count;
+    (shifted >> count == a).then_some(shifted)
+}
+
+/// Constant right shift, counts beyond the width shift all the bits out
+fn shift_right(a: i128, count: i128) -> Option<i128> {
+    let count = u32::try_from(count).ok()?;
+    Some(a >> count.min(i128::BITS - 1))
+}
+
+/// Evaluates a unary operation at compile time, see `fold_binary`
+pub(crate) fn fold_unary(op: &OpCode, val: &Value) -> Option<Value> {
+    if let (OpCode::Negate, Value::IntLiteral(a)) = (op, val) {
+        return a.checked_neg().map(Value::IntLiteral);
+    }
+
+    let mut res = val.clone();
+    let folded = match op {
+        OpCode::Negate => res.negate(),
+        OpCode::Not => res.not(),
+        OpCode::BitwiseNot => res.bitwise_complement(),
+        OpCode::PlusNoop => res.plus_noop(),
+        _ => return None,
+    };
+
+    folded.ok().map(|_| res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lex::lexeme::Pos;
+
+    fn chunk_of(codes: Vec<OpCode>, constants: Vec<Value>) -> Chunk {
+        let mut chunk = Chunk::new();
+        for c in constants {
+            chunk.add_constant(c);
+        }
+        for code in codes {
+            chunk.write(code, Pos(1, 1));
+        }
+
+        chunk
+    }
+
+    fn fold(chunk: &mut Chunk) {
+        while fold_constants(chunk) {}
+        chunk.prune_constants();
+    }
+
+    #[test]
+    fn test_fold_arithmetic() {
+        // 1 + 2 * 3
+        let mut chunk = chunk_of(
+            vec![
+                OpCode::Constant(0),
+                OpCode::Constant(1),
+                OpCode::Constant(2),
+                OpCode::Multiply,
+                OpCode::Add,
+                OpCode::Negate,
+            ],
+            vec![
+                Value::IntLiteral(1),
+                Value::IntLiteral(2),
+                Value::IntLiteral(3),
+            ],
+        );
+        fold(&mut chunk);
+
+        assert_eq!(chunk.codes().len(), 1);
+        assert_eq!(chunk.constants(), &[Value::IntLiteral(-7)]);
+    }
+
+    #[test]
+    fn test_no_fold() {
+        let faulty = vec![
+            (Value::IntLiteral(1), Value::IntLiteral(0), OpCode::Divide),
+            (
+                Value::IntLiteral(i128::MAX),
+                Value::IntLiteral(1),
+                OpCode::Add,
+            ),
+            (
+                Value::IntLiteral(1),
+                Value::IntLiteral(-1),
+                OpCode::LeftShift,
+            ),
+            (Value::IntLiteral(1), Value::String("a".into()), OpCode::Add),
+        ];
+
+        for (a, b, op) in faulty {
+            let mut chunk = chunk_of(
+                vec![OpCode::Constant(0), OpCode::Constant(1), op],
+                vec![a, b],
+            );
+            fold(&mut chunk);
+            assert_eq!(chunk.codes().len(), 3);
+        }
+
+        // the second operand is a jump target
+        let mut chunk = chunk_of(
+            vec![
+                OpCode::Jump(2),
+                OpCode::Constant(0),
+                OpCode::Constant(1),
+                OpCode::Add,
+            ],
+            vec![Value::IntLiteral(1), Value::IntLiteral(2)],
+        );
+        fold(&mut chunk);
+        assert_eq!(chunk.codes().len(), 4);
+    }
+
+    #[test]
+    fn test_collapse_conditions() {
+        // if true { 1 } else { 2 }
+        let mut chunk = chunk_of(
+            vec![
+                OpCode::Constant(0),
+                OpCode::IfFalseJump(6),
+                OpCode::Pop,
+                OpCode::Constant(1),
+                OpCode::Pop,
+                OpCode::Jump(9),
+                OpCode::Pop,
+                OpCode::Constant(2),
+                OpCode::Pop,
+            ],
+            vec![
+                Value::Bool(true),
+                Value::IntLiteral(1),
+                Value::IntLiteral(2),
+            ],
+        );
+        fold(&mut chunk);
+        assert!(matches!(chunk.codes()[0], OpCode::Constant(_)));
+        assert_eq!(chunk.codes().len(), 6);
+        assert_eq!(chunk.codes()[2].jump_target(), Some(6));
+
+        // if false {}
+        let mut chunk = chunk_of(
+            vec![
+                OpCode::Constant(0),
+                OpCode::IfFalseJump(4),
+                OpCode::Pop,
+                OpCode::Jump(5),
+                OpCode::Pop,
+            ],
+            vec![Value::Bool(false)],
+        );
+        fold(&mut chunk);
+        assert!(matches!(chunk.codes()[1], OpCode::Jump(4)));
+        assert!(chunk.verify(0).is_ok());
+    }
+
+    #[test]
+    fn test_eliminate_unreachable() {
+        // if false {}
+        let mut chunk = chunk_of(
+            vec![
+                OpCode::Constant(0),
+                OpCode::IfFalseJump(4),
+                OpCode::Pop,
+                OpCode::Jump(5),
+                OpCode::Pop,
+            ],
+            vec![Value::Bool(false)],
+        );
+        while fold_constants(&mut chunk) | eliminate_unreachable(&mut chunk) {}
+
+        assert!(matches!(chunk.codes(), [OpCode::Constant(0), OpCode::Pop]));
+
+        // code after return
+        let mut chunk = chunk_of(
+            vec![OpCode::Return(0), OpCode::Constant(0), OpCode::Pop],
+            vec![Value::Bool(false)],
+        );
+        assert!(eliminate_unreachable(&mut chunk));
+        assert!(matches!(chunk.codes(), [OpCode::Return(0)]));
+        assert!(!eliminate_unreachable(&mut chunk));
+    }
+
+    #[test]
+    fn test_eliminate_unused_functions() {
+        let src = r#"
+package main
+
+func transitive() int {
+    return 1
+}
+
+func used() int {
+    return transitive()
+}
+
+func recursive() {
+    recursive()
+}
+
+func unused() {
+    recursive()
+}
+
+var x = used()
+
+func main() {}
+"#;
+        let options = crate::CompileOptions { optimize: true };
+        let cunit =
+            crate::compile_with_options(src, &mut crate::ToStderrErrorHandler::default(), &options);
+
+        let mut globals: Vec<&str> = cunit
+            .chunk()
+            .codes()
+            .iter()
+            .filter_map(|c| match c {
+                OpCode::VarGlobal(name, _) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        globals.sort_unstable();
+
+        assert_eq!(globals, vec!["main", "transitive", "used", "x"]);
+    }
+
+    #[test]
+    fn test_rewrite_peephole() {
+        // x++; 1
+        let mut chunk = chunk_of(
+            vec![
+                OpCode::GetLocal(0),
+                OpCode::Constant(0),
+                OpCode::Add,
+                OpCode::SetLocal(0),
+                OpCode::Pop,
+                OpCode::Constant(0),
+                OpCode::Pop,
+            ],
+            vec![Value::IntLiteral(1)],
+        );
+        while rewrite_peephole(&mut chunk) {}
+
+        assert!(matches!(chunk.codes(), [OpCode::IncLocal(0, 0)]));
+
+        // the pop right after a store is skipped by the vm
+        let mut chunk = chunk_of(
+            vec![OpCode::SetLocal(0), OpCode::Constant(0), OpCode::Pop],
+            vec![Value::IntLiteral(1)],
+        );
+        assert!(!rewrite_peephole(&mut chunk));
+
+        // jump chains
+        let mut chunk = chunk_of(
+            vec![
+                OpCode::Jump(2),
+                OpCode::Noop,
+                OpCode::Jump(4),
+                OpCode::Noop,
+                OpCode::Jump(4),
+            ],
+            vec![],
+        );
+        assert!(rewrite_peephole(&mut chunk));
+        assert!(matches!(chunk.codes()[0], OpCode::Jump(4)));
+        assert!(!rewrite_peephole(&mut chunk));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::lex::lexeme::{Pos, Span};
+
+/// Source locations of the opcodes of a chunk.
+/// Consecutive opcodes are mostly emitted for the same lexeme,
+/// so a location is stored once for the whole run of opcodes sharing it.
+#[derive(Clone, Default)]
+pub(crate) struct PosTable {
+    runs: Vec<Run>,
+}
+
+#[derive(Clone, Copy)]
+struct Run {
+    /// Index of the opcode following the run
+    end: usize,
+    pos: Pos,
+    span: Span,
+}
+
+impl PosTable {
+    /// Table of runs of `(count, pos, span)`, as returned by `runs()`.
+    /// `None` if a run is empty or the runs hold more than `usize::MAX` opcodes.
+    pub(crate) fn from_runs(runs: impl IntoIterator<Item = (usize, Pos, Span)>) -> Option<Self> {
+        let mut table = Self::default();
+        for (count, pos, span) in runs {
+            if count == 0 {
+                return None;
+            }
+            let end = table.len().checked_add(count)?;
+            table.runs.push(Run { end, pos, span });
+        }
+
+        Some(table)
+    }
+
+    /// Number of opcodes the table holds locations for
+    pub(crate) fn len(&self) -> usize {
+        self.runs.last().map_or(0, |run| run.end)
+    }
+
+    pub(crate) fn push(&mut self, pos: Pos, span: Span) {
+        let end = self.len() + 1;
+        match self.runs.last_mut() {
+            Some(run) if run.pos == pos && run.span == span => run.end = end,
+            _ => self.runs.push(Run { end, pos, span }),
+        }
+    }
+
+    pub(crate) fn pop(&mut self) {
+        let start = self
+            .runs
+            .len()
+            .checked_sub(2)
+            .map_or(0, |i| self.runs[i].end);
+        if let Some(run) = self.runs.last_mut() {
+            run.end -= 1;
+            if run.end == start {
+                self.runs.pop();
+            }
+        }
+    }
+
+    /// Location of the opcode at `i`
+    pub(crate) fn get(&self, i: usize) -> (Pos, Span) {
+        let run = &self.runs[self.runs.partition_point(|run| run.end <= i)];
+        (run.pos, run.span)
+    }
+
+    /// Index of the first opcode compiled from the source line
+    pub(crate) fn first_at_line(&self, line: usize) -> Option<usize> {
+        let starts = std::iter::once(0).chain(self.runs.iter().map(|run| run.end));
+        self.runs
+            .iter()
+            .zip(starts)
+            .find(|(run, _)| run.pos.0 == line)
+            .map(|(_, start)| start)
+    }
+
+    /// Runs of `(count, pos, span)`, in the order of the opcodes
+    pub(crate) fn runs(&self) -> impl Iterator<Item = (usize, Pos, Span)> + '_ {
+        let starts = std::iter::once(0).chain(self.runs.iter().map(|run| run.end));
+        self.runs
+            .iter()
+            .zip(starts)
+            .map(|(run, start)| (run.end - start, run.pos, run.span))
+    }
+
+    /// Table of the locations of the opcodes at the given indices, in that order
+    pub(crate) fn select(&self, indices: impl Iterator<Item = usize>) -> Self {
+        let mut table = Self::default();
+        for i in indices {
+            let (pos, span) = self.get(i);
+            table.push(pos, span);
+        }
+
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pos_table() {
+        let span = |start| Span::new(start, start + 1);
+        let mut table = PosTable::default();
+        table.push(Pos(1, 1), span(0));
+        table.push(Pos(1, 1), span(0));
+        table.push(Pos(1, 3), span(2));
+        table.push(Pos(2, 1), span(4));
+        table.push(Pos(2, 1), span(4));
+
+        assert_eq!(table.len(), 5);
+        assert_eq!(table.runs().count(), 3);
+        assert!(table.get(1) == (Pos(1, 1), span(0)));
+        assert!(table.get(2) == (Pos(1, 3), span(2)));
+        assert!(table.get(4) == (Pos(2, 1), span(4)));
+        assert_eq!(table.first_at_line(1), Some(0));
+        assert_eq!(table.first_at_line(2), Some(3));
+        assert_eq!(table.first_at_line(3), None);
+
+        table.pop();
+        table.pop();
+        assert_eq!(table.len(), 3);
+        assert_eq!(table.runs().count(), 2);
+        table.push(Pos(1, 3), span(2));
+        assert_eq!(table.runs().count(), 2);
+
+        let selected = table.select([3, 0, 1].iter().copied());
+        assert!(selected.get(0) == (Pos(1, 3), span(2)));
+        assert!(selected.get(2) == (Pos(1, 1), span(0)));
+        assert_eq!(selected.runs().count(), 2);
+
+        let runs: Vec<_> = table.runs().collect();
+        let restored = PosTable::from_runs(runs).unwrap();
+        assert_eq!(restored.len(), 4);
+        assert!(restored.get(3) == (Pos(1, 3), span(2)));
+
+        assert!(PosTable::from_runs([(1, Pos(1, 1), span(0)), (0, Pos(1, 3), span(2))]).is_none());
+        assert!(
+            PosTable::from_runs([(1, Pos(1, 1), span(0)), (usize::MAX, Pos(1, 3), span(2))])
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use crate::compiler::{check, symbols};
+use crate::lex::lexeme::{Span, Token};
+use crate::lex::Lexer;
+
+/// Declaration an identifier refers to
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Symbol {
+    /// Local variable or constant, by the index of the lexeme declaring it
+    Local(usize),
+    /// Package-level variable, constant or function, by its name
+    Global(String),
+}
+
+/// Symbols the identifiers refer to, gathered by the compiler for `references()`
+#[derive(Default)]
+pub(crate) struct SymbolTable {
+    /// Symbols by the lexeme index of the identifier
+    pub(crate) uses: HashMap<usize, Symbol>,
+    /// Lexeme declaring each name the last time it was declared
+    pub(crate) declared_at: HashMap<String, usize>,
+}
+
+/// Replacement of a piece of source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Edit {
+    pub span: Span,
+    pub text: String,
+}
+
+/// Spans of the declaration and every use of the variable, constant or function
+/// whose name is at the byte offset, in source order.
+/// Empty if there is no such name at the offset
+pub fn references(src: &str, offset: usize) -> Vec<Span> {
+    let symbols = symbols(src);
+    let symbol = match symbols
+        .iter()
+        .find(|(span, _)| span.start <= offset && offset <= span.end)
+    {
+        Some((_, symbol)) => symbol,
+        None => return vec![],
+    };
+
+    symbols
+        .iter()
+        .filter(|(_, other)| other == symbol)
+        .map(|(span, _)| *span)
+        .collect()
+}
+
+/// Edits renaming the variable, constant or function whose name is at the byte offset.
+/// Fails if the new name is not an identifier, or if the renamed program would not compile
+/// the way the original one does, e.g. because of a conflicting declaration
+pub fn rename(src: &str, offset: usize, new_name: &str) -> Result<Vec<Edit>, String> {
+    if new_name == "_" || !is_identifier(new_name) {
+        return Err(format!("\"{}\" is not a valid name.", new_name));
+    }
+
+    let spans = references(src, offset);
+    if spans.is_empty() {
+        return Err("No variable, constant or function to rename at the position.".to_string());
+    }
+
+    let edits: Vec<Edit> = spans
+        .iter()
+        .map(|span| Edit {
+            span: *span,
+            text: new_name.to_string(),
+        })
+        .collect();
+
+    let renamed = apply(src, &edits);
+    let mut shift = 0isize;
+    let renamed_spans: Vec<Span> = spans
+        .iter()
+        .map(|span| {
+            let start = (span.start as isize + shift) as usize;
+            shift += new_name.len() as isize - (span.end - span.start) as isize;
+            Span::new(start, start + new_name.len())
+        })
+        .collect();
+
+    if references(&renamed, renamed_spans[0].start) != renamed_spans
+        || check(&renamed).len() > check(src).len()
+    {
+        return Err(format!(
+            "Renaming to \"{}\" conflicts with another declaration.",
+            new_name
+        ));
+    }
+
+    Ok(edits)
+}
+
+/// Source with the edits applied, they must not overlap and be in source order
+pub fn apply(src: &str, edits: &[Edit]) -> String {
+    let mut applied = String::with_capacity(src.len());
+    let mut last = 0;
+    for edit in edits {
+        applied.push_str(&src[last..edit.span.start]);
+        applied.push_str(&edit.text);
+        last = edit.span.end;
+    }
+    applied.push_str(&src[last..]);
+
+    applied
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut lexer = Lexer::new(name);
+    let (lexemes, errors) = lexer.lex();
+    errors.is_empty()
+        && lexemes[0].token == Token::Identifier
+        && lexemes[0].literal == name
+        && lexemes[1..]
+            .iter()
+            .all(|l| matches!(l.token, Token::Semicolon | Token::Eof))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SRC: &str = r#"package main
+
+var total = 0
+
+func add(x int) {
+    total = total + x
+    if x := 1; x > 0 {
+        total, x = x, 2
+    }
+    y := x
+    for i := range y {
+        add(i)
+    }
+}
+
+func main() {
+    x := 3
+    add(x)
+}
+"#;
+
+    /// Lines and columns of the spans, which are easier to follow than offsets
+    fn positions(spans: &[Span]) -> Vec<(usize, usize)> {
+        spans
+            .iter()
+            .map(|span| {
+                let line = SRC[..span.start].matches('\n').count() + 1;
+                let column = span.start - SRC[..span.start].rfind('\n').map_or(0, |i| i + 1) + 1;
+                (line, column)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_references() {
+        let at = |pattern: &str| SRC.find(pattern).unwrap();
+
+        let params = references(SRC, at("x int"));
+        assert_eq!(positions(&params), vec![(5, 10), (6, 21), (10, 10)]);
+        assert_eq!(references(SRC, at("= 0")), vec![]);
+
+        let shadowing = references(SRC, at("x := 1"));
+        assert_eq!(
+            positions(&shadowing),
+            vec![(7, 8), (7, 16), (8, 16), (8, 20)]
+        );
+
+        let globals = references(SRC, at("total"));
+        assert_eq!(positions(&globals), vec![(3, 5), (6, 5), (6, 13), (8, 9)]);
+
+        let funcs = references(SRC, at("add(x)"));
+        assert_eq!(positions(&funcs), vec![(5, 6), (12, 9), (18, 5)]);
+
+        assert_eq!(
+            positions(&references(SRC, at("i := "))),
+            vec![(11, 9), (12, 13)]
+        );
+        assert!(references(SRC, at("main")).is_empty());
+        assert!(references(SRC, at("range")).is_empty());
+    }
+
+    #[test]
+    fn test_rename() {
+        let at = |pattern: &str| SRC.find(pattern).unwrap();
+
+        let edits = rename(SRC, at("total"), "sum").unwrap();
+        assert_eq!(edits.len(), 4);
+        let renamed = apply(SRC, &edits);
+        assert!(renamed.contains("var sum = 0"));
+        assert!(renamed.contains("sum, x = x, 2"));
+        assert!(check(&renamed).is_empty());
+
+        let renamed = apply(SRC, &rename(SRC, at("y := x"), "count").unwrap());
+        assert!(renamed.contains("count := x\n    for i := range count {"));
+
+        // `y` would be declared twice, `total` would refer to the parameter
+        assert!(rename(SRC, at("x int"), "y").is_err());
+        assert!(rename(SRC, at("x int"), "total").is_err());
+        assert!(rename(SRC, at("x int"), "for").is_err());
+        assert!(rename(SRC, at("x int"), "a b").is_err());
+        assert!(rename(SRC, at("x int"), "_").is_err());
+        assert!(rename(SRC, at("func"), "f").is_err());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::DefinitionError;
+use crate::{ValType, Value};
 
 /// Scope counter and resolver
 /// Tracks the depth of a scope we are in
@@ -8,6 +9,9 @@ use crate::error::DefinitionError;
 pub(super) struct Scope {
     pub(super) vars: Vec<Local>,
     pub(super) depth: usize,
+    /// Most variables declared at once since the count was last taken,
+    /// i.e. the number of stack slots a frame needs for its locals
+    pub(super) slots: usize,
 }
 
 type ScopeDefinitionResult<T> = Result<T, DefinitionError>;
@@ -17,15 +21,18 @@ impl Scope {
         Self {
             vars: Vec::new(),
             depth: 0,
+            slots: 0,
         }
     }
 
     pub(super) fn add_var(&mut self, name: String) {
         self.vars.push(Local::new_var(name));
+        self.slots = self.slots.max(self.vars.len());
     }
 
     pub(super) fn add_const(&mut self, name: String) {
         self.vars.push(Local::new_const(name));
+        self.slots = self.slots.max(self.vars.len());
     }
 
     pub(super) fn has_defined(&self, name: &str) -> bool {
@@ -65,12 +72,22 @@ impl Scope {
     }
 
     pub(super) fn init_last(&mut self) {
-        for var in self.vars.iter_mut().rev() {
-            if !var.is_initialised() {
-                var.depth = self.depth as isize;
-                break;
-            }
-        }
+        self.init_last_as(None);
+    }
+
+    /// Initialises the last declared variable along with its declared type, if any,
+    /// returns its index
+    pub(super) fn init_last_as(&mut self, vtype: Option<ValType>) -> Option<usize> {
+        let (i, var) = self
+            .vars
+            .iter_mut()
+            .enumerate()
+            .rev()
+            .find(|(_, var)| !var.is_initialised())?;
+        var.depth = self.depth as isize;
+        var.vtype = vtype;
+
+        Some(i)
     }
 }
 
@@ -79,6 +96,14 @@ pub(super) struct Local {
     pub(super) name: String,
     pub(super) mutable: bool,
     pub(super) depth: isize,
+    /// Declared type, reported to editor queries
+    pub(super) vtype: Option<ValType>,
+    /// Lexeme declaring the name, known to editor queries only
+    pub(super) decl: Option<usize>,
+    /// Index of the debug info of the variable in the function, once initialised
+    pub(super) debug: Option<usize>,
+    /// Value of a constant, if the compiler could evaluate it
+    pub(super) value: Option<Value>,
 }
 
 impl Local {
@@ -97,10 +122,14 @@ impl Local {
             name,
             depth: Local::UNINITIALISED,
             mutable,
+            vtype: None,
+            decl: None,
+            debug: None,
+            value: None,
         }
     }
 
-    fn is_initialised(&self) -> bool {
+    pub(super) fn is_initialised(&self) -> bool {
         self.depth != Self::UNINITIALISED
     }
 }
@@ -142,7 +171,7 @@ mod tests {
         scope.init_last();
         scope.add_const("c".to_string());
         scope.depth += 1;
-        scope.init_last();
+        scope.init_last_as(Some(ValType::Int));
 
         assert_eq!(
             scope.vars,
@@ -150,17 +179,29 @@ mod tests {
                 Local {
                     name: "a".to_string(),
                     depth: -1,
-                    mutable: true
+                    mutable: true,
+                    vtype: None,
+                    decl: None,
+                    debug: None,
+                    value: None,
                 },
                 Local {
                     name: "b".to_string(),
                     depth: 1,
-                    mutable: true
+                    mutable: true,
+                    vtype: None,
+                    decl: None,
+                    debug: None,
+                    value: None,
                 },
                 Local {
                     name: "c".to_string(),
                     depth: 2,
-                    mutable: false
+                    mutable: false,
+                    vtype: Some(ValType::Int),
+                    decl: None,
+                    debug: None,
+                    value: None,
                 },
             ]
         );
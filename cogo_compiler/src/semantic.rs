@@ -0,0 +1,44 @@
+use crate::lex::lexeme::{Pos, Span, Token};
+
+/// Class of a piece of source, for semantic highlighting
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TokenClass {
+    Keyword,
+    Type,
+    Function,
+    Variable,
+    Constant,
+    /// Name of an imported package
+    Package,
+    String,
+    Number,
+    Comment,
+}
+
+/// Classified lexeme or comment
+#[derive(Debug, Clone, PartialEq)]
+pub struct SemanticToken {
+    pub class: TokenClass,
+    pub pos: Pos,
+    pub span: Span,
+}
+
+/// Class following from the token alone, identifiers are classified by the compiler
+pub(crate) fn token_class(token: Token) -> Option<TokenClass> {
+    use Token::*;
+    let class = match token {
+        Break | Case | Chan | Const | Continue | Default | Defer | Else | Fallthrough | For
+        | Func | Go | Goto | If | Import | Interface | Map | Package | Range | Return | Select
+        | Struct | Switch | Type | Var => TokenClass::Keyword,
+        Bool | Int8 | Int16 | Int32 | Rune | Int64 | Int | Uint8 | Byte | Uint16 | Uint32
+        | Uint64 | Uint | Uintptr | Float32 | Float64 | Complex64 | Complex128 | String => {
+            TokenClass::Type
+        }
+        Nil | True | False => TokenClass::Constant,
+        StringLiteral | RawStringLiteral => TokenClass::String,
+        IntLiteral | FloatLiteral | ImagLiteral => TokenClass::Number,
+        _ => return None,
+    };
+
+    Some(class)
+}
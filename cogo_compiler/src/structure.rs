@@ -1,7 +1,7 @@
 use crate::FuncUnit;
 
 #[derive(Debug, Clone, PartialEq)]
-pub(crate) struct Package(pub String);
+pub struct Package(pub String);
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Function(pub String);
@@ -28,7 +28,7 @@ impl EntryPoint {
     /// Checks that if the function is an entry point in a given package,
     /// it does not violate the required signature.
     pub(super) fn check(&self, pack: &Package, funit: &FuncUnit) -> SignValidationResult<()> {
-        if *pack == self.package && *funit.function() == self.function {
+        if self.is_entry_point(pack, funit) {
             if Self::validate_signature(funit) {
                 Ok(())
             } else {
@@ -42,6 +42,39 @@ impl EntryPoint {
         }
     }
 
+    pub(super) fn is_entry_point(&self, pack: &Package, funit: &FuncUnit) -> bool {
+        *pack == self.package && *funit.function() == self.function
+    }
+
+    /// Checks that a package holding the entry point declares it
+    pub(super) fn check_declared(
+        &self,
+        pack: &Package,
+        declared: bool,
+    ) -> SignValidationResult<()> {
+        if *pack == self.package && !declared {
+            Err(SignatureError(format!(
+                "Function \"{}\" is undeclared in package \"{}\"",
+                self.function.0, self.package.0,
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Checks that a package to be run is the one holding the entry point
+    pub(super) fn check_package(&self, pack: &Package) -> SignValidationResult<()> {
+        if *pack == self.package {
+            Ok(())
+        } else {
+            Err(SignatureError(format!(
+                "Package \"{}\" cannot be run, programs start at function \"{}\" of package \"{}\". \
+                Rename the package to \"{}\" to run it",
+                pack.0, self.function.0, self.package.0, self.package.0,
+            )))
+        }
+    }
+
     fn validate_signature(funit: &FuncUnit) -> bool {
         funit.ret_type().is_void() && funit.argc() == 0
     }
@@ -75,6 +108,21 @@ mod tests {
         assert!(ep.check(&package_a, &funit_c).is_err());
     }
 
+    #[test]
+    fn test_entry_point_declared() {
+        let ep = EntryPoint::new(Package("test".to_string()), Function("test".to_string()));
+
+        assert!(ep
+            .check_declared(&Package("test".to_string()), true)
+            .is_ok());
+        assert!(ep
+            .check_declared(&Package("test".to_string()), false)
+            .is_err());
+        assert!(ep
+            .check_declared(&Package("another".to_string()), false)
+            .is_ok());
+    }
+
     fn create_funit(fname: String) -> FuncUnit {
         FuncUnit::new(
             Some(Function(fname)),
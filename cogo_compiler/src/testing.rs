@@ -0,0 +1,38 @@
+//! Helpers shared by the tests of the compiler and of the crates built on it
+
+use crate::{compile, CompilationUnit, CompilerError, ErrorHandler};
+
+/// Handler keeping the compile errors instead of reporting them
+#[derive(Default)]
+pub struct CollectingErrorHandler(Vec<CompilerError>);
+
+impl CollectingErrorHandler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn errors(&self) -> &[CompilerError] {
+        &self.0
+    }
+
+    pub fn into_errors(self) -> Vec<CompilerError> {
+        self.0
+    }
+}
+
+impl ErrorHandler for CollectingErrorHandler {
+    fn on_error(&mut self, errs: &[CompilerError]) {
+        self.0.extend_from_slice(errs);
+    }
+}
+
+/// Compiles the source, failing the test on the first compile error
+pub fn compile_valid(src: &str) -> CompilationUnit {
+    let mut handler = CollectingErrorHandler::new();
+    let cunit = compile(src, &mut handler);
+    if let Some(err) = handler.errors().first() {
+        panic!("{}", err);
+    }
+
+    cunit
+}
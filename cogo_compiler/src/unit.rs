@@ -1,3 +1,5 @@
+use crate::error::VerificationError;
+use crate::lex::lexeme::Pos;
 use crate::opcode::Chunk;
 use crate::structure::{Function as FunctionItem, Package as PackageItem};
 use crate::vtype::{CompositeType, FuncType};
@@ -34,6 +36,15 @@ impl CompilationUnit {
             Self::Function(f) => &mut f.codes,
         }
     }
+
+    /// Verifies the chunk against the frame the unit is executed in,
+    /// a package has no locals
+    pub fn verify(&self) -> Result<(), VerificationError> {
+        match &self {
+            Self::Package(p) => p.codes.verify(0),
+            Self::Function(f) => f.codes.verify(f.slots),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -50,6 +61,14 @@ impl PackageUnit {
         }
     }
 
+    pub(crate) fn from_codes(package: PackageItem, codes: Chunk) -> Self {
+        Self { package, codes }
+    }
+
+    pub fn package(&self) -> &PackageItem {
+        &self.package
+    }
+
     pub(super) fn set_package(&mut self, package: PackageItem) {
         self.package = package;
     }
@@ -66,6 +85,29 @@ pub struct FuncUnit {
     ftype: FuncType,
     function: FunctionItem,
     codes: Chunk,
+    locals: Vec<LocalVar>,
+    slots: usize,
+}
+
+/// Variable of a function along with the stack slot it takes, for debuggers to name the values
+/// of a frame. Parameters take the first slots
+#[derive(Clone, Debug, PartialEq)]
+pub struct LocalVar {
+    pub name: String,
+    /// Index of the value from the start of the frame
+    pub slot: usize,
+    /// Position of the source the variable is declared at
+    pub start: Pos,
+    /// Position of the source the scope of the variable ends at
+    pub end: Pos,
+}
+
+impl LocalVar {
+    /// Whether the variable is declared and still in scope at the position,
+    /// e.g. the one of the opcode a frame is paused at
+    pub fn in_scope_at(&self, pos: Pos) -> bool {
+        (self.start.0, self.start.1) <= (pos.0, pos.1) && (pos.0, pos.1) < (self.end.0, self.end.1)
+    }
 }
 
 impl FuncUnit {
@@ -75,14 +117,24 @@ impl FuncUnit {
         Self::from_codes(name, ftype, Chunk::new())
     }
 
-    fn from_codes(name: Option<FunctionItem>, ftype: FuncType, codes: Chunk) -> Self {
+    pub(crate) fn from_codes(name: Option<FunctionItem>, ftype: FuncType, codes: Chunk) -> Self {
         Self {
             ftype,
             function: name.unwrap_or_else(|| FunctionItem("".to_string())),
             codes,
+            locals: Vec::new(),
+            slots: 0,
         }
     }
 
+    pub(super) fn set_locals(&mut self, locals: Vec<LocalVar>) {
+        self.locals = locals;
+    }
+
+    pub(super) fn set_slots(&mut self, slots: usize) {
+        self.slots = slots;
+    }
+
     pub fn ret_type(&self) -> &CompositeType {
         self.ftype.ret_type()
     }
@@ -98,4 +150,26 @@ impl FuncUnit {
     pub fn function(&self) -> &FunctionItem {
         &self.function
     }
+
+    pub fn func_type(&self) -> &FuncType {
+        &self.ftype
+    }
+
+    /// Number of stack slots the locals of a frame take, parameters included
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Variables of the function in the order of their declarations
+    pub fn locals(&self) -> &[LocalVar] {
+        &self.locals
+    }
+
+    /// Variables in scope at the position, the innermost last.
+    /// A name shadowing another one in an outer block is listed after it
+    pub fn locals_at(&self, pos: Pos) -> impl Iterator<Item = &LocalVar> {
+        self.locals
+            .iter()
+            .filter(move |local| local.in_scope_at(pos))
+    }
 }
@@ -1,8 +1,10 @@
 use std::cell::RefCell;
+use std::convert::TryFrom;
 use std::fmt::{Display, Formatter};
 use std::mem;
 use std::rc::Rc;
 
+use crate::vtype::FuncType;
 use crate::{TypeError, ValType};
 
 #[allow(dead_code)]
@@ -29,34 +31,218 @@ pub enum Value {
     Complex64(f32, f32),
     Complex128(f64, f64),
 
-    String(String),
-    Func(String),
+    /// Strings are immutable, copies share the contents
+    String(Rc<str>),
+    Func(String, Box<FuncType>),
     FuncBuiltin(String),
 
     Array(RefIterator, usize, ValType),
-    Slice(RefIterator, ValType),
+    Slice(SliceRef, ValType),
     Nil,
 
     // Service values
-    IntLiteral(isize),
+    IntLiteral(i128),
     FloatLiteral(f64),
 }
 
 pub type RefIterator = Rc<RefCell<Vec<Value>>>;
 
+/// Allocates the backing arrays of arrays and slices, every one of them is made through it.
+/// The VM allocates on its heap, which keeps track of the values in use
+pub trait Allocator {
+    fn alloc(&mut self, vals: Vec<Value>) -> RefIterator;
+}
+
+/// Allocator with no bookkeeping, for the values made outside of a VM, e.g. by the decoder
+pub struct Unmanaged;
+
+impl Allocator for Unmanaged {
+    fn alloc(&mut self, vals: Vec<Value>) -> RefIterator {
+        Rc::new(RefCell::new(vals))
+    }
+}
+
+/// Slice is a window of `len` elements into a backing array starting at `offset`,
+/// slices taken from one another or from an array share the backing array
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceRef {
+    array: RefIterator,
+    offset: usize,
+    len: usize,
+    cap: usize,
+}
+
+impl SliceRef {
+    /// Slice owning a new backing array of the values
+    pub fn new(vals: Vec<Value>, heap: &mut dyn Allocator) -> Self {
+        let len = vals.len();
+        Self {
+            array: heap.alloc(vals),
+            offset: 0,
+            len,
+            cap: len,
+        }
+    }
+
+    /// Slice `array[low:high:max]` of an array, bounds must have been checked
+    pub fn of_array(array: &RefIterator, low: usize, high: usize, max: usize) -> Self {
+        Self {
+            array: Rc::clone(array),
+            offset: low,
+            len: high - low,
+            cap: max - low,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn cap(&self) -> usize {
+        self.cap
+    }
+
+    pub fn get(&self, index: usize) -> Option<Value> {
+        if index < self.len {
+            Some(self.array.borrow()[self.offset + index].clone())
+        } else {
+            None
+        }
+    }
+
+    /// Writes the value to the backing array, so that every slice sharing it sees the change.
+    /// Returns `false` if the index is out of range
+    pub fn set(&self, index: usize, value: Value, heap: &mut dyn Allocator) -> bool {
+        if index < self.len {
+            self.array.borrow_mut()[self.offset + index].store(value, heap);
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Slice `s[low:high:max]` sharing the backing array, bounds must have been checked
+    pub fn reslice(&self, low: usize, high: usize, max: usize) -> Self {
+        Self {
+            array: Rc::clone(&self.array),
+            offset: self.offset + low,
+            len: high - low,
+            cap: max - low,
+        }
+    }
+
+    /// Copies of the elements within the length of the slice
+    pub fn to_vec(&self) -> Vec<Value> {
+        self.array.borrow()[self.offset..self.offset + self.len].to_vec()
+    }
+
+    /// Slice with the values appended. They are written to the backing array while its capacity
+    /// allows, otherwise a new one is allocated, with the elements past the length set to `zero`
+    pub fn append(&self, vals: Vec<Value>, zero: &Value, heap: &mut dyn Allocator) -> Self {
+        let len = self.len + vals.len();
+        if len <= self.cap {
+            let start = self.offset + self.len;
+            self.array
+                .borrow_mut()
+                .splice(start..start + vals.len(), vals);
+
+            return Self {
+                len,
+                ..self.clone()
+            };
+        }
+
+        let cap = grow_cap(self.cap, len);
+        let mut array = Vec::with_capacity(cap);
+        array.extend(self.to_vec());
+        array.extend(vals);
+        array.resize(cap, zero.clone());
+
+        Self {
+            array: heap.alloc(array),
+            offset: 0,
+            len,
+            cap,
+        }
+    }
+}
+
+/// Capacity of a slice grown by `append` to fit `len` elements, the way the Go runtime grows it:
+/// small slices double, large ones grow by about 1.25x.
+/// Rounding up to the size classes of the allocator is left out
+fn grow_cap(cap: usize, len: usize) -> usize {
+    const THRESHOLD: usize = 256;
+
+    if len > cap * 2 {
+        return len;
+    }
+    if cap < THRESHOLD {
+        return cap * 2;
+    }
+
+    let mut new_cap = cap;
+    while new_cap < len {
+        new_cap += (new_cap + 3 * THRESHOLD) / 4;
+    }
+
+    new_cap
+}
+
 type OperationResult<T> = Result<T, TypeError>;
 
+/// Shifts the integer in place the way Go does: the bits shifted out are lost,
+/// counts beyond the width of the type leave zero, or all ones for negative signed values
+macro_rules! shift_left {
+    ($lhs:ident, $count:ident) => {
+        *$lhs = $lhs.checked_shl(shift_count(*$count as i128)?).unwrap_or(0)
+    };
+}
+
+macro_rules! shift_right_signed {
+    ($lhs:ident, $count:ident) => {
+        *$lhs = $lhs
+            .checked_shr(shift_count(*$count as i128)?)
+            .unwrap_or(if *$lhs < 0 { -1 } else { 0 })
+    };
+}
+
+macro_rules! shift_right_unsigned {
+    ($lhs:ident, $count:ident) => {
+        *$lhs = $lhs.checked_shr(shift_count(*$count as i128)?).unwrap_or(0)
+    };
+}
+
+/// Shift counts must not be negative, the ones too large for `u32` shift everything out
+fn shift_count(count: i128) -> OperationResult<u32> {
+    if count < 0 {
+        return Err(TypeError(format!("Negative shift amount {}", count)));
+    }
+
+    Ok(u32::try_from(count).unwrap_or(u32::MAX))
+}
+
 impl Value {
-    pub fn new_array(vals: Vec<Self>, size: usize, vtype: ValType) -> Self {
-        Self::Array(Rc::new(RefCell::new(vals)), size, vtype)
+    pub fn new_array(
+        vals: Vec<Self>,
+        size: usize,
+        vtype: ValType,
+        heap: &mut dyn Allocator,
+    ) -> Self {
+        Self::Array(heap.alloc(vals), size, vtype)
     }
 
-    pub fn new_slice(vals: Vec<Self>, vtype: ValType) -> Self {
-        Self::Slice(Rc::new(RefCell::new(vals)), vtype)
+    pub fn new_slice(vals: Vec<Self>, vtype: ValType, heap: &mut dyn Allocator) -> Self {
+        Self::Slice(SliceRef::new(vals, heap), vtype)
     }
 
-    pub fn default(vtype: &ValType) -> Self {
-        match vtype {
+    /// Zero value of the type. Only `nil` and names of undeclared types have none,
+    /// the compiler reports the latter
+    pub fn default(vtype: &ValType, heap: &mut dyn Allocator) -> OperationResult<Self> {
+        let value = match vtype {
             ValType::Bool => Self::Bool(false),
             ValType::Int8 => Self::Int8(0),
             ValType::Int16 => Self::Int16(0),
@@ -73,31 +259,58 @@ impl Value {
             ValType::Float64 => Self::Float64(0_f64),
             ValType::Complex64 => Self::Complex64(0_f32, 0_f32),
             ValType::Complex128 => Self::Complex128(0_f64, 0_f64),
-            ValType::String => Self::String(String::from("")),
+            ValType::String => Self::String("".into()),
             ValType::Array(vtype, size) => {
                 let mut vals = vec![];
                 for _ in 0..*size {
-                    vals.push(Self::default(vtype));
+                    vals.push(Self::default(vtype, heap)?);
                 }
 
-                Self::new_array(vals, *size, ValType::Array(Box::new(*vtype.clone()), *size))
+                let vtype = ValType::Array(Box::new(*vtype.clone()), *size);
+                Self::new_array(vals, *size, vtype, heap)
             }
             ValType::Slice(vtype) => {
-                Self::new_slice(vec![], ValType::Slice(Box::new(*vtype.clone())))
+                Self::new_slice(vec![], ValType::Slice(Box::new(*vtype.clone())), heap)
+            }
+            // nil function, it has no name to be called by
+            ValType::Func(ftype) => Self::Func(String::new(), ftype.clone()),
+            ValType::Nil | ValType::Struct(_) => {
+                return Err(TypeError(format!(
+                    "Cannot construct default value for type \"{}\"",
+                    vtype
+                )))
             }
-            _ => panic!("Cannot construct default value for type {}", vtype),
+        };
+
+        Ok(value)
+    }
+
+    /// Arrays are values in Go, an array gets copied along with the arrays nested in it
+    /// whenever it is stored. Slices keep sharing their backing arrays
+    pub fn copy_if_soft_reference(&mut self, heap: &mut dyn Allocator) {
+        if let Self::Array(vals, ..) = self {
+            let mut copy = vals.borrow().clone();
+            copy.iter_mut()
+                .for_each(|val| val.copy_if_soft_reference(heap));
+            *vals = heap.alloc(copy);
         }
     }
 
-    #[allow(clippy::single_match)]
-    pub fn copy_if_soft_reference(&mut self) {
-        match self {
-            Self::Array(vals, size, vtype) => {
-                let vals = vals.as_ref().borrow().clone();
-                *self = Self::Array(Rc::new(RefCell::new(vals)), *size, vtype.clone());
+    /// Stores the value in place of this one. An array is copied into the storage
+    /// of the array it replaces, so that the slices taken from it see the change
+    pub fn store(&mut self, mut value: Self, heap: &mut dyn Allocator) {
+        if let (Self::Array(target, ..), Self::Array(src, ..)) = (&*self, &value) {
+            if !Rc::ptr_eq(target, src) {
+                let src = src.borrow().clone();
+                for (elem, val) in target.borrow_mut().iter_mut().zip(src) {
+                    elem.store(val, heap);
+                }
             }
-            _ => {}
-        };
+            return;
+        }
+
+        value.copy_if_soft_reference(heap);
+        *self = value;
     }
 
     pub fn to_usize(&self) -> Option<usize> {
@@ -107,8 +320,8 @@ impl Value {
             Self::Int8(v) if *v >= 0 => Some(*v as usize),
             Self::Int32(v) if *v >= 0 => Some(*v as usize),
             Self::Int64(v) if *v >= 0 => Some(*v as usize),
-            Self::Uintptr(v) => Some(*v as usize),
-            Self::Uint(v) => Some(*v as usize),
+            Self::Uintptr(v) => Some(*v),
+            Self::Uint(v) => Some(*v),
             Self::Uint8(v) => Some(*v as usize),
             Self::Uint32(v) => Some(*v as usize),
             Self::Uint64(v) => Some(*v as usize),
@@ -116,9 +329,15 @@ impl Value {
         }
     }
 
+    /// Gives an untyped constant its default type.
+    /// Integers `int` cannot hold stay untyped, they only appear in constant expressions
     pub fn lose_literal_blindly(&mut self) {
         match self {
-            Self::IntLiteral(v) => *self = Self::Int(*v),
+            Self::IntLiteral(v) => {
+                if let Ok(v) = isize::try_from(*v) {
+                    *self = Self::Int(v);
+                }
+            }
             Self::FloatLiteral(v) => *self = Self::Float64(*v),
             _ => {}
         }
@@ -128,7 +347,7 @@ impl Value {
         match self {
             Self::IntLiteral(v) => {
                 *self = match vtype {
-                    ValType::Int => Self::Int(*v),
+                    ValType::Int => Self::Int(*v as isize),
                     ValType::Int8 => Self::Int8(*v as i8),
                     ValType::Int16 => Self::Int16(*v as i16),
                     ValType::Int32 => Self::Int32(*v as i32),
@@ -141,6 +360,8 @@ impl Value {
                     ValType::Uintptr => Self::Uintptr(*v as usize),
                     ValType::Float32 => Self::Float32(*v as f32),
                     ValType::Float64 => Self::Float64(*v as f64),
+                    ValType::Complex64 => Self::Complex64(*v as f32, 0_f32),
+                    ValType::Complex128 => Self::Complex128(*v as f64, 0_f64),
                     _ => return,
                 }
             }
@@ -148,6 +369,11 @@ impl Value {
                 *self = match vtype {
                     ValType::Float32 => Self::Float32(*v as f32),
                     ValType::Float64 => Self::Float64(*v),
+                    ValType::Complex64 => Self::Complex64(*v as f32, 0_f32),
+                    ValType::Complex128 => Self::Complex128(*v, 0_f64),
+                    vtype if vtype.accepts_untyped_float(v.fract() == 0.0) => {
+                        Self::IntLiteral(*v as i128).cast_to(vtype.clone())
+                    }
                     _ => return,
                 }
             }
@@ -185,7 +411,7 @@ impl Value {
             Self::Int16(v) => cast_number!(v, i16),
             Self::Int32(v) => cast_number!(v, i32),
             Self::Int64(v) => cast_number!(v, i64),
-            Self::IntLiteral(v) => cast_number!(v, isize),
+            Self::IntLiteral(v) => cast_number!(v, i128),
             Self::Int(v) => cast_number!(v, isize),
             Self::Uint8(v) => cast_number!(v, u8),
             Self::Uint16(v) => cast_number!(v, u16),
@@ -200,6 +426,77 @@ impl Value {
         }
     }
 
+    /// Conversions between strings and byte or rune slices, and of integers to strings.
+    /// Strings are always valid UTF-8, invalid bytes and runes become U+FFFD.
+    /// Returns `None` if the value cannot be converted to the type this way
+    pub fn convert_string(&self, vtype: &ValType, heap: &mut dyn Allocator) -> Option<Self> {
+        let rune = |r: i64| {
+            u32::try_from(r)
+                .ok()
+                .and_then(char::from_u32)
+                .unwrap_or(char::REPLACEMENT_CHARACTER)
+        };
+
+        let converted = match (self, vtype) {
+            (Self::String(s), ValType::String) => Self::String(s.clone()),
+            (Self::String(s), ValType::Slice(elem)) => match **elem {
+                ValType::Uint8 => {
+                    Self::new_slice(s.bytes().map(Self::Uint8).collect(), vtype.clone(), heap)
+                }
+                ValType::Int32 => {
+                    let runes = s.chars().map(|c| Self::Int32(c as i32)).collect();
+                    Self::new_slice(runes, vtype.clone(), heap)
+                }
+                _ => return None,
+            },
+            (Self::Slice(slice, ValType::Slice(elem)), ValType::String) => match **elem {
+                ValType::Uint8 => {
+                    let bytes: Vec<u8> = slice
+                        .to_vec()
+                        .iter()
+                        .filter_map(|b| match b {
+                            Self::Uint8(b) => Some(*b),
+                            _ => None,
+                        })
+                        .collect();
+                    Self::String(String::from_utf8_lossy(&bytes).into())
+                }
+                ValType::Int32 => Self::String(
+                    slice
+                        .to_vec()
+                        .iter()
+                        .filter_map(|r| match r {
+                            Self::Int32(r) => Some(rune(*r as i64)),
+                            _ => None,
+                        })
+                        .collect::<String>()
+                        .into(),
+                ),
+                _ => return None,
+            },
+            (v, ValType::String) => {
+                let r = match *v {
+                    Self::Int8(v) => v as i64,
+                    Self::Int16(v) => v as i64,
+                    Self::Int32(v) => v as i64,
+                    Self::Int64(v) => v,
+                    Self::Int(v) => v as i64,
+                    Self::IntLiteral(v) => v as i64,
+                    Self::Uint8(v) => v as i64,
+                    Self::Uint16(v) => v as i64,
+                    Self::Uint32(v) => v as i64,
+                    Self::Uint64(v) => i64::try_from(v).unwrap_or(-1),
+                    Self::Uint(v) | Self::Uintptr(v) => i64::try_from(v).unwrap_or(-1),
+                    _ => return None,
+                };
+                Self::String(rune(r).to_string().into())
+            }
+            _ => return None,
+        };
+
+        Some(converted)
+    }
+
     fn is_literal(&self) -> bool {
         matches!(self, Self::IntLiteral(_) | Self::FloatLiteral(_))
     }
@@ -281,7 +578,7 @@ impl Value {
             (Int16(lhs), IntLiteral(rhs)) => *lhs += *rhs as i16,
             (Int32(lhs), IntLiteral(rhs)) => *lhs += *rhs as i32,
             (Int64(lhs), IntLiteral(rhs)) => *lhs += *rhs as i64,
-            (Int(lhs), IntLiteral(rhs)) => *lhs += rhs,
+            (Int(lhs), IntLiteral(rhs)) => *lhs += *rhs as isize,
             (Uint8(lhs), IntLiteral(rhs)) => *lhs += *rhs as u8,
             (Uint16(lhs), IntLiteral(rhs)) => *lhs += *rhs as u16,
             (Uint32(lhs), IntLiteral(rhs)) => *lhs += *rhs as u32,
@@ -319,7 +616,7 @@ impl Value {
                 *lhs_i += *rhs_i;
             }
             (String(lhs), String(rhs)) => {
-                *lhs = format!("{}{}", lhs, rhs);
+                *lhs = format!("{}{}", lhs, rhs).into();
             }
             (lhs, rhs) => {
                 return Err(TypeError::expected_same_type_operands(
@@ -344,7 +641,7 @@ impl Value {
             (Int16(lhs), IntLiteral(rhs)) => *lhs -= *rhs as i16,
             (Int32(lhs), IntLiteral(rhs)) => *lhs -= *rhs as i32,
             (Int64(lhs), IntLiteral(rhs)) => *lhs -= *rhs as i64,
-            (Int(lhs), IntLiteral(rhs)) => *lhs -= rhs,
+            (Int(lhs), IntLiteral(rhs)) => *lhs -= *rhs as isize,
             (Uint8(lhs), IntLiteral(rhs)) => *lhs = u8::wrapping_sub(*lhs, *rhs as u8),
             (Uint16(lhs), IntLiteral(rhs)) => *lhs = u16::wrapping_sub(*lhs, *rhs as u16),
             (Uint32(lhs), IntLiteral(rhs)) => *lhs = u32::wrapping_sub(*lhs, *rhs as u32),
@@ -404,7 +701,7 @@ impl Value {
             (Int16(lhs), IntLiteral(rhs)) => *lhs *= *rhs as i16,
             (Int32(lhs), IntLiteral(rhs)) => *lhs *= *rhs as i32,
             (Int64(lhs), IntLiteral(rhs)) => *lhs *= *rhs as i64,
-            (Int(lhs), IntLiteral(rhs)) => *lhs *= rhs,
+            (Int(lhs), IntLiteral(rhs)) => *lhs *= *rhs as isize,
             (Uint8(lhs), IntLiteral(rhs)) => *lhs *= *rhs as u8,
             (Uint16(lhs), IntLiteral(rhs)) => *lhs *= *rhs as u16,
             (Uint32(lhs), IntLiteral(rhs)) => *lhs *= *rhs as u32,
@@ -464,7 +761,7 @@ impl Value {
             (Int16(lhs), IntLiteral(rhs)) => *lhs /= *rhs as i16,
             (Int32(lhs), IntLiteral(rhs)) => *lhs /= *rhs as i32,
             (Int64(lhs), IntLiteral(rhs)) => *lhs /= *rhs as i64,
-            (Int(lhs), IntLiteral(rhs)) => *lhs /= rhs,
+            (Int(lhs), IntLiteral(rhs)) => *lhs /= *rhs as isize,
             (Uint8(lhs), IntLiteral(rhs)) => *lhs /= *rhs as u8,
             (Uint16(lhs), IntLiteral(rhs)) => *lhs /= *rhs as u16,
             (Uint32(lhs), IntLiteral(rhs)) => *lhs /= *rhs as u32,
@@ -524,7 +821,7 @@ impl Value {
             (Int16(lhs), IntLiteral(rhs)) => *lhs %= *rhs as i16,
             (Int32(lhs), IntLiteral(rhs)) => *lhs %= *rhs as i32,
             (Int64(lhs), IntLiteral(rhs)) => *lhs %= *rhs as i64,
-            (Int(lhs), IntLiteral(rhs)) => *lhs %= rhs,
+            (Int(lhs), IntLiteral(rhs)) => *lhs %= *rhs as isize,
             (Uint8(lhs), IntLiteral(rhs)) => *lhs %= *rhs as u8,
             (Uint16(lhs), IntLiteral(rhs)) => *lhs %= *rhs as u16,
             (Uint32(lhs), IntLiteral(rhs)) => *lhs %= *rhs as u32,
@@ -584,7 +881,7 @@ impl Value {
             (Int16(lhs), IntLiteral(rhs)) => *lhs &= *rhs as i16,
             (Int32(lhs), IntLiteral(rhs)) => *lhs &= *rhs as i32,
             (Int64(lhs), IntLiteral(rhs)) => *lhs &= *rhs as i64,
-            (Int(lhs), IntLiteral(rhs)) => *lhs &= rhs,
+            (Int(lhs), IntLiteral(rhs)) => *lhs &= *rhs as isize,
             (Uint8(lhs), IntLiteral(rhs)) => *lhs &= *rhs as u8,
             (Uint16(lhs), IntLiteral(rhs)) => *lhs &= *rhs as u16,
             (Uint32(lhs), IntLiteral(rhs)) => *lhs &= *rhs as u32,
@@ -627,7 +924,7 @@ impl Value {
             (Int16(lhs), IntLiteral(rhs)) => *lhs |= *rhs as i16,
             (Int32(lhs), IntLiteral(rhs)) => *lhs |= *rhs as i32,
             (Int64(lhs), IntLiteral(rhs)) => *lhs |= *rhs as i64,
-            (Int(lhs), IntLiteral(rhs)) => *lhs |= rhs,
+            (Int(lhs), IntLiteral(rhs)) => *lhs |= *rhs as isize,
             (Uint8(lhs), IntLiteral(rhs)) => *lhs |= *rhs as u8,
             (Uint16(lhs), IntLiteral(rhs)) => *lhs |= *rhs as u16,
             (Uint32(lhs), IntLiteral(rhs)) => *lhs |= *rhs as u32,
@@ -670,7 +967,7 @@ impl Value {
             (Int16(lhs), IntLiteral(rhs)) => *lhs ^= *rhs as i16,
             (Int32(lhs), IntLiteral(rhs)) => *lhs ^= *rhs as i32,
             (Int64(lhs), IntLiteral(rhs)) => *lhs ^= *rhs as i64,
-            (Int(lhs), IntLiteral(rhs)) => *lhs ^= rhs,
+            (Int(lhs), IntLiteral(rhs)) => *lhs ^= *rhs as isize,
             (Uint8(lhs), IntLiteral(rhs)) => *lhs ^= *rhs as u8,
             (Uint16(lhs), IntLiteral(rhs)) => *lhs ^= *rhs as u16,
             (Uint32(lhs), IntLiteral(rhs)) => *lhs ^= *rhs as u32,
@@ -713,7 +1010,7 @@ impl Value {
             (Int16(lhs), IntLiteral(rhs)) => *lhs &= !*rhs as i16,
             (Int32(lhs), IntLiteral(rhs)) => *lhs &= !*rhs as i32,
             (Int64(lhs), IntLiteral(rhs)) => *lhs &= !*rhs as i64,
-            (Int(lhs), IntLiteral(rhs)) => *lhs &= !rhs,
+            (Int(lhs), IntLiteral(rhs)) => *lhs &= !(*rhs as isize),
             (Uint8(lhs), IntLiteral(rhs)) => *lhs &= !*rhs as u8,
             (Uint16(lhs), IntLiteral(rhs)) => *lhs &= !*rhs as u16,
             (Uint32(lhs), IntLiteral(rhs)) => *lhs &= !*rhs as u32,
@@ -751,30 +1048,30 @@ impl Value {
 
         use Value::*;
         match (self, other) {
-            (IntLiteral(lhs), IntLiteral(rhs)) => *lhs <<= rhs,
-            (Int8(lhs), IntLiteral(rhs)) => *lhs <<= *rhs as i8,
-            (Int16(lhs), IntLiteral(rhs)) => *lhs <<= *rhs as i16,
-            (Int32(lhs), IntLiteral(rhs)) => *lhs <<= *rhs as i32,
-            (Int64(lhs), IntLiteral(rhs)) => *lhs <<= *rhs as i64,
-            (Int(lhs), IntLiteral(rhs)) => *lhs <<= rhs,
-            (Uint8(lhs), IntLiteral(rhs)) => *lhs <<= *rhs as u8,
-            (Uint16(lhs), IntLiteral(rhs)) => *lhs <<= *rhs as u16,
-            (Uint32(lhs), IntLiteral(rhs)) => *lhs <<= *rhs as u32,
-            (Uint64(lhs), IntLiteral(rhs)) => *lhs <<= *rhs as u64,
-            (Uintptr(lhs), IntLiteral(rhs)) => *lhs <<= *rhs as usize,
-            (Uint(lhs), IntLiteral(rhs)) => *lhs <<= *rhs as usize,
-
-            (Int8(lhs), Int8(rhs)) => *lhs <<= rhs,
-            (Int16(lhs), Int16(rhs)) => *lhs <<= rhs,
-            (Int32(lhs), Int32(rhs)) => *lhs <<= rhs,
-            (Int64(lhs), Int64(rhs)) => *lhs <<= rhs,
-            (Int(lhs), Int(rhs)) => *lhs <<= rhs,
-            (Uint8(lhs), Uint8(rhs)) => *lhs <<= rhs,
-            (Uint16(lhs), Uint16(rhs)) => *lhs <<= rhs,
-            (Uint32(lhs), Uint32(rhs)) => *lhs <<= rhs,
-            (Uint64(lhs), Uint64(rhs)) => *lhs <<= rhs,
-            (Uintptr(lhs), Uintptr(rhs)) => *lhs <<= rhs,
-            (Uint(lhs), Uint(rhs)) => *lhs <<= rhs,
+            (IntLiteral(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+            (Int8(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+            (Int16(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+            (Int32(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+            (Int64(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+            (Int(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+            (Uint8(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+            (Uint16(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+            (Uint32(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+            (Uint64(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+            (Uintptr(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+            (Uint(lhs), IntLiteral(rhs)) => shift_left!(lhs, rhs),
+
+            (Int8(lhs), Int8(rhs)) => shift_left!(lhs, rhs),
+            (Int16(lhs), Int16(rhs)) => shift_left!(lhs, rhs),
+            (Int32(lhs), Int32(rhs)) => shift_left!(lhs, rhs),
+            (Int64(lhs), Int64(rhs)) => shift_left!(lhs, rhs),
+            (Int(lhs), Int(rhs)) => shift_left!(lhs, rhs),
+            (Uint8(lhs), Uint8(rhs)) => shift_left!(lhs, rhs),
+            (Uint16(lhs), Uint16(rhs)) => shift_left!(lhs, rhs),
+            (Uint32(lhs), Uint32(rhs)) => shift_left!(lhs, rhs),
+            (Uint64(lhs), Uint64(rhs)) => shift_left!(lhs, rhs),
+            (Uintptr(lhs), Uintptr(rhs)) => shift_left!(lhs, rhs),
+            (Uint(lhs), Uint(rhs)) => shift_left!(lhs, rhs),
 
             (lhs, rhs) => {
                 return Err(TypeError::expected_same_type_operands(
@@ -794,30 +1091,30 @@ impl Value {
 
         use Value::*;
         match (self, other) {
-            (IntLiteral(lhs), IntLiteral(rhs)) => *lhs >>= rhs,
-            (Int8(lhs), IntLiteral(rhs)) => *lhs >>= *rhs as i8,
-            (Int16(lhs), IntLiteral(rhs)) => *lhs >>= *rhs as i16,
-            (Int32(lhs), IntLiteral(rhs)) => *lhs >>= *rhs as i32,
-            (Int64(lhs), IntLiteral(rhs)) => *lhs >>= *rhs as i64,
-            (Int(lhs), IntLiteral(rhs)) => *lhs >>= rhs,
-            (Uint8(lhs), IntLiteral(rhs)) => *lhs >>= *rhs as u8,
-            (Uint16(lhs), IntLiteral(rhs)) => *lhs >>= *rhs as u16,
-            (Uint32(lhs), IntLiteral(rhs)) => *lhs >>= *rhs as u32,
-            (Uint64(lhs), IntLiteral(rhs)) => *lhs >>= *rhs as u64,
-            (Uintptr(lhs), IntLiteral(rhs)) => *lhs >>= *rhs as usize,
-            (Uint(lhs), IntLiteral(rhs)) => *lhs >>= *rhs as usize,
-
-            (Int8(lhs), Int8(rhs)) => *lhs >>= rhs,
-            (Int16(lhs), Int16(rhs)) => *lhs >>= rhs,
-            (Int32(lhs), Int32(rhs)) => *lhs >>= rhs,
-            (Int64(lhs), Int64(rhs)) => *lhs >>= rhs,
-            (Int(lhs), Int(rhs)) => *lhs >>= rhs,
-            (Uint8(lhs), Uint8(rhs)) => *lhs >>= rhs,
-            (Uint16(lhs), Uint16(rhs)) => *lhs >>= rhs,
-            (Uint32(lhs), Uint32(rhs)) => *lhs >>= rhs,
-            (Uint64(lhs), Uint64(rhs)) => *lhs >>= rhs,
-            (Uintptr(lhs), Uintptr(rhs)) => *lhs >>= rhs,
-            (Uint(lhs), Uint(rhs)) => *lhs >>= rhs,
+            (IntLiteral(lhs), IntLiteral(rhs)) => shift_right_signed!(lhs, rhs),
+            (Int8(lhs), IntLiteral(rhs)) => shift_right_signed!(lhs, rhs),
+            (Int16(lhs), IntLiteral(rhs)) => shift_right_signed!(lhs, rhs),
+            (Int32(lhs), IntLiteral(rhs)) => shift_right_signed!(lhs, rhs),
+            (Int64(lhs), IntLiteral(rhs)) => shift_right_signed!(lhs, rhs),
+            (Int(lhs), IntLiteral(rhs)) => shift_right_signed!(lhs, rhs),
+            (Uint8(lhs), IntLiteral(rhs)) => shift_right_unsigned!(lhs, rhs),
+            (Uint16(lhs), IntLiteral(rhs)) => shift_right_unsigned!(lhs, rhs),
+            (Uint32(lhs), IntLiteral(rhs)) => shift_right_unsigned!(lhs, rhs),
+            (Uint64(lhs), IntLiteral(rhs)) => shift_right_unsigned!(lhs, rhs),
+            (Uintptr(lhs), IntLiteral(rhs)) => shift_right_unsigned!(lhs, rhs),
+            (Uint(lhs), IntLiteral(rhs)) => shift_right_unsigned!(lhs, rhs),
+
+            (Int8(lhs), Int8(rhs)) => shift_right_signed!(lhs, rhs),
+            (Int16(lhs), Int16(rhs)) => shift_right_signed!(lhs, rhs),
+            (Int32(lhs), Int32(rhs)) => shift_right_signed!(lhs, rhs),
+            (Int64(lhs), Int64(rhs)) => shift_right_signed!(lhs, rhs),
+            (Int(lhs), Int(rhs)) => shift_right_signed!(lhs, rhs),
+            (Uint8(lhs), Uint8(rhs)) => shift_right_unsigned!(lhs, rhs),
+            (Uint16(lhs), Uint16(rhs)) => shift_right_unsigned!(lhs, rhs),
+            (Uint32(lhs), Uint32(rhs)) => shift_right_unsigned!(lhs, rhs),
+            (Uint64(lhs), Uint64(rhs)) => shift_right_unsigned!(lhs, rhs),
+            (Uintptr(lhs), Uintptr(rhs)) => shift_right_unsigned!(lhs, rhs),
+            (Uint(lhs), Uint(rhs)) => shift_right_unsigned!(lhs, rhs),
 
             (lhs, rhs) => {
                 return Err(TypeError::expected_same_type_operands(
@@ -845,23 +1142,23 @@ impl Value {
     pub fn equal(&self, other: &Self) -> OperationResult<Self> {
         use Value::*;
         let res = match (self, other) {
-            (IntLiteral(lhs), Int8(rhs)) => Bool(lhs == &(*rhs as isize)),
-            (IntLiteral(lhs), Int16(rhs)) => Bool(lhs == &(*rhs as isize)),
-            (IntLiteral(lhs), Int32(rhs)) => Bool(lhs == &(*rhs as isize)),
-            (IntLiteral(lhs), Int64(rhs)) => Bool(lhs == &(*rhs as isize)),
-            (IntLiteral(lhs), Int(rhs)) => Bool(lhs == &(*rhs as isize)),
-            (IntLiteral(lhs), Uint8(rhs)) => Bool(lhs == &(*rhs as isize)),
-            (IntLiteral(lhs), Uint16(rhs)) => Bool(lhs == &(*rhs as isize)),
-            (IntLiteral(lhs), Uint32(rhs)) => Bool(lhs == &(*rhs as isize)),
-            (IntLiteral(lhs), Uint64(rhs)) => Bool(lhs == &(*rhs as isize)),
-            (IntLiteral(lhs), Uintptr(rhs)) => Bool(lhs == &(*rhs as isize)),
-            (IntLiteral(lhs), Uint(rhs)) => Bool(lhs == &(*rhs as isize)),
+            (IntLiteral(lhs), Int8(rhs)) => Bool(lhs == &(*rhs as i128)),
+            (IntLiteral(lhs), Int16(rhs)) => Bool(lhs == &(*rhs as i128)),
+            (IntLiteral(lhs), Int32(rhs)) => Bool(lhs == &(*rhs as i128)),
+            (IntLiteral(lhs), Int64(rhs)) => Bool(lhs == &(*rhs as i128)),
+            (IntLiteral(lhs), Int(rhs)) => Bool(*lhs == *rhs as i128),
+            (IntLiteral(lhs), Uint8(rhs)) => Bool(lhs == &(*rhs as i128)),
+            (IntLiteral(lhs), Uint16(rhs)) => Bool(lhs == &(*rhs as i128)),
+            (IntLiteral(lhs), Uint32(rhs)) => Bool(lhs == &(*rhs as i128)),
+            (IntLiteral(lhs), Uint64(rhs)) => Bool(lhs == &(*rhs as i128)),
+            (IntLiteral(lhs), Uintptr(rhs)) => Bool(lhs == &(*rhs as i128)),
+            (IntLiteral(lhs), Uint(rhs)) => Bool(lhs == &(*rhs as i128)),
 
             (Int8(lhs), IntLiteral(rhs)) => Bool(lhs == &(*rhs as i8)),
             (Int16(lhs), IntLiteral(rhs)) => Bool(lhs == &(*rhs as i16)),
             (Int32(lhs), IntLiteral(rhs)) => Bool(lhs == &(*rhs as i32)),
             (Int64(lhs), IntLiteral(rhs)) => Bool(lhs == &(*rhs as i64)),
-            (Int(lhs), IntLiteral(rhs)) => Bool(lhs == rhs),
+            (Int(lhs), IntLiteral(rhs)) => Bool(*lhs == *rhs as isize),
             (Uint8(lhs), IntLiteral(rhs)) => Bool(lhs == &(*rhs as u8)),
             (Uint16(lhs), IntLiteral(rhs)) => Bool(lhs == &(*rhs as u16)),
             (Uint32(lhs), IntLiteral(rhs)) => Bool(lhs == &(*rhs as u32)),
@@ -869,18 +1166,18 @@ impl Value {
             (Uintptr(lhs), IntLiteral(rhs)) => Bool(lhs == &(*rhs as usize)),
             (Uint(lhs), IntLiteral(rhs)) => Bool(lhs == &(*rhs as usize)),
 
-            (IntLiteral(lhs), FloatLiteral(rhs)) => Bool(lhs == &(*rhs as isize)),
+            (IntLiteral(lhs), FloatLiteral(rhs)) => Bool(lhs == &(*rhs as i128)),
             (FloatLiteral(lhs), IntLiteral(rhs)) => {
                 Bool((lhs - (*rhs as f64)).abs() < f64::EPSILON)
             }
 
             (Float32(lhs), FloatLiteral(rhs)) => Bool((lhs - (*rhs as f32)).abs() < f32::EPSILON),
             (Float32(lhs), IntLiteral(rhs)) => Bool((lhs - (*rhs as f32)).abs() < f32::EPSILON),
-            (IntLiteral(lhs), Float32(rhs)) => Bool(lhs == &(*rhs as isize)),
+            (IntLiteral(lhs), Float32(rhs)) => Bool(lhs == &(*rhs as i128)),
 
             (Float64(lhs), FloatLiteral(rhs)) => Bool((lhs - rhs).abs() < f64::EPSILON),
             (Float64(lhs), IntLiteral(rhs)) => Bool((lhs - (*rhs as f64)).abs() < f64::EPSILON),
-            (IntLiteral(lhs), Float64(rhs)) => Bool(lhs == &(*rhs as isize)),
+            (IntLiteral(lhs), Float64(rhs)) => Bool(lhs == &(*rhs as i128)),
 
             _ => {
                 if mem::discriminant(self) != mem::discriminant(other) {
@@ -900,25 +1197,25 @@ impl Value {
     pub fn greater(&self, other: &Self) -> OperationResult<Self> {
         use Value::*;
         let val = match (self, other) {
-            (IntLiteral(lhs), Int8(rhs)) => Bool(lhs > &(*rhs as isize)),
-            (IntLiteral(lhs), Int16(rhs)) => Bool(lhs > &(*rhs as isize)),
-            (IntLiteral(lhs), Int32(rhs)) => Bool(lhs > &(*rhs as isize)),
-            (IntLiteral(lhs), Int64(rhs)) => Bool(lhs > &(*rhs as isize)),
-            (IntLiteral(lhs), Int(rhs)) => Bool(lhs > &(*rhs as isize)),
-            (IntLiteral(lhs), Uint8(rhs)) => Bool(lhs > &(*rhs as isize)),
-            (IntLiteral(lhs), Uint16(rhs)) => Bool(lhs > &(*rhs as isize)),
-            (IntLiteral(lhs), Uint32(rhs)) => Bool(lhs > &(*rhs as isize)),
-            (IntLiteral(lhs), Uint64(rhs)) => Bool(lhs > &(*rhs as isize)),
-            (IntLiteral(lhs), Uintptr(rhs)) => Bool(lhs > &(*rhs as isize)),
-            (IntLiteral(lhs), Uint(rhs)) => Bool(lhs > &(*rhs as isize)),
-
-            (IntLiteral(lhs), IntLiteral(rhs)) => Bool(lhs > &(*rhs as isize)),
+            (IntLiteral(lhs), Int8(rhs)) => Bool(lhs > &(*rhs as i128)),
+            (IntLiteral(lhs), Int16(rhs)) => Bool(lhs > &(*rhs as i128)),
+            (IntLiteral(lhs), Int32(rhs)) => Bool(lhs > &(*rhs as i128)),
+            (IntLiteral(lhs), Int64(rhs)) => Bool(lhs > &(*rhs as i128)),
+            (IntLiteral(lhs), Int(rhs)) => Bool(*lhs > *rhs as i128),
+            (IntLiteral(lhs), Uint8(rhs)) => Bool(lhs > &(*rhs as i128)),
+            (IntLiteral(lhs), Uint16(rhs)) => Bool(lhs > &(*rhs as i128)),
+            (IntLiteral(lhs), Uint32(rhs)) => Bool(lhs > &(*rhs as i128)),
+            (IntLiteral(lhs), Uint64(rhs)) => Bool(lhs > &(*rhs as i128)),
+            (IntLiteral(lhs), Uintptr(rhs)) => Bool(lhs > &(*rhs as i128)),
+            (IntLiteral(lhs), Uint(rhs)) => Bool(lhs > &(*rhs as i128)),
+
+            (IntLiteral(lhs), IntLiteral(rhs)) => Bool(lhs > rhs),
 
             (Int8(lhs), IntLiteral(rhs)) => Bool(lhs > &(*rhs as i8)),
             (Int16(lhs), IntLiteral(rhs)) => Bool(lhs > &(*rhs as i16)),
             (Int32(lhs), IntLiteral(rhs)) => Bool(lhs > &(*rhs as i32)),
             (Int64(lhs), IntLiteral(rhs)) => Bool(lhs > &(*rhs as i64)),
-            (Int(lhs), IntLiteral(rhs)) => Bool(lhs > rhs),
+            (Int(lhs), IntLiteral(rhs)) => Bool(*lhs > *rhs as isize),
             (Uint8(lhs), IntLiteral(rhs)) => Bool(lhs > &(*rhs as u8)),
             (Uint16(lhs), IntLiteral(rhs)) => Bool(lhs > &(*rhs as u16)),
             (Uint32(lhs), IntLiteral(rhs)) => Bool(lhs > &(*rhs as u32)),
@@ -961,24 +1258,24 @@ impl Value {
     pub fn greater_equal(&self, other: &Self) -> OperationResult<Self> {
         use Value::*;
         let val = match (self, other) {
-            (IntLiteral(lhs), Int8(rhs)) => Bool(lhs >= &(*rhs as isize)),
-            (IntLiteral(lhs), Int16(rhs)) => Bool(lhs >= &(*rhs as isize)),
-            (IntLiteral(lhs), Int32(rhs)) => Bool(lhs >= &(*rhs as isize)),
-            (IntLiteral(lhs), Int64(rhs)) => Bool(lhs >= &(*rhs as isize)),
-            (IntLiteral(lhs), Int(rhs)) => Bool(lhs >= &(*rhs as isize)),
-            (IntLiteral(lhs), Uint8(rhs)) => Bool(lhs >= &(*rhs as isize)),
-            (IntLiteral(lhs), Uint16(rhs)) => Bool(lhs >= &(*rhs as isize)),
-            (IntLiteral(lhs), Uint32(rhs)) => Bool(lhs >= &(*rhs as isize)),
-            (IntLiteral(lhs), Uint64(rhs)) => Bool(lhs >= &(*rhs as isize)),
-            (IntLiteral(lhs), Uintptr(rhs)) => Bool(lhs >= &(*rhs as isize)),
-            (IntLiteral(lhs), Uint(rhs)) => Bool(lhs >= &(*rhs as isize)),
-
-            (IntLiteral(lhs), IntLiteral(rhs)) => Bool(lhs >= &(*rhs as isize)),
+            (IntLiteral(lhs), Int8(rhs)) => Bool(lhs >= &(*rhs as i128)),
+            (IntLiteral(lhs), Int16(rhs)) => Bool(lhs >= &(*rhs as i128)),
+            (IntLiteral(lhs), Int32(rhs)) => Bool(lhs >= &(*rhs as i128)),
+            (IntLiteral(lhs), Int64(rhs)) => Bool(lhs >= &(*rhs as i128)),
+            (IntLiteral(lhs), Int(rhs)) => Bool(*lhs >= *rhs as i128),
+            (IntLiteral(lhs), Uint8(rhs)) => Bool(lhs >= &(*rhs as i128)),
+            (IntLiteral(lhs), Uint16(rhs)) => Bool(lhs >= &(*rhs as i128)),
+            (IntLiteral(lhs), Uint32(rhs)) => Bool(lhs >= &(*rhs as i128)),
+            (IntLiteral(lhs), Uint64(rhs)) => Bool(lhs >= &(*rhs as i128)),
+            (IntLiteral(lhs), Uintptr(rhs)) => Bool(lhs >= &(*rhs as i128)),
+            (IntLiteral(lhs), Uint(rhs)) => Bool(lhs >= &(*rhs as i128)),
+
+            (IntLiteral(lhs), IntLiteral(rhs)) => Bool(lhs >= rhs),
             (Int8(lhs), IntLiteral(rhs)) => Bool(lhs >= &(*rhs as i8)),
             (Int16(lhs), IntLiteral(rhs)) => Bool(lhs >= &(*rhs as i16)),
             (Int32(lhs), IntLiteral(rhs)) => Bool(lhs >= &(*rhs as i32)),
             (Int64(lhs), IntLiteral(rhs)) => Bool(lhs >= &(*rhs as i64)),
-            (Int(lhs), IntLiteral(rhs)) => Bool(lhs >= rhs),
+            (Int(lhs), IntLiteral(rhs)) => Bool(*lhs >= *rhs as isize),
             (Uint8(lhs), IntLiteral(rhs)) => Bool(lhs >= &(*rhs as u8)),
             (Uint16(lhs), IntLiteral(rhs)) => Bool(lhs >= &(*rhs as u16)),
             (Uint32(lhs), IntLiteral(rhs)) => Bool(lhs >= &(*rhs as u32)),
@@ -1021,24 +1318,24 @@ impl Value {
     pub fn less(&self, other: &Self) -> OperationResult<Self> {
         use Value::*;
         let val = match (self, other) {
-            (IntLiteral(lhs), Int8(rhs)) => Bool(lhs < &(*rhs as isize)),
-            (IntLiteral(lhs), Int16(rhs)) => Bool(lhs < &(*rhs as isize)),
-            (IntLiteral(lhs), Int32(rhs)) => Bool(lhs < &(*rhs as isize)),
-            (IntLiteral(lhs), Int64(rhs)) => Bool(lhs < &(*rhs as isize)),
-            (IntLiteral(lhs), Int(rhs)) => Bool(lhs < &(*rhs as isize)),
-            (IntLiteral(lhs), Uint8(rhs)) => Bool(lhs < &(*rhs as isize)),
-            (IntLiteral(lhs), Uint16(rhs)) => Bool(lhs < &(*rhs as isize)),
-            (IntLiteral(lhs), Uint32(rhs)) => Bool(lhs < &(*rhs as isize)),
-            (IntLiteral(lhs), Uint64(rhs)) => Bool(lhs < &(*rhs as isize)),
-            (IntLiteral(lhs), Uintptr(rhs)) => Bool(lhs < &(*rhs as isize)),
-            (IntLiteral(lhs), Uint(rhs)) => Bool(lhs < &(*rhs as isize)),
-
-            (IntLiteral(lhs), IntLiteral(rhs)) => Bool(lhs < &(*rhs as isize)),
+            (IntLiteral(lhs), Int8(rhs)) => Bool(lhs < &(*rhs as i128)),
+            (IntLiteral(lhs), Int16(rhs)) => Bool(lhs < &(*rhs as i128)),
+            (IntLiteral(lhs), Int32(rhs)) => Bool(lhs < &(*rhs as i128)),
+            (IntLiteral(lhs), Int64(rhs)) => Bool(lhs < &(*rhs as i128)),
+            (IntLiteral(lhs), Int(rhs)) => Bool(*lhs < *rhs as i128),
+            (IntLiteral(lhs), Uint8(rhs)) => Bool(lhs < &(*rhs as i128)),
+            (IntLiteral(lhs), Uint16(rhs)) => Bool(lhs < &(*rhs as i128)),
+            (IntLiteral(lhs), Uint32(rhs)) => Bool(lhs < &(*rhs as i128)),
+            (IntLiteral(lhs), Uint64(rhs)) => Bool(lhs < &(*rhs as i128)),
+            (IntLiteral(lhs), Uintptr(rhs)) => Bool(lhs < &(*rhs as i128)),
+            (IntLiteral(lhs), Uint(rhs)) => Bool(lhs < &(*rhs as i128)),
+
+            (IntLiteral(lhs), IntLiteral(rhs)) => Bool(lhs < rhs),
             (Int8(lhs), IntLiteral(rhs)) => Bool(lhs < &(*rhs as i8)),
             (Int16(lhs), IntLiteral(rhs)) => Bool(lhs < &(*rhs as i16)),
             (Int32(lhs), IntLiteral(rhs)) => Bool(lhs < &(*rhs as i32)),
             (Int64(lhs), IntLiteral(rhs)) => Bool(lhs < &(*rhs as i64)),
-            (Int(lhs), IntLiteral(rhs)) => Bool(lhs < rhs),
+            (Int(lhs), IntLiteral(rhs)) => Bool(*lhs < *rhs as isize),
             (Uint8(lhs), IntLiteral(rhs)) => Bool(lhs < &(*rhs as u8)),
             (Uint16(lhs), IntLiteral(rhs)) => Bool(lhs < &(*rhs as u16)),
             (Uint32(lhs), IntLiteral(rhs)) => Bool(lhs < &(*rhs as u32)),
@@ -1081,24 +1378,24 @@ impl Value {
     pub fn less_equal(&self, other: &Self) -> OperationResult<Self> {
         use Value::*;
         let val = match (self, other) {
-            (IntLiteral(lhs), Int8(rhs)) => Bool(lhs <= &(*rhs as isize)),
-            (IntLiteral(lhs), Int16(rhs)) => Bool(lhs <= &(*rhs as isize)),
-            (IntLiteral(lhs), Int32(rhs)) => Bool(lhs <= &(*rhs as isize)),
-            (IntLiteral(lhs), Int64(rhs)) => Bool(lhs <= &(*rhs as isize)),
-            (IntLiteral(lhs), Int(rhs)) => Bool(lhs <= &(*rhs as isize)),
-            (IntLiteral(lhs), Uint8(rhs)) => Bool(lhs <= &(*rhs as isize)),
-            (IntLiteral(lhs), Uint16(rhs)) => Bool(lhs <= &(*rhs as isize)),
-            (IntLiteral(lhs), Uint32(rhs)) => Bool(lhs <= &(*rhs as isize)),
-            (IntLiteral(lhs), Uint64(rhs)) => Bool(lhs <= &(*rhs as isize)),
-            (IntLiteral(lhs), Uintptr(rhs)) => Bool(lhs <= &(*rhs as isize)),
-            (IntLiteral(lhs), Uint(rhs)) => Bool(lhs <= &(*rhs as isize)),
-
-            (IntLiteral(lhs), IntLiteral(rhs)) => Bool(lhs <= &(*rhs as isize)),
+            (IntLiteral(lhs), Int8(rhs)) => Bool(lhs <= &(*rhs as i128)),
+            (IntLiteral(lhs), Int16(rhs)) => Bool(lhs <= &(*rhs as i128)),
+            (IntLiteral(lhs), Int32(rhs)) => Bool(lhs <= &(*rhs as i128)),
+            (IntLiteral(lhs), Int64(rhs)) => Bool(lhs <= &(*rhs as i128)),
+            (IntLiteral(lhs), Int(rhs)) => Bool(*lhs <= *rhs as i128),
+            (IntLiteral(lhs), Uint8(rhs)) => Bool(lhs <= &(*rhs as i128)),
+            (IntLiteral(lhs), Uint16(rhs)) => Bool(lhs <= &(*rhs as i128)),
+            (IntLiteral(lhs), Uint32(rhs)) => Bool(lhs <= &(*rhs as i128)),
+            (IntLiteral(lhs), Uint64(rhs)) => Bool(lhs <= &(*rhs as i128)),
+            (IntLiteral(lhs), Uintptr(rhs)) => Bool(lhs <= &(*rhs as i128)),
+            (IntLiteral(lhs), Uint(rhs)) => Bool(lhs <= &(*rhs as i128)),
+
+            (IntLiteral(lhs), IntLiteral(rhs)) => Bool(lhs <= rhs),
             (Int8(lhs), IntLiteral(rhs)) => Bool(lhs <= &(*rhs as i8)),
             (Int16(lhs), IntLiteral(rhs)) => Bool(lhs <= &(*rhs as i16)),
             (Int32(lhs), IntLiteral(rhs)) => Bool(lhs <= &(*rhs as i32)),
             (Int64(lhs), IntLiteral(rhs)) => Bool(lhs <= &(*rhs as i64)),
-            (Int(lhs), IntLiteral(rhs)) => Bool(lhs <= rhs),
+            (Int(lhs), IntLiteral(rhs)) => Bool(*lhs <= *rhs as isize),
             (Uint8(lhs), IntLiteral(rhs)) => Bool(lhs <= &(*rhs as u8)),
             (Uint16(lhs), IntLiteral(rhs)) => Bool(lhs <= &(*rhs as u16)),
             (Uint32(lhs), IntLiteral(rhs)) => Bool(lhs <= &(*rhs as u32)),
@@ -1162,39 +1459,56 @@ impl Value {
             Self::FloatLiteral(_) => ValType::Float64,
             Self::Array(.., vtype) => vtype.clone(),
             Self::Slice(.., vtype) => vtype.clone(),
-            t => {
-                dbg!(t);
-                panic!("Unknown type")
-            }
+            Self::Func(_, ftype) => ValType::Func(ftype.clone()),
+            Self::FuncBuiltin(name) => panic!("Builtin \"{}\" has no type", name),
         }
     }
 
+    /// Whether the value can be assigned to a variable of type `vtype`
     pub fn is_of_type(&self, vtype: &ValType) -> bool {
         match &self {
-            //FIXME check
-            Self::Func(_) => true,
-            Self::FloatLiteral(_) => matches!(vtype, ValType::Float32 | ValType::Float64),
-            Self::IntLiteral(_) => matches!(
-                vtype,
-                ValType::Int
-                    | ValType::Int8
-                    | ValType::Int16
-                    | ValType::Int32
-                    | ValType::Int64
-                    | ValType::Uint
-                    | ValType::Uint8
-                    | ValType::Uint16
-                    | ValType::Uint32
-                    | ValType::Uint64
-                    | ValType::Uintptr
-            ),
-            Self::Array(.., array_type) => array_type == vtype,
-            _ => self.get_type() == *vtype,
+            Self::IntLiteral(_) => vtype.accepts_untyped_int(),
+            Self::FloatLiteral(v) => vtype.accepts_untyped_float(v.fract() == 0.0),
+            _ => vtype.is_assignable_from(&self.get_type()),
+        }
+    }
+
+    /// Whether the value can be stored in place of `target`.
+    /// Types are only built when they are not told apart by the variants
+    pub fn is_assignable_to(&self, target: &Self) -> bool {
+        match (self, target) {
+            (Self::Array(.., vtype), Self::Array(.., target))
+            | (Self::Slice(_, vtype), Self::Slice(_, target)) => target.is_assignable_from(vtype),
+            _ if self.is_basic() && target.is_basic() => {
+                mem::discriminant(self) == mem::discriminant(target)
+            }
+            _ => self.is_of_type(&target.get_type()),
         }
     }
 
+    /// Converts an untyped constant to the type of the value it is stored in place of
+    pub fn lose_literal_to(&mut self, target: &Self) {
+        if self.is_literal() {
+            self.lose_literal(&target.get_type());
+        }
+    }
+
+    /// Typed values of a predeclared type other than a function, the variant tells the type apart
+    fn is_basic(&self) -> bool {
+        !matches!(
+            self,
+            Self::Array(..)
+                | Self::Slice(..)
+                | Self::Func(..)
+                | Self::FuncBuiltin(_)
+                | Self::Nil
+                | Self::IntLiteral(_)
+                | Self::FloatLiteral(_)
+        )
+    }
+
     pub fn same_type(&self, other: &Self) -> bool {
-        mem::discriminant(self) == mem::discriminant(other)
+        self.get_type().is_identical(&other.get_type())
     }
 }
 
@@ -1220,27 +1534,219 @@ impl Display for Value {
             Self::FloatLiteral(f) => format!("{:e}", f),
             Self::Complex64(c, i) => format!("({:e}+{:e}i)", c, i),
             Self::Complex128(c, i) => format!("({:e}+{:e}i)", c, i),
-            Self::String(s) => s.clone(),
-            Self::Array(iter, _size, vtype) => iter_to_string(iter, vtype),
-            Self::Slice(iter, vtype) => iter_to_string(iter, vtype),
-            v => {
-                panic!("Unknown string type representation for value {:?}", v)
-            }
+            Self::String(s) => s.to_string(),
+            Self::Array(iter, ..) => iter_to_string(&iter.borrow()),
+            Self::Slice(slice, _) => iter_to_string(&slice.to_vec()),
+            Self::Func(name, _) if name.is_empty() => "nil".to_string(),
+            Self::Func(name, _) | Self::FuncBuiltin(name) => func_address(name),
         };
 
         write!(f, "{}", val)
     }
 }
 
-fn iter_to_string(iter: &RefIterator, vtype: &ValType) -> String {
+/// Functions are printed as addresses in Go, a stable one is derived from the name of the function
+fn func_address(name: &str) -> String {
+    // FNV-1a
+    let hash = name.bytes().fold(0x811c_9dc5_u32, |hash, b| {
+        (hash ^ b as u32).wrapping_mul(0x0100_0193)
+    });
+
+    format!("{:#x}", 0x40_0000 + (hash & 0xf_ffff))
+}
+
+/// Elements separated by spaces, nested arrays and slices are formatted the same way
+fn iter_to_string(vals: &[Value]) -> String {
     format!(
-        "<{}>[{}]",
-        vtype,
-        iter.as_ref()
-            .borrow()
-            .iter()
+        "[{}]",
+        vals.iter()
             .map(|v| v.to_string())
             .collect::<Vec<String>>()
             .join(" ")
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vtype::{CompositeType, ParamType};
+
+    #[test]
+    fn test_display_func() {
+        let func = |name: &str| {
+            Value::Func(
+                name.to_string(),
+                Box::new(FuncType::new(vec![], CompositeType::new_void())),
+            )
+        };
+        let f = func("main");
+        assert!(f.to_string().starts_with("0x"));
+        assert_eq!(f.to_string(), func("main").to_string());
+        assert_ne!(f.to_string(), func("other").to_string());
+        assert!(Value::FuncBuiltin("len".to_string())
+            .to_string()
+            .starts_with("0x"));
+        assert_eq!(Value::Nil.to_string(), "nil");
+    }
+
+    #[test]
+    fn test_default() {
+        let array = ValType::Array(Box::new(ValType::Int8), 2);
+        let zero = Value::default(&array, &mut Unmanaged)
+            .ok()
+            .map(|v| v.to_string());
+        assert_eq!(zero.as_deref(), Some("[0 0]"));
+        assert!(Value::default(&ValType::Nil, &mut Unmanaged).is_err());
+        let undeclared = ValType::Array(Box::new(ValType::Struct("point".to_string())), 2);
+        let err = Value::default(&undeclared, &mut Unmanaged)
+            .err()
+            .map(|e| e.0);
+        assert_eq!(
+            err.as_deref(),
+            Some("Cannot construct default value for type \"point\"")
+        );
+    }
+
+    #[test]
+    fn test_func_type_check() {
+        let int_to_int = FuncType::new(
+            vec![ParamType(ValType::Int, false)],
+            CompositeType::new_trivial(ValType::Int),
+        );
+        let string_to_void = FuncType::new(
+            vec![ParamType(ValType::String, false)],
+            CompositeType::new_void(),
+        );
+        let double = Value::Func("double".to_string(), Box::new(int_to_int.clone()));
+        let greet = Value::Func("greet".to_string(), Box::new(string_to_void.clone()));
+
+        assert!(double.is_of_type(&ValType::Func(Box::new(int_to_int))));
+        assert!(!double.is_of_type(&ValType::Func(Box::new(string_to_void))));
+        assert!(!double.is_of_type(&ValType::Int));
+        assert!(double.same_type(&double.clone()));
+        assert!(!double.same_type(&greet));
+    }
+
+    #[test]
+    fn test_assignable_to() {
+        let ints = |vals: Vec<Value>| {
+            Value::new_slice(vals, ValType::Slice(Box::new(ValType::Int)), &mut Unmanaged)
+        };
+        let s = Value::String("a".into());
+
+        assert!(Value::Int(1).is_assignable_to(&Value::Int(2)));
+        assert!(!Value::Int8(1).is_assignable_to(&Value::Int(2)));
+        assert!(!s.is_assignable_to(&Value::Int(2)));
+        assert!(Value::IntLiteral(1).is_assignable_to(&Value::Float32(2.0)));
+        assert!(!Value::FloatLiteral(1.5).is_assignable_to(&Value::Int(2)));
+        assert!(ints(vec![]).is_assignable_to(&ints(vec![Value::Int(1)])));
+        assert!(Value::Nil.is_assignable_to(&ints(vec![])));
+        assert!(!s.is_assignable_to(&ints(vec![])));
+
+        let mut literal = Value::IntLiteral(3);
+        literal.lose_literal_to(&Value::Uint8(0));
+        assert_eq!(literal, Value::Uint8(3));
+
+        let copy = s.clone();
+        assert!(matches!((&s, &copy), (Value::String(a), Value::String(b)) if Rc::ptr_eq(a, b)));
+    }
+
+    #[test]
+    fn test_display_iter() {
+        let inner = Value::new_array(
+            vec![Value::Int(1), Value::Int(2)],
+            2,
+            ValType::Int,
+            &mut Unmanaged,
+        );
+        let outer = Value::new_slice(
+            vec![inner.clone(), inner],
+            ValType::Array(Box::new(ValType::Int), 2),
+            &mut Unmanaged,
+        );
+        assert_eq!(outer.to_string(), "[[1 2] [1 2]]");
+        assert_eq!(
+            Value::new_slice(vec![], ValType::Int, &mut Unmanaged).to_string(),
+            "[]"
+        );
+    }
+
+    #[test]
+    fn test_convert_string() {
+        let bytes = ValType::Slice(Box::new(ValType::Uint8));
+        let runes = ValType::Slice(Box::new(ValType::Int32));
+        let s = Value::String("hé".into());
+
+        let bs = s.convert_string(&bytes, &mut Unmanaged).unwrap();
+        assert_eq!(bs.to_string(), "[104 195 169]");
+        let rs = s.convert_string(&runes, &mut Unmanaged).unwrap();
+        assert_eq!(rs.to_string(), "[104 233]");
+        assert_eq!(
+            bs.convert_string(&ValType::String, &mut Unmanaged),
+            Some(s.clone())
+        );
+        assert_eq!(rs.convert_string(&ValType::String, &mut Unmanaged), Some(s));
+
+        let invalid = Value::new_slice(vec![Value::Uint8(0xff)], bytes.clone(), &mut Unmanaged);
+        assert_eq!(
+            invalid
+                .convert_string(&ValType::String, &mut Unmanaged)
+                .unwrap()
+                .to_string(),
+            "\u{fffd}"
+        );
+        assert_eq!(
+            Value::Int(65)
+                .convert_string(&ValType::String, &mut Unmanaged)
+                .unwrap()
+                .to_string(),
+            "A"
+        );
+        assert_eq!(
+            Value::Int(-1)
+                .convert_string(&ValType::String, &mut Unmanaged)
+                .unwrap()
+                .to_string(),
+            "\u{fffd}"
+        );
+        assert_eq!(Value::Int(1).convert_string(&bytes, &mut Unmanaged), None);
+        assert_eq!(
+            Value::Bool(true).convert_string(&ValType::String, &mut Unmanaged),
+            None
+        );
+    }
+
+    #[test]
+    fn test_slice_ref_shares_array() {
+        let slice = SliceRef::new((1..=4).map(Value::Int).collect(), &mut Unmanaged);
+        assert_eq!(slice.reslice(0, 1, 2).cap(), 2);
+        let sub = slice.reslice(1, 3, 4);
+        assert_eq!((sub.len(), sub.cap()), (2, 3));
+        assert_eq!(sub.to_vec(), vec![Value::Int(2), Value::Int(3)]);
+
+        assert!(sub.set(0, Value::Int(9), &mut Unmanaged));
+        assert!(!sub.set(2, Value::Int(9), &mut Unmanaged));
+        assert_eq!(slice.get(1), Some(Value::Int(9)));
+        assert_eq!(sub.get(2), None);
+
+        let grown = sub.append(vec![Value::Int(5)], &Value::Int(0), &mut Unmanaged);
+        assert_eq!((grown.len(), grown.cap()), (3, 3));
+        assert_eq!(slice.get(3), Some(Value::Int(5)));
+
+        let moved = grown.append(vec![Value::Int(6)], &Value::Int(0), &mut Unmanaged);
+        assert_eq!((moved.len(), moved.cap()), (4, 6));
+        assert!(moved.set(0, Value::Int(0), &mut Unmanaged));
+        assert_eq!(grown.get(0), Some(Value::Int(9)));
+
+        assert_eq!(grow_cap(0, 1), 1);
+        assert_eq!(grow_cap(4, 5), 8);
+        assert_eq!(grow_cap(2, 7), 7);
+        assert_eq!(grow_cap(256, 257), 512);
+        assert_eq!(grow_cap(512, 513), 832);
+
+        let array = Rc::new(RefCell::new(vec![Value::Int(1), Value::Int(2)]));
+        let of_array = SliceRef::of_array(&array, 1, 1, 2);
+        assert!(of_array.is_empty());
+        assert_eq!(of_array.cap(), 1);
+    }
+}
@@ -76,6 +76,76 @@ impl ValType {
             Self::Struct(name) => str::to_string(name),
         }
     }
+
+    /// There are no named types yet, so types are identical when their structure is
+    pub fn is_identical(&self, other: &Self) -> bool {
+        self == other
+    }
+
+    /// Whether a typed value of type `from` can be assigned to a variable of this type.
+    /// Untyped constants are checked with `accepts_untyped_int` and `accepts_untyped_float`
+    pub fn is_assignable_from(&self, from: &Self) -> bool {
+        self.is_identical(from) || *from == Self::Nil && self.is_nillable()
+    }
+
+    /// Whether an untyped integer constant converts to this type
+    pub fn accepts_untyped_int(&self) -> bool {
+        self.is_integer() || self.is_float() || self.is_complex()
+    }
+
+    /// Whether an untyped float constant converts to this type,
+    /// integer types only accept the constants with no fractional part
+    pub fn accepts_untyped_float(&self, integral: bool) -> bool {
+        self.is_float() || self.is_complex() || integral && self.is_integer()
+    }
+
+    /// Smallest and largest values of an integer type
+    pub fn int_range(&self) -> Option<(i128, i128)> {
+        let range = match self {
+            Self::Int8 => (i8::MIN as i128, i8::MAX as i128),
+            Self::Int16 => (i16::MIN as i128, i16::MAX as i128),
+            Self::Int32 => (i32::MIN as i128, i32::MAX as i128),
+            Self::Int64 => (i64::MIN as i128, i64::MAX as i128),
+            Self::Int => (isize::MIN as i128, isize::MAX as i128),
+            Self::Uint8 => (0, u8::MAX as i128),
+            Self::Uint16 => (0, u16::MAX as i128),
+            Self::Uint32 => (0, u32::MAX as i128),
+            Self::Uint64 => (0, u64::MAX as i128),
+            Self::Uint | Self::Uintptr => (0, usize::MAX as i128),
+            _ => return None,
+        };
+
+        Some(range)
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            Self::Int
+                | Self::Int8
+                | Self::Int16
+                | Self::Int32
+                | Self::Int64
+                | Self::Uint
+                | Self::Uint8
+                | Self::Uint16
+                | Self::Uint32
+                | Self::Uint64
+                | Self::Uintptr
+        )
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, Self::Float32 | Self::Float64)
+    }
+
+    pub fn is_complex(&self) -> bool {
+        matches!(self, Self::Complex64 | Self::Complex128)
+    }
+
+    fn is_nillable(&self) -> bool {
+        matches!(self, Self::Slice(_) | Self::Func(_))
+    }
 }
 
 impl fmt::Display for ValType {
@@ -205,6 +275,34 @@ impl fmt::Display for FuncType {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_assignability() {
+        let ints = ValType::Slice(Box::new(ValType::Int));
+        let strings = ValType::Slice(Box::new(ValType::String));
+        assert!(ints.is_assignable_from(&ints.clone()));
+        assert!(!ints.is_assignable_from(&strings));
+        assert!(ints.is_assignable_from(&ValType::Nil));
+        assert!(!ValType::Int.is_assignable_from(&ValType::Nil));
+
+        let pair = ValType::Array(Box::new(ValType::Int), 2);
+        assert!(!pair.is_assignable_from(&ValType::Array(Box::new(ValType::Int), 3)));
+        assert!(!ValType::Int.is_assignable_from(&ValType::Int64));
+
+        let f = |variadic| {
+            let params = vec![ParamType(ValType::Int, variadic)];
+            ValType::Func(Box::new(FuncType::new(params, CompositeType::new_void())))
+        };
+        assert!(f(false).is_assignable_from(&f(false)));
+        assert!(!f(false).is_assignable_from(&f(true)));
+
+        assert!(ValType::Complex64.accepts_untyped_int());
+        assert!(ValType::Float32.accepts_untyped_int());
+        assert!(!ValType::String.accepts_untyped_int());
+        assert!(ValType::Uint8.accepts_untyped_float(true));
+        assert!(!ValType::Uint8.accepts_untyped_float(false));
+        assert!(ValType::Complex128.accepts_untyped_float(false));
+    }
+
     #[test]
     fn test_composite_type() {
         let ct = CompositeType::new_void();
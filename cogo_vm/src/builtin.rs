@@ -1,80 +1,223 @@
 #[allow(unused_imports)]
 use std::io::Write;
-use std::{char, result};
+use std::{fmt, result};
 
-use cogo_compiler::{ValType, Value};
+use cogo_compiler::{ValType, Value, BUILTINS};
 
 use crate::error::VmError;
+use crate::format::{fmt_format, runtime_format};
+use crate::heap::Heap;
 use crate::io::StreamProvider;
 use crate::Vm;
 
 pub(super) struct FuncBuiltin {
     name: &'static str,
-    argc: Option<u8>,
+    signature: Signature,
     func: Builtin,
 }
 
-type Builtin = fn(argv: &[Value], streams: &dyn StreamProvider) -> CallResult;
+type Builtin = fn(argv: &[Value], streams: &dyn StreamProvider, heap: &mut Heap) -> CallResult;
 pub(super) type CallResult = result::Result<Option<Value>, VmError>;
 
 impl FuncBuiltin {
-    pub(super) fn new(name: &'static str, argc: Option<u8>, func: Builtin) -> Self {
-        Self { name, func, argc }
+    pub(super) fn new(name: &'static str, signature: Signature, func: Builtin) -> Self {
+        Self {
+            name,
+            signature,
+            func,
+        }
     }
 
-    pub(super) fn call(&self, argv: &[Value], streams: &dyn StreamProvider) -> CallResult {
-        (self.func)(argv, streams)
+    /// Checks the arguments against the signature before invoking the function
+    pub(super) fn call(
+        &self,
+        argv: &[Value],
+        streams: &dyn StreamProvider,
+        heap: &mut Heap,
+    ) -> CallResult {
+        self.signature.check(argv)?;
+        (self.func)(argv, streams, heap)
     }
 
     pub(super) fn name(&self) -> &str {
         self.name
     }
+}
+
+/// Values accepted by a builtin parameter
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Param {
+    Any,
+    /// Integers and floats, untyped constants included
+    Number,
+    Float,
+    Complex,
+    /// Strings, arrays and slices
+    Sized,
+    /// Arrays and slices
+    Capped,
+    Slice,
+}
+
+impl Param {
+    fn accepts(&self, v: &Value) -> bool {
+        match self {
+            Self::Any => true,
+            Self::Number => matches!(
+                v,
+                Value::Int8(_)
+                    | Value::Int16(_)
+                    | Value::Int32(_)
+                    | Value::Int64(_)
+                    | Value::Int(_)
+                    | Value::Uint8(_)
+                    | Value::Uint16(_)
+                    | Value::Uint32(_)
+                    | Value::Uint64(_)
+                    | Value::Uint(_)
+                    | Value::Uintptr(_)
+                    | Value::Float32(_)
+                    | Value::Float64(_)
+                    | Value::IntLiteral(_)
+                    | Value::FloatLiteral(_)
+            ),
+            Self::Float => matches!(v, Value::Float32(_) | Value::Float64(_)),
+            Self::Complex => matches!(v, Value::Complex64(..) | Value::Complex128(..)),
+            Self::Sized => matches!(v, Value::String(_) | Value::Array(..) | Value::Slice(..)),
+            Self::Capped => matches!(v, Value::Array(..) | Value::Slice(..)),
+            Self::Slice => matches!(v, Value::Slice(..)),
+        }
+    }
+}
 
-    pub(super) fn argc(&self) -> &Option<u8> {
-        &self.argc
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Any => "any",
+            Self::Number => "integer or float",
+            Self::Float => "float",
+            Self::Complex => "complex",
+            Self::Sized => "string, array, slice",
+            Self::Capped => "array, slice",
+            Self::Slice => "slice",
+        };
+        write!(f, "{}", name)
     }
 }
 
+/// Declared parameters of a builtin, the last one of a variadic builtin may repeat any number of times
+pub(super) struct Signature {
+    params: &'static [Param],
+    variadic: bool,
+}
+
+impl Signature {
+    pub(super) const fn fixed(params: &'static [Param]) -> Self {
+        Self {
+            params,
+            variadic: false,
+        }
+    }
+
+    pub(super) const fn variadic(params: &'static [Param]) -> Self {
+        Self {
+            params,
+            variadic: true,
+        }
+    }
+
+    fn check(&self, argv: &[Value]) -> result::Result<(), VmError> {
+        let argc = argv.len() as u8;
+        if self.variadic {
+            let min = self.params.len() - 1;
+            if argv.len() < min {
+                return Err(VmError::mismatched_argc(min, argc));
+            }
+        } else if argv.len() != self.params.len() {
+            return Err(VmError::mismatched_argc(self.params.len(), argc));
+        }
+
+        for (i, arg) in argv.iter().enumerate() {
+            let param = self.params.get(i).or_else(|| self.params.last()).unwrap();
+            if !param.accepts(arg) {
+                return Err(VmError::invalid_argument(
+                    param,
+                    &arg.get_type(),
+                    i as u8 + 1,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+const NUMBER: Signature = Signature::fixed(&[Param::Number]);
+
 impl Vm {
     pub(super) fn define_builtins(&mut self) {
-        self.define_builtin("print", None, builtin_print);
-        self.define_builtin("println", None, builtin_println);
-        self.define_builtin("int", Some(1), builtin_int);
-        self.define_builtin("int8", Some(1), builtin_int8);
-        self.define_builtin("int16", Some(1), builtin_int16);
-        self.define_builtin("int32", Some(1), builtin_int32);
-        self.define_builtin("int64", Some(1), builtin_int64);
-        self.define_builtin("uint", Some(1), builtin_uint);
-        self.define_builtin("uintptr", Some(1), builtin_uintptr);
-        self.define_builtin("uint8", Some(1), builtin_uint8);
-        self.define_builtin("uint16", Some(1), builtin_uint16);
-        self.define_builtin("uint32", Some(1), builtin_uint32);
-        self.define_builtin("uint64", Some(1), builtin_uint64);
-        self.define_builtin("float32", Some(1), builtin_float32);
-        self.define_builtin("float64", Some(1), builtin_float64);
-        self.define_builtin("string", Some(1), builtin_string);
-        self.define_builtin("len", Some(1), builtin_len);
-        self.define_builtin("append", None, builtin_append);
-        self.define_builtin("complex", Some(2), builtin_complex);
-        self.define_builtin("real", Some(1), builtin_real);
-        self.define_builtin("imag", Some(1), builtin_imag);
-        self.define_builtin("panic", Some(1), builtin_panic);
+        use Param::*;
+
+        self.define_builtin("print", Signature::variadic(&[Any]), builtin_print);
+        self.define_builtin("println", Signature::variadic(&[Any]), builtin_println);
+        self.define_builtin("int", NUMBER, builtin_int);
+        self.define_builtin("int8", NUMBER, builtin_int8);
+        self.define_builtin("int16", NUMBER, builtin_int16);
+        self.define_builtin("int32", NUMBER, builtin_int32);
+        self.define_builtin("rune", NUMBER, builtin_int32);
+        self.define_builtin("int64", NUMBER, builtin_int64);
+        self.define_builtin("uint", NUMBER, builtin_uint);
+        self.define_builtin("uintptr", NUMBER, builtin_uintptr);
+        self.define_builtin("uint8", NUMBER, builtin_uint8);
+        self.define_builtin("byte", NUMBER, builtin_uint8);
+        self.define_builtin("uint16", NUMBER, builtin_uint16);
+        self.define_builtin("uint32", NUMBER, builtin_uint32);
+        self.define_builtin("uint64", NUMBER, builtin_uint64);
+        self.define_builtin("float32", NUMBER, builtin_float32);
+        self.define_builtin("float64", NUMBER, builtin_float64);
+        self.define_builtin("string", Signature::fixed(&[Any]), builtin_string);
+        self.define_builtin("len", Signature::fixed(&[Sized]), builtin_len);
+        self.define_builtin("cap", Signature::fixed(&[Capped]), builtin_cap);
+        self.define_builtin("copy", Signature::fixed(&[Slice, Sized]), builtin_copy);
+        self.define_builtin("append", Signature::variadic(&[Slice, Any]), builtin_append);
+        self.define_builtin(
+            "complex",
+            Signature::fixed(&[Float, Float]),
+            builtin_complex,
+        );
+        self.define_builtin("real", Signature::fixed(&[Complex]), builtin_real);
+        self.define_builtin("imag", Signature::fixed(&[Complex]), builtin_imag);
+        self.define_builtin("panic", Signature::fixed(&[Any]), builtin_panic);
+        self.define_builtin("flush", Signature::fixed(&[]), builtin_flush);
+        self.define_builtin("fmt.Print", Signature::variadic(&[Any]), fmt_print);
+        self.define_builtin("fmt.Println", Signature::variadic(&[Any]), fmt_println);
+        self.define_builtin(
+            "runtime.NumGoroutine",
+            Signature::fixed(&[]),
+            runtime_num_goroutine,
+        );
+        self.define_builtin("runtime.Gosched", Signature::fixed(&[]), runtime_gosched);
     }
 
-    fn define_builtin(&mut self, name: &'static str, argc: Option<u8>, func: Builtin) {
-        self.builtins
-            .insert(name.to_string(), FuncBuiltin::new(name, argc, func))
-            .unwrap_or(());
+    /// Builtins are defined in the order of the ids the compiler calls them by
+    fn define_builtin(&mut self, name: &'static str, signature: Signature, func: Builtin) {
+        let id = self.builtins.len();
+        assert_eq!(
+            BUILTINS.get(id),
+            Some(&name),
+            "Builtin defined out of order."
+        );
+        self.builtins.push(FuncBuiltin::new(name, signature, func));
     }
 }
 
 /// https://pkg.go.dev/builtin#print
-fn builtin_print(argv: &[Value], streams: &dyn StreamProvider) -> CallResult {
+fn builtin_print(argv: &[Value], streams: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     write!(
         &mut streams.stream_err(),
         "{}",
         argv.iter()
-            .map(|v| v.to_string())
+            .map(runtime_format)
             .collect::<Vec<String>>()
             .join("")
     )
@@ -83,12 +226,59 @@ fn builtin_print(argv: &[Value], streams: &dyn StreamProvider) -> CallResult {
 }
 
 /// https://pkg.go.dev/builtin#println
-fn builtin_println(argv: &[Value], streams: &dyn StreamProvider) -> CallResult {
+fn builtin_println(argv: &[Value], streams: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     writeln!(
         &mut streams.stream_err(),
         "{}",
         argv.iter()
-            .map(|v| v.to_string())
+            .map(runtime_format)
+            .collect::<Vec<String>>()
+            .join(" ")
+    )
+    .unwrap();
+    Ok(None)
+}
+
+/// Writes out the buffered output, not a Go builtin
+fn builtin_flush(_: &[Value], streams: &dyn StreamProvider, _: &mut Heap) -> CallResult {
+    streams.flush()?;
+    Ok(None)
+}
+
+/// https://pkg.go.dev/fmt#Print
+fn fmt_print(argv: &[Value], streams: &dyn StreamProvider, _: &mut Heap) -> CallResult {
+    let mut out = String::new();
+    for (i, v) in argv.iter().enumerate() {
+        // spaces are added between operands when neither is a string
+        if i > 0 && !matches!(v, Value::String(_)) && !matches!(argv[i - 1], Value::String(_)) {
+            out.push(' ');
+        }
+        out.push_str(&fmt_format(v));
+    }
+
+    write!(&mut streams.stream_out(), "{}", out).unwrap();
+    Ok(None)
+}
+
+/// https://pkg.go.dev/runtime#NumGoroutine
+/// The program runs in a single goroutine
+fn runtime_num_goroutine(_: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
+    Ok(Some(Value::Int(1)))
+}
+
+/// https://pkg.go.dev/runtime#Gosched
+/// There are no other goroutines to yield to
+fn runtime_gosched(_: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
+    Ok(None)
+}
+
+/// https://pkg.go.dev/fmt#Println
+fn fmt_println(argv: &[Value], streams: &dyn StreamProvider, _: &mut Heap) -> CallResult {
+    writeln!(
+        &mut streams.stream_out(),
+        "{}",
+        argv.iter()
+            .map(fmt_format)
             .collect::<Vec<String>>()
             .join(" ")
     )
@@ -97,200 +287,221 @@ fn builtin_println(argv: &[Value], streams: &dyn StreamProvider) -> CallResult {
 }
 
 /// https://pkg.go.dev/builtin#int
-fn builtin_int(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_int(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Int);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#int8
-fn builtin_int8(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_int8(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Int8);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#int16
-fn builtin_int16(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_int16(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Int16);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#int32
-fn builtin_int32(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_int32(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Int32);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#int64
-fn builtin_int64(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_int64(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Int64);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#uint
-fn builtin_uint(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_uint(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Uint);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#uint8
-fn builtin_uint8(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_uint8(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Uint8);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#uint16
-fn builtin_uint16(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_uint16(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Uint16);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#uint32
-fn builtin_uint32(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_uint32(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Uint32);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#uint64
-fn builtin_uint64(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_uint64(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Uint64);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#uintptr
-fn builtin_uintptr(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_uintptr(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Uintptr);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#float32
-fn builtin_float32(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_float32(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Float32);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#float64
-fn builtin_float64(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_float64(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let v = v.cast_to(ValType::Float64);
     Ok(Some(v))
 }
 
 /// https://pkg.go.dev/builtin#string
-fn builtin_string(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_string(argv: &[Value], _: &dyn StreamProvider, heap: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
-    let v = match v {
-        Value::String(v) => v.clone(),
-        Value::Slice(iter, ValType::Slice(vtype))
-            if matches!(**vtype, ValType::Int32 | ValType::Uint8) =>
-        {
-            let chars: String = iter
-                .as_ref()
-                .borrow()
-                .iter()
-                .map(|v| unsafe { char::from_u32_unchecked(v.to_usize().unwrap() as u32) })
-                .collect();
-            chars
-        }
-        _ => {
-            if let Some(int) = v.to_usize() {
-                unsafe { char::from_u32_unchecked(int as u32).to_string() }
-            } else {
-                return Err(VmError::invalid_argument(
-                    "uint8 slice, int32 slice, string or integer",
-                    &v.get_type(),
-                    1,
-                ));
-            }
-        }
-    };
-
-    Ok(Some(Value::String(v)))
+    match v.convert_string(&ValType::String, heap) {
+        Some(s) => Ok(Some(s)),
+        None => Err(VmError::invalid_argument(
+            "uint8 slice, int32 slice, string or integer",
+            &v.get_type(),
+            1,
+        )),
+    }
 }
 
 /// https://pkg.go.dev/builtin#len
-fn builtin_len(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_len(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     let len = match v {
         Value::String(v) => v.len(),
         Value::Array(_, size, _) => *size,
-        Value::Slice(iter, _) => iter.borrow().len(),
-        _ => {
-            return Err(VmError::invalid_argument(
-                "string, array, slice",
-                &v.get_type(),
-                1,
-            ));
-        }
+        Value::Slice(slice, _) => slice.len(),
+        _ => unreachable!(),
     };
 
     // go specification dictates type int, not uint
     Ok(Some(Value::Int(len as isize)))
 }
 
+/// https://pkg.go.dev/builtin#cap
+fn builtin_cap(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
+    let cap = match argv.first().unwrap() {
+        Value::Array(_, size, _) => *size,
+        Value::Slice(slice, _) => slice.cap(),
+        _ => unreachable!(),
+    };
+
+    Ok(Some(Value::Int(cap as isize)))
+}
+
+/// https://pkg.go.dev/builtin#copy
+/// The source is read before anything is written, so overlapping slices are copied correctly
+fn builtin_copy(argv: &[Value], _: &dyn StreamProvider, heap: &mut Heap) -> CallResult {
+    let (dst, dst_type) = match &argv[0] {
+        Value::Slice(dst, dst_type) => (dst, dst_type),
+        _ => unreachable!(),
+    };
+
+    let src = match &argv[1] {
+        Value::Slice(src, src_type) if src_type == dst_type => src.to_vec(),
+        Value::String(s) if *dst_type == ValType::Slice(Box::new(ValType::Uint8)) => {
+            s.bytes().map(Value::Uint8).collect()
+        }
+        v => return Err(VmError::invalid_argument(dst_type, &v.get_type(), 2)),
+    };
+
+    let n = src.len().min(dst.len());
+    for (i, v) in src.into_iter().take(n).enumerate() {
+        dst.set(i, v, heap);
+    }
+
+    Ok(Some(Value::Int(n as isize)))
+}
+
 /// https://pkg.go.dev/builtin#append
-fn builtin_append(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_append(argv: &[Value], _: &dyn StreamProvider, heap: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
-    if let Value::Slice(slice, ValType::Slice(vtype)) = v {
+    if let Value::Slice(slice, slice_type @ ValType::Slice(vtype)) = v {
+        let mut vals = Vec::with_capacity(argv.len() - 1);
         for (i, arg) in argv.iter().skip(1).enumerate() {
             if !arg.is_of_type(vtype) {
-                return Err(VmError::invalid_argument(vtype, &arg.get_type(), i as u8));
+                return Err(VmError::invalid_argument(
+                    vtype,
+                    &arg.get_type(),
+                    i as u8 + 2,
+                ));
             }
 
-            slice.borrow_mut().push(arg.clone());
+            let mut arg = arg.clone();
+            arg.lose_literal(vtype);
+            arg.copy_if_soft_reference(heap);
+            vals.push(arg);
         }
-    } else {
-        return Err(VmError::invalid_argument("slice", &v.get_type(), 1));
+
+        let zero = Value::default(vtype, heap)?;
+        let slice = slice.append(vals, &zero, heap);
+        return Ok(Some(Value::Slice(slice, slice_type.clone())));
     }
 
     Ok(Some(v.clone()))
 }
 
 /// https://pkg.go.dev/builtin#complex
-fn builtin_complex(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_complex(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     match (&argv[0], &argv[1]) {
         (Value::Float32(real), Value::Float32(imag)) => Ok(Some(Value::Complex64(*real, *imag))),
         (Value::Float64(real), Value::Float64(imag)) => Ok(Some(Value::Complex128(*real, *imag))),
-        (real @ Value::Float32(_), imag) | (real @ Value::Float64(_), imag) => Err(
-            VmError::invalid_argument(&real.get_type(), &imag.get_type(), 1),
-        ),
-        (real, _) => Err(VmError::invalid_argument("float", &real.get_type(), 0)),
+        (real, imag) => Err(VmError::invalid_argument(
+            &real.get_type(),
+            &imag.get_type(),
+            2,
+        )),
     }
 }
 
 /// https://pkg.go.dev/builtin#imag
-fn builtin_imag(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_imag(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     match v {
         Value::Complex64(_, imag) => Ok(Some(Value::Float32(*imag))),
         Value::Complex128(_, imag) => Ok(Some(Value::Float64(*imag))),
-        _ => Err(VmError::invalid_argument("complex", &v.get_type(), 0)),
+        _ => unreachable!(),
     }
 }
 
 /// https://pkg.go.dev/builtin#real
-fn builtin_real(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_real(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
     match v {
         Value::Complex64(real, _) => Ok(Some(Value::Float32(*real))),
         Value::Complex128(real, _) => Ok(Some(Value::Float64(*real))),
-        _ => Err(VmError::invalid_argument("complex", &v.get_type(), 0)),
+        _ => unreachable!(),
     }
 }
 
 /// https://pkg.go.dev/builtin#panic
-fn builtin_panic(argv: &[Value], _: &dyn StreamProvider) -> CallResult {
+fn builtin_panic(argv: &[Value], _: &dyn StreamProvider, _: &mut Heap) -> CallResult {
     let v = argv.first().unwrap();
-    panic!("{}", v)
+    Err(VmError::panic(v))
 }
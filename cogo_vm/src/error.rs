@@ -1,28 +1,127 @@
 use std::fmt::Formatter;
 use std::{fmt, io};
 
-use cogo_compiler::{CompilationUnit, TypeError, ValType};
+use cogo_compiler::{
+    CompilationUnit, Diagnostic, Pos, TypeError, ValType, Value, VerificationError,
+};
 
 use crate::name_table::NameError;
 use crate::stack::StackUnderflow;
+use crate::trace::StackTrace;
 
 /// Errors occurred in the virtual machine runtime.
+///
+/// Displayed as `<file>:<line>: <message>` followed by an empty line and the stack trace,
+/// the parts that are unknown are omitted. Panics are displayed as `panic: <value>` instead.
 #[derive(Debug)]
 pub enum VmError {
-    /// Most of the errors belong here as it is the runtime that produces them
+    /// Values of types the operation does not accept
+    Type(String),
+    /// Names that are not defined or cannot be assigned to
+    Name(String),
+    /// Wrong number of arguments passed or values returned
+    Arity(String),
+    /// Program called the `panic` builtin with the value
+    Panic(String),
+    /// Errors of otherwise valid programs, e.g. indices out of range
     Runtime(String),
     /// Despite the runtime nature of the errors, some of them does not make
     /// much sense to users and signify the compilation errors that were not caught during the compilation.
-    Compile(String),
+    Internal(String),
+    /// Error along with the source file and the position of the opcode that caused it
+    Located(Box<VmError>, String, Pos),
+    /// Error along with the call stack at the moment it occurred
+    Traced(Box<VmError>, StackTrace),
 }
 
 impl VmError {
+    pub(crate) fn with_trace(self, trace: StackTrace) -> Self {
+        match self {
+            Self::Traced(..) => self,
+            err => Self::Traced(Box::new(err), trace),
+        }
+    }
+
+    pub(crate) fn at(self, source_name: String, pos: Pos) -> Self {
+        match self {
+            Self::Located(..) | Self::Traced(..) => self,
+            err => Self::Located(Box::new(err), source_name, pos),
+        }
+    }
+
+    pub fn trace(&self) -> Option<&StackTrace> {
+        match self {
+            Self::Traced(_, trace) => Some(trace),
+            _ => None,
+        }
+    }
+
+    /// Position in the source of the opcode that caused the error
+    pub fn pos(&self) -> Option<Pos> {
+        match self {
+            Self::Located(_, _, pos) => Some(*pos),
+            Self::Traced(err, _) => err.pos(),
+            _ => None,
+        }
+    }
+
+    /// Error without the position and the stack trace attached
+    pub fn cause(&self) -> &Self {
+        match self {
+            Self::Located(err, ..) | Self::Traced(err, _) => err.cause(),
+            err => err,
+        }
+    }
+
+    /// Name of the category of the error, e.g. `type` or `panic`
+    pub fn category(&self) -> &'static str {
+        match self.cause() {
+            Self::Type(_) => "type",
+            Self::Name(_) => "name",
+            Self::Arity(_) => "arity",
+            Self::Panic(_) => "panic",
+            Self::Runtime(_) => "runtime",
+            Self::Internal(_) | Self::Located(..) | Self::Traced(..) => "internal",
+        }
+    }
+
+    /// Message of the error alone
+    pub fn msg(&self) -> &str {
+        match self.cause() {
+            Self::Type(msg)
+            | Self::Name(msg)
+            | Self::Arity(msg)
+            | Self::Panic(msg)
+            | Self::Runtime(msg)
+            | Self::Internal(msg) => msg,
+            Self::Located(..) | Self::Traced(..) => "",
+        }
+    }
+
+    /// Status the process running the program exits with, 2 for panics as the Go runtime does
+    pub fn exit_code(&self) -> i32 {
+        match self.cause() {
+            Self::Panic(_) => 2,
+            _ => 1,
+        }
+    }
+
+    /// Structured form of the error, without the source name and the stack trace
+    pub fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(
+            self.category(),
+            self.msg().to_string(),
+            self.pos().unwrap_or(Pos(0, 0)),
+            None,
+        )
+    }
+
     // -----
     // Type errors
 
     /// Generic type error, when the actual type does not match the one that was expected
     pub(super) fn type_error(expected: &ValType, actual: &ValType) -> Self {
-        Self::Runtime(format!(
+        Self::Type(format!(
             "Got value of type \"{}\" but expected type \"{}\"",
             actual, expected,
         ))
@@ -33,7 +132,7 @@ impl VmError {
         T: fmt::Display,
         T: ?Sized,
     {
-        Self::Runtime(format!(
+        Self::Type(format!(
             "Invalid argument {} type \"{}\", expected \"{}\"",
             n, actual, expected,
         ))
@@ -41,7 +140,7 @@ impl VmError {
 
     /// Error in function return value type
     pub(super) fn return_type_error(expected: &ValType, actual: &ValType) -> Self {
-        Self::Runtime(format!(
+        Self::Type(format!(
             "Function must return value of type \"{}\", got type \"{}\".",
             expected, actual,
         ))
@@ -50,9 +149,9 @@ impl VmError {
     /// Error in function return value count
     pub(super) fn return_count_error(expected: usize, actual: usize) -> Self {
         if expected == 0 {
-            Self::Runtime("Function must not return value.".to_string())
+            Self::Arity("Function must not return value.".to_string())
         } else {
-            Self::Runtime(format!(
+            Self::Arity(format!(
                 "Function must return exactly {} values, returned {}.",
                 expected, actual,
             ))
@@ -62,7 +161,7 @@ impl VmError {
     /// When non-boolean values are being used in conditional statements where only booleans
     /// makes sense. `if cond` or `for cond` are the examples
     pub(super) fn non_bool_in_condition(actual: &ValType) -> Self {
-        Self::Runtime(format!(
+        Self::Type(format!(
             "Type \"{}\" used in condition, expected \"bool\"",
             actual,
         ))
@@ -71,24 +170,91 @@ impl VmError {
     /// Accessing an array or a slice with a non-integer type of index.
     /// Indices may only be any of integer types: `uint`, `int8` etc.
     pub(super) fn index_type_error(actual: &ValType) -> Self {
-        Self::Runtime(format!(
+        Self::Type(format!(
             "Indices must be of integer types, got \"{}\"",
             actual,
         ))
     }
 
+    /// Accessing an array or a slice past its length
+    pub(super) fn index_out_of_range(index: usize, len: usize) -> Self {
+        Self::Runtime(format!(
+            "index out of range [{}] with length {}",
+            index, len,
+        ))
+    }
+
+    /// Slice expression with the last bound past the capacity of an array or a slice,
+    /// or past the length of a string. Bounds are formatted as in `:5` or `::5`
+    pub(super) fn slice_bound_out_of_range(bounds: &str, limit: usize, string: bool) -> Self {
+        Self::Runtime(format!(
+            "slice bounds out of range [{}] with {} {}",
+            bounds,
+            if string { "length" } else { "capacity" },
+            limit,
+        ))
+    }
+
+    /// Slice expression with a bound greater than the one following it
+    pub(super) fn slice_bounds_inverted(bounds: &str) -> Self {
+        Self::Runtime(format!("slice bounds out of range [{}]", bounds))
+    }
+
+    /// Conversion between types that are not convertible
+    pub(super) fn conversion(from: &ValType, to: &ValType) -> Self {
+        Self::Type(format!(
+            "Cannot convert value of type \"{}\" to \"{}\"",
+            from, to,
+        ))
+    }
+
+    /// Only integers can be ranged over so far
+    pub(super) fn range(vtype: &ValType) -> Self {
+        Self::Type(format!("Cannot range over value of type \"{}\"", vtype))
+    }
+
+    /// Strings have no capacity to limit with `s[low:high:max]`
+    pub(super) fn string_full_slice() -> Self {
+        Self::Type("Invalid operation: 3-index slice of string".to_string())
+    }
+
+    /// Calls are nested deeper than the VM allows
+    pub(super) fn stack_overflow(max_depth: usize) -> Self {
+        Self::Runtime(format!(
+            "stack overflow, call depth exceeds {} frames",
+            max_depth,
+        ))
+    }
+
+    /// Arrays and slices hold more values than the VM allows
+    pub(super) fn out_of_memory(limit: usize) -> Self {
+        Self::Runtime(format!(
+            "out of memory, arrays and slices hold more than {} values",
+            limit,
+        ))
+    }
+
+    pub(super) fn nil_func_call() -> Self {
+        Self::Runtime("invalid memory address or nil pointer dereference".to_string())
+    }
+
     // -----
 
     pub(super) fn mismatched_argc(expected: usize, actual: u8) -> Self {
-        Self::Runtime(format!("Expected {} params, got {}", expected, actual,))
+        Self::Arity(format!("Expected {} params, got {}", expected, actual,))
     }
 
     pub(super) fn undefined(name: &str) -> Self {
-        Self::Runtime(format!("Undefined \"{}\".", name))
+        Self::Name(format!("Undefined \"{}\".", name))
+    }
+
+    /// Strings are immutable, their bytes cannot be assigned to
+    pub(super) fn string_assignment() -> Self {
+        Self::Type("Cannot assign to an index of a string".to_string())
     }
 
     pub(super) fn assignment(name: &str) -> Self {
-        Self::Runtime(format!("Cannot assign to \"{}\".", name))
+        Self::Name(format!("Cannot assign to \"{}\".", name))
     }
 
     pub(super) fn non_exhaustive_matching_result() -> Self {
@@ -96,36 +262,47 @@ impl VmError {
     }
 
     pub(super) fn wrong_array_size(expected: usize, actual: usize) -> Self {
-        Self::Runtime(format!(
+        Self::Type(format!(
             "Expected array of size \"{}\", got \"{}\".",
             expected, actual,
         ))
     }
 
+    /// Program called `panic(v)`
+    pub(super) fn panic(v: &Value) -> Self {
+        Self::Panic(v.to_string())
+    }
+
     // ------
-    // Compile errors are basically beautified `panic` messages
+    // Internal errors are basically beautified `panic` messages
     // Having them simply means there is an error in the compiler logic
 
-    pub(super) fn iterator_value_expected(actual: &ValType) -> Self {
-        Self::Compile(format!("Expected iterator, got type {}", actual,))
+    pub(super) fn iterator_value_expected(actual: &Value) -> Self {
+        match actual {
+            // builtins are no values of their own type
+            Value::FuncBuiltin(name) => {
+                Self::Internal(format!("Expected iterator, got builtin {}", name))
+            }
+            _ => Self::Internal(format!("Expected iterator, got type {}", actual.get_type())),
+        }
     }
 
     pub(super) fn callable_value_expected(actual: &ValType) -> Self {
-        Self::Compile(format!(
+        Self::Internal(format!(
             "Trying to call a non-callable value \"{}\"",
             actual,
         ))
     }
 
     pub(super) fn incorrectly_typed(what: &str, actual: &ValType) -> Self {
-        Self::Compile(format!(
+        Self::Internal(format!(
             "Value of {} is wrongly typed: \"{}\"",
             what, actual
         ))
     }
 
     pub(super) fn unexpected_matching_result() -> Self {
-        Self::Compile("Unexpected matching result".to_string())
+        Self::Internal("Unexpected matching result".to_string())
     }
 
     // ------
@@ -133,13 +310,19 @@ impl VmError {
 
 impl From<StackUnderflow> for VmError {
     fn from(_: StackUnderflow) -> Self {
-        Self::Compile("Stack Underflow error".to_string())
+        Self::Internal("Stack Underflow error".to_string())
+    }
+}
+
+impl From<VerificationError> for VmError {
+    fn from(e: VerificationError) -> Self {
+        Self::Internal(e.to_string())
     }
 }
 
 impl From<TypeError> for VmError {
     fn from(e: TypeError) -> Self {
-        Self::Runtime(e.0)
+        Self::Type(e.0)
     }
 }
 
@@ -151,18 +334,26 @@ impl From<io::Error> for VmError {
 
 impl From<NameError> for VmError {
     fn from(e: NameError) -> Self {
-        Self::Runtime(e.0)
+        Self::Name(e.0)
     }
 }
 
 impl fmt::Display for VmError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        let msg = match &self {
-            Self::Compile(s) => s,
-            Self::Runtime(s) => s,
-        };
-
-        write!(f, "{}", msg)
+        match &self {
+            Self::Type(s)
+            | Self::Name(s)
+            | Self::Arity(s)
+            | Self::Runtime(s)
+            | Self::Internal(s) => write!(f, "{}", s),
+            Self::Panic(s) => write!(f, "panic: {}", s),
+            // panics are reported the way the Go runtime does, the trace tells the position
+            Self::Located(err, ..) if matches!(**err, Self::Panic(_)) => write!(f, "{}", err),
+            Self::Located(err, source_name, pos) => {
+                write!(f, "{}:{}: {}", source_name, pos.0, err)
+            }
+            Self::Traced(err, trace) => write!(f, "{}\n\n{}", err, trace),
+        }
     }
 }
 
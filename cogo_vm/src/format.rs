@@ -0,0 +1,253 @@
+use cogo_compiler::Value;
+
+/// `fmt` verb a value is formatted with
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FormatVerb {
+    /// `%v`, as `fmt.Print` formats values
+    Value,
+    /// `%+v`, the same as `%v` while there are no structs to name the fields of
+    ValueWithFields,
+    /// `%#v`, the Go syntax of the value along with its type, e.g. `[]int{1, 2}`
+    GoSyntax,
+}
+
+/// Value formatted the way the `fmt` package does with the verb,
+/// also how a debugger shows the values of variables
+pub fn format_value(v: &Value, verb: FormatVerb) -> String {
+    match verb {
+        FormatVerb::Value | FormatVerb::ValueWithFields => fmt_format(v),
+        FormatVerb::GoSyntax => go_syntax_format(v),
+    }
+}
+
+/// Value formatted the way the `print` and `println` builtins of the Go runtime do,
+/// arrays and slices, which the Go runtime cannot print, are formatted as `fmt` does
+pub(crate) fn runtime_format(v: &Value) -> String {
+    match v {
+        Value::Float32(f) => runtime_float(*f as f64),
+        Value::Float64(f) | Value::FloatLiteral(f) => runtime_float(*f),
+        Value::Complex64(r, i) => format!(
+            "({}{}i)",
+            runtime_float(*r as f64),
+            runtime_float(*i as f64)
+        ),
+        Value::Complex128(r, i) => format!("({}{}i)", runtime_float(*r), runtime_float(*i)),
+        Value::Array(iter, ..) => format_elements(&iter.borrow(), runtime_format),
+        Value::Slice(slice, _) => format_elements(&slice.to_vec(), runtime_format),
+        v => v.to_string(),
+    }
+}
+
+/// Value formatted the way `fmt.Print` and `fmt.Println` do with the `%v` verb
+pub(crate) fn fmt_format(v: &Value) -> String {
+    match v {
+        Value::Nil => "<nil>".to_string(),
+        Value::Float32(f) => fmt_float(f.to_string(), format!("{:e}", f)),
+        Value::Float64(f) | Value::FloatLiteral(f) => fmt_float(f.to_string(), format!("{:e}", f)),
+        Value::Complex64(r, i) => fmt_complex(
+            fmt_float(r.to_string(), format!("{:e}", r)),
+            fmt_float(i.to_string(), format!("{:e}", i)),
+        ),
+        Value::Complex128(r, i) => fmt_complex(
+            fmt_float(r.to_string(), format!("{:e}", r)),
+            fmt_float(i.to_string(), format!("{:e}", i)),
+        ),
+        Value::Array(iter, ..) => format_elements(&iter.borrow(), fmt_format),
+        Value::Slice(slice, _) => format_elements(&slice.to_vec(), fmt_format),
+        v => v.to_string(),
+    }
+}
+
+/// Value formatted the way `fmt` does with the `%#v` verb, unsigned integers are hexadecimal
+fn go_syntax_format(v: &Value) -> String {
+    match v {
+        Value::Nil => "<nil>".to_string(),
+        Value::Uint8(i) => format!("{:#x}", i),
+        Value::Uint16(i) => format!("{:#x}", i),
+        Value::Uint32(i) => format!("{:#x}", i),
+        Value::Uint64(i) => format!("{:#x}", i),
+        Value::Uint(i) | Value::Uintptr(i) => format!("{:#x}", i),
+        Value::String(s) => go_quote(s),
+        Value::Array(iter, ..) => go_syntax_elements(v, &iter.borrow()),
+        Value::Slice(slice, _) => go_syntax_elements(v, &slice.to_vec()),
+        Value::Func(_, ftype) => format!("({})({})", ftype, v),
+        v => fmt_format(v),
+    }
+}
+
+/// Composite literal of the array or slice, e.g. `[2]int{1, 2}`
+fn go_syntax_elements(composite: &Value, vals: &[Value]) -> String {
+    format!(
+        "{}{{{}}}",
+        composite.get_type(),
+        vals.iter()
+            .map(go_syntax_format)
+            .collect::<Vec<String>>()
+            .join(", ")
+    )
+}
+
+/// Double-quoted string with the escapes `strconv.Quote` uses
+fn go_quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            '\u{7}' => quoted.push_str("\\a"),
+            '\u{8}' => quoted.push_str("\\b"),
+            '\u{b}' => quoted.push_str("\\v"),
+            '\u{c}' => quoted.push_str("\\f"),
+            c if c.is_control() && (c as u32) < 0x80 => {
+                quoted.push_str(&format!("\\x{:02x}", c as u32))
+            }
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Elements in brackets separated by spaces
+fn format_elements(vals: &[Value], format: fn(&Value) -> String) -> String {
+    format!(
+        "[{}]",
+        vals.iter().map(format).collect::<Vec<String>>().join(" ")
+    )
+}
+
+/// Sign, one digit, six decimals and a three-digit exponent, e.g. `+1.500000e+000`
+fn runtime_float(f: f64) -> String {
+    if f.is_nan() {
+        return "NaN".to_string();
+    }
+    if f.is_infinite() {
+        return if f < 0.0 { "-Inf" } else { "+Inf" }.to_string();
+    }
+
+    let sci = format!("{:.6e}", f.abs());
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+
+    format!(
+        "{}{}e{}{:03}",
+        if f.is_sign_negative() { '-' } else { '+' },
+        mantissa,
+        if exp < 0 { '-' } else { '+' },
+        exp.abs()
+    )
+}
+
+/// Shortest representation, in the exponent form for large and small exponents,
+/// takes both the decimal and the scientific shortest representations of the float
+fn fmt_float(decimal: String, sci: String) -> String {
+    match decimal.as_str() {
+        "NaN" => return decimal,
+        "inf" => return "+Inf".to_string(),
+        "-inf" => return "-Inf".to_string(),
+        _ => {}
+    }
+
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    if !(-4..21).contains(&exp) {
+        format!(
+            "{}e{}{:02}",
+            mantissa,
+            if exp < 0 { '-' } else { '+' },
+            exp.abs()
+        )
+    } else {
+        decimal
+    }
+}
+
+fn fmt_complex(real: String, imag: String) -> String {
+    if imag.starts_with('-') || imag.starts_with('+') {
+        format!("({}{}i)", real, imag)
+    } else {
+        format!("({}+{}i)", real, imag)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cogo_compiler::{Unmanaged, ValType};
+
+    use super::*;
+
+    #[test]
+    fn test_runtime_format() {
+        assert_eq!(runtime_format(&Value::Float64(1.5)), "+1.500000e+000");
+        assert_eq!(runtime_format(&Value::Float64(-0.00034)), "-3.400000e-004");
+        assert_eq!(runtime_format(&Value::Float64(0.0)), "+0.000000e+000");
+        assert_eq!(runtime_format(&Value::Float32(12.9)), "+1.290000e+001");
+        assert_eq!(runtime_format(&Value::Float64(f64::NEG_INFINITY)), "-Inf");
+        assert_eq!(
+            runtime_format(&Value::Complex128(1.0, -2.0)),
+            "(+1.000000e+000-2.000000e+000i)"
+        );
+        assert_eq!(runtime_format(&Value::Int(-7)), "-7");
+    }
+
+    #[test]
+    fn test_fmt_format() {
+        assert_eq!(fmt_format(&Value::Float64(1.5)), "1.5");
+        assert_eq!(fmt_format(&Value::Float64(100.0)), "100");
+        assert_eq!(fmt_format(&Value::Float64(1e21)), "1e+21");
+        assert_eq!(fmt_format(&Value::Float64(0.00001)), "1e-05");
+        assert_eq!(fmt_format(&Value::Float32(12.9)), "12.9");
+        assert_eq!(fmt_format(&Value::Float64(f64::NAN)), "NaN");
+        assert_eq!(fmt_format(&Value::Complex128(1.0, 2.0)), "(1+2i)");
+        assert_eq!(fmt_format(&Value::Complex64(0.5, -2.0)), "(0.5-2i)");
+        assert_eq!(fmt_format(&Value::Nil), "<nil>");
+    }
+
+    #[test]
+    fn test_go_syntax_format() {
+        let go_syntax = |v: &Value| format_value(v, FormatVerb::GoSyntax);
+        assert_eq!(go_syntax(&Value::Int(-7)), "-7");
+        assert_eq!(go_syntax(&Value::Uint8(255)), "0xff");
+        assert_eq!(go_syntax(&Value::Float64(100.0)), "100");
+        assert_eq!(go_syntax(&Value::Bool(true)), "true");
+        assert_eq!(
+            go_syntax(&Value::String("say \"hi\"\n\u{1}é".into())),
+            r#""say \"hi\"\n\x01é""#
+        );
+
+        let ints = ValType::Slice(Box::new(ValType::Int));
+        let nested = Value::new_slice(
+            vec![
+                Value::new_slice(
+                    vec![Value::Int(1), Value::Int(2)],
+                    ints.clone(),
+                    &mut Unmanaged,
+                ),
+                Value::new_slice(vec![], ints.clone(), &mut Unmanaged),
+            ],
+            ValType::Slice(Box::new(ints)),
+            &mut Unmanaged,
+        );
+        assert_eq!(go_syntax(&nested), "[][]int{[]int{1, 2}, []int{}}");
+        assert_eq!(format_value(&nested, FormatVerb::Value), "[[1 2] []]");
+        assert_eq!(
+            format_value(&nested, FormatVerb::ValueWithFields),
+            "[[1 2] []]"
+        );
+
+        let floats = Value::new_array(
+            vec![Value::Float64(1.5), Value::Float64(-2.0)],
+            2,
+            ValType::Array(Box::new(ValType::Float64), 2),
+            &mut Unmanaged,
+        );
+        assert_eq!(go_syntax(&floats), "[2]float64{1.5, -2}");
+        assert_eq!(go_syntax(&Value::Nil), "<nil>");
+    }
+}
@@ -0,0 +1,108 @@
+use std::cell::RefCell;
+use std::rc::{Rc, Weak};
+
+use cogo_compiler::{Allocator, RefIterator, Value};
+
+use crate::error::VmError;
+use crate::vm::VmResult;
+
+/// Sweeps are skipped while the heap knows about fewer allocations
+const MIN_SWEEP: usize = 1024;
+
+/// Backing arrays of the arrays and slices made by the program, every one of them
+/// is allocated here. The heap keeps a weak reference to each of them along with its size,
+/// so it knows how many values are in use and can refuse to hold more than the limit.
+/// The arrays are still freed once the last value referring to them is dropped,
+/// the heap forgets about them on the next sweep
+pub(crate) struct Heap {
+    allocs: Vec<(Weak<RefCell<Vec<Value>>>, usize)>,
+    /// Values held by the allocations the heap knows about, dead ones included until a sweep
+    size: usize,
+    /// Number of allocations the next one sweeps the dead ones at
+    next_sweep: usize,
+    /// Most values the live allocations may hold, `None` means no limit
+    limit: Option<usize>,
+    /// Whether an allocation has exceeded the limit, which fails the execution
+    exhausted: bool,
+}
+
+impl Heap {
+    pub(crate) fn new(limit: Option<usize>) -> Self {
+        Self {
+            allocs: vec![],
+            size: 0,
+            next_sweep: MIN_SWEEP,
+            limit,
+            exhausted: false,
+        }
+    }
+
+    /// Values held by the live arrays and slices
+    pub(crate) fn live_values(&mut self) -> usize {
+        self.sweep();
+        self.size
+    }
+
+    /// Fails once an allocation has made the live values exceed the limit
+    pub(crate) fn check(&self) -> VmResult<()> {
+        match self.limit {
+            Some(limit) if self.exhausted => Err(VmError::out_of_memory(limit)),
+            _ => Ok(()),
+        }
+    }
+
+    /// Forgets the allocations nothing refers to anymore
+    fn sweep(&mut self) {
+        self.allocs.retain(|(array, _)| array.strong_count() > 0);
+        self.size = self.allocs.iter().map(|(_, size)| size).sum();
+        self.next_sweep = (self.allocs.len() * 2).max(MIN_SWEEP);
+    }
+}
+
+impl Allocator for Heap {
+    /// The sizes of the arrays are fixed, appending past the capacity allocates a new one
+    fn alloc(&mut self, vals: Vec<Value>) -> RefIterator {
+        let size = vals.len();
+        let over_limit = self.limit.is_some_and(|limit| self.size + size > limit);
+        if over_limit || self.allocs.len() >= self.next_sweep {
+            self.sweep();
+        }
+
+        self.size += size;
+        self.exhausted |= self.limit.is_some_and(|limit| self.size > limit);
+
+        let array = Rc::new(RefCell::new(vals));
+        self.allocs.push((Rc::downgrade(&array), size));
+
+        array
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_live_values() {
+        let mut heap = Heap::new(None);
+        let kept = heap.alloc(vec![Value::Int(1), Value::Int(2)]);
+        drop(heap.alloc(vec![Value::Int(3)]));
+
+        assert_eq!(heap.live_values(), 2);
+        drop(kept);
+        assert_eq!(heap.live_values(), 0);
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut heap = Heap::new(Some(3));
+        for _ in 0..10 {
+            drop(heap.alloc(vec![Value::Int(1), Value::Int(2)]));
+        }
+        assert!(heap.check().is_ok());
+
+        let _kept = heap.alloc(vec![Value::Int(1), Value::Int(2)]);
+        let _over = heap.alloc(vec![Value::Int(3), Value::Int(4)]);
+        assert!(heap.check().is_err());
+    }
+}
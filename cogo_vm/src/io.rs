@@ -1,5 +1,7 @@
 use std::cell::{RefCell, RefMut};
+use std::fs::File;
 use std::io;
+use std::path::Path;
 use std::rc::Rc;
 
 type WriteStream = dyn io::Write;
@@ -10,11 +12,19 @@ type StderrStream = Rc<RefCell<WriteStream>>;
 type StdinStream = Rc<RefCell<ReadStream>>;
 
 pub trait StreamProvider {
-    fn stream_out(&self) -> RefMut<WriteStream>;
-    fn stream_err(&self) -> RefMut<WriteStream>;
-    fn stream_in(&self) -> RefMut<ReadStream>;
+    fn stream_out(&self) -> RefMut<'_, WriteStream>;
+    fn stream_err(&self) -> RefMut<'_, WriteStream>;
+    fn stream_in(&self) -> RefMut<'_, ReadStream>;
+
+    /// Writes out everything buffered in the output streams,
+    /// the VM calls it once the program exits and on the `flush` builtin
+    fn flush(&self) -> io::Result<()> {
+        self.stream_out().flush()?;
+        self.stream_err().flush()
+    }
 }
 
+/// Streams of the process by default, the output ones are buffered
 pub struct StdStreamProvider {
     stdout: StdoutStream,
     stderr: StderrStream,
@@ -33,10 +43,10 @@ impl StdStreamProvider {
         let (stdout, stderr, stdin) = (
             streams
                 .0
-                .unwrap_or_else(|| Rc::new(RefCell::new(std::io::stdout()))),
+                .unwrap_or_else(|| Rc::new(RefCell::new(io::BufWriter::new(io::stdout())))),
             streams
                 .1
-                .unwrap_or_else(|| Rc::new(RefCell::new(std::io::stderr()))),
+                .unwrap_or_else(|| Rc::new(RefCell::new(io::BufWriter::new(io::stderr())))),
             streams
                 .2
                 .unwrap_or_else(|| Rc::new(RefCell::new(std::io::stdin()))),
@@ -51,15 +61,168 @@ impl StdStreamProvider {
 }
 
 impl StreamProvider for StdStreamProvider {
-    fn stream_out(&self) -> RefMut<WriteStream> {
+    fn stream_out(&self) -> RefMut<'_, WriteStream> {
         self.stdout.borrow_mut()
     }
 
-    fn stream_err(&self) -> RefMut<WriteStream> {
+    fn stream_err(&self) -> RefMut<'_, WriteStream> {
         self.stderr.borrow_mut()
     }
 
-    fn stream_in(&self) -> RefMut<ReadStream> {
+    fn stream_in(&self) -> RefMut<'_, ReadStream> {
         self.stdin.borrow_mut()
     }
 }
+
+/// Writes the output streams to files, the input is read from the standard input
+pub struct FileStreamProvider(StdStreamProvider);
+
+impl FileStreamProvider {
+    /// Creates the files or truncates the existing ones, both streams may go to the same path
+    pub fn create<P: AsRef<Path>>(stdout: P, stderr: P) -> io::Result<Self> {
+        let open = |path: &P| -> io::Result<Rc<RefCell<WriteStream>>> {
+            Ok(Rc::new(RefCell::new(io::BufWriter::new(File::create(
+                path,
+            )?))))
+        };
+
+        let out = open(&stdout)?;
+        let err = if stdout.as_ref() == stderr.as_ref() {
+            out.clone()
+        } else {
+            open(&stderr)?
+        };
+
+        Ok(Self(StdStreamProvider::new(Some((
+            Some(out),
+            Some(err),
+            None,
+        )))))
+    }
+}
+
+impl StreamProvider for FileStreamProvider {
+    fn stream_out(&self) -> RefMut<'_, WriteStream> {
+        self.0.stream_out()
+    }
+
+    fn stream_err(&self) -> RefMut<'_, WriteStream> {
+        self.0.stream_err()
+    }
+
+    fn stream_in(&self) -> RefMut<'_, ReadStream> {
+        self.0.stream_in()
+    }
+}
+
+/// Forwards everything written to the output streams to the callbacks,
+/// the input is read from the standard input
+pub struct CallbackStreamProvider(StdStreamProvider);
+
+impl CallbackStreamProvider {
+    pub fn new<O, E>(on_stdout: O, on_stderr: E) -> Self
+    where
+        O: FnMut(&[u8]) + 'static,
+        E: FnMut(&[u8]) + 'static,
+    {
+        Self(StdStreamProvider::new(Some((
+            Some(Rc::new(RefCell::new(CallbackWriter(on_stdout)))),
+            Some(Rc::new(RefCell::new(CallbackWriter(on_stderr)))),
+            None,
+        ))))
+    }
+}
+
+impl StreamProvider for CallbackStreamProvider {
+    fn stream_out(&self) -> RefMut<'_, WriteStream> {
+        self.0.stream_out()
+    }
+
+    fn stream_err(&self) -> RefMut<'_, WriteStream> {
+        self.0.stream_err()
+    }
+
+    fn stream_in(&self) -> RefMut<'_, ReadStream> {
+        self.0.stream_in()
+    }
+}
+
+struct CallbackWriter<F: FnMut(&[u8])>(F);
+
+impl<F: FnMut(&[u8])> io::Write for CallbackWriter<F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        (self.0)(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    /// Writer appending to a buffer shared with the test
+    struct Shared(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_flush() {
+        let written = Rc::new(RefCell::new(vec![]));
+        let stdout = io::BufWriter::new(Shared(written.clone()));
+        let provider = StdStreamProvider::new(Some((
+            Some(Rc::new(RefCell::new(stdout))),
+            Some(Rc::new(RefCell::new(vec![]))),
+            None,
+        )));
+
+        write!(provider.stream_out(), "buffered").unwrap();
+        assert!(written.borrow().is_empty());
+
+        provider.flush().unwrap();
+        assert_eq!(written.borrow().as_slice(), b"buffered");
+    }
+    #[test]
+    fn test_callback_provider() {
+        let written = Rc::new(RefCell::new(String::new()));
+        let out = written.clone();
+        let err = written.clone();
+        let provider = CallbackStreamProvider::new(
+            move |buf| out.borrow_mut().push_str(&String::from_utf8_lossy(buf)),
+            move |buf| {
+                err.borrow_mut()
+                    .push_str(&format!("[{}]", String::from_utf8_lossy(buf)))
+            },
+        );
+
+        write!(provider.stream_out(), "out").unwrap();
+        write!(provider.stream_err(), "err").unwrap();
+        assert_eq!(written.borrow().as_str(), "out[err]");
+    }
+
+    #[test]
+    fn test_file_provider() {
+        let path = std::env::temp_dir().join(format!("cogo_io_test_{}.txt", std::process::id()));
+        let provider = FileStreamProvider::create(&path, &path).unwrap();
+
+        write!(provider.stream_out(), "out ").unwrap();
+        write!(provider.stream_err(), "err").unwrap();
+        provider.flush().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "out err");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,9 +1,22 @@
 pub mod io;
+pub mod testing;
 
-pub use self::vm::{CUnitFrame, Vm};
+pub use self::error::VmError;
+pub use self::format::{format_value, FormatVerb};
+#[cfg(feature = "stats")]
+pub use self::stats::VmStats;
+pub use self::trace::{FrameState, StackTrace, TraceFrame};
+pub use self::vm::{CUnitFrame, GrowthPolicy, Vm, VmOptions};
 
 mod builtin;
 mod error;
+mod format;
+mod heap;
 mod name_table;
+#[cfg(feature = "register-vm")]
+mod register;
 mod stack;
+#[cfg(feature = "stats")]
+mod stats;
+mod trace;
 mod vm;
@@ -36,6 +36,10 @@ impl<N> NameTable<N> {
         }
     }
 
+    pub(super) fn iter(&self) -> impl Iterator<Item = (&String, &N)> {
+        self.0.iter()
+    }
+
     pub(super) fn get_mut(&mut self, name: &str) -> NameResult<&mut N> {
         let value = self.0.get_mut(name);
         if let Some(value) = value {
@@ -0,0 +1,278 @@
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use cogo_compiler::{Chunk, OpCode, Value};
+
+use crate::vm::VmResult;
+
+/// Value a register instruction reads, without pushing it on the stack first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Operand {
+    /// Frame slot of a local
+    Local(usize),
+    /// Index in the constant pool of the chunk
+    Constant(usize),
+}
+
+/// Binary operations register instructions perform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum BinaryOp {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Remainder,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    BitClear,
+    LeftShift,
+    RightShift,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+}
+
+impl BinaryOp {
+    fn of(code: &OpCode) -> Option<Self> {
+        Some(match code {
+            OpCode::Add => Self::Add,
+            OpCode::Subtract => Self::Subtract,
+            OpCode::Multiply => Self::Multiply,
+            OpCode::Divide => Self::Divide,
+            OpCode::Remainder => Self::Remainder,
+            OpCode::BitwiseAnd => Self::BitwiseAnd,
+            OpCode::BitwiseOr => Self::BitwiseOr,
+            OpCode::BitwiseXor => Self::BitwiseXor,
+            OpCode::BitClear => Self::BitClear,
+            OpCode::LeftShift => Self::LeftShift,
+            OpCode::RightShift => Self::RightShift,
+            OpCode::Equal => Self::Equal,
+            OpCode::NotEqual => Self::NotEqual,
+            OpCode::Greater => Self::Greater,
+            OpCode::GreaterEqual => Self::GreaterEqual,
+            OpCode::Less => Self::Less,
+            OpCode::LessEqual => Self::LessEqual,
+            _ => return None,
+        })
+    }
+
+    /// Same as the handler of the opcode, `a` is the left operand and becomes the result
+    pub(crate) fn apply(self, mut a: Value, b: &Value) -> VmResult<Value> {
+        match self {
+            Self::Add => a.add(b)?,
+            Self::Subtract => a.sub(b)?,
+            Self::Multiply => a.mult(b)?,
+            Self::Divide => a.div(b)?,
+            Self::Remainder => a.modulo(b)?,
+            Self::BitwiseAnd => a.bitwise_and(b)?,
+            Self::BitwiseOr => a.bitwise_or(b)?,
+            Self::BitwiseXor => a.bitwise_xor(b)?,
+            Self::BitClear => a.bit_clear(b)?,
+            Self::LeftShift => a.left_shift(b)?,
+            Self::RightShift => a.right_shift(b)?,
+            Self::Equal => return Ok(a.equal(b)?),
+            Self::NotEqual => {
+                let mut eq = a.equal(b)?;
+                eq.not()?;
+                return Ok(eq);
+            }
+            Self::Greater => return Ok(a.greater(b)?),
+            Self::GreaterEqual => return Ok(a.greater_equal(b)?),
+            Self::Less => return Ok(a.less(b)?),
+            Self::LessEqual => return Ok(a.less_equal(b)?),
+        }
+
+        Ok(a)
+    }
+}
+
+/// Instruction of the register machine, addressing the locals of the frame directly.
+/// Each one replaces a run of stack opcodes, starting at the index it is found at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Instr {
+    /// `dest = src`, replaces `GetLocal`/`Constant`, `SetLocal`, `Pop`
+    Move { src: Operand, dest: usize },
+    /// `lhs op rhs`, replaces the two operands and the operation.
+    /// The result is pushed, or stored in the local by the `SetLocal`, `Pop` which follow
+    Binary {
+        op: BinaryOp,
+        lhs: Operand,
+        rhs: Operand,
+        dest: Option<usize>,
+    },
+}
+
+impl Instr {
+    /// Number of the stack opcodes replaced
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Self::Move { .. } => 3,
+            Self::Binary { dest: None, .. } => 3,
+            Self::Binary { dest: Some(_), .. } => 5,
+        }
+    }
+
+    /// Offset of the `SetLocal` storing the result, which reports the errors of the store
+    pub(crate) fn store_offset(&self) -> usize {
+        self.len() - 2
+    }
+}
+
+/// Register instructions replacing the runs of opcodes starting at their indices.
+/// A run is only replaced if nothing jumps into the middle of it and it is on a single line,
+/// so that jumps and breakpoints land on the first opcode of the run
+pub(crate) fn lower(chunk: &Chunk) -> Rc<[Option<Instr>]> {
+    let codes = chunk.codes();
+    let targets: HashSet<usize> = codes.iter().flat_map(OpCode::jump_targets).collect();
+
+    let mut instrs = vec![None; codes.len()];
+    let mut i = 0;
+    while i < codes.len() {
+        let instr = match_run(&codes[i..]).filter(|instr| {
+            let run = i..i + instr.len();
+            run.clone().skip(1).all(|j| !targets.contains(&j))
+                && run.clone().all(|j| chunk.pos(j).0 == chunk.pos(i).0)
+        });
+
+        match instr {
+            Some(instr) => {
+                instrs[i] = Some(instr);
+                i += instr.len();
+            }
+            None => i += 1,
+        }
+    }
+
+    instrs.into()
+}
+
+/// Instruction the opcodes start with, if any
+fn match_run(codes: &[OpCode]) -> Option<Instr> {
+    let operand = |code: &OpCode| match *code {
+        OpCode::GetLocal(i) => Some(Operand::Local(i)),
+        OpCode::Constant(c) => Some(Operand::Constant(c)),
+        _ => None,
+    };
+    let store = |codes: &[OpCode]| match codes {
+        [OpCode::SetLocal(dest), OpCode::Pop, ..] => Some(*dest),
+        _ => None,
+    };
+
+    let src = operand(codes.first()?)?;
+    if let Some(dest) = store(&codes[1..]) {
+        return Some(Instr::Move { src, dest });
+    }
+
+    let rhs = operand(codes.get(1)?)?;
+    let op = BinaryOp::of(codes.get(2)?)?;
+
+    Some(Instr::Binary {
+        op,
+        lhs: src,
+        rhs,
+        dest: store(&codes[3..]),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use cogo_compiler::testing::CollectingErrorHandler;
+    use cogo_compiler::{compile, CompilationUnit};
+
+    use super::*;
+
+    fn function<'a>(cunit: &'a CompilationUnit, name: &str) -> &'a Chunk {
+        cunit
+            .chunk()
+            .codes()
+            .iter()
+            .find_map(|code| match code {
+                OpCode::Func(funit @ CompilationUnit::Function(f)) if f.function().0 == name => {
+                    Some(funit.chunk())
+                }
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_lower() {
+        let cunit = compile(
+            r#"package main
+
+func main() {
+    x := 3
+    y := x * 2
+    y = y + x
+    x = y
+    println(x < y)
+}
+"#,
+            &mut CollectingErrorHandler::new(),
+        );
+        let instrs: Vec<_> = lower(function(&cunit, "main"))
+            .iter()
+            .flatten()
+            .copied()
+            .collect();
+
+        assert_eq!(
+            instrs,
+            vec![
+                Instr::Binary {
+                    op: BinaryOp::Multiply,
+                    lhs: Operand::Local(0),
+                    rhs: Operand::Constant(1),
+                    dest: None,
+                },
+                Instr::Binary {
+                    op: BinaryOp::Add,
+                    lhs: Operand::Local(1),
+                    rhs: Operand::Local(0),
+                    dest: Some(1),
+                },
+                Instr::Move {
+                    src: Operand::Local(1),
+                    dest: 0,
+                },
+                Instr::Binary {
+                    op: BinaryOp::Less,
+                    lhs: Operand::Local(0),
+                    rhs: Operand::Local(1),
+                    dest: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lower_skips_jump_targets() {
+        let cunit = compile(
+            r#"package main
+
+func main() {
+    i := 0
+    for i < 3 {
+        i = i + 1
+    }
+}
+"#,
+            &mut CollectingErrorHandler::new(),
+        );
+        let chunk = function(&cunit, "main");
+        let instrs = lower(chunk);
+
+        for (i, instr) in instrs.iter().enumerate() {
+            if let Some(instr) = instr {
+                for j in i + 1..i + instr.len() {
+                    assert!(!chunk.codes().iter().any(|c| c.jump_targets().contains(&j)));
+                }
+            }
+        }
+        assert!(instrs.iter().flatten().count() >= 2);
+    }
+}
@@ -1,7 +1,10 @@
 use std::{fmt, result};
 
+use crate::vm::GrowthPolicy;
+
 pub(crate) struct VmStack<T> {
     stack: Vec<T>,
+    growth: GrowthPolicy,
 }
 
 #[derive(Debug)]
@@ -10,10 +13,18 @@ type PopResult<T> = result::Result<T, StackUnderflow>;
 
 impl<T> VmStack<T> {
     pub(super) fn new() -> Self {
-        Self { stack: Vec::new() }
+        Self::with_capacity(0, GrowthPolicy::Double)
+    }
+
+    pub(super) fn with_capacity(capacity: usize, growth: GrowthPolicy) -> Self {
+        Self {
+            stack: Vec::with_capacity(capacity),
+            growth,
+        }
     }
 
     pub(super) fn push(&mut self, v: T) {
+        self.growth.reserve(&mut self.stack);
         self.stack.push(v);
     }
 
@@ -25,48 +36,55 @@ impl<T> VmStack<T> {
         }
     }
 
-    pub(super) fn pop_at(&mut self, at: usize) -> T {
-        self.stack.remove(at)
+    pub(super) fn pop_at(&mut self, at: usize) -> PopResult<T> {
+        if at < self.stack.len() {
+            Ok(self.stack.remove(at))
+        } else {
+            Err(StackUnderflow)
+        }
     }
 
-    pub(super) fn retrieve(&self) -> &T {
-        self.retrieve_at(self.stack.len() - 1)
+    pub(super) fn retrieve(&self) -> PopResult<&T> {
+        self.stack.last().ok_or(StackUnderflow)
     }
 
-    pub(super) fn retrieve_at(&self, i: usize) -> &T {
-        self.stack
-            .get(i)
-            .expect("Cannot retrieve value from stack.")
+    pub(super) fn retrieve_at(&self, i: usize) -> PopResult<&T> {
+        self.stack.get(i).ok_or(StackUnderflow)
     }
 
-    pub(super) fn retrieve_at_mut(&mut self, i: usize) -> &mut T {
-        self.stack
-            .get_mut(i)
-            .expect("Cannot retrieve value from stack.")
+    pub(super) fn retrieve_at_mut(&mut self, i: usize) -> PopResult<&mut T> {
+        self.stack.get_mut(i).ok_or(StackUnderflow)
     }
 
-    pub(super) fn retrieve_by(&self, by: usize) -> &T {
-        self.retrieve_at(self.stack.len() - by - 1)
+    pub(super) fn retrieve_by(&self, by: usize) -> PopResult<&T> {
+        self.retrieve_at(self.below_top(by)?)
     }
 
-    pub(super) fn retrieve_by_mut(&mut self, by: usize) -> &mut T {
-        self.retrieve_at_mut(self.stack.len() - by - 1)
+    pub(super) fn retrieve_by_mut(&mut self, by: usize) -> PopResult<&mut T> {
+        self.retrieve_at_mut(self.below_top(by)?)
     }
 
-    pub(super) fn last_mut(&mut self) -> &mut T {
-        self.stack
-            .last_mut()
-            .expect("Cannot retrieve mutable reference on an empty stack.")
+    pub(super) fn last_mut(&mut self) -> PopResult<&mut T> {
+        self.stack.last_mut().ok_or(StackUnderflow)
     }
 
-    pub(super) fn put_at(&mut self, i: usize, v: T) {
-        self.stack[i] = v;
+    /// Index of the value `by` values below the top one
+    fn below_top(&self, by: usize) -> PopResult<usize> {
+        self.stack
+            .len()
+            .checked_sub(by)
+            .and_then(|i| i.checked_sub(1))
+            .ok_or(StackUnderflow)
     }
 
     pub(super) fn len(&self) -> usize {
         self.stack.len()
     }
 
+    pub(super) fn as_slice(&self) -> &[T] {
+        &self.stack
+    }
+
     pub(super) fn slice(&mut self, s: usize, e: usize) -> &[T] {
         &mut self.stack[s..e]
     }
@@ -0,0 +1,24 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Execution metrics collected when the VM is built with the `stats` feature
+#[derive(Debug, Default, Clone)]
+pub struct VmStats {
+    /// Opcodes executed
+    pub instructions: u64,
+    /// Calls of both functions and builtins
+    pub calls: u64,
+    /// Deepest the call stack has grown, the package frame included
+    pub peak_frame_depth: usize,
+    /// Wall time spent in `Vm::run`
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for VmStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "instructions executed: {}", self.instructions)?;
+        writeln!(f, "calls:                 {}", self.calls)?;
+        writeln!(f, "peak call depth:       {}", self.peak_frame_depth)?;
+        write!(f, "wall time:             {:?}", self.elapsed)
+    }
+}
@@ -0,0 +1,112 @@
+//! Utilities to run programs in tests and to inspect what they produced
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::{fmt, io};
+
+pub use cogo_compiler::testing::CollectingErrorHandler;
+use cogo_compiler::{compile_with_options, CompileOptions, CompilerError};
+
+use crate::io::StdStreamProvider;
+use crate::{CUnitFrame, Vm, VmError, VmOptions};
+
+/// Output streams kept in memory, the input stream is empty
+#[derive(Default)]
+pub struct CapturedStreams {
+    stdout: Rc<RefCell<Vec<u8>>>,
+    stderr: Rc<RefCell<Vec<u8>>>,
+}
+
+impl CapturedStreams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Provider writing to the captured streams, to be passed to the VM
+    pub fn provider(&self) -> StdStreamProvider {
+        StdStreamProvider::new(Some((
+            Some(self.stdout.clone()),
+            Some(self.stderr.clone()),
+            Some(Rc::new(RefCell::new(io::empty()))),
+        )))
+    }
+
+    pub fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.stdout.borrow()).into_owned()
+    }
+
+    pub fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.stderr.borrow()).into_owned()
+    }
+}
+
+/// Reason a program run failed
+#[derive(Debug)]
+pub enum RunError {
+    Compile(Vec<CompilerError>),
+    Runtime(VmError),
+}
+
+impl fmt::Display for RunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Compile(errs) => {
+                let errs: Vec<String> = errs.iter().map(|err| err.to_string()).collect();
+                write!(f, "{}", errs.join("\n"))
+            }
+            Self::Runtime(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+/// Compiles and runs the program, returning its stdout, its stderr and how it ended
+pub fn run_program(src: &str) -> (String, String, Result<(), RunError>) {
+    run_program_with_options(src, &CompileOptions::default(), &VmOptions::default())
+}
+
+/// Same as `run_program` with both the compiler and the VM configured
+pub fn run_program_with_options(
+    src: &str,
+    options: &CompileOptions,
+    vm_options: &VmOptions,
+) -> (String, String, Result<(), RunError>) {
+    let mut err_handler = CollectingErrorHandler::new();
+    let cunit = compile_with_options(src, &mut err_handler, options);
+    if !err_handler.errors().is_empty() {
+        return (
+            String::new(),
+            String::new(),
+            Err(RunError::Compile(err_handler.into_errors())),
+        );
+    }
+
+    let streams = CapturedStreams::new();
+    let mut vm = Vm::with_options(
+        Some(Box::new(streams.provider())),
+        CUnitFrame::new(cunit),
+        vm_options,
+    );
+    let res = vm.run().map_err(RunError::Runtime);
+
+    (streams.stdout(), streams.stderr(), res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_program() {
+        let (stdout, stderr, res) =
+            run_program("package main\n\nimport \"fmt\"\n\nfunc main() {\n    fmt.Println(1)\n    println(2)\n}\n");
+        assert_eq!(stdout, "1\n");
+        assert_eq!(stderr, "2\n");
+        assert!(res.is_ok());
+
+        let (_, _, res) = run_program("package main\n\nfunc main() {\n    x :=\n}\n");
+        assert!(matches!(res, Err(RunError::Compile(errs)) if errs.len() == 1));
+
+        let (_, _, res) = run_program("package main\n\nfunc main() {\n    panic(1)\n}\n");
+        assert!(matches!(res, Err(RunError::Runtime(err)) if err.category() == "panic"));
+    }
+}
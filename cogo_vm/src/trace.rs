@@ -0,0 +1,64 @@
+use std::fmt;
+
+use cogo_compiler::{Pos, Value};
+
+/// Frames printed before the rest are elided, same as the Go runtime does
+const MAX_PRINTED_FRAMES: usize = 100;
+
+/// Call stack of the VM at the moment an error occurred, the innermost call goes first
+#[derive(Debug, Clone)]
+pub struct StackTrace {
+    source_name: String,
+    frames: Vec<TraceFrame>,
+}
+
+/// Function and the position in the source it was executing
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceFrame {
+    pub function: String,
+    pub pos: Pos,
+}
+
+/// Frame of a paused VM along with the values of the variables in scope
+#[derive(Debug, Clone)]
+pub struct FrameState {
+    pub function: String,
+    pub pos: Pos,
+    /// Names and values of the variables, the innermost last
+    pub locals: Vec<(String, Value)>,
+}
+
+impl StackTrace {
+    pub(crate) fn new(source_name: String, frames: Vec<TraceFrame>) -> Self {
+        Self {
+            source_name,
+            frames,
+        }
+    }
+
+    pub fn frames(&self) -> &[TraceFrame] {
+        &self.frames
+    }
+
+    pub fn source_name(&self) -> &str {
+        &self.source_name
+    }
+}
+
+impl fmt::Display for StackTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "goroutine 1 [running]:")?;
+        for frame in self.frames.iter().take(MAX_PRINTED_FRAMES) {
+            write!(
+                f,
+                "\n{}(...)\n\t{}:{}",
+                frame.function, self.source_name, frame.pos.0
+            )?;
+        }
+        if self.frames.len() > MAX_PRINTED_FRAMES {
+            write!(f, "\n...additional frames elided...")?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,15 +1,23 @@
-use std::cell::{Ref, RefCell, RefMut};
+use std::collections::{HashMap, HashSet};
 use std::rc::Rc;
 use std::result;
 
-use cogo_compiler::{CompilationUnit as CUnit, FuncUnit, OpCode, ValType, Value};
+use cogo_compiler::{
+    builtin_id, CompilationUnit as CUnit, OpCode, Pos, SliceRef, ValType, Value, BUILTINS,
+};
 
 use crate::builtin::FuncBuiltin;
 use crate::error;
 use crate::error::VmError;
+use crate::heap::Heap;
 use crate::io::{StdStreamProvider, StreamProvider};
 use crate::name_table::NameTable;
-use crate::stack::VmStack;
+#[cfg(feature = "register-vm")]
+use crate::register::{self, Instr, Operand};
+use crate::stack::{StackUnderflow, VmStack};
+#[cfg(feature = "stats")]
+use crate::stats::VmStats;
+use crate::trace::{FrameState, StackTrace, TraceFrame};
 
 #[derive(Debug)]
 enum VmNamedValue {
@@ -36,600 +44,1294 @@ impl VmNamedValue {
 pub type VmResult<T> = result::Result<T, VmError>;
 type VmRuntimeCall<T> = std::result::Result<T, VmError>;
 
+/// How the value and the frame stacks grow once their capacity is exhausted
+#[derive(Debug, Clone, Copy)]
+pub enum GrowthPolicy {
+    /// Capacity is doubled, which is the default `Vec` behaviour
+    Double,
+    /// Capacity is extended by a fixed number of slots
+    Fixed(usize),
+}
+
+impl GrowthPolicy {
+    pub(crate) fn reserve<T>(&self, vec: &mut Vec<T>) {
+        if let Self::Fixed(by) = self {
+            if vec.len() == vec.capacity() {
+                vec.reserve_exact((*by).max(1));
+            }
+        }
+    }
+}
+
+/// Capacities the VM preallocates, useful to avoid reallocations in deep recursion
+#[derive(Debug, Clone)]
+pub struct VmOptions {
+    pub stack_capacity: usize,
+    pub frame_capacity: usize,
+    pub growth: GrowthPolicy,
+    /// Name of the source file shown in stack traces
+    pub source_name: Option<String>,
+    /// Calls nested deeper than this fail with a stack overflow error, `None` means no limit
+    pub max_frame_depth: Option<usize>,
+    /// Arrays and slices holding more values than this fail with an out of memory error,
+    /// `None` means no limit
+    pub max_heap_values: Option<usize>,
+}
+
+impl Default for VmOptions {
+    fn default() -> Self {
+        Self {
+            stack_capacity: 256,
+            frame_capacity: 64,
+            growth: GrowthPolicy::Double,
+            source_name: None,
+            max_frame_depth: Some(100_000),
+            max_heap_values: None,
+        }
+    }
+}
+
 pub struct Vm {
     globals: NameTable<VmNamedValue>,
-    names: NameTable<FuncUnit>,
-    pub(crate) builtins: NameTable<FuncBuiltin>,
+    /// Declared functions along with the lookups of their opcodes, shared with the frames calling them
+    names: NameTable<(Rc<CUnit>, Lookup)>,
+    /// Builtins in the order of their ids
+    pub(crate) builtins: Vec<FuncBuiltin>,
     std_streams: Box<dyn StreamProvider>,
     stack: VmStack<Value>,
-    frames: VmStack<Rc<RefCell<CUnitFrame>>>,
-    current_frame: usize,
+    /// Backing arrays of the arrays and slices, all of them are allocated on it
+    heap: Heap,
+    /// Call frames, the last one is being executed
+    frames: Vec<CUnitFrame>,
+    growth: GrowthPolicy,
+    source_name: Option<String>,
+    max_frame_depth: Option<usize>,
+    /// Source lines the execution pauses at, before their first opcode
+    breakpoints: Vec<usize>,
+    /// Opcodes of each unit the breakpoints stop at, resolved once the unit is first executed.
+    /// Units are keyed by address, each entry keeps its unit alive so that the address
+    /// cannot be reused by another unit
+    breakpoint_codes: HashMap<*const CUnit, (Rc<CUnit>, HashSet<usize>)>,
+    /// Whether the entry unit along with the units nested in it has been verified,
+    /// which happens once, on the first `run()`
+    verified: bool,
+    /// State of the execution paused at a breakpoint, resumed by the next `run()`
+    paused: Option<ExecState>,
+    #[cfg(feature = "stats")]
+    stats: VmStats,
 }
 
-impl Vm {
-    pub fn new(std_streams: Option<Box<dyn StreamProvider>>, entry_frame: CUnitFrame) -> Self {
-        let mut frames = VmStack::new();
-        frames.push(Rc::new(RefCell::new(entry_frame)));
+impl Vm {
+    pub fn new(std_streams: Option<Box<dyn StreamProvider>>, entry_frame: CUnitFrame) -> Self {
+        Self::with_options(std_streams, entry_frame, &VmOptions::default())
+    }
+
+    pub fn with_options(
+        std_streams: Option<Box<dyn StreamProvider>>,
+        entry_frame: CUnitFrame,
+        options: &VmOptions,
+    ) -> Self {
+        let mut frames = Vec::with_capacity(options.frame_capacity.max(1));
+        frames.push(entry_frame);
+
+        let mut vm = Self {
+            globals: NameTable::new(),
+            names: NameTable::new(),
+            builtins: Vec::with_capacity(BUILTINS.len()),
+            stack: VmStack::with_capacity(options.stack_capacity, options.growth),
+            heap: Heap::new(options.max_heap_values),
+            frames,
+            growth: options.growth,
+            source_name: options.source_name.clone(),
+            max_frame_depth: options.max_frame_depth,
+            breakpoints: Vec::new(),
+            breakpoint_codes: HashMap::new(),
+            verified: false,
+            paused: None,
+            #[cfg(feature = "stats")]
+            stats: VmStats::default(),
+            std_streams: std_streams.unwrap_or_else(|| Box::new(StdStreamProvider::new(None))),
+        };
+
+        vm.define_builtins();
+        vm
+    }
+
+    pub fn run(&mut self) -> VmResult<()> {
+        if !self.verified {
+            self.frames[0].cunit.verify()?;
+            self.verified = true;
+        }
+
+        #[cfg(feature = "stats")]
+        let started = std::time::Instant::now();
+        let res = self.execute();
+        #[cfg(feature = "stats")]
+        {
+            self.stats.elapsed += started.elapsed();
+        }
+
+        // the output is written out before an error gets reported
+        let flushed = self.std_streams.flush();
+        let res = res.and(flushed.map_err(VmError::from));
+
+        res.map_err(|err| {
+            let trace = self.stack_trace();
+            match trace.frames().first() {
+                Some(frame) => err.at(trace.source_name().to_string(), frame.pos),
+                None => err,
+            }
+            .with_trace(trace)
+        })
+    }
+
+    /// Lines `run()` pauses at whenever the execution reaches them, in any function.
+    /// Calling `run()` again resumes the execution
+    pub fn set_breakpoints(&mut self, lines: &[usize]) {
+        self.breakpoints = lines.to_vec();
+        self.breakpoint_codes.clear();
+    }
+
+    /// Values held by the arrays and slices the program still refers to
+    pub fn heap_values(&mut self) -> usize {
+        self.heap.live_values()
+    }
+
+    /// Whether `run()` has returned at a breakpoint rather than at the end of the program
+    pub fn is_paused(&self) -> bool {
+        self.paused.is_some()
+    }
+
+    /// Values on the stack, the top last. The values of the frames start at their bases,
+    /// so they are best read through `frame_states()`
+    pub fn value_stack(&self) -> &[Value] {
+        self.stack.as_slice()
+    }
+
+    /// Frames being executed with the variables in scope, the innermost first
+    pub fn frame_states(&self) -> Vec<FrameState> {
+        self.visible_frames()
+            .map(|(function, pos, frame)| {
+                let locals = match &*frame.cunit {
+                    CUnit::Function(f) => f
+                        .locals_at(pos)
+                        .filter_map(|local| {
+                            let value = self.stack.as_slice().get(frame.stack_pos + local.slot)?;
+                            Some((local.name.clone(), value.clone()))
+                        })
+                        .collect(),
+                    CUnit::Package(_) => vec![],
+                };
+
+                FrameState {
+                    function,
+                    pos,
+                    locals,
+                }
+            })
+            .collect()
+    }
+
+    /// Package-level variables and constants declared so far, sorted by name
+    pub fn globals(&self) -> Vec<(String, Value)> {
+        let mut globals: Vec<_> = self
+            .globals
+            .iter()
+            // `init` functions are named internally
+            .filter(|(name, _)| !name.contains('.'))
+            .map(|(name, value)| (name.clone(), value.val().clone()))
+            .collect();
+        globals.sort_by(|a, b| a.0.cmp(&b.0));
+
+        globals
+    }
+
+    /// Metrics of the execution so far
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> &VmStats {
+        &self.stats
+    }
+
+    fn execute(&mut self) -> VmResult<()> {
+        // a resumed execution does not stop at the breakpoint it has paused at again
+        let (mut state, mut resumed) = match self.paused.take() {
+            Some(state) => (state, true),
+            None => (ExecState::new(), false),
+        };
+
+        loop {
+            let frame = self.current_frame();
+            let pointer = frame.pointer;
+            // the unit outlives the step even if the handler pops its frame
+            let cunit = Rc::clone(&frame.cunit);
+            let code = match cunit.chunk().codes().get(pointer) {
+                Some(code) => code,
+                None if self.frames.len() == 1 => break,
+                None => {
+                    self.frames.pop();
+                    continue;
+                }
+            };
+
+            if !self.breakpoints.is_empty() && !resumed && self.is_breakpoint(&cunit, pointer) {
+                self.paused = Some(state);
+                break;
+            }
+            resumed = false;
+
+            #[cfg(feature = "stats")]
+            {
+                self.stats.instructions += 1;
+            }
+
+            #[cfg(feature = "register-vm")]
+            if let Some(instr) = self.current_frame().lookup.registers[pointer] {
+                self.execute_register(instr, &mut state)?;
+                self.heap.check()?;
+                continue;
+            }
+
+            #[cfg(not(feature = "match-dispatch"))]
+            let flow = (self.current_frame().lookup.handlers[pointer])(self, code, &mut state)?;
+            #[cfg(feature = "match-dispatch")]
+            let flow = dispatch(self, code, &mut state)?;
+            self.heap.check()?;
+
+            if let Flow::Next = flow {
+                self.current_frame_mut().inc_pointer(1);
+            }
+        }
+
+        VmResult::Ok(())
+    }
+
+    fn unary(&mut self, op: impl FnOnce(&mut Value) -> VmResult<()>) -> VmResult<Flow> {
+        let mut a = self.stack.pop()?;
+        op(&mut a)?;
+        self.stack.push(a);
+
+        Ok(Flow::Next)
+    }
+
+    fn binary(&mut self, op: impl FnOnce(&mut Value, &Value) -> VmResult<()>) -> VmResult<Flow> {
+        let b = self.stack.pop()?;
+        let mut a = self.stack.pop()?;
+        op(&mut a, &b)?;
+        self.stack.push(a);
+
+        Ok(Flow::Next)
+    }
+
+    fn comparison(&mut self, op: impl FnOnce(&Value, &Value) -> VmResult<Value>) -> VmResult<Flow> {
+        let b = self.stack.pop()?;
+        let a = self.stack.pop()?;
+        let res = op(&a, &b)?;
+        self.stack.push(res);
+
+        Ok(Flow::Next)
+    }
+
+    fn jump(&mut self, target: usize) -> VmResult<Flow> {
+        self.current_frame_mut().jump_to(target);
+
+        Ok(Flow::Stay)
+    }
+
+    fn op_noop(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        Ok(Flow::Next)
+    }
+
+    fn op_plus_noop(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.unary(|a| Ok(a.plus_noop()?))
+    }
+
+    fn op_negate(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.unary(|a| Ok(a.negate()?))
+    }
+
+    fn op_subtract(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.binary(|a, b| Ok(a.sub(b)?))
+    }
+
+    fn op_add(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.binary(|a, b| Ok(a.add(b)?))
+    }
+
+    fn op_multiply(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.binary(|a, b| Ok(a.mult(b)?))
+    }
+
+    fn op_divide(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.binary(|a, b| Ok(a.div(b)?))
+    }
+
+    fn op_remainder(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.binary(|a, b| Ok(a.modulo(b)?))
+    }
+
+    fn op_bitwise_and(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.binary(|a, b| Ok(a.bitwise_and(b)?))
+    }
+
+    fn op_bitwise_or(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.binary(|a, b| Ok(a.bitwise_or(b)?))
+    }
+
+    fn op_bitwise_xor(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.binary(|a, b| Ok(a.bitwise_xor(b)?))
+    }
+
+    fn op_bitwise_not(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.unary(|a| Ok(a.bitwise_complement()?))
+    }
+
+    fn op_bit_clear(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.binary(|a, b| Ok(a.bit_clear(b)?))
+    }
+
+    fn op_left_shift(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.binary(|a, b| Ok(a.left_shift(b)?))
+    }
+
+    fn op_right_shift(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.binary(|a, b| Ok(a.right_shift(b)?))
+    }
+
+    fn op_return(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::Return(len) = *code else {
+            unreachable!()
+        };
+
+        let mut vals: Vec<Value> = vec![];
+        for _ in 0..len {
+            vals.push(self.stack.pop()?)
+        }
+
+        self.validate_return_type(&mut vals)?;
+        self.discard_frame_stack()?;
+        self.frames.pop();
+
+        if len != 0 {
+            // the values were popped last first
+            for val in vals.into_iter().rev() {
+                self.stack.push(val);
+            }
+        } else {
+            state.ignore_next_pop = true;
+        }
+
+        // the caller has already moved past the call
+        Ok(Flow::Stay)
+    }
+
+    fn op_constant(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::Constant(i) = *code else {
+            unreachable!()
+        };
+
+        let constant = self.current_frame().cunit.chunk().constant(i).clone();
+        self.stack.push(constant);
+
+        Ok(Flow::Next)
+    }
+
+    fn op_func(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::Func(funit) = code else {
+            unreachable!()
+        };
+
+        if let CUnit::Function(func) = funit {
+            let func_name = func.function().0.to_string();
+            let ftype = Box::new(func.func_type().clone());
+            let lookup = Lookup::new(funit);
+            self.names
+                .insert(func_name.clone(), (Rc::new(funit.clone()), lookup))?;
+            self.stack.push(Value::Func(func_name, ftype));
+        } else {
+            error::panic_at_cunit_type(funit);
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn op_not(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.unary(|a| Ok(a.not()?))
+    }
+
+    fn op_equal(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.comparison(|a, b| Ok(a.equal(b)?))
+    }
+
+    fn op_not_equal(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.comparison(|a, b| {
+            let mut eq = a.equal(b)?;
+            eq.not()?;
+            Ok(eq)
+        })
+    }
+
+    fn op_greater(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.comparison(|a, b| Ok(a.greater(b)?))
+    }
+
+    fn op_greater_equal(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.comparison(|a, b| Ok(a.greater_equal(b)?))
+    }
+
+    fn op_less(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.comparison(|a, b| Ok(a.less(b)?))
+    }
+
+    fn op_less_equal(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        self.comparison(|a, b| Ok(a.less_equal(b)?))
+    }
+
+    fn op_pop(&mut self, _: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        if !state.ignore_next_pop {
+            self.stack.pop()?;
+        } else {
+            state.ignore_next_pop = false;
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn op_var_global(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::VarGlobal(name, vtype) = code else {
+            unreachable!()
+        };
+
+        let mut value = self.stack.pop()?;
+        if let Some(vtype) = vtype {
+            if !value.is_of_type(vtype) {
+                return Err(VmError::type_error(vtype, &value.get_type()));
+            }
+            value.lose_literal(vtype);
+        } else {
+            value.lose_literal_blindly();
+        }
+        value.copy_if_soft_reference(&mut self.heap);
+
+        self.globals
+            .insert(name.clone(), VmNamedValue::Var(value))?;
+
+        Ok(Flow::Next)
+    }
+
+    fn op_const_global(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::ConstGlobal(name, vtype) = code else {
+            unreachable!()
+        };
+
+        let mut value = self.stack.pop()?;
+        if let Some(vtype) = vtype {
+            if !value.is_of_type(vtype) {
+                return Err(VmError::type_error(vtype, &value.get_type()));
+            }
+            value.lose_literal(vtype);
+        } else {
+            value.lose_literal_blindly();
+        }
+
+        self.globals
+            .insert(name.clone(), VmNamedValue::Const(value))?;
+
+        Ok(Flow::Next)
+    }
+
+    fn op_get_global(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::GetGlobal(name) = code else {
+            unreachable!()
+        };
+
+        if let Ok(nval) = self.globals.get(name) {
+            self.stack.push(nval.val().clone());
+        } else if let Some(id) = builtin_id(name) {
+            let val = Value::FuncBuiltin(self.builtins[id as usize].name().to_string());
+            self.stack.push(val);
+        } else {
+            return Err(VmError::undefined(name));
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn op_set_global(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::SetGlobal(name) = code else {
+            unreachable!()
+        };
+
+        if !self.globals.has(name) {
+            return Err(VmError::undefined(name));
+        }
+
+        let mut value = self.stack.pop()?;
+        let old_v = self.globals.get_mut(name)?;
+        if let VmNamedValue::Const(_) = old_v {
+            return Err(VmError::assignment(name));
+        }
+
+        let old_v = old_v.val_mut();
+        value.lose_literal_to(old_v);
+        // FIXME: maybe we should store types in a sep hashtable?
+        if !value.is_assignable_to(old_v) {
+            return Err(VmError::type_error(&old_v.get_type(), &value.get_type()));
+        }
+
+        old_v.store(value, &mut self.heap);
+        state.ignore_next_pop = true;
+
+        Ok(Flow::Next)
+    }
+
+    fn op_lose_soft_reference(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::LoseSoftReference(by) = *code else {
+            unreachable!()
+        };
+
+        let value = self.stack.retrieve_by_mut(by)?;
+        value.copy_if_soft_reference(&mut self.heap);
+
+        Ok(Flow::Next)
+    }
+
+    fn op_get_local(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::GetLocal(i) = *code else {
+            unreachable!()
+        };
+
+        let offset = self.current_frame().stack_pos;
+        let mut value = self.stack.retrieve_at(i + offset)?.clone();
+        value.lose_literal_blindly();
+        self.stack.push(value);
+
+        Ok(Flow::Next)
+    }
+
+    fn op_set_local(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::SetLocal(i) = *code else {
+            unreachable!()
+        };
+
+        let value = self.stack.pop()?;
+        self.set_local(i, value)?;
+        state.ignore_next_pop = true;
+
+        Ok(Flow::Next)
+    }
+
+    fn set_local(&mut self, i: usize, mut value: Value) -> VmResult<()> {
+        let offset = self.current_frame().stack_pos;
+        let old_v = self.stack.retrieve_at_mut(i + offset)?;
+        value.lose_literal_to(old_v);
+
+        if !value.is_assignable_to(old_v) {
+            return Err(VmError::type_error(&old_v.get_type(), &value.get_type()));
+        }
+
+        old_v.store(value, &mut self.heap);
+
+        Ok(())
+    }
+
+    /// Executes the register instruction instead of the opcodes it replaces, the same way they would,
+    /// then moves past them. An error is reported at the opcode that would have failed
+    #[cfg(feature = "register-vm")]
+    fn execute_register(&mut self, instr: Instr, state: &mut ExecState) -> VmResult<()> {
+        match instr {
+            Instr::Move { src, dest } => {
+                let value = self.operand(src)?;
+                self.set_local(dest, value)
+                    .map_err(|err| self.error_at(instr.store_offset(), err))?;
+                // the `Pop` after the `SetLocal` would have reset the flag it sets
+                state.ignore_next_pop = false;
+            }
+            Instr::Binary { op, lhs, rhs, dest } => {
+                let a = self.operand(lhs)?;
+                let b = self.operand(rhs)?;
+                let res = op.apply(a, &b).map_err(|err| self.error_at(2, err))?;
+                match dest {
+                    Some(dest) => {
+                        self.set_local(dest, res)
+                            .map_err(|err| self.error_at(instr.store_offset(), err))?;
+                        state.ignore_next_pop = false;
+                    }
+                    None => self.stack.push(res),
+                }
+            }
+        }
+        self.current_frame_mut().inc_pointer(instr.len());
+
+        Ok(())
+    }
+
+    /// Value of the operand, the same one `GetLocal` or `Constant` would push
+    #[cfg(feature = "register-vm")]
+    fn operand(&self, operand: Operand) -> VmResult<Value> {
+        let frame = self.current_frame();
+        match operand {
+            Operand::Local(i) => {
+                let mut value = self.stack.retrieve_at(i + frame.stack_pos)?.clone();
+                value.lose_literal_blindly();
+                Ok(value)
+            }
+            Operand::Constant(c) => Ok(frame.cunit.chunk().constant(c).clone()),
+        }
+    }
+
+    /// Moves the pointer to the opcode `by` past the current one, for the error to be traced there
+    #[cfg(feature = "register-vm")]
+    fn error_at(&mut self, by: usize, err: VmError) -> VmError {
+        self.current_frame_mut().inc_pointer(by);
+        err
+    }
+
+    fn op_inc_dec_local(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let (OpCode::IncLocal(i, c) | OpCode::DecLocal(i, c)) = *code else {
+            unreachable!()
+        };
+
+        let frame = self.frames.last().expect("No frame to execute.");
+        let by = frame.cunit.chunk().constant(c);
+
+        // the local is updated in place, the arithmetic keeps its type
+        let value = self.stack.retrieve_at_mut(i + frame.stack_pos)?;
+        value.lose_literal_blindly();
+        let vtype = value.get_type();
+        if let OpCode::IncLocal(..) = code {
+            value.add(by)?;
+        } else {
+            value.sub(by)?;
+        }
+
+        if !value.is_of_type(&vtype) {
+            return Err(VmError::type_error(&vtype, &value.get_type()));
+        }
+
+        // the fused assignment statement has already discarded its value
+        state.ignore_next_pop = false;
+
+        Ok(Flow::Next)
+    }
+
+    fn op_call(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::Call(argc, spread) = *code else {
+            unreachable!()
+        };
+
+        state.last_call = Call::new(argc, spread);
+        let val = self.stack.retrieve_by(argc as usize)?.clone();
+        match val {
+            Value::Func(name, _) if name.is_empty() => Err(VmError::nil_func_call()),
+            Value::Func(name, _) => {
+                for arg in 0..argc {
+                    let arg = self.stack.retrieve_by_mut(arg as usize)?;
+                    arg.copy_if_soft_reference(&mut self.heap);
+                }
+
+                self.current_frame_mut().inc_pointer(1);
+                self.call_func(&name, argc, spread)?;
+
+                Ok(Flow::Stay)
+            }
+            Value::FuncBuiltin(name) => {
+                let id = builtin_id(&name).ok_or_else(|| VmError::undefined(&name))?;
+                if let Some(val) = self.call_builtin(id, argc)? {
+                    // the result takes the place of the callee
+                    self.stack.pop()?;
+                    self.stack.push(val);
+                }
+
+                Ok(Flow::Next)
+            }
+            _ => Err(VmError::callable_value_expected(&val.get_type())),
+        }
+    }
+
+    fn op_tail_call(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::TailCall(argc, spread) = *code else {
+            unreachable!()
+        };
+
+        state.last_call = Call::new(argc, spread);
+        let val = self.stack.retrieve_by(argc as usize)?.clone();
+        let Value::Func(name, _) = val else {
+            return Err(VmError::callable_value_expected(&val.get_type()));
+        };
+        if name.is_empty() {
+            return Err(VmError::nil_func_call());
+        }
+
+        let mut args = Vec::with_capacity(argc as usize + 1);
+        for _ in 0..=argc {
+            let mut arg = self.stack.pop()?;
+            arg.copy_if_soft_reference(&mut self.heap);
+            args.push(arg);
+        }
+
+        // the arguments and the callee take the place of the current frame
+        self.discard_frame_stack()?;
+        self.frames.pop();
+        for arg in args.into_iter().rev() {
+            self.stack.push(arg);
+        }
+
+        self.call_func(&name, argc, spread)?;
+
+        Ok(Flow::Stay)
+    }
+
+    fn op_call_builtin(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::CallBuiltin(id, argc) = *code else {
+            unreachable!()
+        };
+
+        match self.call_builtin(id, argc)? {
+            Some(val) => self.stack.push(val),
+            // there is no callee left for the statement to discard
+            None => state.ignore_next_pop = true,
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn op_get_index(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let index = self.stack.pop()?;
+        let index = iter_utils::unwrap_index(index)?;
+        let iter = self.stack.pop()?;
+
+        self.stack.push(iter_utils::get_at_index(&iter, index)?);
+
+        Ok(Flow::Next)
+    }
+
+    fn op_get_local_index(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::GetLocalIndex(i) = *code else {
+            unreachable!()
+        };
+
+        let index = self.stack.pop()?;
+        let index = iter_utils::unwrap_index(index)?;
+
+        let offset = self.current_frame().stack_pos;
+        let value = iter_utils::get_at_index(self.stack.retrieve_at(i + offset)?, index)?;
+
+        self.stack.push(value);
+
+        Ok(Flow::Next)
+    }
+
+    fn op_get_global_index(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::GetGlobalIndex(name) = code else {
+            unreachable!()
+        };
+
+        let index = self.stack.pop()?;
+        let index = iter_utils::unwrap_index(index)?;
+
+        let iter = self.globals.get(name)?.val();
+
+        self.stack.push(iter_utils::get_at_index(iter, index)?);
+
+        Ok(Flow::Next)
+    }
+
+    fn op_set_index(&mut self, _: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let value = self.stack.pop()?;
+        let index = self.stack.pop()?;
+        let index = iter_utils::unwrap_index(index)?;
+
+        let mut iter = self.stack.pop()?;
+        iter_utils::set_at_index(&mut iter, index, value, &mut self.heap)?;
+        state.ignore_next_pop = true;
+
+        Ok(Flow::Next)
+    }
+
+    fn op_set_local_index(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::SetLocalIndex(i, index_at, array_at_index) = *code else {
+            unreachable!()
+        };
+
+        let value = self.stack.pop()?;
+        let index = self.stack.pop_at(self.stack.len().wrapping_sub(index_at))?;
+        let index = iter_utils::unwrap_index(index)?;
+
+        if array_at_index {
+            let mut iter = self.stack.pop_at(self.stack.len().wrapping_sub(index_at))?;
+            iter_utils::set_at_index(&mut iter, index, value, &mut self.heap)?;
+        } else {
+            let offset = self.current_frame().stack_pos;
+            let iter = self.stack.retrieve_at_mut(i + offset)?;
+            iter_utils::set_at_index(iter, index, value, &mut self.heap)?;
+        }
+        state.ignore_next_pop = true;
+
+        Ok(Flow::Next)
+    }
+
+    fn op_set_global_index(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::SetGlobalIndex(name, index_at, array_at_index) = code else {
+            unreachable!()
+        };
+
+        let value = self.stack.pop()?;
+        let index = self
+            .stack
+            .pop_at(self.stack.len().wrapping_sub(*index_at))?;
+        let index = iter_utils::unwrap_index(index)?;
+
+        let mut iter = if *array_at_index {
+            self.stack
+                .pop_at(self.stack.len().wrapping_sub(*index_at))?
+        } else {
+            self.globals.get_mut(name)?.val_mut().clone()
+        };
+
+        iter_utils::set_at_index(&mut iter, index, value, &mut self.heap)?;
+        state.ignore_next_pop = true;
+
+        Ok(Flow::Next)
+    }
+
+    fn op_slice_expr(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::SliceExpr(low, high, max) = *code else {
+            unreachable!()
+        };
+
+        let max = if max {
+            Some(iter_utils::unwrap_index(self.stack.pop()?)?)
+        } else {
+            None
+        };
+        let high = if high {
+            Some(iter_utils::unwrap_index(self.stack.pop()?)?)
+        } else {
+            None
+        };
+        let low = if low {
+            Some(iter_utils::unwrap_index(self.stack.pop()?)?)
+        } else {
+            None
+        };
+        let iter = self.stack.pop()?;
+
+        self.stack.push(iter_utils::slice(&iter, low, high, max)?);
+
+        Ok(Flow::Next)
+    }
+
+    fn op_blind_literal_cast(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::BlindLiteralCast(by) = *code else {
+            unreachable!()
+        };
 
-        let mut vm = Self {
-            globals: NameTable::new(),
-            names: NameTable::new(),
-            builtins: NameTable::new(),
-            stack: VmStack::new(),
-            frames,
-            current_frame: 0,
-            std_streams: std_streams.unwrap_or_else(|| Box::new(StdStreamProvider::new(None))),
+        let val = self.stack.retrieve_by_mut(by)?;
+        val.lose_literal_blindly();
+
+        Ok(Flow::Next)
+    }
+
+    fn op_array_literal(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::ArrayLiteral(size, array_type) = code else {
+            unreachable!()
         };
+        let size = *size;
 
-        vm.define_builtins();
-        vm
+        let ValType::Array(vtype, type_size) = array_type else {
+            return Err(VmError::incorrectly_typed("array literal", array_type));
+        };
+        if *type_size != size {
+            return Err(VmError::wrong_array_size(*type_size, size));
+        }
+
+        let mut vals = vec![];
+        for _ in 0..size {
+            let mut val = self.stack.pop()?;
+            val.lose_literal(vtype);
+            if !val.is_of_type(vtype) {
+                return Err(VmError::type_error(vtype, &val.get_type()));
+            }
+            val.copy_if_soft_reference(&mut self.heap);
+            vals.push(val);
+        }
+
+        vals.reverse();
+        self.stack.push(Value::new_array(
+            vals,
+            size,
+            array_type.clone(),
+            &mut self.heap,
+        ));
+
+        Ok(Flow::Next)
     }
 
-    pub fn run(&mut self) -> VmResult<()> {
-        let mut match_val: Option<Value> = None;
-        let mut switches: VmStack<Switch> = VmStack::new();
-        let mut last_call: Call = Call::new(0, false);
-        let mut ignore_next_pop = false;
+    fn op_slice_literal(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::SliceLiteral(size, slice_type) = code else {
+            unreachable!()
+        };
 
-        loop {
-            let op_code = self.current_frame().next().clone();
-            let op_code = if let Some(op_code) = op_code {
-                op_code
-            } else if self.current_frame == 0 {
-                break;
-            } else {
-                self.current_frame -= 1;
-                self.frames.pop()?;
-                continue;
-            };
+        let ValType::Slice(vtype) = slice_type else {
+            return Err(VmError::incorrectly_typed("slice literal", slice_type));
+        };
 
-            match op_code {
-                OpCode::Noop => {}
-                OpCode::PlusNoop => {
-                    let a = self.stack.pop()?;
-                    a.plus_noop()?;
-                    self.stack.push(a);
-                }
-                OpCode::Negate => {
-                    let mut a = self.stack.pop()?;
-                    a.negate()?;
-                    self.stack.push(a);
-                }
-                OpCode::Subtract => {
-                    let b = self.stack.pop()?;
-                    let mut a = self.stack.pop()?;
-                    a.sub(&b)?;
-                    self.stack.push(a);
-                }
-                OpCode::Add => {
-                    let b = self.stack.pop()?;
-                    let mut a = self.stack.pop()?;
-                    a.add(&b)?;
-                    self.stack.push(a);
-                }
-                OpCode::Multiply => {
-                    let b = self.stack.pop()?;
-                    let mut a = self.stack.pop()?;
-                    a.mult(&b)?;
-                    self.stack.push(a);
-                }
-                OpCode::Divide => {
-                    let b = self.stack.pop()?;
-                    let mut a = self.stack.pop()?;
-                    a.div(&b)?;
-                    self.stack.push(a);
-                }
-                OpCode::Remainder => {
-                    let b = self.stack.pop()?;
-                    let mut a = self.stack.pop()?;
-                    a.modulo(&b)?;
-                    self.stack.push(a);
-                }
-                OpCode::BitwiseAnd => {
-                    let b = self.stack.pop()?;
-                    let mut a = self.stack.pop()?;
-                    a.bitwise_and(&b)?;
-                    self.stack.push(a);
-                }
-                OpCode::BitwiseOr => {
-                    let b = self.stack.pop()?;
-                    let mut a = self.stack.pop()?;
-                    a.bitwise_or(&b)?;
-                    self.stack.push(a);
-                }
-                OpCode::BitwiseXor => {
-                    let b = self.stack.pop()?;
-                    let mut a = self.stack.pop()?;
-                    a.bitwise_xor(&b)?;
-                    self.stack.push(a);
-                }
-                OpCode::BitwiseNot => {
-                    let mut a = self.stack.pop()?;
-                    a.bitwise_complement()?;
-                    self.stack.push(a);
-                }
-                OpCode::BitClear => {
-                    let b = self.stack.pop()?;
-                    let mut a = self.stack.pop()?;
-                    a.bit_clear(&b)?;
-                    self.stack.push(a);
-                }
-                OpCode::LeftShift => {
-                    let b = self.stack.pop()?;
-                    let mut a = self.stack.pop()?;
-                    a.left_shift(&b)?;
-                    self.stack.push(a);
-                }
-                OpCode::RightShift => {
-                    let b = self.stack.pop()?;
-                    let mut a = self.stack.pop()?;
-                    a.right_shift(&b)?;
-                    self.stack.push(a);
-                }
-                OpCode::Return(len) => {
-                    let mut vals: Vec<Value> = vec![];
-                    if len != 0 {
-                        for _ in 0..len {
-                            vals.push(self.stack.pop()?)
-                        }
-                    }
+        let mut vals = vec![];
+        for _ in 0..*size {
+            let mut val = self.stack.pop()?;
+            val.lose_literal(vtype);
+            if !val.is_of_type(vtype) {
+                return Err(VmError::type_error(vtype, &val.get_type()));
+            }
+            val.copy_if_soft_reference(&mut self.heap);
+            vals.push(val);
+        }
 
-                    self.validate_return_type(&vals)?;
-                    self.discard_frame_stack()?;
-                    self.current_frame -= 1;
-                    self.frames.pop()?;
-
-                    if len != 0 {
-                        for val in vals {
-                            self.stack.push(val);
-                        }
-                    } else {
-                        ignore_next_pop = true;
-                    }
-                    // we don't want to increment the frame pointer
-                    continue;
-                }
-                OpCode::Bool(v) | OpCode::String(v) => {
-                    self.stack.push(v);
-                }
-                OpCode::IntLiteral(v) | OpCode::FloatLiteral(v) => {
-                    self.stack.push(v);
-                }
-                OpCode::Func(funit) => {
-                    if let CUnit::Function(func) = funit {
-                        let func_name = func.function().0.to_string();
-                        self.names.insert(func_name.clone(), func)?;
-                        self.stack.push(Value::Func(func_name));
-                    } else {
-                        error::panic_at_cunit_type(&funit);
-                    }
-                }
-                OpCode::Not => {
-                    let mut a = self.stack.pop()?;
-                    a.not()?;
-                    self.stack.push(a);
-                }
-                OpCode::Equal => {
-                    let b = self.stack.pop()?;
-                    let a = self.stack.pop()?;
-                    let eq = a.equal(&b)?;
-                    self.stack.push(eq);
-                }
-                OpCode::NotEqual => {
-                    let b = self.stack.pop()?;
-                    let a = self.stack.pop()?;
-                    let mut eq = a.equal(&b)?;
-                    eq.not()?;
-                    self.stack.push(eq);
-                }
-                OpCode::Greater => {
-                    let b = self.stack.pop()?;
-                    let a = self.stack.pop()?;
-                    let g = a.greater(&b)?;
-                    self.stack.push(g);
-                }
-                OpCode::GreaterEqual => {
-                    let b = self.stack.pop()?;
-                    let a = self.stack.pop()?;
-                    let ge = a.greater_equal(&b)?;
-                    self.stack.push(ge);
-                }
-                OpCode::Less => {
-                    let b = self.stack.pop()?;
-                    let a = self.stack.pop()?;
-                    let l = a.less(&b)?;
-                    self.stack.push(l);
-                }
-                OpCode::LessEqual => {
-                    let b = self.stack.pop()?;
-                    let a = self.stack.pop()?;
-                    let le = a.less_equal(&b)?;
-                    self.stack.push(le);
-                }
-                OpCode::Pop => {
-                    if !ignore_next_pop {
-                        self.stack.pop()?;
-                    } else {
-                        ignore_next_pop = false;
-                    }
-                }
-                OpCode::VarGlobal(name, vtype) => {
-                    let mut value = self.stack.pop()?;
-                    if let Some(vtype) = &vtype {
-                        if !value.is_of_type(vtype) {
-                            return Err(VmError::type_error(vtype, &value.get_type()));
-                        }
-                        value.lose_literal(vtype);
-                    } else {
-                        value.lose_literal_blindly();
-                    }
+        vals.reverse();
+        let slice = Value::new_slice(vals, slice_type.clone(), &mut self.heap);
+        self.stack.push(slice);
 
-                    self.globals
-                        .insert(name.clone(), VmNamedValue::Var(value))?;
-                }
-                OpCode::ConstGlobal(name, vtype) => {
-                    let mut value = self.stack.pop()?;
-                    if let Some(vtype) = &vtype {
-                        if !value.is_of_type(vtype) {
-                            return Err(VmError::type_error(vtype, &value.get_type()));
-                        }
-                        value.lose_literal(vtype);
-                    } else {
-                        value.lose_literal_blindly();
-                    }
+        Ok(Flow::Next)
+    }
 
-                    self.globals
-                        .insert(name.clone(), VmNamedValue::Const(value))?;
-                }
-                OpCode::GetGlobal(name) => {
-                    if let Ok(nval) = self.globals.get(&name) {
-                        self.stack.push(nval.val().clone());
-                    } else if let Ok(builtin) = self.builtins.get(&name) {
-                        let val = Value::FuncBuiltin(builtin.name().to_string());
-                        self.stack.push(val);
-                    } else {
-                        return Err(VmError::undefined(&name));
-                    }
-                }
-                OpCode::SetGlobal(name) => {
-                    if !self.globals.has(&name) {
-                        return Err(VmError::undefined(&name));
-                    }
+    fn op_type_validation(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::TypeValidation(vtype, at) = code else {
+            unreachable!()
+        };
 
-                    let mut value = self.stack.pop()?;
-                    value.copy_if_soft_reference();
+        let val = self.stack.retrieve_by_mut(*at)?;
+        val.lose_literal(vtype);
+        if !val.is_of_type(vtype) {
+            return Err(VmError::type_error(vtype, &val.get_type()));
+        }
 
-                    let old_v = self.globals.get_mut(&name)?;
-                    if let VmNamedValue::Const(_) = old_v {
-                        return Err(VmError::assignment(&name));
-                    }
+        Ok(Flow::Next)
+    }
 
-                    let old_v = old_v.val_mut();
-                    // FIXME: maybe we should store types in a sep hashtable?
-                    if !old_v.same_type(&value) {
-                        return Err(VmError::type_error(&old_v.get_type(), &value.get_type()));
-                    }
+    fn op_variadic_slice_cast(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::VariadicSliceCast(vtype, until) = code else {
+            unreachable!()
+        };
 
-                    value.lose_literal(&old_v.get_type());
-                    *old_v = value.clone();
-                    ignore_next_pop = true;
-                }
-                OpCode::LoseSoftReference(by) => {
-                    let value = self.stack.retrieve_by_mut(by);
-                    value.copy_if_soft_reference();
-                }
-                OpCode::GetLocal(i) => {
-                    let offset = self.current_frame().stack_pos;
-                    let mut value = self.stack.retrieve_at(i + offset).clone();
-                    value.lose_literal_blindly();
-                    self.stack.push(value);
+        if !state.last_call.spread {
+            let length = state
+                .last_call
+                .argc
+                .checked_sub(*until)
+                .ok_or(StackUnderflow)?;
+            let mut slice = Vec::<Value>::with_capacity(length as usize);
+            for _ in 0..length {
+                let val = self.stack.pop()?;
+                if !val.is_of_type(vtype) {
+                    return Err(VmError::type_error(vtype, &val.get_type()));
                 }
-                OpCode::SetLocal(i) => {
-                    let offset = self.current_frame().stack_pos;
-                    let stack_pos = i + offset;
+                slice.push(val);
+            }
+            slice.reverse();
 
-                    let old_v = self.stack.retrieve_at(stack_pos).clone();
-                    let mut value = self.stack.pop()?;
-                    value.lose_literal(&old_v.get_type());
-                    value.copy_if_soft_reference();
+            let slice = Value::new_slice(slice, vtype.clone(), &mut self.heap);
+            self.stack.push(slice);
+        }
 
-                    if !old_v.same_type(&value) {
-                        return Err(VmError::type_error(&old_v.get_type(), &value.get_type()));
-                    }
+        Ok(Flow::Next)
+    }
 
-                    self.stack.put_at(stack_pos, value);
-                    ignore_next_pop = true;
-                }
-                OpCode::Call(argc, spread) => {
-                    last_call = Call::new(argc, spread);
-                    let val = self.stack.retrieve_by(argc as usize).clone();
-                    match val {
-                        Value::Func(name) => {
-                            for arg in 0..argc {
-                                let arg = self.stack.retrieve_by_mut(arg as usize);
-                                arg.copy_if_soft_reference();
-                            }
-
-                            self.call_func(&name, argc, spread)?;
-                            self.current_frame_mut().inc_pointer(1);
-                            self.current_frame += 1;
-                            continue;
-                        }
-                        Value::FuncBuiltin(name) => {
-                            self.call_builtin(&name, argc)?;
-                        }
-                        _ => {
-                            return Err(VmError::callable_value_expected(&val.get_type()));
-                        }
-                    }
-                }
-                OpCode::GetIndex => {
-                    let index = self.stack.pop()?;
-                    let index = iter_utils::unwrap_index(index)?;
-                    let iter = self.stack.pop()?;
+    fn op_convert(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::Convert(vtype) = code else {
+            unreachable!()
+        };
 
-                    self.stack.push(iter_utils::get_at_index(&iter, index)?);
-                }
-                OpCode::GetLocalIndex(i) => {
-                    let index = self.stack.pop()?;
-                    let index = iter_utils::unwrap_index(index)?;
+        let val = self.stack.pop()?;
+        let converted = if val.is_of_type(vtype) {
+            Some(val.clone())
+        } else {
+            val.convert_string(vtype, &mut self.heap)
+        };
 
-                    let offset = self.current_frame().stack_pos;
-                    let iter = self.stack.retrieve_at(i + offset).clone();
+        match converted {
+            Some(converted) => self.stack.push(converted),
+            None => return Err(VmError::conversion(&val.get_type(), vtype)),
+        }
 
-                    self.stack.push(iter_utils::get_at_index(&iter, index)?);
-                }
-                OpCode::GetGlobalIndex(name) => {
-                    let index = self.stack.pop()?;
-                    let index = iter_utils::unwrap_index(index)?;
+        Ok(Flow::Next)
+    }
 
-                    let iter = self.globals.get(&name)?.val();
+    fn op_range_int(&mut self, _: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let mut limit = self.stack.pop()?;
+        limit.lose_literal_blindly();
+        let vtype = limit.get_type();
+        if !vtype.is_integer() {
+            return Err(VmError::range(&vtype));
+        }
 
-                    self.stack.push(iter_utils::get_at_index(iter, index)?);
-                }
-                OpCode::SetIndex => {
-                    let value = self.stack.pop()?;
-                    let index = self.stack.pop()?;
-                    let index = iter_utils::unwrap_index(index)?;
-
-                    let mut iter = self.stack.pop()?;
-                    iter_utils::set_at_index(&mut iter, index, value)?;
-                    ignore_next_pop = true;
-                }
-                OpCode::SetLocalIndex(i, index_at, array_at_index) => {
-                    let value = self.stack.pop()?;
-                    let index = self.stack.pop_at(self.stack.len() - index_at);
-                    let index = iter_utils::unwrap_index(index)?;
-
-                    let mut iter = if array_at_index {
-                        self.stack.pop_at(self.stack.len() - index_at)
-                    } else {
-                        let offset = self.current_frame().stack_pos;
-                        self.stack.retrieve_at(i + offset).clone()
-                    };
-
-                    iter_utils::set_at_index(&mut iter, index, value)?;
-                    ignore_next_pop = true;
-                }
-                OpCode::SetGlobalIndex(name, index_at, array_at_index) => {
-                    let value = self.stack.pop()?;
-                    let index = self.stack.pop_at(self.stack.len() - index_at);
-                    let index = iter_utils::unwrap_index(index)?;
-
-                    let mut iter = if array_at_index {
-                        self.stack.pop_at(self.stack.len() - index_at)
-                    } else {
-                        self.globals.get_mut(&name)?.val_mut().clone()
-                    };
-
-                    iter_utils::set_at_index(&mut iter, index, value)?;
-                    ignore_next_pop = true;
-                }
-                OpCode::BlindLiteralCast(by) => {
-                    let val = self.stack.retrieve_by_mut(by);
-                    val.lose_literal_blindly();
-                }
-                OpCode::ArrayLiteral(size, array_type) => {
-                    let mut vals = vec![];
-                    if let ValType::Array(vtype, type_size) = &array_type {
-                        if *type_size != size {
-                            return Err(VmError::wrong_array_size(*type_size, size));
-                        }
-
-                        for _ in 0..size {
-                            let mut val = self.stack.pop()?;
-                            val.lose_literal(vtype);
-                            if !val.is_of_type(vtype) {
-                                return Err(VmError::type_error(vtype, &val.get_type()));
-                            }
-                            vals.push(val);
-                        }
-
-                        vals.reverse();
-                        self.stack.push(Value::new_array(vals, size, array_type));
-                    } else {
-                        return Err(VmError::incorrectly_typed("array literal", &array_type));
-                    }
-                }
-                OpCode::SliceLiteral(size, slice_type) => {
-                    let mut vals = vec![];
-                    if let ValType::Slice(vtype) = &slice_type {
-                        for _ in 0..size {
-                            let mut val = self.stack.pop()?;
-                            val.lose_literal(vtype);
-                            if !val.is_of_type(vtype) {
-                                return Err(VmError::type_error(vtype, &val.get_type()));
-                            }
-                            vals.push(val);
-                        }
-
-                        vals.reverse();
-                        self.stack.push(Value::new_slice(vals, slice_type));
-                    } else {
-                        return Err(VmError::incorrectly_typed("slice literal", &slice_type));
-                    }
-                }
-                OpCode::TypeValidation(vtype, at) => {
-                    let val = self.stack.retrieve_by_mut(at);
-                    val.lose_literal(&vtype);
-                    if !val.is_of_type(&vtype) {
-                        return Err(VmError::type_error(&vtype, &val.get_type()));
-                    }
-                }
-                OpCode::VariadicSliceCast(vtype, until) => {
-                    if !last_call.spread {
-                        let length = last_call.argc - until;
-                        let mut slice = Vec::<Value>::with_capacity(length as usize);
-                        for _ in 0..length {
-                            let val = self.stack.pop()?;
-                            if !val.is_of_type(&vtype) {
-                                return Err(VmError::type_error(&vtype, &val.get_type()));
-                            }
-                            slice.push(val);
-                        }
-                        slice.reverse();
-
-                        let slice = Value::new_slice(slice, vtype);
-                        self.stack.push(slice);
-                    }
-                }
-                OpCode::PutDefaultValue(val_type) => {
-                    self.stack.push(Value::default(&val_type));
-                }
-                OpCode::IfFalseJump(j) => {
-                    let value = self.stack.retrieve();
-                    match value {
-                        Value::Bool(false) => {
-                            self.current_frame_mut().inc_pointer(j);
-                        }
-                        Value::Bool(true) => {}
-                        val => {
-                            return Err(VmError::non_bool_in_condition(&val.get_type()));
-                        }
-                    }
-                }
-                OpCode::Jump(j) => {
-                    self.current_frame_mut().inc_pointer(j);
-                }
-                OpCode::BackJump(j) => {
-                    self.current_frame_mut().dec_pointer(j);
-                }
-                OpCode::DefaultJump(j) => {
-                    let last = switches.last_mut();
-
-                    if last.matched {
-                        switches.pop()?;
-                    } else {
-                        self.current_frame_mut().dec_pointer(j);
-                        last.matched = true;
-                    }
-                }
-                OpCode::CaseBreakJump(j) => {
-                    let mut last = switches.last_mut();
-                    if last.jump_from_case {
-                        self.current_frame_mut().inc_pointer(j);
-                        last.jump_from_case = false;
-                    }
-                }
-                OpCode::DoCaseBreakJump => {
-                    let mut last = switches.last_mut();
-                    last.jump_from_case = true;
-                }
-                OpCode::DefaultCaseJump(j) => {
-                    let mut last = switches.last_mut();
-                    if !last.fall_flag {
-                        self.current_frame_mut().inc_pointer(j);
-                    }
-                    if last.fall_flag {
-                        last.fall_flag = false;
-                    }
-                }
-                OpCode::CaseJump(j) => {
-                    let mut last = switches.last_mut();
-
-                    if !last.fall_flag {
-                        if let Some(match_val) = &match_val {
-                            let val = self.stack.pop()?;
-
-                            match match_val.equal(&val)? {
-                                Value::Bool(true) => {
-                                    last.matched = true;
-                                }
-                                Value::Bool(false) => {
-                                    self.current_frame_mut().inc_pointer(j);
-                                }
-                                _ => return Err(VmError::unexpected_matching_result()),
-                            }
-                        } else {
-                            return Err(VmError::non_exhaustive_matching_result());
-                        }
-                    } else {
-                        last.fall_flag = false;
-                    }
-                }
-                OpCode::Fallthrough => {
-                    let mut last = switches.last_mut();
-                    last.jump_from_case = false;
-                    last.fall_flag = true;
-                }
-                OpCode::Switch => {
-                    let val = self.stack.pop()?;
-                    match_val = Some(val);
-                    switches.push(Switch::new());
-                }
-            }
+        self.stack.push(limit);
+        let zero = Value::default(&vtype, &mut self.heap)?;
+        self.stack.push(zero);
+
+        Ok(Flow::Next)
+    }
+
+    fn op_put_default_value(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::PutDefaultValue(val_type) = code else {
+            unreachable!()
+        };
 
-            self.current_frame_mut().inc_pointer(1);
+        let zero = Value::default(val_type, &mut self.heap)?;
+        self.stack.push(zero);
+
+        Ok(Flow::Next)
+    }
+
+    fn op_if_false_jump(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::IfFalseJump(target) = *code else {
+            unreachable!()
+        };
+
+        match self.stack.retrieve()? {
+            Value::Bool(false) => self.jump(target),
+            Value::Bool(true) => Ok(Flow::Next),
+            val => Err(VmError::non_bool_in_condition(&val.get_type())),
         }
+    }
 
-        VmResult::Ok(())
+    fn op_jump(&mut self, code: &OpCode, _: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::Jump(target) = *code else {
+            unreachable!()
+        };
+
+        self.jump(target)
+    }
+
+    fn op_default_jump(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::DefaultJump(target) = *code else {
+            unreachable!()
+        };
+
+        let last = state.switches.last_mut()?;
+        if last.matched {
+            state.switches.pop()?;
+            Ok(Flow::Next)
+        } else {
+            last.matched = true;
+            self.jump(target)
+        }
+    }
+
+    fn op_case_break_jump(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::CaseBreakJump(target) = *code else {
+            unreachable!()
+        };
+
+        let last = state.switches.last_mut()?;
+        if last.jump_from_case {
+            last.jump_from_case = false;
+            self.jump(target)
+        } else {
+            Ok(Flow::Next)
+        }
+    }
+
+    fn op_do_case_break_jump(&mut self, _: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let last = state.switches.last_mut()?;
+        last.jump_from_case = true;
+
+        Ok(Flow::Next)
+    }
+
+    fn op_default_case_jump(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::DefaultCaseJump(target) = *code else {
+            unreachable!()
+        };
+
+        let last = state.switches.last_mut()?;
+        if last.fall_flag {
+            last.fall_flag = false;
+            Ok(Flow::Next)
+        } else {
+            self.jump(target)
+        }
+    }
+
+    fn op_case_jump(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::CaseJump(target) = *code else {
+            unreachable!()
+        };
+
+        let last = state.switches.last_mut()?;
+        if last.fall_flag {
+            last.fall_flag = false;
+            return Ok(Flow::Next);
+        }
+
+        let Some(match_val) = &state.match_val else {
+            return Err(VmError::non_exhaustive_matching_result());
+        };
+
+        let val = self.stack.pop()?;
+        match match_val.equal(&val)? {
+            Value::Bool(true) => {
+                last.matched = true;
+                Ok(Flow::Next)
+            }
+            Value::Bool(false) => self.jump(target),
+            _ => Err(VmError::unexpected_matching_result()),
+        }
+    }
+
+    fn op_fallthrough(&mut self, _: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let last = state.switches.last_mut()?;
+        last.jump_from_case = false;
+        last.fall_flag = true;
+
+        Ok(Flow::Next)
+    }
+
+    fn op_switch(&mut self, _: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let val = self.stack.pop()?;
+        state.match_val = Some(val);
+        state.switches.push(Switch::new());
+
+        Ok(Flow::Next)
+    }
+
+    fn op_jump_table(&mut self, code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+        let OpCode::JumpTable(table) = code else {
+            unreachable!()
+        };
+
+        // values the table does not cover are matched case by case
+        let Some(val) = state.match_val.as_ref().filter(|val| table.covers(val)) else {
+            return Ok(Flow::Next);
+        };
+
+        let target = match table.lookup(val) {
+            Some(target) => {
+                state.switches.last_mut()?.matched = true;
+                target
+            }
+            None => table.miss(),
+        };
+
+        self.jump(target)
     }
 
     fn call_func(&mut self, name: &str, argc: u8, spread: bool) -> VmRuntimeCall<()> {
-        let f = self.names.get(name)?;
-        if (!f.is_variadic() || f.is_variadic() && spread) && argc as usize != f.argc() {
-            return Err(VmError::mismatched_argc(f.argc(), argc));
+        let (cunit, lookup) = self.names.get(name)?;
+        if let CUnit::Function(f) = &**cunit {
+            if (!f.is_variadic() || f.is_variadic() && spread) && argc as usize != f.argc() {
+                return Err(VmError::mismatched_argc(f.argc(), argc));
+            }
         }
 
-        let mut frame = CUnitFrame::new(CUnit::Function(f.clone()));
-        frame.stack_pos = self.stack.len() - argc as usize;
-        self.frames.push(Rc::new(RefCell::new(frame)));
+        let mut frame = CUnitFrame::shared(Rc::clone(cunit), lookup.clone());
+        frame.stack_pos = self
+            .stack
+            .len()
+            .checked_sub(argc as usize)
+            .ok_or(StackUnderflow)?;
+        if let Some(max) = self.max_frame_depth {
+            if self.frames.len() >= max {
+                return Err(VmError::stack_overflow(max));
+            }
+        }
+        self.growth.reserve(&mut self.frames);
+        self.frames.push(frame);
+
+        #[cfg(feature = "stats")]
+        {
+            self.stats.calls += 1;
+            self.stats.peak_frame_depth = self.stats.peak_frame_depth.max(self.frames.len());
+        }
 
         Ok(())
     }
 
-    fn call_builtin(&mut self, name: &str, argc: u8) -> VmRuntimeCall<()> {
-        let f = self.builtins.get(name)?;
+    /// Calls the builtin with the arguments on top of the stack, which are discarded
+    fn call_builtin(&mut self, id: u8, argc: u8) -> VmRuntimeCall<Option<Value>> {
+        let f = &self.builtins[id as usize];
         let len = self.stack.len();
-        let stack_pos = len - argc as usize;
+        let stack_pos = len.checked_sub(argc as usize).ok_or(StackUnderflow)?;
 
-        if let Some(fargc) = f.argc() {
-            if *fargc != argc {
-                return Err(VmError::mismatched_argc(*fargc as usize, argc));
-            }
+        let argv = self.stack.slice(stack_pos, len);
+        let res = f.call(argv, self.std_streams.as_ref(), &mut self.heap)?;
+        #[cfg(feature = "stats")]
+        {
+            self.stats.calls += 1;
         }
-
-        let res = f.call(self.stack.slice(stack_pos, len), self.std_streams.as_ref())?;
         for _ in 1..=argc {
             self.stack.pop()?;
         }
 
-        if let Some(val) = res {
-            self.stack.pop()?;
-            self.stack.push(val);
-        }
+        Ok(res)
+    }
 
-        Ok(())
+    fn is_breakpoint(&mut self, cunit: &Rc<CUnit>, pointer: usize) -> bool {
+        let lines = &self.breakpoints;
+        self.breakpoint_codes
+            .entry(Rc::as_ptr(cunit))
+            .or_insert_with(|| {
+                let codes = lines
+                    .iter()
+                    .filter_map(|line| cunit.chunk().first_code_at_line(*line))
+                    .collect();
+                (Rc::clone(cunit), codes)
+            })
+            .1
+            .contains(&pointer)
+    }
+
+    /// Frames being executed, the innermost first
+    fn stack_trace(&self) -> StackTrace {
+        let frames = self
+            .visible_frames()
+            .map(|(function, pos, _)| TraceFrame { function, pos })
+            .collect();
+
+        let source_name = self
+            .source_name
+            .clone()
+            .unwrap_or_else(|| "<input>".to_string());
+
+        StackTrace::new(source_name, frames)
+    }
+
+    /// Frames of the functions being executed along with their qualified names
+    /// and the positions they are at, the innermost first
+    fn visible_frames(&self) -> impl Iterator<Item = (String, Pos, &CUnitFrame)> {
+        let package = match &*self.frames[0].cunit {
+            CUnit::Package(p) => p.package().0.as_str(),
+            CUnit::Function(_) => "main",
+        };
+
+        let last = self.frames.len() - 1;
+        self.frames
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(move |(i, frame)| *i == last || matches!(*frame.cunit, CUnit::Function(_)))
+            .map(move |(i, frame)| {
+                // callers have already moved past the call
+                let at = if i == last {
+                    frame.pointer
+                } else {
+                    frame.pointer.saturating_sub(1)
+                };
+
+                let function = match &*frame.cunit {
+                    CUnit::Function(f) => f.function().0.as_str(),
+                    CUnit::Package(_) => "init",
+                };
+
+                (format!("{}.{}", package, function), frame.pos_at(at), frame)
+            })
     }
 
-    fn current_frame(&self) -> Ref<CUnitFrame> {
-        let last_frame = self.frames.retrieve_at(self.current_frame);
-        last_frame.borrow()
+    fn current_frame(&self) -> &CUnitFrame {
+        self.frames.last().expect("No frame to execute.")
     }
 
-    fn current_frame_mut(&mut self) -> RefMut<CUnitFrame> {
-        let last_frame = self.frames.retrieve_at(self.current_frame);
-        last_frame.borrow_mut()
+    fn current_frame_mut(&mut self) -> &mut CUnitFrame {
+        self.frames.last_mut().expect("No frame to execute.")
     }
 
     fn discard_frame_stack(&mut self) -> VmResult<()> {
@@ -642,8 +1344,9 @@ impl Vm {
         Ok(())
     }
 
-    fn validate_return_type(&self, vals: &[Value]) -> VmResult<()> {
-        let cunit = &self.current_frame().cunit;
+    /// Untyped constants take the declared return types
+    fn validate_return_type(&self, vals: &mut [Value]) -> VmResult<()> {
+        let cunit = &*self.current_frame().cunit;
         if let CUnit::Function(funit) = cunit {
             let ctype = funit.ret_type();
             let type_len = ctype.len();
@@ -653,10 +1356,11 @@ impl Vm {
             }
 
             for (i, vtype) in ctype.types().iter().enumerate() {
-                let val = &vals[val_len - i - 1];
+                let val = &mut vals[val_len - i - 1];
                 if !val.is_of_type(vtype) {
                     return Err(VmError::return_type_error(vtype, &val.get_type()));
                 }
+                val.lose_literal(vtype);
             }
 
             Ok(())
@@ -666,6 +1370,142 @@ impl Vm {
     }
 }
 
+/// What the execution loop does once an opcode has been handled
+enum Flow {
+    /// Moves on to the next opcode of the frame
+    Next,
+    /// The handler has jumped, called or returned, the frame pointer is already in place
+    Stay,
+}
+
+/// Execution state outliving a single opcode, besides the stacks of the VM
+struct ExecState {
+    match_val: Option<Value>,
+    switches: VmStack<Switch>,
+    last_call: Call,
+    ignore_next_pop: bool,
+}
+
+impl ExecState {
+    fn new() -> Self {
+        Self {
+            match_val: None,
+            switches: VmStack::new(),
+            last_call: Call::new(0, false),
+            ignore_next_pop: false,
+        }
+    }
+}
+
+/// Executes the opcode it has been chosen for
+type Handler = fn(&mut Vm, &OpCode, &mut ExecState) -> VmResult<Flow>;
+
+/// What the opcodes of a unit are executed with, looked up once per unit
+/// instead of matching every executed opcode
+#[derive(Debug, Clone)]
+struct Lookup {
+    /// Handlers of the opcodes, in the same order
+    #[cfg_attr(feature = "match-dispatch", allow(dead_code))]
+    handlers: Rc<[Handler]>,
+    /// Register instructions executed instead of the runs of opcodes starting at their indices
+    #[cfg(feature = "register-vm")]
+    registers: Rc<[Option<Instr>]>,
+}
+
+impl Lookup {
+    fn new(cunit: &CUnit) -> Self {
+        Self {
+            handlers: cunit.chunk().codes().iter().map(handler).collect(),
+            #[cfg(feature = "register-vm")]
+            registers: register::lower(cunit.chunk()),
+        }
+    }
+}
+
+macro_rules! handlers {
+    ($($op_code:pat => $handler:ident),* $(,)?) => {
+        fn handler(op_code: &OpCode) -> Handler {
+            match op_code {
+                $($op_code => Vm::$handler,)*
+            }
+        }
+
+        /// Matches every executed opcode, kept to compare against the precomputed handlers
+        #[cfg(feature = "match-dispatch")]
+        fn dispatch(vm: &mut Vm, op_code: &OpCode, state: &mut ExecState) -> VmResult<Flow> {
+            match op_code {
+                $($op_code => vm.$handler(op_code, state),)*
+            }
+        }
+    };
+}
+
+handlers! {
+    OpCode::Noop => op_noop,
+    OpCode::PlusNoop => op_plus_noop,
+    OpCode::Negate => op_negate,
+    OpCode::Subtract => op_subtract,
+    OpCode::Add => op_add,
+    OpCode::Multiply => op_multiply,
+    OpCode::Divide => op_divide,
+    OpCode::Remainder => op_remainder,
+    OpCode::BitwiseAnd => op_bitwise_and,
+    OpCode::BitwiseOr => op_bitwise_or,
+    OpCode::BitwiseXor => op_bitwise_xor,
+    OpCode::BitwiseNot => op_bitwise_not,
+    OpCode::BitClear => op_bit_clear,
+    OpCode::LeftShift => op_left_shift,
+    OpCode::RightShift => op_right_shift,
+    OpCode::Return(_) => op_return,
+    OpCode::Constant(_) => op_constant,
+    OpCode::Func(_) => op_func,
+    OpCode::Not => op_not,
+    OpCode::Equal => op_equal,
+    OpCode::NotEqual => op_not_equal,
+    OpCode::Greater => op_greater,
+    OpCode::GreaterEqual => op_greater_equal,
+    OpCode::Less => op_less,
+    OpCode::LessEqual => op_less_equal,
+    OpCode::Pop => op_pop,
+    OpCode::VarGlobal(..) => op_var_global,
+    OpCode::ConstGlobal(..) => op_const_global,
+    OpCode::GetGlobal(_) => op_get_global,
+    OpCode::SetGlobal(_) => op_set_global,
+    OpCode::LoseSoftReference(_) => op_lose_soft_reference,
+    OpCode::GetLocal(_) => op_get_local,
+    OpCode::SetLocal(_) => op_set_local,
+    OpCode::IncLocal(..) => op_inc_dec_local,
+    OpCode::DecLocal(..) => op_inc_dec_local,
+    OpCode::Call(..) => op_call,
+    OpCode::TailCall(..) => op_tail_call,
+    OpCode::CallBuiltin(..) => op_call_builtin,
+    OpCode::GetIndex => op_get_index,
+    OpCode::GetLocalIndex(_) => op_get_local_index,
+    OpCode::GetGlobalIndex(_) => op_get_global_index,
+    OpCode::SetIndex => op_set_index,
+    OpCode::SetLocalIndex(..) => op_set_local_index,
+    OpCode::SetGlobalIndex(..) => op_set_global_index,
+    OpCode::SliceExpr(..) => op_slice_expr,
+    OpCode::BlindLiteralCast(_) => op_blind_literal_cast,
+    OpCode::ArrayLiteral(..) => op_array_literal,
+    OpCode::SliceLiteral(..) => op_slice_literal,
+    OpCode::TypeValidation(..) => op_type_validation,
+    OpCode::VariadicSliceCast(..) => op_variadic_slice_cast,
+    OpCode::Convert(_) => op_convert,
+    OpCode::RangeInt => op_range_int,
+    OpCode::PutDefaultValue(_) => op_put_default_value,
+    OpCode::IfFalseJump(_) => op_if_false_jump,
+    OpCode::Jump(_) => op_jump,
+    OpCode::DefaultJump(_) => op_default_jump,
+    OpCode::CaseBreakJump(_) => op_case_break_jump,
+    OpCode::DoCaseBreakJump => op_do_case_break_jump,
+    OpCode::DefaultCaseJump(_) => op_default_case_jump,
+    OpCode::CaseJump(_) => op_case_jump,
+    OpCode::Fallthrough => op_fallthrough,
+    OpCode::Switch => op_switch,
+    OpCode::JumpTable(_) => op_jump_table,
+}
+
 struct Switch {
     matched: bool,
     jump_from_case: bool,
@@ -695,15 +1535,24 @@ impl Call {
 
 #[derive(Debug)]
 pub struct CUnitFrame {
-    cunit: CUnit,
+    cunit: Rc<CUnit>,
+    #[cfg_attr(feature = "match-dispatch", allow(dead_code))]
+    lookup: Lookup,
     pointer: usize,
     stack_pos: usize,
 }
 
 impl CUnitFrame {
     pub fn new(cunit: CUnit) -> Self {
+        let lookup = Lookup::new(&cunit);
+        Self::shared(Rc::new(cunit), lookup)
+    }
+
+    /// Frame executing a unit other frames may be executing as well, e.g. a recursive function
+    fn shared(cunit: Rc<CUnit>, lookup: Lookup) -> Self {
         Self {
             cunit,
+            lookup,
             pointer: 0,
             stack_pos: 0,
         }
@@ -713,15 +1562,17 @@ impl CUnitFrame {
         self.pointer += by;
     }
 
-    fn dec_pointer(&mut self, by: usize) {
-        self.pointer -= by;
+    /// Jump targets are absolute and verified before the execution
+    fn jump_to(&mut self, target: usize) {
+        self.pointer = target;
     }
 
-    fn next(&self) -> Option<OpCode> {
-        if self.pointer >= self.cunit.chunk().codes().len() {
-            None
-        } else {
-            Some(self.cunit.chunk().codes()[self.pointer].clone())
+    /// Source position of the opcode at `i`, the last one if the frame is exhausted
+    fn pos_at(&self, i: usize) -> Pos {
+        let chunk = self.cunit.chunk();
+        match chunk.codes().len() {
+            0 => Pos(0, 0),
+            len => chunk.pos(i.min(len - 1)),
         }
     }
 }
@@ -737,26 +1588,119 @@ mod iter_utils {
         }
     }
 
-    pub(super) fn set_at_index(iter: &mut Value, index: usize, mut value: Value) -> VmResult<()> {
+    pub(super) fn set_at_index(
+        iter: &mut Value,
+        index: usize,
+        mut value: Value,
+        heap: &mut Heap,
+    ) -> VmResult<()> {
         match iter {
-            Value::Array(iter, _, ValType::Array(ref vtype, ..))
-            | Value::Slice(iter, ValType::Slice(ref vtype)) => {
+            Value::Array(iter, _, ValType::Array(ref vtype, ..)) => {
+                value.lose_literal(vtype);
+                if !value.is_of_type(vtype) {
+                    return Err(VmError::type_error(vtype, &value.get_type()));
+                }
+                let len = iter.borrow().len();
+                if index >= len {
+                    return Err(VmError::index_out_of_range(index, len));
+                }
+                iter.borrow_mut()[index].store(value, heap);
+
+                Ok(())
+            }
+            Value::Slice(slice, ValType::Slice(ref vtype)) => {
                 value.lose_literal(vtype);
                 if !value.is_of_type(vtype) {
                     return Err(VmError::type_error(vtype, &value.get_type()));
                 }
-                iter.borrow_mut()[index] = value;
+                if !slice.set(index, value, heap) {
+                    return Err(VmError::index_out_of_range(index, slice.len()));
+                }
 
                 Ok(())
             }
-            _ => Err(VmError::iterator_value_expected(&iter.get_type())),
+            Value::String(_) => Err(VmError::string_assignment()),
+            _ => Err(VmError::iterator_value_expected(iter)),
         }
     }
 
     pub(super) fn get_at_index(iter: &Value, index: usize) -> VmResult<Value> {
         match iter {
-            Value::Array(iter, ..) | Value::Slice(iter, ..) => Ok(iter.borrow_mut()[index].clone()),
-            _ => Err(VmError::iterator_value_expected(&iter.get_type())),
+            Value::Array(iter, ..) => match iter.borrow().get(index) {
+                Some(value) => Ok(value.clone()),
+                None => Err(VmError::index_out_of_range(index, iter.borrow().len())),
+            },
+            Value::Slice(slice, _) => match slice.get(index) {
+                Some(value) => Ok(value),
+                None => Err(VmError::index_out_of_range(index, slice.len())),
+            },
+            // strings are indexed by bytes
+            Value::String(s) => match s.as_bytes().get(index) {
+                Some(byte) => Ok(Value::Uint8(*byte)),
+                None => Err(VmError::index_out_of_range(index, s.len())),
+            },
+            _ => Err(VmError::iterator_value_expected(iter)),
+        }
+    }
+
+    /// Slice expression `iter[low:high]` or `iter[low:high:max]`,
+    /// slices of arrays and slices share their elements
+    pub(super) fn slice(
+        iter: &Value,
+        low: Option<usize>,
+        high: Option<usize>,
+        max: Option<usize>,
+    ) -> VmResult<Value> {
+        let low = low.unwrap_or(0);
+        let (len, cap) = match iter {
+            Value::Array(_, size, _) => (*size, *size),
+            Value::Slice(slice, _) => (slice.len(), slice.cap()),
+            Value::String(_) if max.is_some() => return Err(VmError::string_full_slice()),
+            Value::String(s) => (s.len(), s.len()),
+            _ => return Err(VmError::iterator_value_expected(iter)),
+        };
+
+        let high = high.unwrap_or(len);
+        if let Some(max) = max {
+            if max > cap {
+                let bounds = format!("::{}", max);
+                return Err(VmError::slice_bound_out_of_range(&bounds, cap, false));
+            }
+            if high > max {
+                let bounds = format!(":{}:{}", high, max);
+                return Err(VmError::slice_bounds_inverted(&bounds));
+            }
+            if low > high {
+                let bounds = format!("{}:{}:", low, high);
+                return Err(VmError::slice_bounds_inverted(&bounds));
+            }
+        } else {
+            if high > cap {
+                let string = matches!(iter, Value::String(_));
+                let bounds = format!(":{}", high);
+                return Err(VmError::slice_bound_out_of_range(&bounds, cap, string));
+            }
+            if low > high {
+                let bounds = format!("{}:{}", low, high);
+                return Err(VmError::slice_bounds_inverted(&bounds));
+            }
         }
+        let max = max.unwrap_or(cap);
+
+        Ok(match iter {
+            Value::Array(array, _, ValType::Array(vtype, _)) => Value::Slice(
+                SliceRef::of_array(array, low, high, max),
+                ValType::Slice(vtype.clone()),
+            ),
+            Value::Slice(slice, vtype) => {
+                Value::Slice(slice.reslice(low, high, max), vtype.clone())
+            }
+            Value::String(s) => match s.get(low..high) {
+                Some(sub) => Value::String(sub.into()),
+                // a multibyte character is cut, the bytes are kept as they are
+                None => Value::String(String::from_utf8_lossy(&s.as_bytes()[low..high]).into()),
+            },
+            _ => unreachable!(),
+        })
     }
 }
@@ -27,11 +27,11 @@ func main() {
     println(x, x[0], x[0][0], x[0][0][0], x[0][0][1])
 }
 "#,
-        r#"<[2]int>[0 0] 0 0
-<[3]bool>[false false false] false false
-<[4]int8>[0 0 0 0]
-<[3][2]int8>[<[2]int8>[0 0] <[2]int8>[0 0] <[2]int8>[0 0]] <[2]int8>[0 0] 0
-<[1][1][2]float64>[<[1][2]float64>[<[2]float64>[0e0 0e0]]] <[1][2]float64>[<[2]float64>[0e0 0e0]] <[2]float64>[0e0 0e0] 0e0 0e0
+        r#"[0 0] 0 0
+[false false false] false false
+[0 0 0 0]
+[[0 0] [0 0] [0 0]] [0 0] 0
+[[[+0.000000e+000 +0.000000e+000]]] [[+0.000000e+000 +0.000000e+000]] [+0.000000e+000 +0.000000e+000] +0.000000e+000 +0.000000e+000
 "#,
     )
 }
@@ -70,12 +70,12 @@ func main() {
     println(x, x[0], x[0][0], x[0][0][0], x[0][0][1])
 }
 "#,
-        r#"<[2]int>[1 2] 2 1
-<[3]bool>[true false true] false true
-<[2]string>[string another string] string another string
-<[4]int8>[1 3 -1 4]
-<[3][2]int>[<[2]int>[1 2] <[2]int>[3 1] <[2]int>[1 2]] <[2]int>[1 2] 2
-<[1][1][2]string>[<[1][2]string>[<[2]string>[string another string]]] <[1][2]string>[<[2]string>[string another string]] <[2]string>[string another string] string another string
+        r#"[1 2] 2 1
+[true false true] false true
+[string another string] string another string
+[1 3 -1 4]
+[[1 2] [3 1] [1 2]] [1 2] 2
+[[[string another string]]] [[string another string]] [string another string] string another string
 "#,
     )
 }
@@ -95,6 +95,12 @@ func main() {
     q[0]--
     println(q, q[1], q[0])
 
+    p := [2]int{3, 2}
+    i := 1
+    p[i]++
+    p[i-1] += p[i]
+    println(p)
+
     w[0] = !true
     w[2] = w[0]
     println(w, w[1], w[0])
@@ -124,13 +130,14 @@ func main() {
     println(j)
 }
 "#,
-        r#"<[2]int>[3 2] 2 3
-<[3]bool>[false false false] false false
-<[2]string>[string!! another string] string!! another string
-<[4]int8>[2 2 -1 4]
-<[3][2]int>[<[2]int>[1 88] <[2]int>[3 1] <[2]int>[3 2]] <[2]int>[1 88] 88
-<[3][2]int>[<[2]int>[1 88] <[2]int>[3 1] <[2]int>[3 2]] <[2]int>[1 88]
-<[2]int>[1 88]
+        r#"[3 2] 2 3
+[6 3]
+[false false false] false false
+[string!! another string] string!! another string
+[2 2 -1 4]
+[[1 88] [3 1] [3 2]] [1 88] 88
+[[1 56] [3 1] [3 2]] [1 56]
+[1 77]
 "#,
     )
 }
@@ -192,8 +199,8 @@ func multiply(array [4]int, by int) [4]int {
     return multiplied
 }
 "#,
-        r#"<[4]int>[6 9 12 15]
-<[4]int>[-20 -30 -40 -50]
+        r#"[6 9 12 15]
+[-20 -30 -40 -50]
 "#,
     )
 }
@@ -219,10 +226,96 @@ func main() {
     println(len(y))
 }
 "#,
-        r#"<[4]int>[2 3 4 5]
+        r#"[2 3 4 5]
 4
-<[4][4]int>[<[4]int>[1 2 3 4] <[4]int>[1 2 5 7] <[4]int>[2 3 4 5] <[4]int>[2 3 4 5]]
+[[1 2 3 4] [1 2 5 7] [2 3 4 5] [2 3 4 5]]
 4
 "#,
     )
 }
+
+#[test]
+fn test_array_constant_len_size() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    var a [len("héllo")]int
+    var b [len([2][2]int{{1, 2}, {3, 4}})]string
+    println(len(a), len(b))
+}
+"#,
+        "6 2\n",
+    )
+}
+
+#[test]
+fn test_array_value_copy() {
+    compare_stderr_output(
+        r#"
+package main
+
+var ga = [2]int{1, 2}
+var gb = ga
+
+func mutate(a [2][2]int) {
+    a[0][0] = 42
+}
+
+func main() {
+    gb[0] = 9
+    println(ga, gb)
+
+    a := [2][2]int{[2]int{1, 2}, [2]int{3, 4}}
+    b := a
+    var c [2][2]int = a
+    b[0][0] = 9
+    c[1][1] = 9
+    mutate(a)
+    println(a, b, c)
+
+    inner := [2]int{5, 6}
+    a[1] = inner
+    lit := [2][2]int{inner, inner}
+    s := [][2]int{inner}
+    s = append(s, inner)
+    inner[0] = 0
+    lit[0][1] = 0
+    println(a, lit, s, inner)
+
+    x := s[0]
+    x[1] = 7
+    println(s, x)
+}
+"#,
+        "[1 2] [9 2]
+[[1 2] [3 4]] [[9 2] [3 4]] [[1 2] [3 9]]
+[[1 2] [5 6]] [[5 0] [5 6]] [[5 6] [5 6]] [0 6]
+[[5 6] [5 6]] [5 7]
+",
+    );
+}
+
+#[test]
+fn test_array_assignment_keeps_slices() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    var a [3]int
+    s := a[1:]
+    a = [3]int{1, 2, 3}
+    println(s, a)
+
+    m := [2][2]int{[2]int{1, 2}, [2]int{3, 4}}
+    row := m[1][:]
+    m = [2][2]int{[2]int{5, 6}, [2]int{7, 8}}
+    m[1][0] = 0
+    println(row, m)
+}
+"#,
+        "[2 3] [1 2 3]\n[0 8] [[5 6] [0 8]]\n",
+    );
+}
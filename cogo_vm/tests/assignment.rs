@@ -34,6 +34,113 @@ func inc(x int) int {
     return x + 1
 }
         "#,
-        "<[10]int>[0 9 0 0 0 0 0 0 0 0] <[2]string>[another1 another2] <[2][2]uint8>[<[2]uint8>[0 18] <[2]uint8>[0 0]] 12 bye hello <[1][1]int64>[<[1]int64>[100]] 1 <[]int>[2]\n",
+        "[0 9 0 0 0 0 0 0 0 0] [another1 another2] [[0 18] [0 0]] 12 bye hello [[100]] 1 [2]\n",
     )
 }
+
+#[test]
+fn test_assignment_call_results() {
+    compare_stderr_output(
+        r#"
+package main
+
+var ga int64
+var gb string
+
+func pair() (int64, string) {
+    return 1, "a"
+}
+
+func main() {
+    var a int64
+    var b string
+    a, b = pair()
+    println(a, b)
+
+    ga, gb = pair()
+    println(ga, gb)
+
+    x := [2]int64{0, 0}
+    x[1], b = pair()
+    println(x, b)
+
+    c, d := pair()
+    c += ga
+    println(c, d)
+}
+"#,
+        "1 a\n1 a\n[0 1] a\n2 a\n",
+    );
+}
+
+#[test]
+fn test_assignment_swap() {
+    compare_stderr_output(
+        r#"
+package main
+
+var g1, g2 = 1, 2
+var gx = [3]int{1, 2, 3}
+var gi = 0
+
+func main() {
+    a, b, c := 1, 2, 3
+    a, b = b, a
+    println(a, b)
+    a, b, c = c, a, b
+    println(a, b, c)
+
+    g1, g2 = g2, g1
+    println(g1, g2)
+
+    x := [3]int{1, 2, 3}
+    x[0], x[2] = x[2], x[0]
+    println(x)
+    x[0], x[1], x[2] = x[1], x[2], x[0]
+    println(x)
+
+    s := []int{5, 6}
+    s[0], s[1] = s[1], s[0]
+    println(s)
+
+    y := [2][2]int{[2]int{1, 2}, [2]int{3, 4}}
+    y[0][1], y[1][0] = y[1][0], y[0][1]
+    println(y)
+}
+"#,
+        "2 1\n3 2 1\n2 1\n[3 2 1]\n[2 1 3]\n[6 5]\n[[1 3] [2 4]]\n",
+    );
+}
+
+#[test]
+fn test_assignment_index_order() {
+    // index operands on the left are evaluated before anything is assigned
+    compare_stderr_output(
+        r#"
+package main
+
+var gx = [3]int{1, 2, 3}
+var gi = 0
+
+func main() {
+    x := [3]int{1, 2, 3}
+    i := 0
+    x[i], i = 9, 2
+    println(i, x)
+    i, x[i] = 0, 7
+    println(i, x)
+
+    y := [2][2]int{[2]int{1, 2}, [2]int{3, 4}}
+    j := 0
+    y[j][j], j = 7, 1
+    println(j, y)
+
+    gi, gx[gi] = 2, 5
+    println(gi, gx)
+    gx[gi], gi = 6, 0
+    println(gi, gx)
+}
+"#,
+        "2 [9 2 3]\n0 [9 2 7]\n1 [[7 2] [3 4]]\n2 [5 2 3]\n0 [5 2 6]\n",
+    );
+}
@@ -0,0 +1,97 @@
+use cogo_compiler::{compile_with_options, CompileOptions, Value};
+use cogo_vm::testing::{CapturedStreams, CollectingErrorHandler};
+use cogo_vm::{CUnitFrame, Vm};
+
+const PROGRAM: &str = r#"package main
+
+var total = 10
+
+func add(a int, b int) int {
+    sum := a + b
+    if sum > 0 {
+        twice := sum * 2
+        total = twice
+    }
+    return sum
+}
+
+func main() {
+    x := 1
+    for i := 0; i < 2; i++ {
+        x = add(x, i)
+    }
+    println(x, total)
+}
+"#;
+
+fn names(locals: &[(String, Value)]) -> Vec<&str> {
+    locals.iter().map(|(name, _)| name.as_str()).collect()
+}
+
+#[test]
+fn test_breakpoint_inspection() {
+    for optimize in [false, true] {
+        let cunit = compile_with_options(
+            PROGRAM,
+            &mut CollectingErrorHandler::new(),
+            &CompileOptions { optimize },
+        );
+        let streams = CapturedStreams::new();
+        let mut vm = Vm::new(Some(Box::new(streams.provider())), CUnitFrame::new(cunit));
+        vm.set_breakpoints(&[9]);
+
+        vm.run().unwrap();
+        assert!(vm.is_paused());
+        let frames = vm.frame_states();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].function, "main.add");
+        assert_eq!(frames[0].pos.0, 9);
+        assert_eq!(names(&frames[0].locals), vec!["a", "b", "sum", "twice"]);
+        assert_eq!(frames[0].locals[3].1, Value::Int(2));
+        assert_eq!(frames[1].function, "main.main");
+        assert_eq!(names(&frames[1].locals), vec!["x", "i"]);
+        assert!(vm
+            .globals()
+            .contains(&("total".to_string(), Value::Int(10))));
+        assert!(!vm.value_stack().is_empty());
+
+        // the second call stops at the same line
+        vm.run().unwrap();
+        assert!(vm.is_paused());
+        let frames = vm.frame_states();
+        assert_eq!(frames[0].locals[0].1, Value::Int(1));
+        assert_eq!(frames[1].locals[1].1, Value::Int(1));
+        assert!(vm.globals().contains(&("total".to_string(), Value::Int(2))));
+
+        vm.set_breakpoints(&[]);
+        vm.run().unwrap();
+        assert!(!vm.is_paused());
+        assert_eq!(streams.stderr(), "2 4\n", "optimize: {}", optimize);
+    }
+}
+
+#[test]
+fn test_heap_values() {
+    let cunit = compile_with_options(
+        r#"package main
+
+func main() {
+    a := []int{1, 2, 3}
+    b := [2]string{"x", "y"}
+    a = []int{}
+    println(len(a), len(b))
+}
+"#,
+        &mut CollectingErrorHandler::new(),
+        &CompileOptions::default(),
+    );
+    let streams = CapturedStreams::new();
+    let mut vm = Vm::new(Some(Box::new(streams.provider())), CUnitFrame::new(cunit));
+    vm.set_breakpoints(&[6, 7]);
+
+    vm.run().unwrap();
+    assert_eq!(vm.heap_values(), 5);
+
+    vm.run().unwrap();
+    assert_eq!(vm.heap_values(), 2);
+}
@@ -1,56 +1,56 @@
-use std::cell::RefCell;
-use std::error::Error;
-use std::rc::Rc;
+use cogo_compiler::CompileOptions;
+use cogo_vm::testing::{run_program_with_options, RunError};
+use cogo_vm::{VmError, VmOptions};
 
-use cogo_compiler::{compile, ErrorHandler};
-use cogo_vm::io::StdStreamProvider;
-use cogo_vm::{CUnitFrame, Vm};
-
-struct TestErrorHandler(Vec<String>);
-
-impl ErrorHandler for TestErrorHandler {
-    fn on_error(&mut self, errs: &[Box<dyn Error>]) {
-        for err in errs {
-            self.0.push(err.to_string());
-        }
-    }
+/// Runs the program both as is and optimized, comparing the output of each run
+#[allow(dead_code)]
+pub fn compare_stderr_output(program: &str, expected_stderr: &str) {
+    compare_stderr_output_with_vm_options(program, expected_stderr, &VmOptions::default());
 }
 
-impl TestErrorHandler {
-    fn new() -> Self {
-        Self(vec![])
-    }
-
-    fn errs(&self) -> &[String] {
-        &self.0
+/// Same as `compare_stderr_output` with the VM configured by `vm_options`
+#[allow(dead_code)]
+pub fn compare_stderr_output_with_vm_options(
+    program: &str,
+    expected_stderr: &str,
+    vm_options: &VmOptions,
+) {
+    for optimize in [false, true] {
+        let options = CompileOptions { optimize };
+        let (_, stderr, res) = run_program_with_options(program, &options, vm_options);
+
+        assert!(res.is_ok(), "{}", res.err().unwrap());
+        assert_eq!(stderr, expected_stderr, "optimize: {}", optimize);
     }
 }
 
-pub fn compare_stderr_output(program: &str, expected_stderr: &str) {
-    let stdout = Rc::new(RefCell::new(Vec::<u8>::new()));
-    let stderr = Rc::new(RefCell::new(Vec::<u8>::new()));
-
-    let vecout = Rc::clone(&stdout);
-    let vecerr = Rc::clone(&stderr);
-
-    let stream_provider = StdStreamProvider::new(Some((Some(stdout), Some(stderr), None)));
-
-    let mut err_handler = TestErrorHandler::new();
-    let cunit = compile(program, &mut err_handler);
-    let frame = CUnitFrame::new(cunit);
-
-    assert!(err_handler.errs().is_empty());
-
-    let mut vm = Vm::new(Some(Box::new(stream_provider)), frame);
-    let res = vm.run();
-
-    assert!(res.is_ok(), "{}", res.err().unwrap().to_string());
-
-    let _out = &*vecout.borrow();
-    let _out = String::from_utf8_lossy(_out);
+/// Runs the program expecting it to fail with `expected_error`
+#[allow(dead_code)]
+pub fn compare_runtime_error(program: &str, expected_error: &str) {
+    compare_runtime_error_with_vm_options(program, expected_error, &VmOptions::default());
+}
 
-    let err = &*vecerr.borrow();
-    let err = String::from_utf8_lossy(err);
+/// Runs the program with the given VM options expecting it to fail with `expected_error`
+#[allow(dead_code)]
+pub fn compare_runtime_error_with_vm_options(
+    program: &str,
+    expected_error: &str,
+    vm_options: &VmOptions,
+) {
+    assert_eq!(run_error(program, vm_options).to_string(), expected_error);
+}
 
-    assert_eq!(err, expected_stderr);
+/// Error the program fails with when run as "main.go"
+#[allow(dead_code)]
+pub fn run_error(program: &str, vm_options: &VmOptions) -> VmError {
+    let vm_options = VmOptions {
+        source_name: Some("main.go".to_string()),
+        ..vm_options.clone()
+    };
+
+    match run_program_with_options(program, &CompileOptions::default(), &vm_options) {
+        (_, _, Ok(())) => panic!("Program was expected to fail"),
+        (_, _, Err(RunError::Compile(errs))) => panic!("Program failed to compile: {:?}", errs),
+        (_, _, Err(RunError::Runtime(err))) => err,
+    }
 }
@@ -45,7 +45,7 @@ func main() {
     println(x17, x18, x19, x20, x21, x22, x23, x24, x25, x26, x27, x28, x29, x30, x33)
 }
         "#,
-        "true 10 53 -76 56 43 56 89 64 89 53 45 1.29e1 2.2022222222222223e0 string\nfalse 0 -45 1 56 -923824832 33 5 9 12 6 4 -3.66e0 3.4e-1 string\n",
+        "true 10 53 -76 56 43 56 89 64 89 53 45 +1.290000e+001 +2.202222e+000 string\nfalse 0 -45 1 56 -923824832 33 5 9 12 6 4 -3.660000e+000 +3.400000e-001 string\n",
     )
 }
 
@@ -166,3 +166,37 @@ func main() {
 ",
     )
 }
+
+#[test]
+fn test_const_iota() {
+    compare_stderr_output(
+        r#"
+package main
+
+const (
+    A = iota
+    B
+    C
+)
+
+const (
+    _, x = iota * 10, iota + 100
+    y, z
+)
+
+const (
+    KB int64 = 1 << (10 * (iota + 1))
+    MB
+)
+
+func main() {
+    const (
+        p = "s"
+        q
+    )
+    println(A, B, C, x, y, z, KB, MB, p, q)
+}
+"#,
+        "0 1 2 100 10 101 1024 1048576 s s\n",
+    );
+}
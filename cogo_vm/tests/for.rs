@@ -275,3 +275,65 @@ nested
 "#,
     )
 }
+
+#[test]
+fn test_for_at_function_start() {
+    compare_stderr_output(
+        r#"
+package main
+
+var x = 0
+
+func main() {
+    for x < 3 {
+        x++
+        continue
+    }
+    println(x)
+}
+        "#,
+        "3\n",
+    );
+}
+
+#[test]
+fn test_for_range_int() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    n := 0
+    for i := range 5 {
+        if i == 1 {
+            continue
+        }
+        if i == 4 {
+            break
+        }
+        i += 10
+        n += i
+    }
+    println(n)
+
+    for range 3 {
+        n++
+    }
+
+    var u uint8 = 3
+    for j := range u {
+        x := j * 2
+        println(x)
+    }
+
+    for i := range 0 {
+        println(i)
+    }
+
+    y := 7
+    println(n, y)
+}
+        "#,
+        "35\n0\n2\n4\n38 7\n",
+    );
+}
@@ -1,6 +1,7 @@
 mod common;
 
-use common::compare_stderr_output;
+use cogo_vm::{GrowthPolicy, VmOptions};
+use common::{compare_stderr_output, compare_stderr_output_with_vm_options, run_error};
 
 #[test]
 fn test_func_void() {
@@ -211,10 +212,10 @@ func f4() ([2]int, uint, [1]string) {
     return [2]int{1,2}, 3, [...]string{"hi"}
 }
         "#,
-        "4 2
-false 1
-1 hi true
-<[1]string>[hi] 3 <[2]int>[1 2]
+        "2 4
+1 false
+true hi 1
+[1 2] 3 [hi]
 ",
     )
 }
@@ -251,7 +252,278 @@ func f3(a , b []int, c string, z ...int) {
         "#,
         "1333
 true false true hi hello
-<[]int>[2 3 4] <[]int>[22 33 44] hi <int>[1 2 3 4 5]
+[2 3 4] [22 33 44] hi [1 2 3 4 5]
 ",
     )
 }
+
+#[test]
+fn test_func_deep_recursion() {
+    let program = r#"
+package main
+
+func sum(n int) int {
+    if n == 0 {
+        return 0
+    }
+
+    return n + sum(n - 1)
+}
+
+func main() {
+    println(sum(1000))
+}
+"#;
+
+    let tiny = VmOptions {
+        stack_capacity: 0,
+        frame_capacity: 0,
+        growth: GrowthPolicy::Fixed(1),
+        ..VmOptions::default()
+    };
+    let preallocated = VmOptions {
+        stack_capacity: 4096,
+        frame_capacity: 1024,
+        growth: GrowthPolicy::Fixed(512),
+        ..VmOptions::default()
+    };
+
+    compare_stderr_output_with_vm_options(program, "500500\n", &tiny);
+    compare_stderr_output_with_vm_options(program, "500500\n", &preallocated);
+    compare_stderr_output(program, "500500\n");
+}
+
+#[test]
+fn test_func_tail_call() {
+    compare_stderr_output(
+        r#"
+package main
+
+func count(n int, acc int) int {
+    if n == 0 {
+        return acc
+    }
+
+    return count(n - 1, acc + n)
+}
+
+func countdown(n int) {
+    println(n)
+    if n == 0 {
+        return
+    }
+
+    countdown(n - 1)
+}
+
+func fact(n int) int {
+    if n <= 1 {
+        return 1
+    }
+
+    return n * fact(n - 1)
+}
+
+func main() {
+    var x int = 5
+    println(count(100000, 0), x)
+    countdown(2)
+    println(fact(10), count(3, 1))
+}
+"#,
+        "5000050000 5\n2\n1\n0\n3628800 7\n",
+    );
+}
+
+#[test]
+fn test_func_single_line_body() {
+    compare_stderr_output(
+        "package main
+
+func one() int { return 1 }
+func add(a int, b int) int { x := a + b; return x }
+func void() {}
+
+func main() {
+    void()
+    println(one(), add(
+        2,
+        3,
+    ))
+}",
+        "1 5\n",
+    );
+}
+
+#[test]
+fn test_func_value_type() {
+    compare_stderr_output(
+        r#"
+package main
+
+func double(x int) int {
+    return x * 2
+}
+
+func square(x int) int {
+    return x * x
+}
+
+func apply(f func(int) int, x int) int {
+    return f(x)
+}
+
+func pick(sq bool) func(int) int {
+    if sq {
+        return square
+    }
+    return double
+}
+
+func main() {
+    var f func(int) int
+    println(f)
+    f = double
+    println(f(2), apply(square, 3), pick(true)(4))
+}
+"#,
+        "nil\n4 9 16\n",
+    );
+}
+
+#[test]
+fn test_func_type_named_params() {
+    compare_stderr_output(
+        r#"
+package main
+
+func add(a int, b int) int {
+    return a + b
+}
+
+func sum(xs ...int) int {
+    total := 0
+    for i := 0; i < len(xs); i++ {
+        total += xs[i]
+    }
+    return total
+}
+
+func fold(f func(acc, x int) int, g func(nums ...int) int) int {
+    return f(g(1, 2, 3), 4)
+}
+
+func main() {
+    var f func(x, y int) int = add
+    var g func(int, int) int = f
+    println(f(1, 2), g(3, 4), fold(add, sum))
+}
+"#,
+        "3 7 10\n",
+    );
+}
+
+#[test]
+fn test_func_value_type_mismatch() {
+    let decls = r#"
+func double(x int) int {
+    return x * 2
+}
+
+func greet(s string) {
+    println(s)
+}
+
+func apply(f func(int) int) int {
+    return f(1)
+}
+
+func pick() func(int) int {
+    return greet
+}
+"#;
+    let cases = [
+        "f := double\n    f = greet",
+        "var f func(int) int = greet\n    f(1)",
+        "apply(greet)",
+        "pick()",
+    ];
+
+    for body in cases {
+        let program = format!(
+            "package main\n{}\nfunc main() {{\n    {}\n}}\n",
+            decls, body
+        );
+        let err = run_error(&program, &VmOptions::default());
+        assert_eq!(err.category(), "type", "{}", err);
+        assert!(err.msg().contains("\"func (string)\""), "{}", err);
+    }
+
+    let err = run_error(
+        "package main\n\nfunc main() {\n    var f func()\n    f()\n}\n",
+        &VmOptions::default(),
+    );
+    assert_eq!(err.category(), "runtime", "{}", err);
+}
+
+#[test]
+fn test_func_init() {
+    compare_stderr_output(
+        r#"
+package main
+
+var order string = "var "
+
+func init() {
+    order += "first "
+}
+
+func main() {
+    println(order)
+}
+
+func init() {
+    order += "second"
+}
+"#,
+        "var first second\n",
+    );
+}
+
+#[test]
+fn test_func_shadowed_builtin() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    s := []int{1, 2}
+    println(len(s), cap("s"))
+    for i := 0; i < 2; println("post", i) {
+        i++
+    }
+
+    f := println
+    f("indirect", len("abc"))
+    {
+        len := double
+        println(len(4))
+    }
+}
+
+func double(a int) int {
+    return a * 2
+}
+
+func cap(s string) string {
+    return "shadowed " + s
+}
+"#,
+        "2 shadowed s
+post 1
+post 2
+indirect 3
+8
+",
+    );
+}
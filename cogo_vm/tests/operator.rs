@@ -280,3 +280,24 @@ func main() {
 ",
     )
 }
+
+#[test]
+fn test_operator_shift_wide() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    const big = 1 << 100
+    n := 70
+    x := 5
+    var u uint64 = 1<<64 - 1
+
+    println(big >> 98, big / (1 << 99))
+    println(x << n, -x >> n, x >> 64)
+    println(u, u >> 63)
+}
+        "#,
+        "4 2\n0 -1 0\n18446744073709551615 1\n",
+    )
+}
@@ -0,0 +1,152 @@
+//! Golden-file conformance tests.
+//!
+//! Every `programs/*.go` file is compiled and run, its stdout, stderr and exit code are compared
+//! against the sibling `.out`, `.err` and `.code` files. A missing golden file means an empty
+//! output, or the exit code `0`.
+//!
+//! `test_against_go_toolchain` additionally runs the programs under `go run` and diffs the
+//! output with cogo. It needs the Go toolchain, hence is ignored by default:
+//! `cargo test --test programs -- --ignored`.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use cogo_compiler::CompileOptions;
+use cogo_vm::testing::{run_program_with_options, RunError};
+use cogo_vm::VmOptions;
+
+const PROGRAMS_DIR: &str = "tests/programs";
+
+/// What a program run produced, compared against the golden files
+#[derive(Debug, PartialEq)]
+struct Outcome {
+    stdout: String,
+    stderr: String,
+    code: i32,
+}
+
+impl Outcome {
+    fn expected(program: &Path) -> Self {
+        let read = |ext: &str| fs::read_to_string(program.with_extension(ext)).unwrap_or_default();
+
+        Self {
+            stdout: read("out"),
+            stderr: read("err"),
+            code: read("code").trim().parse().unwrap_or(0),
+        }
+    }
+}
+
+fn run_program(program: &Path, options: &CompileOptions) -> Outcome {
+    let src = fs::read_to_string(program).unwrap();
+    let vm_options = VmOptions {
+        source_name: program
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned()),
+        ..VmOptions::default()
+    };
+
+    let (stdout, mut stderr, res) = run_program_with_options(&src, options, &vm_options);
+    let code = match res {
+        Ok(()) => 0,
+        Err(RunError::Compile(errs)) => {
+            let errs: Vec<String> = errs.iter().map(|err| err.to_string()).collect();
+            stderr = errs.join("\n") + "\n";
+            1
+        }
+        Err(RunError::Runtime(err)) => {
+            stderr.push_str(&format!("{}\n", err));
+            err.exit_code()
+        }
+    };
+
+    Outcome {
+        stdout,
+        stderr,
+        code,
+    }
+}
+
+fn programs() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(PROGRAMS_DIR);
+    let mut programs: Vec<PathBuf> = fs::read_dir(&dir)
+        .unwrap_or_else(|_| panic!("Cannot read \"{}\"", dir.display()))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "go"))
+        .collect();
+    programs.sort();
+
+    programs
+}
+
+#[test]
+fn test_golden_programs() {
+    let programs = programs();
+    assert!(
+        !programs.is_empty(),
+        "No programs found in \"{}\"",
+        PROGRAMS_DIR
+    );
+
+    let mut failures = vec![];
+    for program in &programs {
+        let expected = Outcome::expected(program);
+        for optimize in [false, true] {
+            let actual = run_program(program, &CompileOptions { optimize });
+            if actual != expected {
+                failures.push(format!(
+                    "{} (optimize: {})\nexpected: {:?}\n  actual: {:?}",
+                    program.display(),
+                    optimize,
+                    expected,
+                    actual,
+                ));
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+/// Runs the program with `go run`, `None` if the toolchain is not available
+fn run_go_program(program: &Path) -> Option<Outcome> {
+    let output = Command::new("go").arg("run").arg(program).output().ok()?;
+
+    Some(Outcome {
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        code: output.status.code().unwrap_or(-1),
+    })
+}
+
+#[test]
+#[ignore]
+fn test_against_go_toolchain() {
+    let mut failures = vec![];
+    for program in &programs() {
+        // failing programs report errors in their own format, only successful runs are comparable
+        if Outcome::expected(program).code != 0 {
+            continue;
+        }
+
+        let expected = match run_go_program(program) {
+            Some(outcome) => outcome,
+            None => {
+                eprintln!("Go toolchain not found, skipping");
+                return;
+            }
+        };
+        let actual = run_program(program, &CompileOptions::default());
+        if actual != expected {
+            failures.push(format!(
+                "{}\n    go: {:?}\n  cogo: {:?}",
+                program.display(),
+                expected,
+                actual,
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
@@ -0,0 +1,13 @@
+package main
+
+func main() {
+    x := 1 + & 2
+    println(x)
+    y := ]
+    println(y)
+    z := 2 *
+}
+
+func f() {
+    println(3)
+}
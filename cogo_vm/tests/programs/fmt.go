@@ -0,0 +1,12 @@
+package main
+
+import "fmt"
+
+func main() {
+    var a []int = []int{1, 2, 3}
+
+    fmt.Println("sum:", 1 + 2, 2.5, 1e21, a, true)
+    fmt.Print(1, 2, "three", 4)
+    fmt.Println()
+    println("stderr", 2.5)
+}
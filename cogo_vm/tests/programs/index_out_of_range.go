@@ -0,0 +1,11 @@
+package main
+
+func at(a []int, i int) int {
+    return a[i]
+}
+
+func main() {
+    var a []int = []int{1, 2, 3}
+    println(at(a, 2))
+    println(at(a, 3))
+}
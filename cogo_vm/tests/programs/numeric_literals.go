@@ -0,0 +1,9 @@
+package main
+
+func main() {
+    println(0b1010, 0o17, 017, 0xFF, 0X_1f, 1_000_000)
+    println(1.5, 1e3, 2.5E-1)
+
+    var mask uint8 = 0b1111_0000
+    println(mask)
+}
@@ -0,0 +1,12 @@
+package main
+
+import (
+    "fmt"
+    "runtime"
+)
+
+func main() {
+    runtime.Gosched()
+    n := runtime.NumGoroutine()
+    fmt.Println("goroutines:", n, n + 1)
+}
@@ -0,0 +1,254 @@
+mod common;
+
+use cogo_vm::VmOptions;
+use common::{compare_runtime_error, compare_runtime_error_with_vm_options, run_error};
+
+#[test]
+fn test_stack_trace() {
+    compare_runtime_error(
+        r#"
+package main
+
+func get(i int) int {
+    var a [2]int = [2]int{1, 2}
+    return a[i] + "str"
+}
+
+func wrap(i int) int {
+    x := get(i)
+    return x
+}
+
+func main() {
+    wrap(1)
+}
+"#,
+        "main.go:6: Both operands must be of same type, got \"int\" and \"string\"
+
+goroutine 1 [running]:
+main.get(...)
+	main.go:6
+main.wrap(...)
+	main.go:10
+main.main(...)
+	main.go:15",
+    );
+}
+
+#[test]
+fn test_error_position() {
+    compare_runtime_error(
+        r#"
+package main
+
+func main() {
+    var a []int = []int{1, 2}
+    a[1] = 3
+    a[2] = 3
+}
+"#,
+        "main.go:7: index out of range [2] with length 2
+
+goroutine 1 [running]:
+main.main(...)
+\tmain.go:7",
+    );
+}
+
+#[test]
+fn test_stack_overflow() {
+    compare_runtime_error_with_vm_options(
+        r#"
+package main
+
+func down(n int) int {
+    return down(n + 1) + 1
+}
+
+func main() {
+    down(0)
+}
+"#,
+        "main.go:5: stack overflow, call depth exceeds 3 frames
+
+goroutine 1 [running]:
+main.down(...)
+\tmain.go:5
+main.main(...)
+\tmain.go:9",
+        &VmOptions {
+            max_frame_depth: Some(3),
+            ..VmOptions::default()
+        },
+    );
+}
+
+#[test]
+fn test_out_of_memory() {
+    compare_runtime_error_with_vm_options(
+        r#"
+package main
+
+func main() {
+    s := []int{}
+    for i := 0; i < 100; i++ {
+        s = append(s, i)
+    }
+}
+"#,
+        "main.go:7: out of memory, arrays and slices hold more than 10 values
+
+goroutine 1 [running]:
+main.main(...)
+\tmain.go:7",
+        &VmOptions {
+            max_heap_values: Some(10),
+            ..VmOptions::default()
+        },
+    );
+}
+
+#[test]
+fn test_error_category() {
+    let cases = [
+        (
+            "var a int = 1\n    a = \"s\"",
+            "type",
+            "Got value of type \"string\"",
+        ),
+        (
+            "x := len(1, 2)\n    x++",
+            "arity",
+            "Expected 1 params, got 2",
+        ),
+        ("panic(\"boom\")", "panic", "boom"),
+        (
+            "var a []int\n    a[0] = 1",
+            "runtime",
+            "index out of range [0] with length 0",
+        ),
+    ];
+
+    for (body, category, msg) in cases {
+        let program = format!("package main\n\nfunc main() {{\n    {}\n}}\n", body);
+        let err = run_error(&program, &VmOptions::default());
+        assert_eq!(err.category(), category, "{}", err);
+        assert!(err.msg().starts_with(msg), "{}", err);
+        assert!(err.trace().is_some());
+    }
+}
+
+#[test]
+fn test_builtin_argument_type() {
+    let cases = [
+        (
+            "x := int(\"s\")\n    x++",
+            "Invalid argument 1 type \"string\", expected \"integer or float\"",
+        ),
+        (
+            "x := float64(true)\n    x++",
+            "Invalid argument 1 type \"bool\", expected \"integer or float\"",
+        ),
+        (
+            "x := len(true)\n    x++",
+            "Invalid argument 1 type \"bool\", expected \"string, array, slice\"",
+        ),
+        (
+            "x := append(1, 2)\n    x++",
+            "Invalid argument 1 type \"int\", expected \"slice\"",
+        ),
+        (
+            "x := real(1)\n    x++",
+            "Invalid argument 1 type \"int\", expected \"complex\"",
+        ),
+    ];
+
+    for (body, msg) in cases {
+        let program = format!("package main\n\nfunc main() {{\n    {}\n}}\n", body);
+        let err = run_error(&program, &VmOptions::default());
+        assert_eq!(err.category(), "type", "{}", err);
+        assert_eq!(err.msg(), msg);
+    }
+}
+
+#[test]
+fn test_slice_bounds() {
+    let cases = [
+        (
+            "s := []int{1, 2, 3}\n    t := s[1:2]\n    println(t[:3])",
+            "slice bounds out of range [:3] with capacity 2",
+        ),
+        (
+            "var a [2]int\n    println(a[:3])",
+            "slice bounds out of range [:3] with capacity 2",
+        ),
+        (
+            "s := \"go\"\n    println(s[1:3])",
+            "slice bounds out of range [:3] with length 2",
+        ),
+        (
+            "s, i := []int{1, 2, 3}, 2\n    println(s[i:1])",
+            "slice bounds out of range [2:1]",
+        ),
+        (
+            "s, i := []int{1, 2, 3}, 4\n    println(s[:1:i])",
+            "slice bounds out of range [::4] with capacity 3",
+        ),
+        (
+            "s, i := []int{1, 2, 3}, 3\n    println(s[:i:2])",
+            "slice bounds out of range [:3:2]",
+        ),
+        (
+            "s, i := []int{1, 2, 3}, 2\n    println(s[i:1:3])",
+            "slice bounds out of range [2:1:]",
+        ),
+    ];
+
+    for (body, msg) in cases {
+        let program = format!("package main\n\nfunc main() {{\n    {}\n}}\n", body);
+        let err = run_error(&program, &VmOptions::default());
+        assert_eq!(err.category(), "runtime", "{}", err);
+        assert_eq!(err.msg(), msg);
+    }
+}
+
+#[test]
+fn test_conversion_error() {
+    let program = "package main\n\nfunc main() {\n    x := []uint8(5)\n    println(x)\n}\n";
+    let err = run_error(program, &VmOptions::default());
+    assert_eq!(err.category(), "type", "{}", err);
+    assert_eq!(
+        err.msg(),
+        "Cannot convert value of type \"int\" to \"[]uint8\""
+    );
+}
+
+#[test]
+fn test_assignment_type_error() {
+    let cases = [
+        ("var a [2]int\n    a = [3]int{1, 2, 3}", "[2]int", "[3]int"),
+        ("var s []int\n    s = []string{}", "[]int", "[]string"),
+        ("var u uint8 = 2.5", "uint8", "float64"),
+    ];
+
+    for (stmt, expected, got) in cases.iter() {
+        let program = format!("package main\n\nfunc main() {{\n    {}\n}}\n", stmt);
+        let err = run_error(&program, &VmOptions::default());
+        assert_eq!(err.category(), "type", "{}", err);
+        assert_eq!(
+            err.msg(),
+            format!(
+                "Got value of type \"{}\" but expected type \"{}\"",
+                got, expected
+            )
+        );
+    }
+}
+
+#[test]
+fn test_range_error() {
+    let program = "package main\n\nfunc main() {\n    for range \"ab\" {\n    }\n}\n";
+    let err = run_error(program, &VmOptions::default());
+    assert_eq!(err.category(), "type", "{}", err);
+    assert_eq!(err.msg(), "Cannot range over value of type \"string\"");
+}
@@ -0,0 +1,167 @@
+mod common;
+
+use common::compare_stderr_output;
+
+#[test]
+fn test_slice_expr() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    a := [5]int{1, 2, 3, 4, 5}
+    s := a[1:3]
+    println(s, len(s), cap(s))
+    println(a[:2], a[3:], a[:], s[:0], len(s[:0]), cap(s[:0]))
+
+    t := s[1:4]
+    println(t, len(t), cap(t))
+
+    str := "hello"
+    println(str[1:3], str[:2], str[3:], str[:])
+}
+"#,
+        "[2 3] 2 4\n[1 2] [4 5] [1 2 3 4 5] [] 0 4\n[3 4 5] 3 3\nel he lo hello\n",
+    );
+}
+
+#[test]
+fn test_slice_aliasing() {
+    compare_stderr_output(
+        r#"
+package main
+
+var g = []int{1, 2, 3}
+
+func modify(s []int) {
+    s[0] = 100
+}
+
+func main() {
+    a := [5]int{1, 2, 3, 4, 5}
+    s := a[1:3]
+    s[0] = 20
+    println(a, s)
+
+    t := s[1:4]
+    t[2] = 50
+    println(a, s, t)
+
+    u := s
+    u[1] = 30
+    println(s, t, a)
+
+    modify(a[2:])
+    println(a, s)
+
+    h := g[1:]
+    h[0] = 7
+    modify(g)
+    println(g, h)
+
+    b := a
+    b[0] = 0
+    println(a[0], b[0])
+}
+"#,
+        "[1 20 3 4 5] [20 3]
+[1 20 3 4 50] [20 3] [3 4 50]
+[20 30] [30 4 50] [1 20 30 4 50]
+[1 20 100 4 50] [20 100]
+[100 7 3] [7 3]
+1 0
+",
+    );
+}
+
+#[test]
+fn test_slice_copy() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    x := []int{1, 2, 3, 4}
+    n := copy(x[1:], x)
+    println(n, x)
+
+    y := make3()
+    println(copy(y, x[:2]), y)
+
+    bs := []uint8{0, 0}
+    println(copy(bs, "hi"), bs)
+}
+
+func make3() []int {
+    return []int{0, 0, 0}
+}
+"#,
+        "3 [1 1 2 3]\n2 [1 1 0]\n2 [104 105]\n",
+    );
+}
+
+#[test]
+fn test_slice_append() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    var s []int
+    for i := 0; i < 5; i++ {
+        s = append(s, i)
+        println(len(s), cap(s))
+    }
+
+    a := s[:2]
+    b := append(a, 10)
+    println(s, a, b)
+
+    c := append(a, 7, 8, 9, 10)
+    c[0] = 99
+    println(s, c, len(c), cap(c))
+
+    d := append(c, 11, 12, 13)
+    d[0] = 0
+    println(c[0], d, len(d), cap(d))
+}
+"#,
+        "1 1
+2 2
+3 4
+4 4
+5 8
+[0 1 10 3 4] [0 1] [0 1 10]
+[99 1 7 8 9] [99 1 7 8 9 10] 6 8
+99 [0 1 7 8 9 10 11 12 13] 9 16
+",
+    );
+}
+
+#[test]
+fn test_slice_full_expr() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    a := [6]int{0, 1, 2, 3, 4, 5}
+    s := a[1:3:4]
+    println(s, len(s), cap(s))
+
+    t := a[:2:2]
+    t = append(t, 9)
+    t[0] = 8
+    println(a, t, cap(t))
+
+    u := s[:1:2]
+    u = append(u, 7)
+    println(a, u, cap(u))
+
+    i := 4
+    println(a[1:i:i], cap(a[1:i:i]))
+}
+"#,
+        "[1 2] 2 3\n[0 1 2 3 4 5] [8 1 9] 4\n[0 1 7 3 4 5] [1 7] 2\n[1 7 3] 3\n",
+    );
+}
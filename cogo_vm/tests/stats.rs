@@ -0,0 +1,33 @@
+#![cfg(feature = "stats")]
+
+use cogo_compiler::{compile, ToStderrErrorHandler};
+use cogo_vm::io::StdStreamProvider;
+use cogo_vm::{CUnitFrame, Vm};
+
+#[test]
+fn test_stats() {
+    let program = r#"
+package main
+
+func fact(n int) int {
+    if n <= 1 {
+        return 1
+    }
+    return n * fact(n - 1)
+}
+
+func main() {
+    fact(5)
+}
+"#;
+    let cunit = compile(program, &mut ToStderrErrorHandler::default());
+    let stream_provider = StdStreamProvider::new(Some((None, None, None)));
+    let mut vm = Vm::new(Some(Box::new(stream_provider)), CUnitFrame::new(cunit));
+    vm.run().unwrap();
+
+    let stats = vm.stats();
+    // main and five calls of fact
+    assert_eq!(stats.calls, 6);
+    assert_eq!(stats.peak_frame_depth, 7);
+    assert!(stats.instructions > 0);
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use common::{compare_runtime_error, compare_stderr_output};
+
+#[test]
+fn test_string_bytes() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    var s string = "héllo"
+    println(len(s), s[0], s[1], s[2], s[5])
+
+    const n = len("abc")
+    println(n)
+}
+"#,
+        "6 104 195 169 111\n3\n",
+    )
+}
+
+#[test]
+fn test_string_index_errors() {
+    compare_runtime_error(
+        r#"
+package main
+
+func main() {
+    var s string = "abc"
+    println(s[3])
+}
+"#,
+        "main.go:6: index out of range [3] with length 3
+
+goroutine 1 [running]:
+main.main(...)
+\tmain.go:6",
+    );
+}
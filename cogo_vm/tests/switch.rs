@@ -165,3 +165,75 @@ inner
 "#,
     )
 }
+
+#[test]
+fn test_switch_constant_cases() {
+    compare_stderr_output(
+        r#"
+package main
+
+func name(n int) string {
+    switch n {
+    case 1:
+        return "one"
+    case -2:
+        return "minus two"
+    case 3:
+        fallthrough
+    case 4:
+        return "three or four"
+    default:
+        return "many"
+    case 5:
+        return "five"
+    }
+    return ""
+}
+
+func kind(s string) int {
+    switch s {
+    case "a":
+        return 1
+    case "b":
+        return 2
+    case "c":
+        return 3
+    case "d":
+        return 4
+    }
+    return 0
+}
+
+func main() {
+    for i := range 8 {
+        println(name(i - 2))
+    }
+
+    var u uint8 = 5
+    switch u {
+    case 1:
+        println("u1")
+    case 2:
+        println("u2")
+    case 3:
+        println("u3")
+    case 5:
+        println("u5")
+    }
+
+    println(kind("c"), kind("z"))
+}
+"#,
+        r#"minus two
+many
+many
+one
+many
+three or four
+three or four
+five
+u5
+3 0
+"#,
+    )
+}
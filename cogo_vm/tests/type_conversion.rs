@@ -35,3 +35,66 @@ func main() {
         "40\n32\n",
     )
 }
+
+#[test]
+fn test_string_conversion() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    s := "héllo, 世界"
+    bs := []byte(s)
+    rs := []rune(s)
+    println(len(bs), len(rs), bs[1], rs[1])
+
+    bs[0] = 72
+    rs[8] = 120
+    println(string(bs), string(rs), string(rs[7:]), s)
+
+    b := []byte("hi")
+    b = append(b, 33)
+    println(string(b), string(65), string(rune(0x4e16)), string(byte(97)))
+    println(string([]byte{0xff}) == string(-1))
+}
+"#,
+        "14 9 195 233\nHéllo, 世界 héllo, 世x 世x héllo, 世界\nhi! A 世 a\ntrue\n",
+    );
+}
+
+#[test]
+fn test_untyped_constant_assignment() {
+    compare_stderr_output(
+        r#"
+package main
+
+var c complex128 = 1
+
+func main() {
+    var i int = 2.0
+    var f float32 = 3
+    f += 1.5
+    println(i, f, c)
+}
+        "#,
+        "2 +4.500000e+000 (+1.000000e+000+0.000000e+000i)\n",
+    )
+}
+
+#[test]
+fn test_number_literal_forms() {
+    compare_stderr_output(
+        r#"
+package main
+
+func main() {
+    c := 1 + 2.5i
+    println(.5, 1., 0x1p-2, 0x1.8p1, 0x_Ap0)
+    println(c, real(c), imag(c), 0123i, 0x10i)
+}
+        "#,
+        "+5.000000e-001 +1.000000e+000 +2.500000e-001 +3.000000e+000 +1.000000e+001\n\
+        (+1.000000e+000+2.500000e+000i) +1.000000e+000 +2.500000e+000 \
+        (+0.000000e+000+1.230000e+002i) (+0.000000e+000+1.600000e+001i)\n",
+    )
+}
@@ -49,7 +49,7 @@ func main() {
     println(x17, x18, x19, x20, x21, x22, x23, x24, x25, x26, x27, x28, x29, x30, x31, x32, x33)
 }
 "#,
-        "false 0 0 0 0 0 0 0 0 0 0 0 0e0 0e0 (0e0+0e0i) (0e0+0e0i) \nfalse 0 0 0 0 0 0 0 0 0 0 0 0e0 0e0 (0e0+0e0i) (0e0+0e0i) \n",
+        "false 0 0 0 0 0 0 0 0 0 0 0 +0.000000e+000 +0.000000e+000 (+0.000000e+000+0.000000e+000i) (+0.000000e+000+0.000000e+000i) \nfalse 0 0 0 0 0 0 0 0 0 0 0 +0.000000e+000 +0.000000e+000 (+0.000000e+000+0.000000e+000i) (+0.000000e+000+0.000000e+000i) \n",
     )
 }
 
@@ -96,7 +96,7 @@ func main() {
     println(x17, x18, x19, x20, x21, x22, x23, x24, x25, x26, x27, x28, x29, x30, x33)
 }
         "#,
-        "true 10 53 -76 56 43 56 89 64 89 53 45 1.29e1 2.2022222222222223e0 string\nfalse 0 -45 1 56 -923824832 33 5 9 12 6 4 -3.66e0 3.4e-1 string\n",
+        "true 10 53 -76 56 43 56 89 64 89 53 45 +1.290000e+001 +2.202222e+000 string\nfalse 0 -45 1 56 -923824832 33 5 9 12 6 4 -3.660000e+000 +3.400000e-001 string\n",
     )
 }
 
@@ -263,7 +263,7 @@ func main() {
     println(a, s, d)
 }
         "#,
-        "1 0 string\n9 0e0 another\n",
+        "1 0 string\n9 +0.000000e+000 another\n",
     )
 }
 
@@ -296,8 +296,8 @@ func main() {
     println(a,b,c,d,e,f,g,h,i,j,k,l)
 }
         "#,
-        "-8 21 string1 string2 string3 <[2]uint>[0 0] <[2]uint>[0 0] 8 12 <[1]int>[2] <[1]int>[3] <[1]int>[6]
-99 0 string4 string5 string6 <[3]uint>[0 0 0] <[3]uint>[0 0 0] -99 -100 <[2]int>[0 0] <[2]int>[0 0] <[2]int>[0 0]
+        "-8 21 string1 string2 string3 [0 0] [0 0] 8 12 [2] [3] [6]
+99 0 string4 string5 string6 [0 0 0] [0 0 0] -99 -100 [0 0] [0 0] [0 0]
 ",
     )
 }
@@ -317,8 +317,35 @@ func main() {
     println(a,b,c,t)
 }
         "#,
-        "hi <[1]int>[1] 98
-hello <[1]int>[2] 1 888
+        "hi [1] 98
+hello [2] 1 888
 ",
     )
 }
+
+#[test]
+fn test_var_init_order() {
+    compare_stderr_output(
+        r#"
+package main
+
+var a = b + 1
+var (
+    c = f()
+    b = 2
+)
+
+const k = j * 2
+const j = 3
+
+func f() int {
+    return a * 10
+}
+
+func main() {
+    println(a, b, c, k)
+}
+"#,
+        "3 2 30 6\n",
+    );
+}
@@ -56,25 +56,25 @@ func v3(x ...[]string) {
     println(x)
 }
         "#,
-        "<int>[1]
+        "[1]
 0
-<int>[1 45]
+[1 45]
 1
-<int>[1 2 45 4 5]
+[1 2 45 4 5]
 4
-<int>[]
+[]
 -1
-<string>[hi]
+[hi]
 false
-<string>[good day]
+[good day]
 false
-<string>[hi hello good day]
+[hi hello good day]
 false
-<string>[]
+[]
 true
-<[]string>[<[]string>[hi]]
-<[]string>[<[]string>[bye farewell] <[]string>[hi]]
-<[]string>[<[]string>[hi] <[]string>[hello] <[]string>[good day] <[]string>[hi] <[]string>[bye farewell]]
+[[hi]]
+[[bye farewell] [hi]]
+[[hi] [hello] [good day] [hi] [bye farewell]]
 ",
     )
 }
@@ -125,26 +125,26 @@ func v3(f bool, x ...[]string) int {
     return len(x)
 }
         "#,
-        "<[]int>[1 2]
-<int>[1]
-<[]int>[1 2]
-<int>[1 45]
-<[]int>[1 2]
-<int>[1 2 45 4 5]
+        "[1 2]
+[1]
+[1 2]
+[1 45]
+[1 2]
+[1 2 45 4 5]
 first
-<string>[hi]
+[hi]
 first
-<string>[good day]
+[good day]
 first
-<string>[hi hello good day]
+[hi hello good day]
 true
-<[]string>[<[]string>[hi]]
+[[hi]]
 1
 true
-<[]string>[<[]string>[bye farewell] <[]string>[hi]]
+[[bye farewell] [hi]]
 2
 false
-<[]string>[<[]string>[hi] <[]string>[hello good day] <[]string>[hi] <[]string>[bye farewell]]
+[[hi] [hello good day] [hi] [bye farewell]]
 4
 ",
     )
@@ -196,18 +196,18 @@ func v3(f bool, x ...[]string) int {
     return len(x)
 }
         "#,
-        "<[]int>[1]
-<[]int>[1 45]
-<[]int>[1 2 45 4 5]
+        "[1]
+[1 45]
+[1 2 45 4 5]
 01
-<[]string>[good day hi]
+[good day hi]
 02
-<[]string>[first second]
+[first second]
 true
-<[][]string>[<[]string>[hi] <[]string>[bye farewell]]
+[[hi] [bye farewell]]
 2
 true
-<[][]string>[]
+[]
 0
 ",
     )